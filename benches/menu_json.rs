@@ -0,0 +1,44 @@
+// Compares serializing a /api/menu body the old way (String, then Bytes) with
+// ResponseBuilder::json_bytes, for small to large menus. Run with
+// `cargo bench --bench menu_json`.
+
+use bytes::Bytes;
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use rotiride::menu::MenuItem;
+use rotiride::response::ResponseBuilder;
+
+fn menu(items: i64) -> Vec<MenuItem> {
+    (1..=items)
+        .map(|id| MenuItem {
+            id,
+            sku: format!("SKU-{id:04}"),
+            name: format!("Paneer tikka roll {id}"),
+            description: "Charred paneer, mint chutney and pickled onion in a flaky paratha".to_string(),
+            category: if id % 3 == 0 { "Drinks" } else { "Rolls" }.to_string(),
+            price_paise: 14_900 + id * 100,
+            available: true,
+            calories_kcal: Some(420),
+            protein_grams: Some(18),
+            allergens: vec!["milk".to_string(), "gluten".to_string()],
+            available_now: true,
+            next_available_at: None,
+        })
+        .collect()
+}
+
+fn menu_json(c: &mut Criterion) {
+    let mut group = c.benchmark_group("menu_json");
+    for size in [10, 60, 250] {
+        let items = menu(size);
+        group.bench_with_input(BenchmarkId::new("to_string", size), &items, |b, items| {
+            b.iter(|| Bytes::from(serde_json::to_string(black_box(items)).unwrap()))
+        });
+        group.bench_with_input(BenchmarkId::new("json_bytes", size), &items, |b, items| {
+            b.iter(|| ResponseBuilder::json_bytes(black_box(items)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, menu_json);
+criterion_main!(benches);
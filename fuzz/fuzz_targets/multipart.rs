@@ -0,0 +1,7 @@
+#![no_main]
+
+// See rotiride_fuzz::multipart.
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| rotiride_fuzz::multipart(data));
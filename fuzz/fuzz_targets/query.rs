@@ -0,0 +1,7 @@
+#![no_main]
+
+// See rotiride_fuzz::query.
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| rotiride_fuzz::query(data));
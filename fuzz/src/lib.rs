@@ -0,0 +1,95 @@
+// What each fuzz target does with one input. The targets in fuzz_targets/ and the
+// regression test in tests/ both go through these, so a saved input replays exactly
+// as the fuzzer ran it. Inputs are decoded the way fuzz_target! decodes a typed input.
+
+use arbitrary::{Arbitrary, Unstructured};
+use bytes::Bytes;
+use http::Request;
+use rotiride::audit::AuditQuery;
+use rotiride::multipart::{self, MultipartLimits, MultipartParser};
+use rotiride::request::RequestContext;
+use rotiride::response::ResponseBuilder;
+use std::net::{Ipv4Addr, SocketAddr};
+
+// Every target, by the name of its fuzz_targets/ file and regressions/ directory.
+pub const TARGETS: [(&str, fn(&[u8])); 3] =
+    [("request_context", request_context), ("query", query), ("multipart", multipart)];
+
+fn decode<'a, T: Arbitrary<'a>>(data: &'a [u8]) -> Option<T> {
+    T::arbitrary_take_rest(Unstructured::new(data)).ok()
+}
+
+#[derive(Debug, Arbitrary)]
+struct RequestInput<'a> {
+    method: &'a str,
+    uri: &'a str,
+    headers: Vec<(&'a str, &'a [u8])>,
+    body: &'a [u8],
+}
+
+// Arbitrary request heads and bodies through RequestContext::from_request and the
+// lookups handlers make on the context.
+pub fn request_context(data: &[u8]) {
+    let Some(input) = decode::<RequestInput>(data) else {
+        return;
+    };
+    let mut request = Request::builder().method(input.method).uri(input.uri);
+    for (name, value) in &input.headers {
+        request = request.header(*name, *value);
+    }
+    // h3 only hands over request heads http accepts; others never reach the context.
+    let Ok(request) = request.body(()) else {
+        return;
+    };
+    let remote = SocketAddr::from((Ipv4Addr::LOCALHOST, 443));
+    let ctx = RequestContext::from_request(request, Bytes::copy_from_slice(input.body), remote);
+    let _ = ctx.query();
+    let _ = ctx.header("content-type");
+    let _ = ctx.bearer_token();
+    let _ = ctx.json::<serde_json::Value>();
+    let _ = ResponseBuilder::wants_envelope(&ctx);
+}
+
+// Arbitrary query strings through RequestContext::query and the typed parameters
+// parsed from it, using the audit log search (numbers, timestamps, free text).
+pub fn query(data: &[u8]) {
+    let Some(query) = decode::<&str>(data) else {
+        return;
+    };
+    let Ok(request) = Request::builder().uri(format!("https://localhost/api/admin/audit?{query}")).body(()) else {
+        return;
+    };
+    let ctx = RequestContext::from_request(request, Bytes::new(), SocketAddr::from((Ipv4Addr::LOCALHOST, 443)));
+    let _ = ctx.query_param("limit");
+    let _ = AuditQuery::from_request(&ctx);
+}
+
+#[derive(Debug, Arbitrary)]
+struct MultipartInput<'a> {
+    content_type: &'a str,
+    chunks: Vec<&'a [u8]>,
+}
+
+// Arbitrary content types and bodies, split into arbitrary chunks as they would
+// arrive from the stream, through the multipart/form-data parser.
+pub fn multipart(data: &[u8]) {
+    let Some(input) = decode::<MultipartInput>(data) else {
+        return;
+    };
+    let Some(boundary) = multipart::boundary(input.content_type) else {
+        return;
+    };
+    // Small limits so the limit checks are reached too.
+    let limits = MultipartLimits { max_part_bytes: 4 * 1024, max_total_bytes: 16 * 1024, max_parts: 8 };
+    let mut parser = MultipartParser::new(&boundary, limits);
+    for chunk in input.chunks {
+        if parser.feed(chunk).is_err() {
+            return;
+        }
+    }
+    if let Ok(parts) = parser.finish() {
+        for part in &parts {
+            let _ = part.text();
+        }
+    }
+}
@@ -0,0 +1,24 @@
+// Replays every input saved under regressions/<target>/ through its target, so
+// crashes the fuzzer found stay fixed. Runs on stable: cargo test in fuzz/.
+
+use std::fs;
+use std::path::Path;
+
+#[test]
+fn saved_inputs_still_pass() {
+    for (target, run) in rotiride_fuzz::TARGETS {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("regressions").join(target);
+        let entries = fs::read_dir(&dir).unwrap_or_else(|err| panic!("{}: {err}", dir.display()));
+        for entry in entries {
+            let path = entry.expect("readable directory entry").path();
+            if path.file_name().is_some_and(|name| name == ".gitkeep") {
+                continue;
+            }
+            let data = fs::read(&path).unwrap_or_else(|err| panic!("{}: {err}", path.display()));
+            // Name the input that failed; the target's own panic message doesn't.
+            if std::panic::catch_unwind(|| run(&data)).is_err() {
+                panic!("{} no longer passes", path.display());
+            }
+        }
+    }
+}
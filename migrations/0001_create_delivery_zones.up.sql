@@ -0,0 +1,11 @@
+-- Delivery zones: a polygon of [{"latitude":..,"longitude":..}] points plus per-zone pricing and ETA.
+CREATE TABLE IF NOT EXISTS delivery_zones (
+    id BIGINT NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    name VARCHAR(100) NOT NULL UNIQUE,
+    polygon JSON NOT NULL,
+    delivery_fee_paise BIGINT NOT NULL DEFAULT 0,
+    base_eta_minutes INT NOT NULL DEFAULT 30,
+    active BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP
+);
@@ -0,0 +1,8 @@
+-- Business settings that can change without a restart (delivery fee, tax rate, ...).
+-- Values are text; ConfigService parses them into the typed getters.
+CREATE TABLE IF NOT EXISTS system_configurations (
+    config_key VARCHAR(100) NOT NULL PRIMARY KEY,
+    config_value TEXT NOT NULL,
+    description VARCHAR(500) NOT NULL DEFAULT '',
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP
+);
@@ -0,0 +1,8 @@
+-- Fails while two restaurants share a sku, rather than picking which item to keep.
+ALTER TABLE menu_items DROP FOREIGN KEY fk_menu_items_restaurant;
+ALTER TABLE menu_items
+    DROP INDEX uq_menu_items_restaurant_sku,
+    DROP COLUMN restaurant_id,
+    ADD UNIQUE KEY sku (sku);
+
+DROP TABLE IF EXISTS restaurants;
@@ -0,0 +1,20 @@
+-- Restaurants (tenants). Existing single-restaurant data belongs to restaurant 1, "default".
+-- admin_token_hash is the hex SHA-256 of the restaurant's own admin bearer token.
+CREATE TABLE IF NOT EXISTS restaurants (
+    id BIGINT NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    slug VARCHAR(64) NOT NULL UNIQUE,
+    name VARCHAR(200) NOT NULL,
+    admin_token_hash CHAR(64) NULL,
+    active BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP
+);
+
+INSERT IGNORE INTO restaurants (id, slug, name) VALUES (1, 'default', 'Default restaurant');
+
+-- Menu items are scoped per restaurant; skus only need to be unique within one.
+ALTER TABLE menu_items
+    ADD COLUMN restaurant_id BIGINT NOT NULL DEFAULT 1 AFTER id,
+    DROP INDEX sku,
+    ADD UNIQUE KEY uq_menu_items_restaurant_sku (restaurant_id, sku),
+    ADD CONSTRAINT fk_menu_items_restaurant FOREIGN KEY (restaurant_id) REFERENCES restaurants (id);
@@ -0,0 +1,11 @@
+ALTER TABLE webhook_subscriptions DROP FOREIGN KEY fk_webhook_subscriptions_restaurant;
+ALTER TABLE webhook_subscriptions
+    DROP INDEX idx_webhook_subscriptions_restaurant,
+    DROP COLUMN restaurant_id;
+
+-- Fails while two restaurants have zones of the same name.
+ALTER TABLE delivery_zones DROP FOREIGN KEY fk_delivery_zones_restaurant;
+ALTER TABLE delivery_zones
+    DROP INDEX uq_delivery_zones_restaurant_name,
+    DROP COLUMN restaurant_id,
+    ADD UNIQUE KEY name (name);
@@ -0,0 +1,17 @@
+-- Delivery zones and webhook subscriptions belong to a restaurant. Rows created before
+-- restaurants existed are backfilled to restaurant 1, which 0005 created for them.
+ALTER TABLE delivery_zones ADD COLUMN restaurant_id BIGINT NULL AFTER id;
+UPDATE delivery_zones SET restaurant_id = 1 WHERE restaurant_id IS NULL;
+-- Zone names only need to be unique within a restaurant.
+ALTER TABLE delivery_zones
+    MODIFY COLUMN restaurant_id BIGINT NOT NULL,
+    DROP INDEX name,
+    ADD UNIQUE KEY uq_delivery_zones_restaurant_name (restaurant_id, name),
+    ADD CONSTRAINT fk_delivery_zones_restaurant FOREIGN KEY (restaurant_id) REFERENCES restaurants (id);
+
+ALTER TABLE webhook_subscriptions ADD COLUMN restaurant_id BIGINT NULL AFTER id;
+UPDATE webhook_subscriptions SET restaurant_id = 1 WHERE restaurant_id IS NULL;
+ALTER TABLE webhook_subscriptions
+    MODIFY COLUMN restaurant_id BIGINT NOT NULL,
+    ADD INDEX idx_webhook_subscriptions_restaurant (restaurant_id, active),
+    ADD CONSTRAINT fk_webhook_subscriptions_restaurant FOREIGN KEY (restaurant_id) REFERENCES restaurants (id);
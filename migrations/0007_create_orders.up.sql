@@ -0,0 +1,46 @@
+-- Tables and hotel rooms guests can order from. token is printed as a QR code on the
+-- table or room card and identifies both the restaurant and the seat.
+CREATE TABLE IF NOT EXISTS dining_tables (
+    id BIGINT NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    restaurant_id BIGINT NOT NULL,
+    label VARCHAR(64) NOT NULL,
+    kind ENUM('table', 'room') NOT NULL DEFAULT 'table',
+    token CHAR(32) NOT NULL UNIQUE,
+    active BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE KEY uq_dining_tables_restaurant_label (restaurant_id, label),
+    CONSTRAINT fk_dining_tables_restaurant FOREIGN KEY (restaurant_id) REFERENCES restaurants (id)
+);
+
+-- Orders. Dine-in orders name the table they are served to instead of an address.
+CREATE TABLE IF NOT EXISTS orders (
+    id BIGINT NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    restaurant_id BIGINT NOT NULL,
+    fulfillment ENUM('delivery', 'dine_in') NOT NULL,
+    table_id BIGINT NULL,
+    status ENUM('placed', 'preparing', 'ready', 'served', 'out_for_delivery', 'delivered', 'cancelled')
+        NOT NULL DEFAULT 'placed',
+    subtotal_paise BIGINT NOT NULL,
+    tax_paise BIGINT NOT NULL,
+    delivery_fee_paise BIGINT NOT NULL DEFAULT 0,
+    total_paise BIGINT NOT NULL,
+    notes VARCHAR(500) NOT NULL DEFAULT '',
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
+    KEY idx_orders_restaurant_status (restaurant_id, status),
+    CONSTRAINT fk_orders_restaurant FOREIGN KEY (restaurant_id) REFERENCES restaurants (id),
+    CONSTRAINT fk_orders_table FOREIGN KEY (table_id) REFERENCES dining_tables (id)
+);
+
+-- Line items copy the name and price at order time so menu edits don't rewrite history.
+CREATE TABLE IF NOT EXISTS order_items (
+    id BIGINT NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    order_id BIGINT NOT NULL,
+    menu_item_id BIGINT NOT NULL,
+    sku VARCHAR(64) NOT NULL,
+    name VARCHAR(200) NOT NULL,
+    unit_price_paise BIGINT NOT NULL,
+    quantity INT NOT NULL,
+    CONSTRAINT fk_order_items_order FOREIGN KEY (order_id) REFERENCES orders (id) ON DELETE CASCADE,
+    CONSTRAINT fk_order_items_menu_item FOREIGN KEY (menu_item_id) REFERENCES menu_items (id)
+);
@@ -0,0 +1,24 @@
+-- Append-only record of privileged actions. The triggers reject any UPDATE or DELETE,
+-- so rows can't be rewritten even by code holding the application's credentials.
+CREATE TABLE IF NOT EXISTS audit_logs (
+    id BIGINT NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    occurred_at TIMESTAMP(3) NOT NULL DEFAULT CURRENT_TIMESTAMP(3),
+    actor VARCHAR(100) NOT NULL,
+    action VARCHAR(100) NOT NULL,
+    target_type VARCHAR(50) NOT NULL,
+    target_id VARCHAR(100) NOT NULL,
+    restaurant_id BIGINT NULL,
+    request_id VARCHAR(128) NOT NULL,
+    remote_addr VARCHAR(64) NOT NULL,
+    before_state JSON NULL,
+    after_state JSON NULL,
+    KEY idx_audit_logs_occurred (occurred_at),
+    KEY idx_audit_logs_target (target_type, target_id),
+    KEY idx_audit_logs_action (action)
+);
+
+CREATE TRIGGER audit_logs_no_update BEFORE UPDATE ON audit_logs FOR EACH ROW
+    SIGNAL SQLSTATE '45000' SET MESSAGE_TEXT = 'audit_logs is append-only';
+
+CREATE TRIGGER audit_logs_no_delete BEFORE DELETE ON audit_logs FOR EACH ROW
+    SIGNAL SQLSTATE '45000' SET MESSAGE_TEXT = 'audit_logs is append-only';
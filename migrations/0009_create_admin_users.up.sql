@@ -0,0 +1,11 @@
+-- Platform administrators, created with `server create-admin --phone`. Each has its own
+-- bearer token ("adm_<id>_<secret>"), accepted wherever ADMIN_API_TOKEN is.
+-- token_hash is the hex SHA-256 of the secret part.
+CREATE TABLE IF NOT EXISTS admin_users (
+    id BIGINT NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    phone VARCHAR(20) NOT NULL UNIQUE,
+    token_hash CHAR(64) NOT NULL,
+    active BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP
+);
@@ -0,0 +1,2 @@
+-- Bumped on every change to an order's lines, so concurrent edits can't overwrite each other.
+ALTER TABLE orders ADD COLUMN version INT NOT NULL DEFAULT 0 AFTER notes;
@@ -0,0 +1,3 @@
+ALTER TABLE order_items DROP COLUMN options;
+DROP TABLE IF EXISTS item_options;
+DROP TABLE IF EXISTS option_groups;
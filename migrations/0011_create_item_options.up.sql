@@ -0,0 +1,29 @@
+-- Customisation schema for menu items. Each item can have option groups (size,
+-- toppings, spice level); min_select/max_select bound how many of a group's options a
+-- guest picks, so a required single choice is 1/1 and optional add-ons are 0/n.
+CREATE TABLE IF NOT EXISTS option_groups (
+    id BIGINT NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    menu_item_id BIGINT NOT NULL,
+    name VARCHAR(100) NOT NULL,
+    min_select INT NOT NULL DEFAULT 0,
+    max_select INT NOT NULL DEFAULT 1,
+    position INT NOT NULL DEFAULT 0,
+    UNIQUE KEY uq_option_groups_item_name (menu_item_id, name),
+    CONSTRAINT fk_option_groups_menu_item FOREIGN KEY (menu_item_id) REFERENCES menu_items (id) ON DELETE CASCADE
+);
+
+-- price_delta_paise is added to the item's price for each unit ordered with the option.
+CREATE TABLE IF NOT EXISTS item_options (
+    id BIGINT NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    option_group_id BIGINT NOT NULL,
+    name VARCHAR(100) NOT NULL,
+    price_delta_paise BIGINT NOT NULL DEFAULT 0,
+    available BOOLEAN NOT NULL DEFAULT TRUE,
+    position INT NOT NULL DEFAULT 0,
+    UNIQUE KEY uq_item_options_group_name (option_group_id, name),
+    CONSTRAINT fk_item_options_group FOREIGN KEY (option_group_id) REFERENCES option_groups (id) ON DELETE CASCADE
+);
+
+-- The options chosen for a line, copied like the name and price so later schema edits
+-- don't rewrite history: [{"option_id", "group", "name", "price_delta_paise"}].
+ALTER TABLE order_items ADD COLUMN options JSON NOT NULL DEFAULT (JSON_ARRAY()) AFTER quantity;
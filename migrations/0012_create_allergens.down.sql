@@ -0,0 +1,6 @@
+ALTER TABLE menu_items
+    DROP COLUMN protein_grams,
+    DROP COLUMN calories_kcal;
+
+DROP TABLE IF EXISTS menu_item_allergens;
+DROP TABLE IF EXISTS allergens;
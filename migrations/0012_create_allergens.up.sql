@@ -0,0 +1,37 @@
+-- Allergens items can declare, by code. Menus filter on the codes and the kitchen view
+-- shows them on order lines.
+CREATE TABLE IF NOT EXISTS allergens (
+    id BIGINT NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    code VARCHAR(32) NOT NULL UNIQUE,
+    name VARCHAR(100) NOT NULL
+);
+
+INSERT IGNORE INTO allergens (code, name) VALUES
+    ('gluten', 'Cereals containing gluten'),
+    ('crustacean', 'Crustaceans'),
+    ('egg', 'Eggs'),
+    ('fish', 'Fish'),
+    ('peanut', 'Peanuts'),
+    ('soy', 'Soybeans'),
+    ('milk', 'Milk'),
+    ('tree_nut', 'Tree nuts'),
+    ('celery', 'Celery'),
+    ('mustard', 'Mustard'),
+    ('sesame', 'Sesame'),
+    ('sulphite', 'Sulphites'),
+    ('lupin', 'Lupin'),
+    ('mollusc', 'Molluscs');
+
+CREATE TABLE IF NOT EXISTS menu_item_allergens (
+    menu_item_id BIGINT NOT NULL,
+    allergen_id BIGINT NOT NULL,
+    PRIMARY KEY (menu_item_id, allergen_id),
+    KEY idx_menu_item_allergens_allergen (allergen_id),
+    CONSTRAINT fk_menu_item_allergens_item FOREIGN KEY (menu_item_id) REFERENCES menu_items (id) ON DELETE CASCADE,
+    CONSTRAINT fk_menu_item_allergens_allergen FOREIGN KEY (allergen_id) REFERENCES allergens (id)
+);
+
+-- Nutrition facts per serving; NULL when the restaurant hasn't provided them.
+ALTER TABLE menu_items
+    ADD COLUMN calories_kcal INT NULL AFTER price_paise,
+    ADD COLUMN protein_grams INT NULL AFTER calories_kcal;
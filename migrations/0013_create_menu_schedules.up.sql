@@ -0,0 +1,16 @@
+-- Availability windows for menu items. A row applies to one item (menu_item_id) or to
+-- every item in a category (category); an item's own rows replace its category's.
+-- weekdays is a bitmask with bit 0 = Monday; times are minutes after local midnight,
+-- and an end at or before the start runs past midnight.
+CREATE TABLE IF NOT EXISTS menu_schedules (
+    id BIGINT NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    restaurant_id BIGINT NOT NULL,
+    menu_item_id BIGINT NULL,
+    category VARCHAR(100) NULL,
+    weekdays TINYINT UNSIGNED NOT NULL,
+    start_minute INT UNSIGNED NOT NULL,
+    end_minute INT UNSIGNED NOT NULL,
+    KEY idx_menu_schedules_restaurant (restaurant_id),
+    CONSTRAINT fk_menu_schedules_restaurant FOREIGN KEY (restaurant_id) REFERENCES restaurants (id),
+    CONSTRAINT fk_menu_schedules_item FOREIGN KEY (menu_item_id) REFERENCES menu_items (id) ON DELETE CASCADE
+);
@@ -0,0 +1,5 @@
+-- Orders still waiting for the kitchen are admitted rather than lost.
+UPDATE orders SET status = 'placed' WHERE status = 'queued';
+ALTER TABLE orders MODIFY COLUMN status
+    ENUM('placed', 'preparing', 'ready', 'served', 'out_for_delivery', 'delivered', 'cancelled')
+    NOT NULL DEFAULT 'placed';
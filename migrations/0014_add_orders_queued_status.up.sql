@@ -0,0 +1,5 @@
+-- Orders placed while the kitchen is at capacity wait as 'queued' until they are
+-- admitted as 'placed', oldest first.
+ALTER TABLE orders MODIFY COLUMN status
+    ENUM('queued', 'placed', 'preparing', 'ready', 'served', 'out_for_delivery', 'delivered', 'cancelled')
+    NOT NULL DEFAULT 'placed';
@@ -0,0 +1,20 @@
+-- Complaints and refund requests guests raise about an order, and how staff settled
+-- them. first_response_at and resolved_at drive the SLA timers.
+CREATE TABLE IF NOT EXISTS support_tickets (
+    id BIGINT NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    restaurant_id BIGINT NOT NULL,
+    order_id BIGINT NOT NULL,
+    category ENUM('missing_items', 'wrong_items', 'late', 'quality', 'refund_request', 'other') NOT NULL,
+    description VARCHAR(1000) NOT NULL DEFAULT '',
+    status ENUM('open', 'in_progress', 'resolved', 'rejected') NOT NULL DEFAULT 'open',
+    resolution ENUM('partial_refund', 'full_refund', 'replacement', 'apology', 'no_action') NULL,
+    refund_paise BIGINT NOT NULL DEFAULT 0,
+    resolution_note VARCHAR(1000) NOT NULL DEFAULT '',
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    first_response_at TIMESTAMP NULL,
+    resolved_at TIMESTAMP NULL,
+    KEY idx_support_tickets_restaurant_status (restaurant_id, status, created_at),
+    KEY idx_support_tickets_order (order_id),
+    CONSTRAINT fk_support_tickets_restaurant FOREIGN KEY (restaurant_id) REFERENCES restaurants (id),
+    CONSTRAINT fk_support_tickets_order FOREIGN KEY (order_id) REFERENCES orders (id)
+);
@@ -0,0 +1,13 @@
+-- Feature flags, cached in memory by FlagService. A flag is on for a subject (an order,
+-- table, restaurant, ...) when it is enabled and the subject either has an override
+-- or falls in the first rollout_percent of 100 hash buckets. enabled = FALSE is the
+-- kill switch: the flag is off for everyone, overrides included.
+CREATE TABLE IF NOT EXISTS feature_flags (
+    flag_key VARCHAR(100) NOT NULL PRIMARY KEY,
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    rollout_percent TINYINT UNSIGNED NOT NULL DEFAULT 0,
+    -- Subject -> forced value, e.g. {"order:42": true}.
+    overrides JSON NOT NULL DEFAULT (JSON_OBJECT()),
+    description VARCHAR(500) NOT NULL DEFAULT '',
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP
+);
@@ -0,0 +1,26 @@
+-- A/B experiments. Dining tables are bucketed into variants (weights sum to 100) and
+-- the first time a table is shown a variant it is logged in experiment_exposures;
+-- conversions are that table's orders placed afterwards.
+CREATE TABLE IF NOT EXISTS experiments (
+    experiment_key VARCHAR(100) NOT NULL PRIMARY KEY,
+    -- [{"name": "control", "weight": 50}, {"name": "treatment", "weight": 50}]
+    variants JSON NOT NULL,
+    active BOOLEAN NOT NULL DEFAULT TRUE,
+    description VARCHAR(500) NOT NULL DEFAULT '',
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS experiment_exposures (
+    id BIGINT AUTO_INCREMENT PRIMARY KEY,
+    experiment_key VARCHAR(100) NOT NULL,
+    variant VARCHAR(50) NOT NULL,
+    restaurant_id BIGINT NOT NULL,
+    table_id BIGINT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE KEY uq_experiment_exposures_table (experiment_key, table_id),
+    KEY idx_experiment_exposures_variant (experiment_key, variant),
+    CONSTRAINT fk_experiment_exposures_experiment FOREIGN KEY (experiment_key)
+        REFERENCES experiments (experiment_key) ON DELETE CASCADE,
+    CONSTRAINT fk_experiment_exposures_table FOREIGN KEY (table_id) REFERENCES dining_tables (id)
+);
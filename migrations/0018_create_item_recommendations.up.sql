@@ -0,0 +1,14 @@
+-- "Frequently bought together": how many recent orders had both items, rebuilt
+-- nightly from order_items by recommendations::spawn_miner.
+CREATE TABLE IF NOT EXISTS item_recommendations (
+    restaurant_id BIGINT NOT NULL,
+    menu_item_id BIGINT NOT NULL,
+    recommended_item_id BIGINT NOT NULL,
+    together_count INT UNSIGNED NOT NULL,
+    computed_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (menu_item_id, recommended_item_id),
+    KEY idx_item_recommendations_restaurant (restaurant_id),
+    CONSTRAINT fk_item_recommendations_item FOREIGN KEY (menu_item_id) REFERENCES menu_items (id) ON DELETE CASCADE,
+    CONSTRAINT fk_item_recommendations_recommended FOREIGN KEY (recommended_item_id)
+        REFERENCES menu_items (id) ON DELETE CASCADE
+);
@@ -0,0 +1,16 @@
+-- Append-only history of every change to an order, numbered per order from 1. The
+-- orders and order_items rows are a read model that order_events::replay can rebuild.
+CREATE TABLE IF NOT EXISTS order_events (
+    id BIGINT NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    order_id BIGINT NOT NULL,
+    restaurant_id BIGINT NOT NULL,
+    sequence INT UNSIGNED NOT NULL,
+    event_type VARCHAR(50) NOT NULL,
+    payload JSON NOT NULL,
+    -- "table:<id>", an admin actor as in audit_logs, or "kitchen_queue".
+    actor VARCHAR(200) NOT NULL,
+    created_at TIMESTAMP(3) NOT NULL DEFAULT CURRENT_TIMESTAMP(3),
+    UNIQUE KEY uq_order_events_sequence (order_id, sequence),
+    KEY idx_order_events_restaurant (restaurant_id, created_at),
+    CONSTRAINT fk_order_events_order FOREIGN KEY (order_id) REFERENCES orders (id)
+);
@@ -0,0 +1,4 @@
+DROP TABLE IF EXISTS projection_cursors;
+DROP TABLE IF EXISTS projected_orders;
+DROP TABLE IF EXISTS item_popularity;
+DROP TABLE IF EXISTS daily_sales;
@@ -0,0 +1,43 @@
+-- Pre-aggregated sales, maintained from order_events by projections::spawn_worker so
+-- dashboards don't aggregate the live orders tables. Days are the restaurant-local
+-- date the order was placed (runtime config utc_offset_minutes).
+CREATE TABLE IF NOT EXISTS daily_sales (
+    restaurant_id BIGINT NOT NULL,
+    day DATE NOT NULL,
+    orders BIGINT NOT NULL DEFAULT 0,
+    cancelled_orders BIGINT NOT NULL DEFAULT 0,
+    -- Totals of orders not cancelled, tax included.
+    revenue_paise BIGINT NOT NULL DEFAULT 0,
+    PRIMARY KEY (restaurant_id, day)
+);
+
+CREATE TABLE IF NOT EXISTS item_popularity (
+    restaurant_id BIGINT NOT NULL,
+    day DATE NOT NULL,
+    menu_item_id BIGINT NOT NULL,
+    quantity BIGINT NOT NULL DEFAULT 0,
+    -- Line subtotals, before tax.
+    revenue_paise BIGINT NOT NULL DEFAULT 0,
+    PRIMARY KEY (restaurant_id, day, menu_item_id),
+    KEY idx_item_popularity_item (menu_item_id)
+);
+
+-- What each order currently contributes to the tables above, so a later event can
+-- take it back out before adding the order's new state.
+CREATE TABLE IF NOT EXISTS projected_orders (
+    order_id BIGINT NOT NULL PRIMARY KEY,
+    restaurant_id BIGINT NOT NULL,
+    day DATE NOT NULL,
+    cancelled BOOLEAN NOT NULL,
+    total_paise BIGINT NOT NULL,
+    -- [[menu_item_id, quantity, revenue_paise], ...]
+    line_totals JSON NOT NULL
+);
+
+-- How far each projection has read order_events.
+CREATE TABLE IF NOT EXISTS projection_cursors (
+    name VARCHAR(50) NOT NULL PRIMARY KEY,
+    last_event_id BIGINT NOT NULL DEFAULT 0
+);
+
+INSERT IGNORE INTO projection_cursors (name) VALUES ('sales');
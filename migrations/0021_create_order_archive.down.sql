@@ -0,0 +1,6 @@
+-- Archived lines go back to order_items so the orders keep their lines.
+INSERT INTO order_items (id, order_id, menu_item_id, sku, name, unit_price_paise, quantity, options)
+    SELECT id, order_id, menu_item_id, sku, name, unit_price_paise, quantity, options FROM archived_order_items;
+
+DROP TABLE IF EXISTS archived_order_items;
+ALTER TABLE orders DROP COLUMN archived_at;
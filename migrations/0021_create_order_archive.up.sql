@@ -0,0 +1,16 @@
+-- Finished orders older than runtime config order_archive_after_months are archived:
+-- their lines move to archived_order_items and the orders row stays behind as a stub
+-- (with archived_at set) so ids, foreign keys and lookups by id keep working.
+ALTER TABLE orders ADD COLUMN archived_at TIMESTAMP NULL DEFAULT NULL;
+
+CREATE TABLE IF NOT EXISTS archived_order_items (
+    id BIGINT NOT NULL PRIMARY KEY,
+    order_id BIGINT NOT NULL,
+    menu_item_id BIGINT NOT NULL,
+    sku VARCHAR(64) NOT NULL,
+    name VARCHAR(200) NOT NULL,
+    unit_price_paise BIGINT NOT NULL,
+    quantity INT NOT NULL,
+    options JSON NOT NULL,
+    KEY idx_archived_order_items_order (order_id)
+);
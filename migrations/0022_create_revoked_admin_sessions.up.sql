@@ -0,0 +1,9 @@
+-- Signed admin sessions (ADMIN_SESSION_SECRET) that were logged out before they expired.
+-- Every instance keeps the list in memory; rows are purged once the session would have
+-- expired anyway.
+CREATE TABLE IF NOT EXISTS revoked_admin_sessions (
+    session_id CHAR(36) NOT NULL PRIMARY KEY,
+    expires_at TIMESTAMP NOT NULL,
+    revoked_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    INDEX idx_revoked_admin_sessions_expiry (expires_at)
+);
@@ -0,0 +1,5 @@
+ALTER TABLE admin_users
+    DROP COLUMN recovery_code_hashes,
+    DROP COLUMN totp_last_step,
+    DROP COLUMN totp_enabled,
+    DROP COLUMN totp_secret;
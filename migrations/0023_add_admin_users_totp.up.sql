@@ -0,0 +1,9 @@
+-- Optional TOTP two-factor authentication for admin users. totp_secret is the base32
+-- shared secret; it only takes effect once totp_enabled is set by a confirmed code.
+-- totp_last_step is the last 30-second step a code was accepted for, so a code can't
+-- be replayed. recovery_code_hashes holds the hex SHA-256 of each unused recovery code.
+ALTER TABLE admin_users
+    ADD COLUMN totp_secret VARCHAR(64) NULL,
+    ADD COLUMN totp_enabled BOOLEAN NOT NULL DEFAULT FALSE,
+    ADD COLUMN totp_last_step BIGINT NULL,
+    ADD COLUMN recovery_code_hashes JSON NULL;
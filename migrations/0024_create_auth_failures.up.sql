@@ -0,0 +1,11 @@
+-- Failed second-factor attempts per identifier (e.g. scope 'admin_totp', identifier the
+-- admin user id), kept in the database so a restart or another instance doesn't reset
+-- them. A row is deleted on the next success.
+CREATE TABLE IF NOT EXISTS auth_failures (
+    scope VARCHAR(32) NOT NULL,
+    identifier VARCHAR(64) NOT NULL,
+    failures INT UNSIGNED NOT NULL,
+    last_failure_at TIMESTAMP NOT NULL,
+    blocked_until TIMESTAMP NULL,
+    PRIMARY KEY (scope, identifier)
+);
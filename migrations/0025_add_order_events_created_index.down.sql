@@ -0,0 +1 @@
+ALTER TABLE order_events DROP KEY idx_order_events_created;
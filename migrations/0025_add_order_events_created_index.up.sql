@@ -0,0 +1,3 @@
+-- projections::run_once replays the orders with events from the last couple of minutes
+-- on every pass, to pick up events that committed after the cursor passed their id.
+ALTER TABLE order_events ADD KEY idx_order_events_created (created_at);
@@ -0,0 +1,3 @@
+-- Identity (subject CN, else first DNS SAN) of the client certificate an admin user
+-- presents on the mTLS admin listener. Certificates no admin user claims are refused.
+ALTER TABLE admin_users ADD COLUMN client_identity VARCHAR(255) NULL UNIQUE AFTER phone;
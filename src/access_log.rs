@@ -0,0 +1,129 @@
+use crate::config;
+use crate::logging::{FileLog, HttpShipper, RotatingFile, Rotation};
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use std::borrow::Cow;
+
+// One access-log record, built by the server for every request it answers.
+#[derive(Debug, Clone, Serialize)]
+pub struct RequestLog {
+    pub timestamp: String,
+    pub request_id: Option<String>,
+    pub method: String,
+    pub path: String,
+    pub status: u16,
+    pub latency_ms: f64,
+    pub user_id: Option<String>,
+    pub bytes_received: usize,
+    // Size of the response body produced, whether or not it reached the client.
+    pub response_bytes: usize,
+    pub bytes_sent: usize,
+    pub remote_addr: String,
+}
+
+// Path prefixes whose next segment is a credential: the table token of GET
+// /api/tables/:token/orders lets anyone holding it order on that table's bill.
+const SECRET_SEGMENT_AFTER: &[&str] = &["/api/tables/"];
+
+// `path` as it may be logged, with credential segments replaced by ":token". Every
+// log event that records a request path passes it through this.
+pub fn redacted_path(path: &str) -> Cow<'_, str> {
+    for prefix in SECRET_SEGMENT_AFTER {
+        let Some(start) = path.find(prefix).map(|at| at + prefix.len()) else {
+            continue;
+        };
+        let end = path[start..].find('/').map_or(path.len(), |len| start + len);
+        if end > start {
+            return Cow::Owned(format!("{}:token{}", &path[..start], &path[end..]));
+        }
+    }
+    Cow::Borrowed(path)
+}
+
+// Response extension a handler can set to attribute the request to a user in the access log.
+#[derive(Debug, Clone)]
+pub struct LoggedUser(pub String);
+
+// Destination for access-log records.
+pub trait AccessLogSink: Send + Sync {
+    fn emit(&self, log: &RequestLog);
+}
+
+// Writes each record as a JSON line on stdout.
+pub struct StdoutSink;
+
+impl AccessLogSink for StdoutSink {
+    fn emit(&self, log: &RequestLog) {
+        if let Ok(line) = serde_json::to_string(log) {
+            println!("{line}");
+        }
+    }
+}
+
+// Appends JSON lines to a size-rotated file.
+pub struct FileSink {
+    file: FileLog,
+}
+
+impl FileSink {
+    pub fn new(file: RotatingFile) -> Self {
+        Self { file: FileLog::new(file) }
+    }
+}
+
+impl AccessLogSink for FileSink {
+    fn emit(&self, log: &RequestLog) {
+        if let Ok(line) = serde_json::to_string(log) {
+            self.file.write_line(&line);
+        }
+    }
+}
+
+// Ships records in batches to an HTTP collector.
+pub struct HttpSink {
+    shipper: HttpShipper,
+}
+
+impl HttpSink {
+    // Starts the background shipper task. Must be called inside the tokio runtime.
+    pub fn spawn(url: String) -> Self {
+        Self {
+            shipper: HttpShipper::spawn(url),
+        }
+    }
+}
+
+impl AccessLogSink for HttpSink {
+    fn emit(&self, log: &RequestLog) {
+        if let Ok(value) = serde_json::to_value(log) {
+            self.shipper.send(value);
+        }
+    }
+}
+
+// Builds the sink selected by ACCESS_LOG_SINK: "stdout" (default), "file" or "http".
+//   file: ACCESS_LOG_FILE (default access.log), ACCESS_LOG_MAX_BYTES (default 100 MiB),
+//         ACCESS_LOG_MAX_FILES (default 5)
+//   http: ACCESS_LOG_HTTP_URL
+pub fn sink_from_env() -> Result<Box<dyn AccessLogSink>> {
+    let sink = config::var("ACCESS_LOG_SINK").unwrap_or_else(|| "stdout".into());
+    match sink.as_str() {
+        "stdout" => Ok(Box::new(StdoutSink)),
+        "file" => {
+            let path = config::var("ACCESS_LOG_FILE").unwrap_or_else(|| "access.log".into());
+            let max_bytes = config::var("ACCESS_LOG_MAX_BYTES")
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(100 * 1024 * 1024);
+            let max_files = config::var("ACCESS_LOG_MAX_FILES")
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(5);
+            Ok(Box::new(FileSink::new(RotatingFile::open(path, Rotation::Size(max_bytes), max_files)?)))
+        }
+        "http" => {
+            let url = config::var("ACCESS_LOG_HTTP_URL")
+                .ok_or_else(|| anyhow!("ACCESS_LOG_HTTP_URL must be set when ACCESS_LOG_SINK=http"))?;
+            Ok(Box::new(HttpSink::spawn(url)))
+        }
+        other => Err(anyhow!("unknown ACCESS_LOG_SINK: {other}")),
+    }
+}
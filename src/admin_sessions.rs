@@ -0,0 +1,229 @@
+use crate::app::AppServices;
+use crate::audit::AuditLogger;
+use crate::auth::AdminUserRepository;
+use crate::config;
+use crate::error::{AppError, AppResult};
+use crate::logging;
+use crate::openapi::ApiSchema;
+use crate::request::RequestContext;
+use crate::response::{CachePolicy, ResponseBuilder};
+use crate::router::Router;
+use crate::totp::{self, SecondFactor};
+use anyhow::{anyhow, Result};
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use http::{Response, StatusCode};
+use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sqlx::mysql::MySqlPool;
+use std::collections::HashSet;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use uuid::Uuid;
+
+// Stateless admin sessions. Checking an admin user's token ("adm_<id>_<secret>") costs
+// a database lookup on every request; with ADMIN_SESSION_SECRET set, an admin user can
+// trade it for a short-lived signed session token (HS256 JWT) that is checked by its
+// signature alone. Logging out revokes the session: revocations are stored in
+// revoked_admin_sessions and every instance polls them into memory, so a logged-out
+// session stops working everywhere within REVOCATION_REFRESH. A deactivated admin
+// user's sessions keep working until they expire.
+//   ADMIN_SESSION_SECRET    HMAC key of session tokens; unset disables sessions
+//   ADMIN_SESSION_TTL_SECS  lifetime of a session token (default 900)
+
+const REVOCATION_REFRESH: Duration = Duration::from_secs(5);
+const ROLE_ADMIN: &str = "admin";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Claims {
+    // Admin user id.
+    pub sub: i64,
+    pub role: String,
+    // Session id, the key revocations are stored under.
+    pub sid: String,
+    pub iat: i64,
+    pub exp: i64,
+}
+
+// Response of POST /api/admin/sessions.
+#[derive(Debug, Serialize)]
+pub struct IssuedSession {
+    pub token: String,
+    pub session_id: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl ApiSchema for IssuedSession {
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["token", "session_id", "expires_at"],
+            "properties": {
+                "token": { "type": "string" },
+                "session_id": { "type": "string", "format": "uuid" },
+                "expires_at": { "type": "string", "format": "date-time" }
+            }
+        })
+    }
+}
+
+struct Keys {
+    encoding: EncodingKey,
+    decoding: DecodingKey,
+}
+
+pub struct AdminSessions {
+    // None when ADMIN_SESSION_SECRET is unset.
+    keys: Option<Keys>,
+    ttl_secs: i64,
+    revoked: RwLock<Arc<HashSet<String>>>,
+}
+
+impl AdminSessions {
+    pub fn from_env() -> Result<Self> {
+        let keys = match config::var("ADMIN_SESSION_SECRET") {
+            Some(secret) if secret.len() < 32 => return Err(anyhow!("ADMIN_SESSION_SECRET must be at least 32 bytes")),
+            Some(secret) => Some(Keys {
+                encoding: EncodingKey::from_secret(secret.as_bytes()),
+                decoding: DecodingKey::from_secret(secret.as_bytes()),
+            }),
+            None => None,
+        };
+        let ttl_secs = match config::var("ADMIN_SESSION_TTL_SECS") {
+            Some(v) => v
+                .parse::<i64>()
+                .ok()
+                .filter(|secs| (60..=86_400).contains(secs))
+                .ok_or_else(|| anyhow!("ADMIN_SESSION_TTL_SECS must be between 60 and 86400"))?,
+            None => 900,
+        };
+        Ok(Self { keys, ttl_secs, revoked: RwLock::default() })
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.keys.is_some()
+    }
+
+    // Signs a new session for `admin_id`.
+    pub fn issue(&self, admin_id: i64) -> Result<IssuedSession> {
+        let keys = self.keys.as_ref().ok_or_else(|| anyhow!("admin sessions are disabled"))?;
+        let now = Utc::now();
+        let claims = Claims {
+            sub: admin_id,
+            role: ROLE_ADMIN.to_string(),
+            sid: Uuid::new_v4().to_string(),
+            iat: now.timestamp(),
+            exp: now.timestamp() + self.ttl_secs,
+        };
+        let token = encode(&Header::new(Algorithm::HS256), &claims, &keys.encoding)?;
+        let expires_at = DateTime::from_timestamp(claims.exp, 0).unwrap_or(now);
+        Ok(IssuedSession { token, session_id: claims.sid, expires_at })
+    }
+
+    // The claims of `token` if it is a valid, unexpired and unrevoked session. No
+    // database access.
+    pub fn verify(&self, token: &str) -> Option<Claims> {
+        let keys = self.keys.as_ref()?;
+        let mut validation = Validation::new(Algorithm::HS256);
+        validation.leeway = 0;
+        let claims = decode::<Claims>(token, &keys.decoding, &validation).ok()?.claims;
+        if claims.role != ROLE_ADMIN || self.is_revoked(&claims.sid) {
+            return None;
+        }
+        Some(claims)
+    }
+
+    fn is_revoked(&self, session_id: &str) -> bool {
+        self.revoked.read().unwrap_or_else(|p| p.into_inner()).contains(session_id)
+    }
+
+    // Revokes the session, here at once and on other instances at their next refresh.
+    pub async fn revoke(&self, pool: &MySqlPool, claims: &Claims) -> Result<()> {
+        let expires_at = DateTime::from_timestamp(claims.exp, 0).unwrap_or_else(Utc::now);
+        sqlx::query("INSERT IGNORE INTO revoked_admin_sessions (session_id, expires_at) VALUES (?, ?)")
+            .bind(&claims.sid)
+            .bind(expires_at)
+            .execute(pool)
+            .await?;
+        let mut revoked = self.revoked.write().unwrap_or_else(|p| p.into_inner());
+        let mut fresh = HashSet::clone(&revoked);
+        fresh.insert(claims.sid.clone());
+        *revoked = Arc::new(fresh);
+        Ok(())
+    }
+
+    // Reloads the revocations that still matter and drops the ones past expiry.
+    pub async fn refresh(&self, pool: &MySqlPool) -> Result<()> {
+        sqlx::query("DELETE FROM revoked_admin_sessions WHERE expires_at < NOW()")
+            .execute(pool)
+            .await?;
+        let ids: Vec<String> = sqlx::query_scalar("SELECT session_id FROM revoked_admin_sessions")
+            .fetch_all(pool)
+            .await?;
+        *self.revoked.write().unwrap_or_else(|p| p.into_inner()) = Arc::new(ids.into_iter().collect());
+        Ok(())
+    }
+
+    pub fn spawn_refresh(self: Arc<Self>, pool: MySqlPool) {
+        if !self.enabled() {
+            return;
+        }
+        tokio::spawn(async move {
+            loop {
+                if let Err(err) = self.refresh(&pool).await {
+                    logging::warn("admin session revocation refresh failed", json!({ "error": format!("{err:#}") }));
+                }
+                tokio::time::sleep(REVOCATION_REFRESH).await;
+            }
+        });
+    }
+}
+
+// Registers the admin session endpoints.
+pub fn routes(router: Router) -> Router {
+    router
+        .post("/api/admin/sessions", create_session)
+        .summary("Exchange an admin user token, plus a two-factor code if enrolled, for a short-lived session token")
+        .cache(CachePolicy::NoStore)
+        .response_schema(IssuedSession::schema())
+        .delete("/api/admin/sessions/current", end_session)
+        .summary("Log out: revoke the session token sent with the request")
+}
+
+// Only an admin user's own token is exchanged: the global ADMIN_API_TOKEN names no
+// user, and a session can't be renewed with itself. Users with two-factor
+// authentication also send a current or recovery code (see totp::SecondFactor).
+async fn create_session(ctx: RequestContext, services: Arc<AppServices>) -> AppResult<Response<Bytes>> {
+    if !services.admin_sessions.enabled() {
+        return Err(AppError::ServiceUnavailable(
+            "admin sessions are disabled (ADMIN_SESSION_SECRET not set)".to_string(),
+        ));
+    }
+    let Some(token) = ctx.bearer_token() else {
+        return Err(AppError::Unauthorized("missing bearer token".to_string()));
+    };
+    let pool = services.db()?;
+    let credential = AdminUserRepository::new(pool)
+        .find(token)
+        .await?
+        .ok_or_else(|| AppError::Forbidden("sessions are issued for admin user tokens only".to_string()))?;
+    if credential.two_factor {
+        let factor: SecondFactor = if ctx.body.is_empty() { SecondFactor::default() } else { ctx.json()? };
+        totp::verify(pool, credential.id, &factor, &AuditLogger::for_request(&ctx, &services)).await?;
+    }
+    let session = services.admin_sessions.issue(credential.id)?;
+    ResponseBuilder::json(StatusCode::CREATED, &session)
+}
+
+async fn end_session(ctx: RequestContext, services: Arc<AppServices>) -> AppResult<Response<Bytes>> {
+    let Some(token) = ctx.bearer_token() else {
+        return Err(AppError::Unauthorized("missing bearer token".to_string()));
+    };
+    let claims = services
+        .admin_sessions
+        .verify(token)
+        .ok_or_else(|| AppError::Unauthorized("not a valid session token".to_string()))?;
+    services.admin_sessions.revoke(services.db()?, &claims).await?;
+    ResponseBuilder::no_content()
+}
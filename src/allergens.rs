@@ -0,0 +1,245 @@
+use crate::audit::{AuditEvent, AuditLogger};
+use crate::error::{AppError, AppResult, FieldError};
+use crate::extract::{Path, State, TenantAdmin, ValidJson};
+use crate::openapi::ApiSchema;
+use crate::response::{CachePolicy, ResponseBuilder};
+use crate::router::Router;
+use crate::validation::Validate;
+use anyhow::Result;
+use bytes::Bytes;
+use http::{Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sqlx::mysql::{MySql, MySqlPool};
+use std::collections::HashMap;
+
+// Allergens and nutrition facts of menu items. Allergens are a fixed list of codes
+// (migration 0012) that items link to; guests filter the menu on them and the kitchen
+// sees them on every order line.
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct Allergen {
+    pub code: String,
+    pub name: String,
+}
+
+impl ApiSchema for Allergen {
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["code", "name"],
+            "properties": {
+                "code": { "type": "string" },
+                "name": { "type": "string" }
+            }
+        })
+    }
+}
+
+// Body of PUT /api/admin/menu/items/:sku/dietary, and its response. The allergen list
+// is complete: codes left out are removed from the item.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Dietary {
+    pub allergens: Vec<String>,
+    pub calories_kcal: Option<i32>,
+    pub protein_grams: Option<i32>,
+}
+
+impl ApiSchema for Dietary {
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["allergens"],
+            "properties": {
+                "allergens": { "type": "array", "maxItems": 32, "items": { "type": "string" } },
+                "calories_kcal": { "type": ["integer", "null"], "minimum": 0, "maximum": 10000 },
+                "protein_grams": { "type": ["integer", "null"], "minimum": 0, "maximum": 1000 }
+            }
+        })
+    }
+}
+
+impl Validate for Dietary {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        if self.calories_kcal.is_some_and(|kcal| kcal < 0) {
+            errors.push(FieldError::new("calories_kcal", "must not be negative"));
+        }
+        if self.protein_grams.is_some_and(|grams| grams < 0) {
+            errors.push(FieldError::new("protein_grams", "must not be negative"));
+        }
+        errors
+    }
+}
+
+// Allergen codes of the given menu items, keyed by item id and sorted.
+pub async fn for_items<'e, E>(executor: E, menu_item_ids: &[i64]) -> Result<HashMap<i64, Vec<String>>>
+where
+    E: sqlx::Executor<'e, Database = MySql>,
+{
+    if menu_item_ids.is_empty() {
+        return Ok(HashMap::new());
+    }
+    let placeholders = vec!["?"; menu_item_ids.len()].join(", ");
+    let sql = format!(
+        "SELECT mia.menu_item_id, a.code FROM menu_item_allergens mia \
+         JOIN allergens a ON a.id = mia.allergen_id \
+         WHERE mia.menu_item_id IN ({placeholders}) ORDER BY a.code"
+    );
+    let mut query = sqlx::query_as::<_, (i64, String)>(&sql);
+    for id in menu_item_ids {
+        query = query.bind(id);
+    }
+    let mut codes: HashMap<i64, Vec<String>> = HashMap::new();
+    for (item_id, code) in query.fetch_all(executor).await? {
+        codes.entry(item_id).or_default().push(code);
+    }
+    Ok(codes)
+}
+
+pub struct AllergenRepository<'a> {
+    pool: &'a MySqlPool,
+}
+
+impl<'a> AllergenRepository<'a> {
+    pub fn new(pool: &'a MySqlPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn list(&self) -> Result<Vec<Allergen>> {
+        Ok(sqlx::query_as("SELECT code, name FROM allergens ORDER BY code")
+            .fetch_all(self.pool)
+            .await?)
+    }
+
+    // Checks a comma-separated list of codes, e.g. "peanut,gluten", as given in a query
+    // parameter. Blank entries are ignored; unknown codes are a validation error on `field`.
+    pub async fn parse_codes(&self, field: &str, list: &str) -> AppResult<Vec<String>> {
+        let codes: Vec<String> = list
+            .split(',')
+            .map(|code| code.trim().to_ascii_lowercase())
+            .filter(|code| !code.is_empty())
+            .collect();
+        self.check(field, &codes).await?;
+        Ok(codes)
+    }
+
+    async fn check(&self, field: &str, codes: &[String]) -> AppResult<()> {
+        let known = self.list().await?;
+        let unknown: Vec<&str> = codes
+            .iter()
+            .filter(|code| !known.iter().any(|allergen| allergen.code == **code))
+            .map(String::as_str)
+            .collect();
+        if !unknown.is_empty() {
+            return Err(AppError::Validation(vec![FieldError::new(
+                field,
+                format!("unknown allergen {}; see GET /api/allergens", unknown.join(", ")),
+            )]));
+        }
+        Ok(())
+    }
+
+    // Sets an item's allergens and nutrition facts. The item's updated_at is bumped even
+    // when only allergens change, so cached menus revalidate.
+    pub async fn set_for_item(
+        &self,
+        restaurant_id: i64,
+        sku: &str,
+        input: &Dietary,
+        audit: &AuditLogger,
+    ) -> AppResult<Dietary> {
+        let mut codes: Vec<String> = input.allergens.iter().map(|code| code.trim().to_ascii_lowercase()).collect();
+        codes.sort();
+        codes.dedup();
+        self.check("allergens", &codes).await?;
+
+        let mut tx = self.pool.begin().await?;
+        let item: Option<(i64, Option<i32>, Option<i32>)> = sqlx::query_as(
+            "SELECT id, calories_kcal, protein_grams FROM menu_items WHERE restaurant_id = ? AND sku = ? FOR UPDATE",
+        )
+        .bind(restaurant_id)
+        .bind(sku)
+        .fetch_optional(&mut *tx)
+        .await?;
+        let (item_id, calories_kcal, protein_grams) =
+            item.ok_or_else(|| AppError::NotFound("menu item not found".to_string()))?;
+        let before = Dietary {
+            allergens: for_items(&mut *tx, &[item_id]).await?.remove(&item_id).unwrap_or_default(),
+            calories_kcal,
+            protein_grams,
+        };
+
+        sqlx::query(
+            "UPDATE menu_items SET calories_kcal = ?, protein_grams = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?",
+        )
+        .bind(input.calories_kcal)
+        .bind(input.protein_grams)
+        .bind(item_id)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query("DELETE FROM menu_item_allergens WHERE menu_item_id = ?")
+            .bind(item_id)
+            .execute(&mut *tx)
+            .await?;
+        for code in &codes {
+            sqlx::query(
+                "INSERT INTO menu_item_allergens (menu_item_id, allergen_id) \
+                 SELECT ?, id FROM allergens WHERE code = ?",
+            )
+            .bind(item_id)
+            .bind(code)
+            .execute(&mut *tx)
+            .await?;
+        }
+        let after = Dietary { allergens: codes, calories_kcal: input.calories_kcal, protein_grams: input.protein_grams };
+
+        let (old, new) = (json!(before), json!(after));
+        if old != new {
+            let event = AuditEvent {
+                action: "menu.dietary",
+                target_type: "menu_item",
+                target_id: sku.to_string(),
+                restaurant_id: Some(restaurant_id),
+                before: Some(old),
+                after: Some(new),
+            };
+            audit.record(&mut *tx, event).await?;
+        }
+        tx.commit().await?;
+        Ok(after)
+    }
+}
+
+// Registers the allergen list and the admin endpoint that sets an item's dietary data.
+pub fn routes(router: Router) -> Router {
+    router
+        .get("/api/allergens", list_allergens)
+        .summary("Allergen codes items can declare and the menu can be filtered on")
+        .cache(CachePolicy::public(3600))
+        .response_schema(json!({ "type": "array", "items": Allergen::schema() }))
+        .put("/api/admin/menu/items/:sku/dietary", set_dietary)
+        .summary("Set an item's allergens and nutrition facts")
+        .urgency(6)
+        .requires_tenant_admin()
+        .request_schema(Dietary::schema())
+        .response_schema(Dietary::schema())
+}
+
+async fn list_allergens(State(services): State) -> AppResult<Response<Bytes>> {
+    let allergens = AllergenRepository::new(services.db()?).list().await?;
+    ResponseBuilder::json(StatusCode::OK, &allergens)
+}
+
+async fn set_dietary(
+    TenantAdmin(restaurant): TenantAdmin,
+    Path(sku): Path<String>,
+    ValidJson(input): ValidJson<Dietary>,
+    audit: AuditLogger,
+    State(services): State,
+) -> AppResult<Response<Bytes>> {
+    let dietary = AllergenRepository::new(services.db()?)
+        .set_for_item(restaurant.id, &sku, &input, &audit)
+        .await?;
+    ResponseBuilder::json(StatusCode::OK, &dietary)
+}
@@ -0,0 +1,124 @@
+use crate::access_log::{self, AccessLogSink};
+use crate::admin_sessions::AdminSessions;
+use crate::backpressure::TaskLimits;
+use crate::config;
+use crate::connections::ConnectionRegistry;
+use crate::db_breaker::{self, DbBreaker};
+use crate::error::{AppError, AppResult};
+use crate::faults;
+use crate::feature_flags::FlagService;
+use crate::load_shed::LoadShedder;
+use crate::menu_search::SuggestCache;
+use crate::multipart::MultipartLimits;
+use crate::qlog::QlogConfig;
+use crate::redis::RedisClient;
+use crate::runtime_config::ConfigService;
+use crate::scheduler::PriorityScheduler;
+use crate::security::{CorsConfig, SecurityHeaders};
+use crate::server::BodyLimits;
+use anyhow::{anyhow, Result};
+use sqlx::mysql::{MySqlConnectOptions, MySqlPool, MySqlPoolOptions};
+use std::str::FromStr;
+use std::sync::Arc;
+
+// Shared services handed to every request handler.
+pub struct AppServices {
+    // MySQL pool, present only when DATABASE_URL is configured.
+    pub db: Option<MySqlPool>,
+    // Fails database requests fast while the database is unreachable.
+    pub db_breaker: Arc<DbBreaker>,
+    // Where per-request access-log records are written (ACCESS_LOG_SINK).
+    pub access_log: Box<dyn AccessLogSink>,
+    // Shared Redis (REDIS_URL) for cross-instance cache, pub/sub and rate limits.
+    pub redis: Option<Arc<RedisClient>>,
+    // Size limits applied to multipart/form-data uploads while they stream in.
+    pub multipart_limits: MultipartLimits,
+    // Request body cap and large-response warning threshold.
+    pub body_limits: BodyLimits,
+    // Signed short-lived admin sessions and their revocations (ADMIN_SESSION_SECRET).
+    pub admin_sessions: Arc<AdminSessions>,
+    // Business settings from system_configurations, cached and refreshed in the background.
+    pub runtime_config: Arc<ConfigService>,
+    // Feature flags from feature_flags, cached and refreshed in the background.
+    pub feature_flags: Arc<FlagService>,
+    // In-memory menu autocomplete indexes, one per restaurant.
+    pub menu_suggest: SuggestCache,
+    // Cross-origin policy and headers added to every response.
+    pub cors: CorsConfig,
+    pub security_headers: SecurityHeaders,
+    // Open QUIC connections and their transport statistics.
+    pub connections: ConnectionRegistry,
+    // Where and for which connections qlog traces are written.
+    pub qlog: QlogConfig,
+    // Overload detection; low-priority requests are shed while it trips.
+    pub load_shedder: Arc<LoadShedder>,
+    // Orders handler execution by urgency when HANDLER_CONCURRENCY caps it.
+    pub scheduler: PriorityScheduler,
+    // Caps on connection and request tasks; requests past the queue are refused with 503.
+    pub task_limits: TaskLimits,
+}
+
+impl AppServices {
+    // Builds the services from environment variables.
+    // The pool is created lazily so the server can start before the database is reachable.
+    // Each connection caches up to DB_STATEMENT_CACHE_CAPACITY prepared statements
+    // (default 256); capacity times pool size must stay under the server's
+    // max_prepared_stmt_count.
+    pub fn from_env() -> Result<Self> {
+        let db = match config::var("DATABASE_URL") {
+            Some(url) => {
+                let capacity = match config::var("DB_STATEMENT_CACHE_CAPACITY") {
+                    Some(v) => v
+                        .parse::<usize>()
+                        .ok()
+                        .filter(|n| (1..=1024).contains(n))
+                        .ok_or_else(|| anyhow!("DB_STATEMENT_CACHE_CAPACITY must be between 1 and 1024"))?,
+                    None => 256,
+                };
+                let options = MySqlConnectOptions::from_str(&url)?.statement_cache_capacity(capacity);
+                Some(MySqlPoolOptions::new().connect_lazy_with(options))
+            }
+            None => None,
+        };
+        Ok(Self {
+            db,
+            db_breaker: Arc::new(DbBreaker::from_env()?),
+            access_log: access_log::sink_from_env()?,
+            redis: RedisClient::from_env()?.map(Arc::new),
+            multipart_limits: MultipartLimits::from_env(),
+            body_limits: BodyLimits::from_env(),
+            admin_sessions: Arc::new(AdminSessions::from_env()?),
+            runtime_config: Arc::new(ConfigService::new()),
+            feature_flags: Arc::new(FlagService::new()),
+            menu_suggest: SuggestCache::new(),
+            cors: CorsConfig::from_env()?,
+            security_headers: SecurityHeaders::from_env()?,
+            connections: ConnectionRegistry::new(),
+            qlog: QlogConfig::from_env(),
+            load_shedder: Arc::new(LoadShedder::from_env()?),
+            scheduler: PriorityScheduler::from_env(),
+            task_limits: TaskLimits::from_env()?,
+        })
+    }
+
+    // Bearer token required by admin endpoints (ADMIN_API_TOKEN). Read on every call so a
+    // token rotated in the secrets manager takes effect without a restart.
+    pub fn admin_token(&self) -> Option<String> {
+        config::var("ADMIN_API_TOKEN")
+    }
+
+    // Returns the database pool, or an error if the server runs without one or the
+    // database circuit breaker is open.
+    pub fn db(&self) -> AppResult<&MySqlPool> {
+        let pool = self
+            .db
+            .as_ref()
+            .ok_or_else(|| AppError::ServiceUnavailable("database is not configured".to_string()))?;
+        self.db_breaker.check()?;
+        db_breaker::touch();
+        if let Some(err) = faults::database_unavailable() {
+            return Err(err.into());
+        }
+        Ok(pool)
+    }
+}
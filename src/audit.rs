@@ -0,0 +1,281 @@
+use crate::app::AppServices;
+use crate::auth::{admin_user_id, constant_time_eq};
+use crate::error::{AppError, AppResult};
+use crate::openapi::ApiSchema;
+use crate::request::RequestContext;
+use crate::response::{Pagination, ResponseBuilder};
+use crate::router::Router;
+use crate::tables::DiningTable;
+use anyhow::Result;
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use http::{Response, StatusCode};
+use serde::Serialize;
+use serde_json::{json, Value};
+use sqlx::mysql::MySqlPool;
+use sqlx::{MySql, QueryBuilder};
+use std::sync::Arc;
+
+// Who made a privileged change: "admin" for the global ADMIN_API_TOKEN, "admin_user:<id>"
+// for an admin user's token, session or client certificate, "tenant_admin" for a restaurant's own token (the row's
+// restaurant_id says which).
+pub fn actor(ctx: &RequestContext, services: &AppServices) -> String {
+    if let Some(identity) = &ctx.client_identity {
+        return format!("admin_user:{}", identity.admin_user_id);
+    }
+    let global = ctx
+        .bearer_token()
+        .zip(services.admin_token())
+        .is_some_and(|(token, expected)| constant_time_eq(token.as_bytes(), expected.as_bytes()));
+    if global {
+        return "admin".to_string();
+    }
+    if let Some(claims) = ctx.bearer_token().and_then(|token| services.admin_sessions.verify(token)) {
+        return format!("admin_user:{}", claims.sub);
+    }
+    // Only reached after the token was verified, so its id can be trusted.
+    match ctx.bearer_token().and_then(admin_user_id) {
+        Some(id) => format!("admin_user:{id}"),
+        None => "tenant_admin".to_string(),
+    }
+}
+
+// One change to record. `before`/`after` are snapshots of the target; None for
+// creations and deletions respectively.
+pub struct AuditEvent<'a> {
+    pub action: &'a str,
+    pub target_type: &'a str,
+    pub target_id: String,
+    pub restaurant_id: Option<i64>,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+}
+
+// Records privileged actions on behalf of one request. Pass the caller's transaction
+// so the entry commits or rolls back with the change it describes.
+pub struct AuditLogger {
+    actor: String,
+    request_id: String,
+    remote_addr: String,
+}
+
+impl AuditLogger {
+    pub fn for_request(ctx: &RequestContext, services: &AppServices) -> Self {
+        Self {
+            actor: actor(ctx, services),
+            request_id: ctx.request_id.clone(),
+            remote_addr: ctx.remote_addr.ip().to_string(),
+        }
+    }
+
+    // For changes a guest makes with a table token; the actor is "table:<id>".
+    pub fn as_guest(self, table: &DiningTable) -> Self {
+        Self { actor: format!("table:{}", table.id), ..self }
+    }
+
+    pub fn actor(&self) -> &str {
+        &self.actor
+    }
+
+    pub async fn record<'e, E>(&self, executor: E, event: AuditEvent<'_>) -> Result<()>
+    where
+        E: sqlx::Executor<'e, Database = MySql>,
+    {
+        sqlx::query(
+            "INSERT INTO audit_logs (actor, action, target_type, target_id, restaurant_id, request_id, \
+             remote_addr, before_state, after_state) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(&self.actor)
+        .bind(event.action)
+        .bind(event.target_type)
+        .bind(&event.target_id)
+        .bind(event.restaurant_id)
+        .bind(&self.request_id)
+        .bind(&self.remote_addr)
+        .bind(event.before.map(|v| v.to_string()))
+        .bind(event.after.map(|v| v.to_string()))
+        .execute(executor)
+        .await?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub occurred_at: DateTime<Utc>,
+    pub actor: String,
+    pub action: String,
+    pub target_type: String,
+    pub target_id: String,
+    pub restaurant_id: Option<i64>,
+    pub request_id: String,
+    pub remote_addr: String,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+}
+
+impl ApiSchema for AuditEntry {
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["id", "occurred_at", "actor", "action", "target_type", "target_id", "request_id", "remote_addr"],
+            "properties": {
+                "id": { "type": "integer" },
+                "occurred_at": { "type": "string", "format": "date-time" },
+                "actor": { "type": "string" },
+                "action": { "type": "string" },
+                "target_type": { "type": "string" },
+                "target_id": { "type": "string" },
+                "restaurant_id": { "type": ["integer", "null"] },
+                "request_id": { "type": "string" },
+                "remote_addr": { "type": "string" },
+                "before": {},
+                "after": {}
+            }
+        })
+    }
+}
+
+// Raw row; the snapshots are JSON and are read back as text.
+#[derive(sqlx::FromRow)]
+struct AuditRow {
+    id: i64,
+    occurred_at: DateTime<Utc>,
+    actor: String,
+    action: String,
+    target_type: String,
+    target_id: String,
+    restaurant_id: Option<i64>,
+    request_id: String,
+    remote_addr: String,
+    before_state: Option<String>,
+    after_state: Option<String>,
+}
+
+impl TryFrom<AuditRow> for AuditEntry {
+    type Error = anyhow::Error;
+
+    fn try_from(row: AuditRow) -> Result<Self> {
+        let parse = |raw: Option<String>| raw.map(|s| serde_json::from_str(&s)).transpose();
+        Ok(Self {
+            id: row.id,
+            occurred_at: row.occurred_at,
+            actor: row.actor,
+            action: row.action,
+            target_type: row.target_type,
+            target_id: row.target_id,
+            restaurant_id: row.restaurant_id,
+            request_id: row.request_id,
+            remote_addr: row.remote_addr,
+            before: parse(row.before_state)?,
+            after: parse(row.after_state)?,
+        })
+    }
+}
+
+// Filters accepted by GET /api/admin/audit. Results are newest first; pass the last
+// id seen as before_id to page further back.
+#[derive(Debug, Default)]
+pub struct AuditQuery {
+    pub actor: Option<String>,
+    pub action: Option<String>,
+    pub target_type: Option<String>,
+    pub target_id: Option<String>,
+    pub restaurant_id: Option<i64>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    pub before_id: Option<i64>,
+    pub limit: u32,
+}
+
+const DEFAULT_LIMIT: u32 = 50;
+const MAX_LIMIT: u32 = 500;
+
+impl AuditQuery {
+    pub fn from_request(ctx: &RequestContext) -> AppResult<Self> {
+        fn parsed<T: std::str::FromStr>(ctx: &RequestContext, name: &str) -> AppResult<Option<T>> {
+            ctx.query_param(name)
+                .map(|v| v.parse().map_err(|_| AppError::BadRequest(format!("invalid {name}"))))
+                .transpose()
+        }
+        let text = |name: &str| ctx.query_param(name).map(str::to_string);
+        Ok(Self {
+            actor: text("actor"),
+            action: text("action"),
+            target_type: text("target_type"),
+            target_id: text("target_id"),
+            restaurant_id: parsed(ctx, "restaurant_id")?,
+            since: parsed(ctx, "since")?,
+            until: parsed(ctx, "until")?,
+            before_id: parsed(ctx, "before_id")?,
+            limit: parsed(ctx, "limit")?.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT),
+        })
+    }
+}
+
+pub async fn search(pool: &MySqlPool, query: &AuditQuery) -> Result<Vec<AuditEntry>> {
+    let mut sql = QueryBuilder::<MySql>::new(
+        "SELECT id, occurred_at, actor, action, target_type, target_id, restaurant_id, request_id, remote_addr, \
+         CAST(before_state AS CHAR) AS before_state, CAST(after_state AS CHAR) AS after_state \
+         FROM audit_logs WHERE 1 = 1",
+    );
+    for (column, value) in [
+        ("actor", &query.actor),
+        ("action", &query.action),
+        ("target_type", &query.target_type),
+        ("target_id", &query.target_id),
+    ] {
+        if let Some(value) = value {
+            sql.push(format!(" AND {column} = ")).push_bind(value.clone());
+        }
+    }
+    if let Some(restaurant_id) = query.restaurant_id {
+        sql.push(" AND restaurant_id = ").push_bind(restaurant_id);
+    }
+    if let Some(since) = query.since {
+        sql.push(" AND occurred_at >= ").push_bind(since);
+    }
+    if let Some(until) = query.until {
+        sql.push(" AND occurred_at < ").push_bind(until);
+    }
+    if let Some(before_id) = query.before_id {
+        sql.push(" AND id < ").push_bind(before_id);
+    }
+    sql.push(" ORDER BY id DESC LIMIT ").push_bind(query.limit);
+
+    let rows: Vec<AuditRow> = sql.build_query_as().fetch_all(pool).await?;
+    rows.into_iter().map(AuditEntry::try_from).collect()
+}
+
+// Registers the read-only audit endpoint. There is deliberately no write or delete route.
+pub fn routes(router: Router) -> Router {
+    router
+        .get("/api/admin/audit", list_audit)
+        .summary("Search the audit log, newest first")
+        .urgency(6)
+        .requires_admin()
+        .query_param("actor", false)
+        .query_param("action", false)
+        .query_param("target_type", false)
+        .query_param("target_id", false)
+        .query_param("restaurant_id", false)
+        .query_param("since", false)
+        .query_param("until", false)
+        .query_param("before_id", false)
+        .query_param("limit", false)
+        .response_schema(json!({ "type": "array", "items": AuditEntry::schema() }))
+}
+
+// Reached only through requires_admin's AdminOnly middleware.
+async fn list_audit(ctx: RequestContext, services: Arc<AppServices>) -> AppResult<Response<Bytes>> {
+    let query = AuditQuery::from_request(&ctx)?;
+    let entries = search(services.db()?, &query).await?;
+    // A full page may have more behind it; its last id is the next before_id.
+    let next_cursor = match entries.last() {
+        Some(last) if entries.len() == query.limit as usize => Some(last.id.to_string()),
+        _ => None,
+    };
+    let pagination = Pagination { limit: query.limit, next_cursor };
+    ResponseBuilder::json_enveloped(&ctx, StatusCode::OK, &entries, Some(pagination))
+}
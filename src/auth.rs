@@ -0,0 +1,177 @@
+use crate::app::AppServices;
+use crate::error::{AppError, AppResult};
+use crate::middleware::Middleware;
+use crate::request::RequestContext;
+use anyhow::{anyhow, Result};
+use futures::future::BoxFuture;
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use sqlx::mysql::MySqlPool;
+
+// Checks the admin bearer token; returns an error to send back when the caller
+// is not allowed through. Besides the global ADMIN_API_TOKEN, tokens issued to admin
+// users (see AdminUserRepository, unless they enrolled in two-factor authentication)
+// and their signed sessions (see admin_sessions) are accepted. Requests over the mTLS
+// admin listener were authenticated as an admin user by their client certificate when
+// the connection opened, and skip these checks, two-factor included (see mtls).
+pub async fn require_admin(ctx: &RequestContext, services: &AppServices) -> AppResult<()> {
+    if ctx.client_identity.is_some() {
+        return Ok(());
+    }
+    let expected = services.admin_token();
+    if expected.is_none() && services.db.is_none() {
+        return Err(AppError::ServiceUnavailable(
+            "admin API is disabled (ADMIN_API_TOKEN not set)".to_string(),
+        ));
+    }
+
+    let Some(token) = ctx.bearer_token() else {
+        return Err(AppError::Unauthorized("missing bearer token".to_string()));
+    };
+    if expected.is_some_and(|expected| constant_time_eq(token.as_bytes(), expected.as_bytes())) {
+        return Ok(());
+    }
+    if services.admin_sessions.verify(token).is_some() {
+        return Ok(());
+    }
+    if let Some(pool) = &services.db
+        && admin_token_allowed(pool, token).await?
+    {
+        return Ok(());
+    }
+    Err(AppError::Forbidden("forbidden".to_string()))
+}
+
+// require_admin as a middleware, attached by Router::requires_admin. It is the only place
+// admin routes check the caller; the Admin extractor reads the pass it records.
+pub struct AdminOnly;
+
+impl Middleware for AdminOnly {
+    fn name(&self) -> &'static str {
+        "admin_only"
+    }
+
+    fn before<'a>(&'a self, ctx: &'a RequestContext, services: &'a AppServices) -> BoxFuture<'a, AppResult<()>> {
+        Box::pin(async move {
+            require_admin(ctx, services).await?;
+            let _ = ctx.admin.set(());
+            Ok(())
+        })
+    }
+}
+
+// Compares two byte strings without short-circuiting on the first difference.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+// The admin user id carried in an "adm_<id>_<secret>" token, if it is one.
+pub fn admin_user_id(token: &str) -> Option<i64> {
+    let (id, _) = token.strip_prefix("adm_")?.split_once('_')?;
+    id.parse().ok()
+}
+
+// Accepts +<country code><number>, ignoring spaces and dashes; returns the digits with
+// the leading "+".
+pub fn normalize_phone(phone: &str) -> Result<String> {
+    let digits: String = phone.chars().filter(|c| !matches!(c, ' ' | '-')).collect();
+    match digits.strip_prefix('+') {
+        Some(rest) if (8..=15).contains(&rest.len()) && rest.bytes().all(|b| b.is_ascii_digit()) => Ok(digits),
+        _ => Err(anyhow!("phone must be in international format, e.g. +919876543210")),
+    }
+}
+
+// Database access for admin users.
+pub struct AdminUserRepository<'a> {
+    pool: &'a MySqlPool,
+}
+
+impl<'a> AdminUserRepository<'a> {
+    pub fn new(pool: &'a MySqlPool) -> Self {
+        Self { pool }
+    }
+
+    // Creates the admin for `phone`, or reactivates it with a new token if it exists,
+    // and returns its id and token. Only the hash of the token's secret is stored.
+    pub async fn create_or_reissue(&self, phone: &str) -> Result<(i64, String)> {
+        let phone = normalize_phone(phone)?;
+        let mut bytes = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        let secret = hex::encode(bytes);
+        sqlx::query(
+            "INSERT INTO admin_users (phone, token_hash) VALUES (?, ?) \
+             ON DUPLICATE KEY UPDATE token_hash = VALUES(token_hash), active = TRUE",
+        )
+        .bind(&phone)
+        .bind(hex::encode(Sha256::digest(secret.as_bytes())))
+        .execute(self.pool)
+        .await?;
+        let id: i64 = sqlx::query_scalar("SELECT id FROM admin_users WHERE phone = ?")
+            .bind(&phone)
+            .fetch_one(self.pool)
+            .await?;
+        Ok((id, format!("adm_{id}_{secret}")))
+    }
+
+    // The active admin user `token` belongs to, if any.
+    pub async fn find(&self, token: &str) -> Result<Option<AdminCredential>> {
+        let Some(id) = admin_user_id(token) else {
+            return Ok(None);
+        };
+        let secret = token.rsplit('_').next().unwrap_or_default();
+        let stored: Option<(String, bool)> =
+            sqlx::query_as("SELECT token_hash, totp_enabled FROM admin_users WHERE id = ? AND active")
+                .bind(id)
+                .fetch_optional(self.pool)
+                .await?;
+        let actual = hex::encode(Sha256::digest(secret.as_bytes()));
+        Ok(stored
+            .filter(|(stored, _)| constant_time_eq(actual.as_bytes(), stored.as_bytes()))
+            .map(|(_, two_factor)| AdminCredential { id, two_factor }))
+    }
+
+    // The id of the active admin user `token` belongs to, if any.
+    pub async fn verify(&self, token: &str) -> Result<Option<i64>> {
+        Ok(self.find(token).await?.map(|credential| credential.id))
+    }
+
+    // Lets the admin in over the mTLS admin listener with a client certificate whose
+    // identity (subject CN, else first DNS SAN) is `identity`.
+    pub async fn set_client_identity(&self, id: i64, identity: &str) -> Result<()> {
+        sqlx::query("UPDATE admin_users SET client_identity = ? WHERE id = ?")
+            .bind(identity)
+            .bind(id)
+            .execute(self.pool)
+            .await?;
+        Ok(())
+    }
+
+    // The id of the active admin user a client certificate identity belongs to, if any.
+    pub async fn by_client_identity(&self, identity: &str) -> Result<Option<i64>> {
+        Ok(sqlx::query_scalar("SELECT id FROM admin_users WHERE client_identity = ? AND active")
+            .bind(identity)
+            .fetch_optional(self.pool)
+            .await?)
+    }
+}
+
+// An admin user identified by their token.
+#[derive(Debug, Clone, Copy)]
+pub struct AdminCredential {
+    pub id: i64,
+    // Whether the user enrolled in two-factor authentication. Their token alone is
+    // then only good for opening a session (see totp).
+    pub two_factor: bool,
+}
+
+// Whether an admin user's token may be used on its own for admin endpoints. Users with
+// two-factor authentication must sign in through a session.
+pub async fn admin_token_allowed(pool: &MySqlPool, token: &str) -> AppResult<bool> {
+    match AdminUserRepository::new(pool).find(token).await? {
+        Some(credential) if credential.two_factor => Err(AppError::Unauthorized(
+            "two-factor authentication is enabled; sign in with POST /api/admin/sessions".to_string(),
+        )),
+        Some(_) => Ok(true),
+        None => Ok(false),
+    }
+}
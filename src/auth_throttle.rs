@@ -0,0 +1,113 @@
+use crate::audit::{AuditEvent, AuditLogger};
+use crate::error::{AppError, AppResult};
+use crate::logging;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde_json::json;
+use sqlx::mysql::MySqlPool;
+
+// Brute-force protection for guessable credentials such as six-digit two-factor codes.
+// Failures are counted per scope and identifier in auth_failures, so restarts and other
+// instances see the same counts. After each failure the next attempt must wait
+// 2^(failures - 1) seconds (capped at MAX_DELAY_SECS); at LOCKOUT_FAILURES the
+// identifier is blocked for LOCKOUT_MINUTES and the lockout is written to the audit log.
+// Counts reset on success, or once the last failure is an hour old.
+
+const MAX_DELAY_SECS: i64 = 60;
+const LOCKOUT_FAILURES: u32 = 10;
+const LOCKOUT_MINUTES: i64 = 15;
+
+#[derive(sqlx::FromRow)]
+struct Failures {
+    failures: u32,
+    last_failure_at: DateTime<Utc>,
+    blocked_until: Option<DateTime<Utc>>,
+}
+
+fn delay_secs(failures: u32) -> i64 {
+    (1i64 << failures.saturating_sub(1).min(6)).min(MAX_DELAY_SECS)
+}
+
+async fn load(pool: &MySqlPool, scope: &str, identifier: &str) -> Result<Option<Failures>> {
+    Ok(sqlx::query_as(
+        "SELECT failures, last_failure_at, blocked_until FROM auth_failures \
+         WHERE scope = ? AND identifier = ? AND last_failure_at > NOW() - INTERVAL 1 HOUR",
+    )
+    .bind(scope)
+    .bind(identifier)
+    .fetch_optional(pool)
+    .await?)
+}
+
+// Refuses the attempt with 429 while the identifier is blocked or still waiting out the
+// delay of its last failure.
+pub async fn check(pool: &MySqlPool, scope: &str, identifier: &str) -> AppResult<()> {
+    let Some(state) = load(pool, scope, identifier).await? else {
+        return Ok(());
+    };
+    let now = Utc::now();
+    let allowed_at = match state.blocked_until {
+        Some(until) if until > now => until,
+        _ => state.last_failure_at + chrono::Duration::seconds(delay_secs(state.failures)),
+    };
+    if allowed_at > now {
+        let retry_after_secs = (allowed_at - now).num_seconds().max(1) as u64;
+        return Err(AppError::TooManyRequests { retry_after_secs });
+    }
+    Ok(())
+}
+
+// Counts a failed attempt, blocking the identifier when it reaches LOCKOUT_FAILURES.
+pub async fn record_failure(pool: &MySqlPool, scope: &str, identifier: &str, audit: &AuditLogger) -> Result<()> {
+    let mut tx = pool.begin().await?;
+    sqlx::query(
+        "INSERT INTO auth_failures (scope, identifier, failures, last_failure_at) VALUES (?, ?, 1, NOW()) \
+         ON DUPLICATE KEY UPDATE \
+         failures = IF(last_failure_at < NOW() - INTERVAL 1 HOUR, 1, failures + 1), \
+         blocked_until = IF(last_failure_at < NOW() - INTERVAL 1 HOUR, NULL, blocked_until), \
+         last_failure_at = NOW()",
+    )
+    .bind(scope)
+    .bind(identifier)
+    .execute(&mut *tx)
+    .await?;
+    let failures: u32 = sqlx::query_scalar("SELECT failures FROM auth_failures WHERE scope = ? AND identifier = ?")
+        .bind(scope)
+        .bind(identifier)
+        .fetch_one(&mut *tx)
+        .await?;
+    if failures >= LOCKOUT_FAILURES && failures % LOCKOUT_FAILURES == 0 {
+        sqlx::query(
+            "UPDATE auth_failures SET blocked_until = NOW() + INTERVAL ? MINUTE WHERE scope = ? AND identifier = ?",
+        )
+        .bind(LOCKOUT_MINUTES)
+        .bind(scope)
+        .bind(identifier)
+        .execute(&mut *tx)
+        .await?;
+        let event = AuditEvent {
+            action: "auth.lockout",
+            target_type: scope,
+            target_id: identifier.to_string(),
+            restaurant_id: None,
+            before: None,
+            after: Some(json!({ "failures": failures, "blocked_minutes": LOCKOUT_MINUTES })),
+        };
+        audit.record(&mut *tx, event).await?;
+        logging::warn(
+            "authentication locked out",
+            json!({ "scope": scope, "identifier": identifier, "failures": failures }),
+        );
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+pub async fn record_success(pool: &MySqlPool, scope: &str, identifier: &str) -> Result<()> {
+    sqlx::query("DELETE FROM auth_failures WHERE scope = ? AND identifier = ?")
+        .bind(scope)
+        .bind(identifier)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
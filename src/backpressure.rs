@@ -0,0 +1,77 @@
+use crate::config;
+use anyhow::{anyhow, Result};
+use std::sync::Arc;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+// Bounds the tasks the server spawns, so a burst of connections or request streams
+// cannot grow memory without limit:
+//   MAX_CONNECTIONS          open QUIC connections; further handshakes are refused
+//                            (default 10000)
+//   MAX_CONCURRENT_REQUESTS  request tasks running at once (default 1024)
+//   MAX_QUEUED_REQUESTS      requests waiting for one of those slots (default 1024)
+//   SATURATED_RETRY_AFTER_SECS  Retry-After sent when both are full (default 1)
+// A request arriving while every slot and queue place is taken is answered 503 without
+// spawning a task for it.
+pub struct TaskLimits {
+    connections: Arc<Semaphore>,
+    running: Arc<Semaphore>,
+    queue: Arc<Semaphore>,
+    pub retry_after_secs: u64,
+}
+
+impl TaskLimits {
+    pub fn from_env() -> Result<Self> {
+        let number = |name: &str, default: usize| -> Result<usize> {
+            match config::var(name) {
+                Some(v) => v
+                    .parse()
+                    .ok()
+                    .filter(|n| *n > 0)
+                    .ok_or_else(|| anyhow!("{name} must be a positive integer")),
+                None => Ok(default),
+            }
+        };
+        Ok(Self {
+            connections: Arc::new(Semaphore::new(number("MAX_CONNECTIONS", 10_000)?)),
+            running: Arc::new(Semaphore::new(number("MAX_CONCURRENT_REQUESTS", 1024)?)),
+            queue: Arc::new(Semaphore::new(number("MAX_QUEUED_REQUESTS", 1024)?)),
+            retry_after_secs: number("SATURATED_RETRY_AFTER_SECS", 1)? as u64,
+        })
+    }
+
+    // A place for a new connection, held for its lifetime; None when MAX_CONNECTIONS are open.
+    pub fn connection(&self) -> Option<OwnedSemaphorePermit> {
+        self.connections.clone().try_acquire_owned().ok()
+    }
+
+    // Admits a new request: a running slot if one is free, otherwise a place in the
+    // queue. None means the server is saturated and the request should be refused.
+    pub fn admit(&self) -> Option<RequestSlot> {
+        if let Ok(permit) = self.running.clone().try_acquire_owned() {
+            return Some(RequestSlot::Running(permit));
+        }
+        let place = self.queue.clone().try_acquire_owned().ok()?;
+        Some(RequestSlot::Queued { place, running: self.running.clone() })
+    }
+}
+
+// A request's claim on the executor. A queued request gives its queue place back once
+// it starts running.
+pub enum RequestSlot {
+    Running(OwnedSemaphorePermit),
+    Queued { place: OwnedSemaphorePermit, running: Arc<Semaphore> },
+}
+
+impl RequestSlot {
+    // Waits until the request may run; the returned permit frees the slot on drop.
+    pub async fn ready(self) -> OwnedSemaphorePermit {
+        match self {
+            RequestSlot::Running(permit) => permit,
+            RequestSlot::Queued { place, running } => {
+                let permit = running.acquire_owned().await.expect("request semaphore is never closed");
+                drop(place);
+                permit
+            }
+        }
+    }
+}
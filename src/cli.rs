@@ -0,0 +1,299 @@
+use crate::auth::AdminUserRepository;
+use crate::config;
+use crate::contract;
+use crate::router::Router;
+use crate::sdk_gen;
+use crate::seed::{self, SeedOptions};
+use anyhow::{anyhow, Context, Result};
+use clap::{Args, CommandFactory, FromArgMatches, Parser, Subcommand};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use sqlx::migrate::{Migrate, Migrator};
+use sqlx::mysql::MySqlPool;
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+// Command line of the server binary. Without a command it serves, so existing
+// `server --<setting>=<value>` invocations keep working.
+#[derive(Debug, Parser)]
+#[command(name = "server", version, about = "RotiRide HTTP/3 server and operational tasks")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    #[command(about = "Run the HTTP/3 server")]
+    Serve(Settings),
+    #[command(about = "Apply, roll back or list database migrations")]
+    Migrate {
+        #[command(subcommand)]
+        action: MigrateAction,
+    },
+    #[command(about = "Create an admin user, or issue a new token to an existing one")]
+    CreateAdmin {
+        #[arg(long, help = "Phone number in international format, e.g. +919876543210")]
+        phone: String,
+        #[arg(long, help = "Subject CN (or DNS name) of the admin's client certificate for the mTLS admin listener")]
+        client_identity: Option<String>,
+        #[command(flatten)]
+        settings: Settings,
+    },
+    #[command(about = "Fill a restaurant with demo menu, tables, admins and past orders; safe to re-run")]
+    Seed {
+        #[arg(long, default_value = "default", help = "Slug of the restaurant to seed; created if missing")]
+        restaurant: String,
+        #[arg(long, default_value_t = 60, help = "Number of historical orders")]
+        orders: u32,
+        #[arg(long, default_value_t = 30, help = "Days before today the orders are spread over")]
+        days: u32,
+        #[command(flatten)]
+        settings: Settings,
+    },
+    #[command(about = "Inspect the configuration")]
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+    #[command(about = "Write a self-signed certificate and key for TLS_CERT_FILE / TLS_KEY_FILE")]
+    GenCert {
+        #[arg(long = "host", default_value = "localhost", help = "DNS name or IP the certificate is for; repeatable")]
+        hosts: Vec<String>,
+        #[arg(long, default_value = ".", help = "Directory to write cert.pem and key.pem into")]
+        out_dir: PathBuf,
+    },
+    #[command(about = "Replay recorded requests against a running server and check them against its OpenAPI spec")]
+    ContractTest {
+        #[arg(long, help = "File of recorded requests, one JSON object per line")]
+        recordings: PathBuf,
+        #[arg(long, default_value = "https://localhost:443", help = "Server to replay against")]
+        base_url: String,
+        #[arg(long, help = "Certificate the server presents (default: TLS_CERT_FILE)")]
+        cert: Option<PathBuf>,
+        #[command(flatten)]
+        settings: Settings,
+    },
+    #[command(about = "Write typed TypeScript and Dart API clients generated from the routes")]
+    GenClient {
+        #[arg(long, default_value = ".", help = "Directory to write rotiride_client.ts and rotiride_client.dart into")]
+        out_dir: PathBuf,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum MigrateAction {
+    #[command(about = "Apply every pending migration")]
+    Run(Settings),
+    #[command(about = "Undo applied migrations newer than --to (default: the latest one)")]
+    Rollback {
+        #[arg(long, help = "Version to roll back to; it stays applied")]
+        to: Option<i64>,
+        #[command(flatten)]
+        settings: Settings,
+    },
+    #[command(about = "List migrations and whether each is applied")]
+    Status(Settings),
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ConfigAction {
+    #[command(about = "Validate the merged configuration and show where each value comes from")]
+    Check(Settings),
+}
+
+// Setting overrides accepted by every command that reads the configuration; see
+// config.rs. `--config <file.json>` picks the config file.
+#[derive(Debug, Args)]
+pub struct Settings {
+    #[arg(
+        trailing_var_arg = true,
+        allow_hyphen_values = true,
+        value_name = "--SETTING=VALUE",
+        help = "Setting overrides, e.g. --database-url=mysql://..."
+    )]
+    pub args: Vec<String>,
+}
+
+impl Cli {
+    // Parses the process arguments, defaulting to `serve` when no command is given.
+    pub fn from_args() -> Self {
+        let mut args: Vec<String> = std::env::args().collect();
+        let first = args.get(1).map(String::as_str);
+        let no_command = match first {
+            None => true,
+            Some("-h" | "--help" | "-V" | "--version") => false,
+            Some(arg) => arg.starts_with("--"),
+        };
+        if no_command {
+            args.insert(1, "serve".to_string());
+        }
+        let command = Cli::command().mut_subcommand("serve", |serve| serve.after_long_help(config::usage()));
+        Cli::from_arg_matches(&command.get_matches_from(args)).unwrap_or_else(|err| err.exit())
+    }
+}
+
+// Connects to DATABASE_URL for a one-off command.
+async fn connect() -> Result<MySqlPool> {
+    let url = config::var("DATABASE_URL").ok_or_else(|| anyhow!("DATABASE_URL is not set"))?;
+    MySqlPool::connect(&url).await.context("connecting to DATABASE_URL")
+}
+
+fn migrator() -> Migrator {
+    sqlx::migrate!("./migrations")
+}
+
+// Versions recorded as applied, creating sqlx's bookkeeping table on first use.
+async fn applied_versions(pool: &MySqlPool) -> Result<HashSet<i64>> {
+    let mut conn = pool.acquire().await?;
+    conn.ensure_migrations_table().await?;
+    Ok(conn
+        .list_applied_migrations()
+        .await?
+        .into_iter()
+        .map(|migration| migration.version)
+        .collect())
+}
+
+pub async fn migrate(action: MigrateAction) -> Result<()> {
+    let (MigrateAction::Run(settings) | MigrateAction::Status(settings) | MigrateAction::Rollback { settings, .. }) =
+        &action;
+    config::load(settings.args.clone())?;
+    let pool = connect().await?;
+    let migrator = migrator();
+
+    match action {
+        MigrateAction::Run(_) => {
+            migrator.run(&pool).await?;
+            println!("migrations applied");
+        }
+        MigrateAction::Status(_) => {
+            let applied = applied_versions(&pool).await?;
+            for migration in migrator.iter().filter(|m| !m.migration_type.is_down_migration()) {
+                let state = if applied.contains(&migration.version) { "applied" } else { "pending" };
+                println!("{:>4}  {:<8} {}", migration.version, state, migration.description);
+            }
+        }
+        MigrateAction::Rollback { to, .. } => {
+            let mut newest_first: Vec<i64> = applied_versions(&pool).await?.into_iter().collect();
+            newest_first.sort_unstable_by(|a, b| b.cmp(a));
+            let target = to.unwrap_or_else(|| newest_first.get(1).copied().unwrap_or(0));
+            // Migrations come as .up.sql/.down.sql pairs. A plain .sql one has no down
+            // script, and undoing past it would leave the schema and the migrations
+            // table out of step, so refuse before touching anything.
+            let reversible: HashSet<i64> = migrator
+                .iter()
+                .filter(|m| m.migration_type.is_down_migration())
+                .map(|m| m.version)
+                .collect();
+            if let Some(version) = newest_first.iter().find(|v| **v > target && !reversible.contains(v)) {
+                return Err(anyhow!("migration {version} has no .down.sql script and cannot be rolled back"));
+            }
+            migrator.undo(&pool, target).await?;
+            println!("rolled back to version {target}");
+        }
+    }
+    Ok(())
+}
+
+// Creates the admin and prints its bearer token; it is not stored and cannot be shown again.
+pub async fn create_admin(phone: &str, client_identity: Option<&str>, settings: Settings) -> Result<()> {
+    config::load(settings.args)?;
+    let pool = connect().await?;
+    let admins = AdminUserRepository::new(&pool);
+    let (id, token) = admins.create_or_reissue(phone).await?;
+    if let Some(identity) = client_identity {
+        admins.set_client_identity(id, identity).await?;
+    }
+    println!("admin user {id} ({phone})");
+    println!("token: {token}");
+    Ok(())
+}
+
+pub async fn seed(options: SeedOptions, settings: Settings) -> Result<()> {
+    config::load(settings.args)?;
+    let pool = connect().await?;
+    let summary = seed::run(&pool, &options).await?;
+    if summary.restaurant_created {
+        println!("created restaurant {}", options.restaurant_slug);
+    }
+    println!(
+        "added {} menu items, {} tables and {} orders",
+        summary.menu_items, summary.dining_tables, summary.orders
+    );
+    for (phone, token) in &summary.admin_users {
+        println!("admin {phone}: {token}");
+    }
+    Ok(())
+}
+
+// Loads and validates the configuration like `serve` does, without starting anything.
+pub fn config_check(settings: Settings) -> Result<()> {
+    config::load(settings.args)?;
+    for line in config::describe() {
+        println!("{line}");
+    }
+    println!("configuration OK");
+    Ok(())
+}
+
+pub fn gen_cert(hosts: Vec<String>, out_dir: PathBuf) -> Result<()> {
+    let cert = rcgen::generate_simple_self_signed(hosts.clone())?;
+    std::fs::create_dir_all(&out_dir).with_context(|| format!("creating {}", out_dir.display()))?;
+    let (cert_path, key_path) = (out_dir.join("cert.pem"), out_dir.join("key.pem"));
+    std::fs::write(&cert_path, cert.cert.pem()).with_context(|| format!("writing {}", cert_path.display()))?;
+    std::fs::write(&key_path, cert.signing_key.serialize_pem()).with_context(|| format!("writing {}", key_path.display()))?;
+    println!("wrote {} and {} for {}", cert_path.display(), key_path.display(), hosts.join(", "));
+    Ok(())
+}
+
+// Fails when any recorded exchange breaks the contract, so CI can run it against a
+// test server.
+pub async fn contract_test(
+    recordings: PathBuf,
+    base_url: &str,
+    cert: Option<PathBuf>,
+    settings: Settings,
+) -> Result<()> {
+    config::load(settings.args)?;
+    let cert = cert
+        .or_else(|| config::var("TLS_CERT_FILE").map(PathBuf::from))
+        .ok_or_else(|| anyhow!("pass --cert or set TLS_CERT_FILE to the server's certificate"))?;
+    let recordings = contract::load_recordings(&recordings)?;
+    let report = contract::run(base_url, contract::read_certificate(&cert)?, &recordings).await?;
+    for (recording, problem) in &report.failures {
+        println!("FAIL {recording}: {problem}");
+    }
+    println!("{} recordings replayed, {} contract violations", report.checked, report.failures.len());
+    if report.failures.is_empty() {
+        Ok(())
+    } else {
+        Err(anyhow!("the server does not match its OpenAPI spec"))
+    }
+}
+
+// Rerun after changing routes or their schemas so the clients follow.
+pub fn gen_client(router: &Router, out_dir: PathBuf) -> Result<()> {
+    std::fs::create_dir_all(&out_dir).with_context(|| format!("creating {}", out_dir.display()))?;
+    let (ts_path, dart_path) = (out_dir.join("rotiride_client.ts"), out_dir.join("rotiride_client.dart"));
+    std::fs::write(&ts_path, sdk_gen::typescript(router)).with_context(|| format!("writing {}", ts_path.display()))?;
+    std::fs::write(&dart_path, sdk_gen::dart(router)).with_context(|| format!("writing {}", dart_path.display()))?;
+    println!("wrote {} and {}", ts_path.display(), dart_path.display());
+    Ok(())
+}
+
+// Certificate chain and key from TLS_CERT_FILE / TLS_KEY_FILE, if configured.
+pub fn tls_files() -> Result<Option<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>)>> {
+    let (Some(cert), Some(key)) = (config::var("TLS_CERT_FILE"), config::var("TLS_KEY_FILE")) else {
+        return Ok(None);
+    };
+    let chain = CertificateDer::pem_file_iter(&cert)
+        .with_context(|| format!("reading {cert}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("parsing {cert}"))?;
+    if chain.is_empty() {
+        return Err(anyhow!("{cert} contains no certificates"));
+    }
+    let key = PrivateKeyDer::from_pem_file(&key).with_context(|| format!("reading {key}"))?;
+    Ok(Some((chain, key)))
+}
@@ -0,0 +1,249 @@
+use crate::error::{AppError, AppResult};
+use crate::extract::{Path, State};
+use crate::openapi::ApiSchema;
+use crate::qlog::QlogConfig;
+use crate::response::ResponseBuilder;
+use crate::router::Router;
+use chrono::{DateTime, Utc};
+use http::StatusCode;
+use quinn::ConnectionStats;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+// Transport statistics of one QUIC connection, as reported by quinn.
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectionSnapshot {
+    pub id: usize,
+    pub remote_addr: String,
+    pub opened_at: DateTime<Utc>,
+    pub rtt_ms: f64,
+    pub cwnd_bytes: u64,
+    pub congestion_events: u64,
+    pub sent_packets: u64,
+    pub lost_packets: u64,
+    pub lost_bytes: u64,
+    pub datagrams_sent: u64,
+    pub datagrams_received: u64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub mtu: u16,
+    // Whether a qlog trace is being written for the connection.
+    pub qlog: bool,
+}
+
+impl ConnectionSnapshot {
+    fn new(id: usize, live: &LiveConnection) -> Self {
+        let LiveConnection { conn, opened_at, qlog } = live;
+        let stats: ConnectionStats = conn.stats();
+        Self {
+            id,
+            remote_addr: conn.remote_address().to_string(),
+            opened_at: *opened_at,
+            rtt_ms: stats.path.rtt.as_secs_f64() * 1000.0,
+            cwnd_bytes: stats.path.cwnd,
+            congestion_events: stats.path.congestion_events,
+            sent_packets: stats.path.sent_packets,
+            lost_packets: stats.path.lost_packets,
+            lost_bytes: stats.path.lost_bytes,
+            datagrams_sent: stats.udp_tx.datagrams,
+            datagrams_received: stats.udp_rx.datagrams,
+            bytes_sent: stats.udp_tx.bytes,
+            bytes_received: stats.udp_rx.bytes,
+            mtu: stats.path.current_mtu,
+            qlog: *qlog,
+        }
+    }
+}
+
+impl ApiSchema for ConnectionSnapshot {
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "id": { "type": "integer" },
+                "remote_addr": { "type": "string" },
+                "opened_at": { "type": "string", "format": "date-time" },
+                "rtt_ms": { "type": "number", "description": "Smoothed round-trip time" },
+                "cwnd_bytes": { "type": "integer", "description": "Congestion window" },
+                "congestion_events": { "type": "integer" },
+                "sent_packets": { "type": "integer" },
+                "lost_packets": { "type": "integer" },
+                "lost_bytes": { "type": "integer" },
+                "datagrams_sent": { "type": "integer" },
+                "datagrams_received": { "type": "integer" },
+                "bytes_sent": { "type": "integer" },
+                "bytes_received": { "type": "integer" },
+                "mtu": { "type": "integer" },
+                "qlog": { "type": "boolean" }
+            }
+        })
+    }
+}
+
+// Sums over connections; closed connections keep contributing their final numbers.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct ConnectionTotals {
+    pub connections: u64,
+    pub sent_packets: u64,
+    pub lost_packets: u64,
+    pub congestion_events: u64,
+    pub datagrams_sent: u64,
+    pub datagrams_received: u64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+}
+
+impl ConnectionTotals {
+    fn add(&mut self, snapshot: &ConnectionSnapshot) {
+        self.connections += 1;
+        self.sent_packets += snapshot.sent_packets;
+        self.lost_packets += snapshot.lost_packets;
+        self.congestion_events += snapshot.congestion_events;
+        self.datagrams_sent += snapshot.datagrams_sent;
+        self.datagrams_received += snapshot.datagrams_received;
+        self.bytes_sent += snapshot.bytes_sent;
+        self.bytes_received += snapshot.bytes_received;
+    }
+}
+
+struct LiveConnection {
+    conn: quinn::Connection,
+    opened_at: DateTime<Utc>,
+    qlog: bool,
+}
+
+// Open QUIC connections, tracked from handshake to close so their statistics can be
+// inspected while they run and folded into the totals when they end.
+#[derive(Default)]
+pub struct ConnectionRegistry {
+    live: Mutex<HashMap<usize, LiveConnection>>,
+    closed: Mutex<ConnectionTotals>,
+}
+
+impl ConnectionRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Tracks `conn` until the returned guard is dropped.
+    pub fn track(&self, conn: &quinn::Connection) -> TrackedConnection<'_> {
+        let id = conn.stable_id();
+        let live = LiveConnection { conn: conn.clone(), opened_at: Utc::now(), qlog: false };
+        self.live.lock().unwrap().insert(id, live);
+        TrackedConnection { registry: self, id }
+    }
+
+    fn untrack(&self, id: usize) {
+        if let Some(live) = self.live.lock().unwrap().remove(&id) {
+            self.closed.lock().unwrap().add(&ConnectionSnapshot::new(id, &live));
+        }
+    }
+
+    // Starts a qlog trace of an open connection. Returns the trace file.
+    pub fn start_qlog(&self, id: usize, qlog: &QlogConfig) -> AppResult<PathBuf> {
+        let mut live = self.live.lock().unwrap();
+        let connection = live
+            .get_mut(&id)
+            .ok_or_else(|| AppError::NotFound(format!("connection {id} is not open")))?;
+        if connection.qlog {
+            return Err(AppError::Conflict(format!("connection {id} is already traced")));
+        }
+        let path = qlog
+            .trace(&connection.conn)?
+            .ok_or_else(|| AppError::ServiceUnavailable("qlog is disabled (QLOG_DIR not set)".to_string()))?;
+        connection.qlog = true;
+        Ok(path)
+    }
+
+    // Current statistics of every open connection, oldest first.
+    pub fn snapshot(&self) -> Vec<ConnectionSnapshot> {
+        let mut connections: Vec<ConnectionSnapshot> = self
+            .live
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(id, live)| ConnectionSnapshot::new(*id, live))
+            .collect();
+        connections.sort_by_key(|c| (c.opened_at, c.id));
+        connections
+    }
+
+    // Totals over closed connections plus the given open ones.
+    pub fn totals(&self, live: &[ConnectionSnapshot]) -> ConnectionTotals {
+        let mut totals = *self.closed.lock().unwrap();
+        live.iter().for_each(|snapshot| totals.add(snapshot));
+        totals
+    }
+
+    // Connection gauges and counters in the Prometheus text format, appended to /metrics.
+    pub fn render_prometheus(&self) -> String {
+        let live = self.snapshot();
+        let totals = self.totals(&live);
+        let mut out = String::new();
+        let _ = writeln!(out, "# HELP rotiride_quic_connections_open QUIC connections currently open");
+        let _ = writeln!(out, "# TYPE rotiride_quic_connections_open gauge");
+        let _ = writeln!(out, "rotiride_quic_connections_open {}", live.len());
+        let counters = [
+            ("rotiride_quic_connections_total", "QUIC connections accepted", totals.connections),
+            ("rotiride_quic_sent_packets_total", "QUIC packets sent", totals.sent_packets),
+            ("rotiride_quic_lost_packets_total", "QUIC packets declared lost", totals.lost_packets),
+            ("rotiride_quic_congestion_events_total", "QUIC congestion events", totals.congestion_events),
+            ("rotiride_quic_datagrams_sent_total", "UDP datagrams sent", totals.datagrams_sent),
+            ("rotiride_quic_datagrams_received_total", "UDP datagrams received", totals.datagrams_received),
+        ];
+        for (name, help, value) in counters {
+            let _ = writeln!(out, "# HELP {name} {help}");
+            let _ = writeln!(out, "# TYPE {name} counter");
+            let _ = writeln!(out, "{name} {value}");
+        }
+        let rtts: Vec<f64> = live.iter().map(|c| c.rtt_ms).collect();
+        if !rtts.is_empty() {
+            let _ = writeln!(out, "# HELP rotiride_quic_rtt_ms_avg Mean smoothed RTT over open connections");
+            let _ = writeln!(out, "# TYPE rotiride_quic_rtt_ms_avg gauge");
+            let _ = writeln!(out, "rotiride_quic_rtt_ms_avg {:.3}", rtts.iter().sum::<f64>() / rtts.len() as f64);
+        }
+        out
+    }
+}
+
+// Removes its connection from the registry when dropped.
+pub struct TrackedConnection<'a> {
+    registry: &'a ConnectionRegistry,
+    id: usize,
+}
+
+impl Drop for TrackedConnection<'_> {
+    fn drop(&mut self) {
+        self.registry.untrack(self.id);
+    }
+}
+
+// Registers GET /api/admin/connections and the per-connection qlog switch.
+pub fn routes(router: Router) -> Router {
+    router.group("/api/admin/connections", |group| {
+        group
+            .requires_admin()
+            .get("", |State(services): State| async move {
+                let connections = services.connections.snapshot();
+                let totals = services.connections.totals(&connections);
+                ResponseBuilder::json(StatusCode::OK, &json!({ "totals": totals, "connections": connections }))
+            })
+            .summary("Transport statistics of open QUIC connections, with totals since startup")
+            .response_schema(json!({
+                "type": "object",
+                "properties": {
+                    "totals": { "type": "object" },
+                    "connections": { "type": "array", "items": ConnectionSnapshot::schema() }
+                }
+            }))
+            .post("/:id/qlog", |Path(id): Path<usize>, State(services): State| async move {
+                let path = services.connections.start_qlog(id, &services.qlog)?;
+                ResponseBuilder::json(StatusCode::OK, &json!({ "id": id, "qlog_path": path.display().to_string() }))
+            })
+            .summary("Start writing a qlog trace of an open connection (requires QLOG_DIR)")
+    })
+}
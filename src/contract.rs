@@ -0,0 +1,323 @@
+use crate::error::ErrorResponse;
+use crate::tenant;
+use crate::validation::ValidationMiddleware;
+use anyhow::{anyhow, Context, Result};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use http::{Method, Request, Uri};
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::CryptoProvider;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{DigitallySignedStruct, SignatureScheme};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+// Contract checks of a running server against its own OpenAPI document, for
+// `server contract-test`. Recorded requests (one JSON object per line, see Recording)
+// are replayed over HTTP/3, and each exchange is checked against the spec the server
+// publishes at /api/openapi.json: the route must be documented, the recorded request
+// must carry the documented query parameters and match the request body schema, a
+// successful response must match the response schema, and an error response must be
+// an ErrorResponse. Any mismatch fails the run, so CI catches handlers drifting from
+// what they document.
+//
+// The server's certificate is pinned rather than verified against a CA, so a test
+// server can use the self-signed certificate from `server gen-cert`.
+
+const SPEC_PATH: &str = "/api/openapi.json";
+
+// One recorded request, e.g.
+// {"method": "POST", "path": "/api/orders", "headers": {"authorization": "Bearer ..."}, "body": {...}, "status": 201}
+#[derive(Debug, Deserialize)]
+pub struct Recording {
+    pub method: String,
+    // Path as sent, including any "/r/<slug>" tenant prefix.
+    pub path: String,
+    // Raw query string without the "?".
+    #[serde(default)]
+    pub query: Option<String>,
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    #[serde(default)]
+    pub body: Option<Value>,
+    // Status the replay must get, when it matters.
+    #[serde(default)]
+    pub status: Option<u16>,
+}
+
+impl Recording {
+    fn label(&self) -> String {
+        format!("{} {}", self.method, self.path)
+    }
+}
+
+pub struct ContractReport {
+    pub checked: usize,
+    // (recording, problem) for every violation found.
+    pub failures: Vec<(String, String)>,
+}
+
+// Reads the recordings in `path`, skipping blank lines.
+pub fn load_recordings(path: &Path) -> Result<Vec<Recording>> {
+    let text = std::fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| serde_json::from_str(line).with_context(|| format!("{}:{}", path.display(), i + 1)))
+        .collect()
+}
+
+// Replays `recordings` against the server at `base_url`, whose certificate must be `cert`.
+pub async fn run(base_url: &str, cert: CertificateDer<'static>, recordings: &[Recording]) -> Result<ContractReport> {
+    let client = Client::connect(base_url, cert).await?;
+    let spec = client.send(Method::GET, SPEC_PATH, &HashMap::new(), None).await?;
+    if spec.status != 200 {
+        return Err(anyhow!("GET {SPEC_PATH} answered {}", spec.status));
+    }
+    let spec: Value = serde_json::from_slice(&spec.body).context("parsing the OpenAPI document")?;
+
+    let mut failures = Vec::new();
+    for recording in recordings {
+        for problem in check(&client, &spec, recording).await {
+            failures.push((recording.label(), problem));
+        }
+    }
+    Ok(ContractReport { checked: recordings.len(), failures })
+}
+
+// Problems with one recorded exchange; empty when it honours the contract.
+async fn check(client: &Client, spec: &Value, recording: &Recording) -> Vec<String> {
+    let Ok(method) = recording.method.parse::<Method>() else {
+        return vec![format!("invalid method {:?}", recording.method)];
+    };
+    let (path, _) = tenant::split_path(&recording.path);
+    let Some(operation) = find_operation(spec, &method, &path) else {
+        return vec!["route is not documented in the OpenAPI spec".to_string()];
+    };
+
+    let mut problems = Vec::new();
+    let query: Vec<String> = recording
+        .query
+        .as_deref()
+        .map(|q| form_urlencoded::parse(q.as_bytes()).map(|(name, _)| name.into_owned()).collect())
+        .unwrap_or_default();
+    for parameter in operation["parameters"].as_array().into_iter().flatten() {
+        if parameter["in"] == "query"
+            && parameter["required"] == true
+            && let Some(name) = parameter["name"].as_str()
+            && !query.iter().any(|q| q == name)
+        {
+            problems.push(format!("request lacks required query parameter {name}"));
+        }
+    }
+    let request_schema = &operation["requestBody"]["content"]["application/json"]["schema"];
+    let body = recording.body.as_ref().map(|body| Bytes::from(body.to_string()));
+    if !request_schema.is_null() {
+        match &body {
+            Some(body) => problems.extend(violations("request", request_schema, body)),
+            None => problems.push("request has no body but the route documents one".to_string()),
+        }
+    }
+
+    let target = match &recording.query {
+        Some(query) => format!("{}?{query}", recording.path),
+        None => recording.path.clone(),
+    };
+    let response = match client.send(method, &target, &recording.headers, body).await {
+        Ok(response) => response,
+        Err(err) => {
+            problems.push(format!("request failed: {err:#}"));
+            return problems;
+        }
+    };
+    if let Some(expected) = recording.status
+        && expected != response.status
+    {
+        problems.push(format!("expected status {expected}, got {}", response.status));
+    }
+
+    let body = unwrap_envelope(&response);
+    if response.status >= 400 {
+        if serde_json::from_slice::<ErrorResponse>(&body).is_err() {
+            problems.push(format!("{} response is not an ErrorResponse", response.status));
+        }
+    } else if (200..300).contains(&response.status) && response.status != 204 {
+        let response_schema = &operation["responses"]["200"]["content"]["application/json"]["schema"];
+        if !response_schema.is_null() {
+            if !response.content_type.starts_with("application/json") {
+                problems.push(format!("expected a JSON response, got {:?}", response.content_type));
+            } else {
+                problems.extend(violations("response", response_schema, &body));
+            }
+        }
+    }
+    problems
+}
+
+fn violations(what: &str, schema: &Value, body: &[u8]) -> Vec<String> {
+    match ValidationMiddleware::validate_body(schema, body) {
+        Ok(()) => Vec::new(),
+        Err(errors) => errors.into_iter().map(|e| format!("{what} {}: {}", e.field, e.message)).collect(),
+    }
+}
+
+// The payload of an enveloped response (see response::ApiResponse): `data` on
+// success, the first error otherwise. Other bodies are returned as they are.
+fn unwrap_envelope(response: &Reply) -> Bytes {
+    if !response.content_type.contains("profile=\"envelope\"") {
+        return response.body.clone();
+    }
+    let Ok(envelope) = serde_json::from_slice::<Value>(&response.body) else {
+        return response.body.clone();
+    };
+    let payload = if response.status >= 400 { &envelope["errors"][0] } else { &envelope["data"] };
+    Bytes::from(payload.to_string())
+}
+
+// The operation documented for `method` on `path`, matching "{name}" templates
+// against any one segment. HEAD is documented as GET.
+fn find_operation<'a>(spec: &'a Value, method: &Method, path: &str) -> Option<&'a Value> {
+    let method = if *method == Method::HEAD { "get".to_string() } else { method.as_str().to_lowercase() };
+    let segments: Vec<&str> = path.trim_end_matches('/').split('/').collect();
+    spec["paths"].as_object()?.iter().find_map(|(template, item)| {
+        let pattern: Vec<&str> = template.trim_end_matches('/').split('/').collect();
+        let matches = pattern.len() == segments.len()
+            && pattern.iter().zip(&segments).all(|(p, s)| (p.starts_with('{') && !s.is_empty()) || p == s);
+        matches.then(|| item.get(&method)).flatten()
+    })
+}
+
+// A response read in full.
+struct Reply {
+    status: u16,
+    content_type: String,
+    body: Bytes,
+}
+
+// HTTP/3 client used for the replay; one connection, a request stream per exchange.
+struct Client {
+    authority: String,
+    send: h3::client::SendRequest<h3_quinn::OpenStreams, Bytes>,
+    // Keeps the endpoint's socket open for the connection.
+    _endpoint: quinn::Endpoint,
+}
+
+impl Client {
+    async fn connect(base_url: &str, cert: CertificateDer<'static>) -> Result<Self> {
+        let uri: Uri = base_url.parse().with_context(|| format!("invalid base URL {base_url}"))?;
+        let host = uri.host().ok_or_else(|| anyhow!("base URL {base_url} has no host"))?.to_string();
+        let port = uri.port_u16().unwrap_or(443);
+        let addr = tokio::net::lookup_host((host.as_str(), port))
+            .await?
+            .next()
+            .ok_or_else(|| anyhow!("{host} did not resolve"))?;
+
+        let provider = Arc::new(rustls::crypto::aws_lc_rs::default_provider());
+        let mut tls = rustls::ClientConfig::builder_with_provider(provider.clone())
+            .with_protocol_versions(&[&rustls::version::TLS13])?
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(PinnedCertificate { cert, provider }))
+            .with_no_client_auth();
+        tls.alpn_protocols = vec![b"h3".to_vec()];
+        let config = quinn::ClientConfig::new(Arc::new(quinn::crypto::rustls::QuicClientConfig::try_from(tls)?));
+
+        let bind = if addr.is_ipv6() { "[::]:0" } else { "0.0.0.0:0" };
+        let mut endpoint = quinn::Endpoint::client(bind.parse()?)?;
+        endpoint.set_default_client_config(config);
+        let conn = endpoint.connect(addr, &host)?.await.with_context(|| format!("connecting to {addr}"))?;
+        let (mut driver, send) = h3::client::new(h3_quinn::Connection::new(conn)).await?;
+        tokio::spawn(async move {
+            let _ = futures::future::poll_fn(|cx| driver.poll_close(cx)).await;
+        });
+        Ok(Self { authority: format!("{host}:{port}"), send, _endpoint: endpoint })
+    }
+
+    async fn send(
+        &self,
+        method: Method,
+        target: &str,
+        headers: &HashMap<String, String>,
+        body: Option<Bytes>,
+    ) -> Result<Reply> {
+        let mut request = Request::builder().method(method).uri(format!("https://{}{target}", self.authority));
+        for (name, value) in headers {
+            request = request.header(name.as_str(), value.as_str());
+        }
+        if body.is_some() && !headers.keys().any(|name| name.eq_ignore_ascii_case("content-type")) {
+            request = request.header("content-type", "application/json");
+        }
+        let mut stream = self.send.clone().send_request(request.body(())?).await?;
+        if let Some(body) = body {
+            stream.send_data(body).await?;
+        }
+        stream.finish().await?;
+
+        let response = stream.recv_response().await?;
+        let mut body = BytesMut::new();
+        while let Some(mut chunk) = stream.recv_data().await? {
+            body.put(chunk.copy_to_bytes(chunk.remaining()));
+        }
+        let content_type = response
+            .headers()
+            .get(http::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+        Ok(Reply { status: response.status().as_u16(), content_type, body: body.freeze() })
+    }
+}
+
+// Reads the certificate the server under test presents.
+pub fn read_certificate(path: &Path) -> Result<CertificateDer<'static>> {
+    CertificateDer::from_pem_file(path).with_context(|| format!("reading {}", path.display()))
+}
+
+// Accepts exactly one certificate, whoever issued it; handshake signatures are still
+// checked.
+#[derive(Debug)]
+struct PinnedCertificate {
+    cert: CertificateDer<'static>,
+    provider: Arc<CryptoProvider>,
+}
+
+impl ServerCertVerifier for PinnedCertificate {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        if end_entity.as_ref() == self.cert.as_ref() {
+            Ok(ServerCertVerified::assertion())
+        } else {
+            Err(rustls::Error::InvalidCertificate(rustls::CertificateError::UnknownIssuer))
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls12_signature(message, cert, dss, &self.provider.signature_verification_algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls13_signature(message, cert, dss, &self.provider.signature_verification_algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider.signature_verification_algorithms.supported_schemes()
+    }
+}
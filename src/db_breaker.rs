@@ -0,0 +1,232 @@
+use crate::config;
+use crate::error::{AppError, AppResult};
+use crate::logging;
+use crate::metrics::{Metrics, METRICS};
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use serde_json::json;
+use sqlx::mysql::MySqlPool;
+use std::cell::Cell;
+use std::fmt::Write;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// Circuit breaker in front of the database. Requests that fail because the database
+// can't be reached (connection, TLS or protocol errors, pool timeouts) are counted;
+// after DB_BREAKER_FAILURES of them in a row the breaker opens and AppServices::db()
+// answers 503 at once instead of letting every request wait out its own timeout. While
+// open, a background probe pings the database every DB_BREAKER_COOLDOWN_SECS and
+// closes the breaker on the first success.
+//   DB_BREAKER_FAILURES       consecutive failed requests that open the breaker (default 5)
+//   DB_BREAKER_COOLDOWN_SECS  time between probes while open (default 15)
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+const PROBE_TICK: Duration = Duration::from_secs(1);
+
+tokio::task_local! {
+    // How the request being served by the current task used the database.
+    static USAGE: Usage;
+}
+
+#[derive(Default)]
+struct Usage {
+    touched: Cell<bool>,
+    unreachable: Cell<bool>,
+}
+
+// What a request told the breaker about the database.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DbUse {
+    // The request never asked for the database.
+    Unused,
+    // The request used the database and got through to it.
+    Reached,
+    // The request failed because the database couldn't be reached.
+    Unreachable,
+}
+
+// Runs `future` (a request handler) and reports how it used the database.
+pub async fn track<F: Future>(future: F) -> (F::Output, DbUse) {
+    USAGE
+        .scope(Usage::default(), async move {
+            let output = future.await;
+            let usage = USAGE.with(|usage| {
+                if usage.unreachable.get() {
+                    DbUse::Unreachable
+                } else if usage.touched.get() {
+                    DbUse::Reached
+                } else {
+                    DbUse::Unused
+                }
+            });
+            (output, usage)
+        })
+        .await
+}
+
+// Marks the current request as using the database. Called by AppServices::db().
+pub fn touch() {
+    let _ = USAGE.try_with(|usage| usage.touched.set(true));
+}
+
+// Marks the current request as failed by an unreachable database if `err` says so.
+// Called when database errors are converted into AppError.
+pub fn note_sqlx(err: &sqlx::Error) {
+    if is_unreachable(err) {
+        let _ = USAGE.try_with(|usage| usage.unreachable.set(true));
+    }
+}
+
+pub fn note_anyhow(err: &anyhow::Error) {
+    if err.chain().filter_map(|cause| cause.downcast_ref::<sqlx::Error>()).any(is_unreachable) {
+        let _ = USAGE.try_with(|usage| usage.unreachable.set(true));
+    }
+}
+
+// Errors that say nothing about the query, only that the database didn't answer.
+fn is_unreachable(err: &sqlx::Error) -> bool {
+    matches!(
+        err,
+        sqlx::Error::Io(_)
+            | sqlx::Error::Tls(_)
+            | sqlx::Error::Protocol(_)
+            | sqlx::Error::PoolTimedOut
+            | sqlx::Error::PoolClosed
+            | sqlx::Error::WorkerCrashed
+    )
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BreakerState {
+    Closed,
+    Open,
+}
+
+struct Inner {
+    consecutive_failures: u32,
+    // When the breaker opened or last failed a probe; None while closed.
+    opened_at: Option<Instant>,
+}
+
+pub struct DbBreaker {
+    threshold: u32,
+    cooldown: Duration,
+    inner: Mutex<Inner>,
+}
+
+impl DbBreaker {
+    pub fn from_env() -> Result<Self> {
+        let number = |name: &str, default: u64| -> Result<u64> {
+            config::var(name)
+                .map(|v| v.parse().map_err(|_| anyhow!("{name} must be a positive integer")))
+                .transpose()
+                .map(|v| v.unwrap_or(default).max(1))
+        };
+        Ok(Self {
+            threshold: number("DB_BREAKER_FAILURES", 5)? as u32,
+            cooldown: Duration::from_secs(number("DB_BREAKER_COOLDOWN_SECS", 15)?),
+            inner: Mutex::new(Inner { consecutive_failures: 0, opened_at: None }),
+        })
+    }
+
+    fn inner(&self) -> std::sync::MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(|p| p.into_inner())
+    }
+
+    pub fn state(&self) -> BreakerState {
+        if self.inner().opened_at.is_some() {
+            BreakerState::Open
+        } else {
+            BreakerState::Closed
+        }
+    }
+
+    // Fails fast while the breaker is open.
+    pub fn check(&self) -> AppResult<()> {
+        if self.state() == BreakerState::Open {
+            Metrics::increment(&METRICS.db_breaker_rejected_total);
+            return Err(AppError::ServiceUnavailable("database is unavailable; try again shortly".to_string()));
+        }
+        Ok(())
+    }
+
+    // Counts a finished request. Requests that didn't use the database change nothing.
+    pub fn observe(&self, usage: DbUse) {
+        let mut inner = self.inner();
+        match usage {
+            DbUse::Unused => {}
+            DbUse::Reached => inner.consecutive_failures = 0,
+            DbUse::Unreachable => {
+                inner.consecutive_failures += 1;
+                if inner.opened_at.is_none() && inner.consecutive_failures >= self.threshold {
+                    inner.opened_at = Some(Instant::now());
+                    Metrics::increment(&METRICS.db_breaker_trips_total);
+                    logging::warn(
+                        "database circuit breaker opened",
+                        json!({
+                            "consecutive_failures": inner.consecutive_failures,
+                            "cooldown_secs": self.cooldown.as_secs(),
+                        }),
+                    );
+                }
+            }
+        }
+    }
+
+    // Pings the database whenever the breaker has been open for a cool-down, closing
+    // it on success and starting another cool-down on failure.
+    pub fn spawn_probe(self: Arc<Self>, pool: MySqlPool) {
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(PROBE_TICK).await;
+                let due = self.inner().opened_at.is_some_and(|at| at.elapsed() >= self.cooldown);
+                if !due {
+                    continue;
+                }
+                let ping = tokio::time::timeout(PROBE_TIMEOUT, sqlx::query("SELECT 1").execute(&pool)).await;
+                let mut inner = self.inner();
+                match ping {
+                    Ok(Ok(_)) => {
+                        inner.opened_at = None;
+                        inner.consecutive_failures = 0;
+                        logging::info("database circuit breaker closed", json!({}));
+                    }
+                    Ok(Err(err)) => {
+                        inner.opened_at = Some(Instant::now());
+                        logging::warn("database probe failed", json!({ "error": format!("{err:#}") }));
+                    }
+                    Err(_) => {
+                        inner.opened_at = Some(Instant::now());
+                        logging::warn("database probe timed out", json!({ "timeout_secs": PROBE_TIMEOUT.as_secs() }));
+                    }
+                }
+            }
+        });
+    }
+
+    // The breaker's current state as Prometheus gauges.
+    pub fn render_prometheus(&self) -> String {
+        let inner = self.inner();
+        let mut out = String::new();
+        let gauges = [
+            (
+                "rotiride_db_breaker_open",
+                "1 while the database circuit breaker is open",
+                u64::from(inner.opened_at.is_some()),
+            ),
+            (
+                "rotiride_db_breaker_consecutive_failures",
+                "Requests failed by an unreachable database since the last success",
+                u64::from(inner.consecutive_failures),
+            ),
+        ];
+        for (name, help, value) in gauges {
+            let _ = writeln!(out, "# HELP {name} {help}");
+            let _ = writeln!(out, "# TYPE {name} gauge");
+            let _ = writeln!(out, "{name} {value}");
+        }
+        out
+    }
+}
@@ -0,0 +1,125 @@
+use crate::error::AppError;
+use crate::faults;
+use crate::logging;
+use crate::metrics::{Metrics, METRICS};
+use rand::Rng;
+use serde_json::json;
+use sqlx::mysql::MySqlDatabaseError;
+use std::future::Future;
+use std::time::Duration;
+
+// Retries of database work that failed for reasons unrelated to the work itself.
+// Deadlocks (1213) and lock wait timeouts (1205) are retried for reads and writes
+// alike: MySQL rolls the victim back, and the operation retried must be a whole
+// transaction, which is dropped (so rolled back) before the next attempt. A dropped
+// connection is only retried for reads, because a write may have committed before the
+// connection went away.
+
+const MAX_ATTEMPTS: u32 = 3;
+const BASE_DELAY: Duration = Duration::from_millis(25);
+const MAX_DELAY: Duration = Duration::from_millis(500);
+
+// A deadlock (MySQL 1213) reports the standard SQLSTATE for a transaction the server
+// rolled back; a lock wait timeout only the generic HY000, so it's matched by number.
+const SERIALIZATION_FAILURE: &str = "40001";
+const ER_LOCK_WAIT_TIMEOUT: u16 = 1205;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    // Only reads; safe to run again whatever happened.
+    Read,
+    // Writes in one transaction; only run again when MySQL rolled it back.
+    Write,
+}
+
+// Errors the retry layer can look into for the database error behind them.
+pub trait SqlFailure {
+    fn sql_error(&self) -> Option<&sqlx::Error>;
+}
+
+impl SqlFailure for sqlx::Error {
+    fn sql_error(&self) -> Option<&sqlx::Error> {
+        Some(self)
+    }
+}
+
+impl SqlFailure for anyhow::Error {
+    fn sql_error(&self) -> Option<&sqlx::Error> {
+        self.chain().find_map(|cause| cause.downcast_ref::<sqlx::Error>())
+    }
+}
+
+impl SqlFailure for AppError {
+    fn sql_error(&self) -> Option<&sqlx::Error> {
+        match self {
+            AppError::Internal(err) => err.sql_error(),
+            _ => None,
+        }
+    }
+}
+
+fn is_transient(err: &sqlx::Error, access: Access) -> bool {
+    match err {
+        sqlx::Error::Database(db) => {
+            db.code().as_deref() == Some(SERIALIZATION_FAILURE)
+                || db.try_downcast_ref::<MySqlDatabaseError>().is_some_and(|e| e.number() == ER_LOCK_WAIT_TIMEOUT)
+        }
+        sqlx::Error::Io(_) | sqlx::Error::Protocol(_) | sqlx::Error::PoolTimedOut => access == Access::Read,
+        _ => false,
+    }
+}
+
+// Delay before retry number `attempt` (1-based): exponential with full jitter, so
+// transactions that deadlocked each other don't collide again in lockstep.
+fn backoff(attempt: u32) -> Duration {
+    let ceiling = (BASE_DELAY * 2u32.pow(attempt - 1)).min(MAX_DELAY);
+    ceiling.mul_f64(rand::thread_rng().gen_range(0.0..=1.0))
+}
+
+// Runs `attempt` until it succeeds, fails with an error that isn't transient for
+// `access`, or has failed MAX_ATTEMPTS times. `operation` names it in logs.
+pub async fn retry<T, E, F, Fut>(access: Access, operation: &str, mut attempt: F) -> Result<T, E>
+where
+    E: SqlFailure + From<sqlx::Error>,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    let mut attempts = 1;
+    loop {
+        let outcome = match faults::database_attempt().await {
+            Ok(()) => attempt().await,
+            Err(err) => Err(E::from(err)),
+        };
+        let err = match outcome {
+            Ok(value) => return Ok(value),
+            Err(err) => err,
+        };
+        let Some(sql) = err.sql_error().filter(|sql| is_transient(sql, access)) else {
+            return Err(err);
+        };
+        if attempts >= MAX_ATTEMPTS {
+            Metrics::increment(&METRICS.db_retries_exhausted_total);
+            logging::warn(
+                "database retries exhausted",
+                json!({ "operation": operation, "attempts": attempts, "error": sql.to_string() }),
+            );
+            return Err(err);
+        }
+        Metrics::increment(match access {
+            Access::Read => &METRICS.db_read_retries_total,
+            Access::Write => &METRICS.db_write_retries_total,
+        });
+        let delay = backoff(attempts);
+        logging::info(
+            "retrying database operation",
+            json!({
+                "operation": operation,
+                "attempt": attempts,
+                "delay_ms": delay.as_millis() as u64,
+                "error": sql.to_string(),
+            }),
+        );
+        tokio::time::sleep(delay).await;
+        attempts += 1;
+    }
+}
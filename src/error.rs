@@ -0,0 +1,246 @@
+use crate::access_log::redacted_path;
+use crate::db_breaker;
+use crate::i18n;
+use crate::logging;
+use crate::response::ResponseBuilder;
+use bytes::Bytes;
+use http::{Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::fmt;
+
+// A problem with a single input field, e.g. {"field": "polygon[2].latitude", "message": "..."}.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(field: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            field: field.into(),
+            message: message.into(),
+        }
+    }
+}
+
+// Standard JSON error body returned by the API.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ErrorResponse {
+    // Machine-readable code, e.g. "validation_error".
+    pub error: String,
+    // Human-readable description.
+    pub message: String,
+    // End-user text for the error code in the negotiated language.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub localized_message: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub details: Vec<FieldError>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
+impl ErrorResponse {
+    pub fn new(error: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            error: error.into(),
+            message: message.into(),
+            localized_message: None,
+            details: Vec::new(),
+            request_id: None,
+        }
+    }
+
+    // 422 body listing every invalid field.
+    pub fn validation_error(details: Vec<FieldError>) -> Self {
+        Self {
+            details,
+            ..Self::new("validation_error", "request body failed validation")
+        }
+    }
+
+    pub fn with_request_id(mut self, request_id: Option<String>) -> Self {
+        self.request_id = request_id;
+        self
+    }
+
+    pub fn with_language(mut self, lang: &str) -> Self {
+        self.localized_message = i18n::t(lang, &format!("error.{}", self.error)).map(str::to_string);
+        self
+    }
+
+    // Serializes the error as a JSON response with the given status.
+    // Falls back to a bare status if serialization somehow fails.
+    pub fn into_response(self, status: StatusCode) -> Response<Bytes> {
+        ResponseBuilder::json(status, &self).unwrap_or_else(|_| {
+            let mut response = Response::new(Bytes::new());
+            *response.status_mut() = status;
+            response
+        })
+    }
+}
+
+// Errors returned by handlers. The router renders them into an ErrorResponse.
+#[derive(Debug)]
+pub enum AppError {
+    BadRequest(String),
+    Unauthorized(String),
+    Forbidden(String),
+    NotFound(String),
+    Conflict(String),
+    // The path exists but not for this method; lists the methods it does accept.
+    MethodNotAllowed { allowed: Vec<http::Method> },
+    Validation(Vec<FieldError>),
+    // Request body (or one multipart part) exceeded a size limit.
+    PayloadTooLarge(String),
+    // Rate limited; the client may retry after this many seconds.
+    TooManyRequests { retry_after_secs: u64 },
+    // Shed under overload; the client may retry after this many seconds.
+    Overloaded { retry_after_secs: u64 },
+    ServiceUnavailable(String),
+    // Anything unexpected. The cause is logged but never sent to the client.
+    Internal(anyhow::Error),
+}
+
+// Result type used by handlers.
+pub type AppResult<T> = std::result::Result<T, AppError>;
+
+impl AppError {
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            AppError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            AppError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden(_) => StatusCode::FORBIDDEN,
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::Conflict(_) => StatusCode::CONFLICT,
+            AppError::MethodNotAllowed { .. } => StatusCode::METHOD_NOT_ALLOWED,
+            AppError::Validation(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            AppError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            AppError::TooManyRequests { .. } => StatusCode::TOO_MANY_REQUESTS,
+            AppError::Overloaded { .. } => StatusCode::SERVICE_UNAVAILABLE,
+            AppError::ServiceUnavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    pub fn error_code(&self) -> &'static str {
+        match self {
+            AppError::BadRequest(_) => "bad_request",
+            AppError::Unauthorized(_) => "unauthorized",
+            AppError::Forbidden(_) => "forbidden",
+            AppError::NotFound(_) => "not_found",
+            AppError::Conflict(_) => "conflict",
+            AppError::MethodNotAllowed { .. } => "method_not_allowed",
+            AppError::Validation(_) => "validation_error",
+            AppError::PayloadTooLarge(_) => "payload_too_large",
+            AppError::TooManyRequests { .. } => "rate_limited",
+            AppError::Overloaded { .. } => "overloaded",
+            AppError::ServiceUnavailable(_) => "service_unavailable",
+            AppError::Internal(_) => "internal_error",
+        }
+    }
+
+    // True for 4xx errors caused by the request rather than the server.
+    pub fn is_client_error(&self) -> bool {
+        self.status_code().is_client_error()
+    }
+
+    // Builds the client-facing body. Internal causes are replaced by a generic message.
+    pub fn to_error_response(&self, request_id: Option<String>) -> ErrorResponse {
+        let response = match self {
+            AppError::Validation(details) => ErrorResponse::validation_error(details.clone()),
+            AppError::Internal(_) => ErrorResponse::new(self.error_code(), "internal server error"),
+            _ => ErrorResponse::new(self.error_code(), self.to_string()),
+        };
+        response.with_request_id(request_id)
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppError::BadRequest(msg)
+            | AppError::Unauthorized(msg)
+            | AppError::Forbidden(msg)
+            | AppError::NotFound(msg)
+            | AppError::Conflict(msg)
+            | AppError::PayloadTooLarge(msg)
+            | AppError::ServiceUnavailable(msg) => f.write_str(msg),
+            AppError::MethodNotAllowed { allowed } => write!(f, "method not allowed; use {}", allow_header(allowed)),
+            AppError::Validation(details) => write!(f, "{} invalid field(s)", details.len()),
+            AppError::TooManyRequests { retry_after_secs } => {
+                write!(f, "too many requests; retry after {retry_after_secs}s")
+            }
+            AppError::Overloaded { retry_after_secs } => {
+                write!(f, "server is overloaded; retry after {retry_after_secs}s")
+            }
+            AppError::Internal(err) => write!(f, "{err:#}"),
+        }
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl From<anyhow::Error> for AppError {
+    fn from(err: anyhow::Error) -> Self {
+        db_breaker::note_anyhow(&err);
+        AppError::Internal(err)
+    }
+}
+
+impl From<http::Error> for AppError {
+    fn from(err: http::Error) -> Self {
+        AppError::Internal(err.into())
+    }
+}
+
+impl From<sqlx::Error> for AppError {
+    fn from(err: sqlx::Error) -> Self {
+        db_breaker::note_sqlx(&err);
+        match err {
+            sqlx::Error::RowNotFound => AppError::NotFound("record not found".to_string()),
+            sqlx::Error::PoolTimedOut => AppError::ServiceUnavailable("database is busy".to_string()),
+            other => AppError::Internal(other.into()),
+        }
+    }
+}
+
+// Central error renderer used by the router for every handler: logs the error
+// (client errors at warn, server errors at error, with the internal cause) and
+// converts it into an ErrorResponse carrying the request id and a message in `lang`.
+pub fn render_error(err: &AppError, request_id: Option<String>, lang: &str, method: &str, path: &str) -> Response<Bytes> {
+    let fields = json!({
+        "method": method,
+        "path": redacted_path(path),
+        "status": err.status_code().as_u16(),
+        "error_code": err.error_code(),
+        "request_id": request_id,
+        "error": err.to_string(),
+    });
+    if err.is_client_error() {
+        logging::warn("request failed", fields);
+    } else {
+        logging::error("request failed", fields);
+    }
+    let mut response = err
+        .to_error_response(request_id)
+        .with_language(lang)
+        .into_response(err.status_code());
+    if let AppError::TooManyRequests { retry_after_secs } | AppError::Overloaded { retry_after_secs } = err {
+        response
+            .headers_mut()
+            .insert(http::header::RETRY_AFTER, http::HeaderValue::from((*retry_after_secs).max(1)));
+    }
+    if let AppError::MethodNotAllowed { allowed } = err
+        && let Ok(value) = http::HeaderValue::from_str(&allow_header(allowed))
+    {
+        response.headers_mut().insert(http::header::ALLOW, value);
+    }
+    response
+}
+
+// Value of the Allow header, e.g. "GET, HEAD, POST".
+fn allow_header(methods: &[http::Method]) -> String {
+    methods.iter().map(http::Method::as_str).collect::<Vec<_>>().join(", ")
+}
@@ -0,0 +1,161 @@
+use crate::geocoding::Coordinates;
+use crate::runtime_config::ConfigService;
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+
+// Where an order is in its lifecycle. Estimates are made afresh for every order
+// response, so after a transition the phases already behind it drop out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderStage {
+    Placed,
+    Preparing,
+    ReadyForPickup,
+    OutForDelivery,
+    Delivered,
+}
+
+impl OrderStage {
+    // The stage of an order in `status` (see orders::STATUSES); None once it is finished
+    // and nothing is left to estimate.
+    pub fn from_status(status: &str) -> Option<Self> {
+        match status {
+            "queued" | "placed" => Some(Self::Placed),
+            "preparing" => Some(Self::Preparing),
+            "ready" => Some(Self::ReadyForPickup),
+            "out_for_delivery" => Some(Self::OutForDelivery),
+            _ => None,
+        }
+    }
+}
+
+// Everything the estimator needs to know about one order.
+#[derive(Debug, Clone)]
+pub struct EtaInputs {
+    pub stage: OrderStage,
+    // preparation_time (minutes) of each ordered item, one entry per unit. When empty,
+    // the kitchen's average minutes per order is used.
+    pub item_preparation_minutes: Vec<u32>,
+    // Orders ahead of this one in the kitchen queue.
+    pub kitchen_queue_depth: u32,
+    // The delivery leg; None for dine-in orders, which are done once the kitchen is.
+    pub delivery: Option<DeliveryRoute>,
+    // When the current stage started; preparation already spent is subtracted from the estimate.
+    pub stage_started_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct DeliveryRoute {
+    // Courier position, if one has been assigned.
+    pub courier_location: Option<Coordinates>,
+    // Restaurant (pickup) location.
+    pub restaurant_location: Coordinates,
+    // Customer (drop-off) location.
+    pub delivery_location: Coordinates,
+}
+
+// Result of an estimate, with the per-phase breakdown for debugging and display.
+#[derive(Debug, Clone, Serialize)]
+pub struct EtaEstimate {
+    pub estimated_delivery_time: DateTime<Utc>,
+    pub queue_wait_minutes: i64,
+    pub preparation_minutes: i64,
+    pub travel_minutes: i64,
+    pub stage: OrderStage,
+}
+
+const ROAD_DISTANCE_FACTOR: f64 = 1.3;
+
+// Tunable parameters for the estimate, see from_config.
+#[derive(Debug, Clone)]
+pub struct EtaEstimator {
+    // How many orders the kitchen works on at once.
+    pub kitchen_parallelism: u32,
+    // Average minutes each queued order adds before ours is started.
+    pub minutes_per_queued_order: f64,
+    // Average courier speed, including traffic and stops.
+    pub courier_speed_kmh: f64,
+    // Fixed overhead for pickup and handover at the door.
+    pub handoff_minutes: f64,
+    // Road distance is longer than the straight line; this scales haversine distance.
+    pub road_distance_factor: f64,
+}
+
+impl EtaEstimator {
+    // The estimator for the current runtime config. The kitchen knobs are the ones its
+    // queue runs on (kitchen_queue.rs): kitchen_max_active_orders orders at once, each
+    // taking about kitchen_minutes_per_order minutes.
+    pub fn from_config(config: &ConfigService) -> Self {
+        Self {
+            kitchen_parallelism: config.kitchen_max_active_orders().max(1),
+            minutes_per_queued_order: f64::from(config.kitchen_minutes_per_order()),
+            courier_speed_kmh: config.courier_speed_kmh(),
+            handoff_minutes: config.delivery_handoff_minutes(),
+            road_distance_factor: ROAD_DISTANCE_FACTOR,
+        }
+    }
+
+    // Minutes until the kitchen starts on an order with `depth` orders ahead of it.
+    pub fn queue_wait_minutes(&self, depth: u32) -> f64 {
+        f64::from(depth) / f64::from(self.kitchen_parallelism) * self.minutes_per_queued_order
+    }
+
+    // Estimates the delivery time as of `now`.
+    pub fn estimate(&self, inputs: &EtaInputs, now: DateTime<Utc>) -> EtaEstimate {
+        let elapsed_in_stage = (now - inputs.stage_started_at).num_minutes().max(0) as f64;
+
+        // Queue wait only applies before the kitchen has started on the order.
+        let queue_wait = match inputs.stage {
+            OrderStage::Placed => self.queue_wait_minutes(inputs.kitchen_queue_depth),
+            _ => 0.0,
+        };
+
+        // Items are cooked in parallel, so the slowest item bounds preparation.
+        let preparation = match inputs.stage {
+            OrderStage::Placed => self.preparation_minutes(inputs),
+            OrderStage::Preparing => (self.preparation_minutes(inputs) - elapsed_in_stage).max(1.0),
+            _ => 0.0,
+        };
+
+        let travel = match (inputs.stage, inputs.delivery) {
+            (OrderStage::Delivered, _) | (_, None) => 0.0,
+            // Once out for delivery, only the leg from the courier to the customer remains.
+            (OrderStage::OutForDelivery, Some(route)) => {
+                let from = route.courier_location.unwrap_or(route.restaurant_location);
+                self.travel_minutes(from, route.delivery_location)
+            }
+            // Before pickup the courier must first reach the restaurant, which can overlap preparation.
+            (_, Some(route)) => {
+                let to_restaurant = route
+                    .courier_location
+                    .map(|c| self.travel_minutes(c, route.restaurant_location))
+                    .unwrap_or(0.0);
+                let ahead = (to_restaurant - queue_wait - preparation).max(0.0);
+                ahead + self.travel_minutes(route.restaurant_location, route.delivery_location)
+            }
+        };
+
+        let total = queue_wait + preparation + travel;
+        EtaEstimate {
+            estimated_delivery_time: now + Duration::seconds((total * 60.0).round() as i64),
+            queue_wait_minutes: queue_wait.round() as i64,
+            preparation_minutes: preparation.round() as i64,
+            travel_minutes: travel.round() as i64,
+            stage: inputs.stage,
+        }
+    }
+
+    fn preparation_minutes(&self, inputs: &EtaInputs) -> f64 {
+        inputs
+            .item_preparation_minutes
+            .iter()
+            .copied()
+            .max()
+            .map_or(self.minutes_per_queued_order, f64::from)
+    }
+
+    fn travel_minutes(&self, from: Coordinates, to: Coordinates) -> f64 {
+        let km = from.distance_km(to) * self.road_distance_factor;
+        km / self.courier_speed_kmh * 60.0 + self.handoff_minutes
+    }
+}
@@ -0,0 +1,362 @@
+use crate::audit::{AuditEvent, AuditLogger};
+use crate::error::{AppError, AppResult, FieldError};
+use crate::extract::{Admin, Path, Query, State, ValidJson};
+use crate::feature_flags;
+use crate::openapi::ApiSchema;
+use crate::orders::table_for_token;
+use crate::response::ResponseBuilder;
+use crate::router::Router;
+use crate::tables::DiningTable;
+use crate::validation::Validate;
+use anyhow::Result;
+use bytes::Bytes;
+use http::{Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sqlx::mysql::MySqlPool;
+use sqlx::types::Json;
+
+// A/B experiments over dining tables, the closest thing to a user guests have. A
+// table's variant is picked with the same stable hash buckets as feature flag
+// rollouts, so it never changes while the weights stay put. The first time a variant
+// decides what a table sees, assign() logs an exposure; the results endpoint then
+// compares the orders each variant's tables placed after their exposure.
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Variant {
+    pub name: String,
+    // Share of tables, in percent; an experiment's weights sum to 100.
+    pub weight: u8,
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct Experiment {
+    #[sqlx(rename = "experiment_key")]
+    pub key: String,
+    #[sqlx(json)]
+    pub variants: Vec<Variant>,
+    pub active: bool,
+    pub description: String,
+}
+
+impl Experiment {
+    // The variant for `table`: the one whose weight range holds the table's bucket.
+    pub fn variant_for(&self, table: &DiningTable) -> &Variant {
+        let bucket = feature_flags::bucket(&self.key, &format!("table:{}", table.id));
+        let mut upper = 0;
+        for variant in &self.variants {
+            upper += variant.weight;
+            if bucket < upper {
+                return variant;
+            }
+        }
+        // Weights are checked to sum to 100, so this is only reached for a
+        // hand-edited row; fall back to the control.
+        &self.variants[0]
+    }
+}
+
+fn variant_schema() -> Value {
+    json!({
+        "type": "object",
+        "required": ["name", "weight"],
+        "properties": {
+            "name": { "type": "string", "minLength": 1, "maxLength": 50 },
+            "weight": { "type": "integer", "minimum": 0, "maximum": 100 }
+        }
+    })
+}
+
+impl ApiSchema for Experiment {
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["key", "variants", "active", "description"],
+            "properties": {
+                "key": { "type": "string" },
+                "variants": { "type": "array", "items": variant_schema() },
+                "active": { "type": "boolean" },
+                "description": { "type": "string" }
+            }
+        })
+    }
+}
+
+// Body of PUT /api/admin/experiments/:key. The first variant is the control, shown
+// to everyone while the experiment is inactive.
+#[derive(Debug, Deserialize)]
+pub struct ExperimentInput {
+    pub variants: Vec<Variant>,
+    #[serde(default = "default_active")]
+    pub active: bool,
+    #[serde(default)]
+    pub description: String,
+}
+
+fn default_active() -> bool {
+    true
+}
+
+impl ApiSchema for ExperimentInput {
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["variants"],
+            "properties": {
+                "variants": { "type": "array", "minItems": 2, "maxItems": 10, "items": variant_schema() },
+                "active": { "type": "boolean" },
+                "description": { "type": "string", "maxLength": 500 }
+            }
+        })
+    }
+}
+
+impl Validate for ExperimentInput {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        if self.variants.len() < 2 {
+            errors.push(FieldError::new("variants", "an experiment needs at least two variants"));
+        }
+        if self.variants.iter().map(|v| u32::from(v.weight)).sum::<u32>() != 100 {
+            errors.push(FieldError::new("variants", "weights must sum to 100"));
+        }
+        for (i, variant) in self.variants.iter().enumerate() {
+            if variant.name.trim().is_empty() || variant.name.len() > 50 {
+                errors.push(FieldError::new(format!("variants[{i}].name"), "must be 1 to 50 characters"));
+            }
+            if self.variants[..i].iter().any(|other| other.name == variant.name) {
+                errors.push(FieldError::new(format!("variants[{i}].name"), "is used twice"));
+            }
+        }
+        errors
+    }
+}
+
+// What GET /api/experiments/:key tells the app to show.
+#[derive(Debug, Serialize)]
+pub struct Assignment {
+    pub experiment: String,
+    pub variant: String,
+    // False when the experiment is inactive and the control is shown without logging.
+    pub exposed: bool,
+}
+
+impl ApiSchema for Assignment {
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["experiment", "variant", "exposed"],
+            "properties": {
+                "experiment": { "type": "string" },
+                "variant": { "type": "string" },
+                "exposed": { "type": "boolean" }
+            }
+        })
+    }
+}
+
+// Conversion figures of one variant.
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct VariantResult {
+    pub variant: String,
+    pub exposed_tables: i64,
+    // Exposed tables that placed at least one order afterwards.
+    pub converted_tables: i64,
+    pub orders: i64,
+    pub revenue_paise: i64,
+    #[sqlx(skip)]
+    pub conversion_rate: f64,
+}
+
+impl ApiSchema for VariantResult {
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["variant", "exposed_tables", "converted_tables", "orders", "revenue_paise", "conversion_rate"],
+            "properties": {
+                "variant": { "type": "string" },
+                "exposed_tables": { "type": "integer" },
+                "converted_tables": { "type": "integer" },
+                "orders": { "type": "integer" },
+                "revenue_paise": { "type": "integer" },
+                "conversion_rate": { "type": "number" }
+            }
+        })
+    }
+}
+
+const SELECT_EXPERIMENT: &str = "SELECT experiment_key, variants, active, description FROM experiments";
+
+pub struct ExperimentRepository<'a> {
+    pool: &'a MySqlPool,
+}
+
+impl<'a> ExperimentRepository<'a> {
+    pub fn new(pool: &'a MySqlPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn list(&self) -> Result<Vec<Experiment>> {
+        Ok(sqlx::query_as(&format!("{SELECT_EXPERIMENT} ORDER BY experiment_key"))
+            .fetch_all(self.pool)
+            .await?)
+    }
+
+    pub async fn get(&self, key: &str) -> Result<Option<Experiment>> {
+        Ok(sqlx::query_as(&format!("{SELECT_EXPERIMENT} WHERE experiment_key = ?"))
+            .bind(key)
+            .fetch_optional(self.pool)
+            .await?)
+    }
+
+    pub async fn upsert(&self, key: &str, input: &ExperimentInput) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO experiments (experiment_key, variants, active, description) VALUES (?, ?, ?, ?) \
+             ON DUPLICATE KEY UPDATE variants = VALUES(variants), active = VALUES(active), \
+             description = VALUES(description)",
+        )
+        .bind(key)
+        .bind(Json(&input.variants))
+        .bind(input.active)
+        .bind(input.description.trim())
+        .execute(self.pool)
+        .await?;
+        Ok(())
+    }
+
+    // Logs the table's first exposure; later ones keep the original time and variant.
+    pub async fn record_exposure(&self, key: &str, variant: &str, table: &DiningTable) -> Result<()> {
+        sqlx::query(
+            "INSERT IGNORE INTO experiment_exposures (experiment_key, variant, restaurant_id, table_id) \
+             VALUES (?, ?, ?, ?)",
+        )
+        .bind(key)
+        .bind(variant)
+        .bind(table.restaurant_id)
+        .bind(table.id)
+        .execute(self.pool)
+        .await?;
+        Ok(())
+    }
+
+    // Per-variant conversions: orders not cancelled that exposed tables placed after
+    // their exposure.
+    pub async fn results(&self, key: &str) -> Result<Vec<VariantResult>> {
+        let mut results: Vec<VariantResult> = sqlx::query_as(
+            "SELECT e.variant, COUNT(DISTINCT e.table_id) AS exposed_tables, \
+             COUNT(DISTINCT o.table_id) AS converted_tables, COUNT(o.id) AS orders, \
+             CAST(COALESCE(SUM(o.total_paise), 0) AS SIGNED) AS revenue_paise \
+             FROM experiment_exposures e \
+             LEFT JOIN orders o ON o.table_id = e.table_id AND o.created_at >= e.created_at \
+             AND o.status <> 'cancelled' \
+             WHERE e.experiment_key = ? GROUP BY e.variant ORDER BY e.variant",
+        )
+        .bind(key)
+        .fetch_all(self.pool)
+        .await?;
+        for result in &mut results {
+            if result.exposed_tables > 0 {
+                result.conversion_rate = result.converted_tables as f64 / result.exposed_tables as f64;
+            }
+        }
+        Ok(results)
+    }
+}
+
+// The variant `table` should see, logging the exposure. Call this only where the
+// variant actually changes the response, so unexposed tables don't dilute results.
+pub async fn assign(pool: &MySqlPool, key: &str, table: &DiningTable) -> AppResult<Assignment> {
+    let repository = ExperimentRepository::new(pool);
+    let experiment = repository.get(key).await?.ok_or_else(experiment_not_found)?;
+    if !experiment.active {
+        return Ok(Assignment { experiment: experiment.key, variant: experiment.variants[0].name.clone(), exposed: false });
+    }
+    let variant = experiment.variant_for(table).name.clone();
+    repository.record_exposure(key, &variant, table).await?;
+    Ok(Assignment { experiment: experiment.key, variant, exposed: true })
+}
+
+// Query of GET /api/experiments/:key.
+#[derive(Debug, Deserialize)]
+pub struct AssignmentQuery {
+    pub table_token: String,
+}
+
+// Registers the guest assignment endpoint, for variants the app renders itself, and
+// the admin experiment endpoints.
+pub fn routes(router: Router) -> Router {
+    router
+        .get("/api/experiments/:key", get_assignment)
+        .summary("The experiment variant a table sees; logs the exposure")
+        .query_param("table_token", true)
+        .response_schema(Assignment::schema())
+        .group("/api/admin/experiments", |group| {
+            group
+                .requires_admin()
+                .get("", list_experiments)
+                .summary("List experiments")
+                .response_schema(json!({ "type": "array", "items": Experiment::schema() }))
+                .put("/:key", put_experiment)
+                .summary("Create or replace an experiment")
+                .request_schema(ExperimentInput::schema())
+                .response_schema(Experiment::schema())
+                .get("/:key/results", experiment_results)
+                .summary("Exposures and order conversions per variant")
+                .response_schema(json!({ "type": "array", "items": VariantResult::schema() }))
+        })
+}
+
+fn experiment_not_found() -> AppError {
+    AppError::NotFound("experiment not found".to_string())
+}
+
+async fn get_assignment(
+    Path(key): Path<String>,
+    Query(query): Query<AssignmentQuery>,
+    State(services): State,
+) -> AppResult<Response<Bytes>> {
+    let table = table_for_token(&services, &query.table_token).await?;
+    let assignment = assign(services.db()?, &key, &table).await?;
+    ResponseBuilder::json(StatusCode::OK, &assignment)
+}
+
+async fn list_experiments(_: Admin, State(services): State) -> AppResult<Response<Bytes>> {
+    let experiments = ExperimentRepository::new(services.db()?).list().await?;
+    ResponseBuilder::json(StatusCode::OK, &experiments)
+}
+
+// Changing weights of a running experiment moves tables between variants; their
+// logged exposures keep the variant they first saw.
+async fn put_experiment(
+    _: Admin,
+    Path(key): Path<String>,
+    ValidJson(input): ValidJson<ExperimentInput>,
+    audit: AuditLogger,
+    State(services): State,
+) -> AppResult<Response<Bytes>> {
+    if key.is_empty() || key.len() > 100 {
+        return Err(AppError::BadRequest("experiment keys are 1 to 100 characters".to_string()));
+    }
+    let pool = services.db()?;
+    let repository = ExperimentRepository::new(pool);
+    let before = repository.get(&key).await?;
+    repository.upsert(&key, &input).await?;
+    let experiment = repository.get(&key).await?.ok_or_else(experiment_not_found)?;
+    let event = AuditEvent {
+        action: "experiment.update",
+        target_type: "experiment",
+        target_id: key.clone(),
+        restaurant_id: None,
+        before: before.map(|e| json!(e)),
+        after: Some(json!(experiment)),
+    };
+    audit.record(pool, event).await?;
+    ResponseBuilder::json(StatusCode::OK, &experiment)
+}
+
+async fn experiment_results(_: Admin, Path(key): Path<String>, State(services): State) -> AppResult<Response<Bytes>> {
+    let repository = ExperimentRepository::new(services.db()?);
+    repository.get(&key).await?.ok_or_else(experiment_not_found)?;
+    let results = repository.results(&key).await?;
+    ResponseBuilder::json(StatusCode::OK, &results)
+}
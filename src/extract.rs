@@ -0,0 +1,260 @@
+use crate::app::AppServices;
+use crate::audit::AuditLogger;
+use crate::error::{AppError, AppResult};
+use crate::request::RequestContext;
+use crate::router::Handler;
+use crate::tenant::{self, Restaurant};
+use crate::validation::Validate;
+use anyhow::anyhow;
+use bytes::Bytes;
+use futures::future::BoxFuture;
+use http::Response;
+use serde::de::value::{Error as DeError, MapDeserializer};
+use serde::de::{self, DeserializeOwned, Deserializer, IntoDeserializer, Visitor};
+use std::future::Future;
+use std::sync::Arc;
+
+// Typed handler arguments. A handler declares what it needs, e.g.
+//
+//     async fn update_webhook(
+//         TenantAdmin(restaurant): TenantAdmin,
+//         Path(id): Path<i64>,
+//         ValidJson(input): ValidJson<WebhookInput>,
+//         State(services): State,
+//     )
+//
+// and the router extracts each argument in order before calling it. The first
+// extractor that fails short-circuits the request with its error, so the handler only
+// runs with well-formed input. Callers are authorized before that, by the route's
+// requires_admin/requires_tenant_admin middleware. Handlers taking (RequestContext,
+// Arc<AppServices>) keep working unchanged.
+pub trait FromRequest: Sized + Send {
+    fn from_request<'a>(ctx: &'a RequestContext, services: &'a Arc<AppServices>) -> BoxFuture<'a, AppResult<Self>>;
+}
+
+// Path captures (":id") deserialized into T: a single value when the route has one
+// capture, or a struct with a field per capture.
+pub struct Path<T>(pub T);
+
+impl<T: DeserializeOwned + Send> FromRequest for Path<T> {
+    fn from_request<'a>(ctx: &'a RequestContext, _: &'a Arc<AppServices>) -> BoxFuture<'a, AppResult<Self>> {
+        Box::pin(async move {
+            let result = match ctx.params.len() {
+                1 => T::deserialize(Param(ctx.params.values().next().map_or("", String::as_str))),
+                _ => T::deserialize(params(ctx.params.iter())),
+            };
+            result
+                .map(Path)
+                .map_err(|err| AppError::BadRequest(format!("invalid path parameter: {err}")))
+        })
+    }
+}
+
+// Query string deserialized into a struct; use Option fields for optional parameters.
+pub struct Query<T>(pub T);
+
+impl<T: DeserializeOwned + Send> FromRequest for Query<T> {
+    fn from_request<'a>(ctx: &'a RequestContext, _: &'a Arc<AppServices>) -> BoxFuture<'a, AppResult<Self>> {
+        Box::pin(async move {
+            T::deserialize(params(ctx.query().iter()))
+                .map(Query)
+                .map_err(|err| AppError::BadRequest(format!("invalid query string: {err}")))
+        })
+    }
+}
+
+// JSON body deserialized into T.
+pub struct Json<T>(pub T);
+
+impl<T: DeserializeOwned + Send> FromRequest for Json<T> {
+    fn from_request<'a>(ctx: &'a RequestContext, _: &'a Arc<AppServices>) -> BoxFuture<'a, AppResult<Self>> {
+        Box::pin(async move { ctx.json().map(Json) })
+    }
+}
+
+// JSON body deserialized into T and checked with its Validate impl; violations are 422.
+pub struct ValidJson<T>(pub T);
+
+impl<T: DeserializeOwned + Validate + Send> FromRequest for ValidJson<T> {
+    fn from_request<'a>(ctx: &'a RequestContext, _: &'a Arc<AppServices>) -> BoxFuture<'a, AppResult<Self>> {
+        Box::pin(async move {
+            let value: T = ctx.json()?;
+            let errors = value.validate();
+            if errors.is_empty() {
+                Ok(ValidJson(value))
+            } else {
+                Err(AppError::Validation(errors))
+            }
+        })
+    }
+}
+
+// The shared services.
+pub struct State(pub Arc<AppServices>);
+
+impl FromRequest for State {
+    fn from_request<'a>(_: &'a RequestContext, services: &'a Arc<AppServices>) -> BoxFuture<'a, AppResult<Self>> {
+        Box::pin(async move { Ok(State(services.clone())) })
+    }
+}
+
+// Proof that the route's AdminOnly middleware let the caller through: the global
+// ADMIN_API_TOKEN or an admin user (see auth::require_admin). It checks nothing itself,
+// and fails with an internal error on a route without requires_admin.
+pub struct Admin;
+
+impl FromRequest for Admin {
+    fn from_request<'a>(ctx: &'a RequestContext, _: &'a Arc<AppServices>) -> BoxFuture<'a, AppResult<Self>> {
+        Box::pin(async move {
+            ctx.admin.get().map(|()| Admin).ok_or_else(|| {
+                AppError::Internal(anyhow!("{} {} takes Admin without requires_admin", ctx.method, ctx.path))
+            })
+        })
+    }
+}
+
+// The request's restaurant, whose admin (or the global admin) the route's
+// TenantAdminOnly middleware found the caller to be. See tenant::verified.
+pub struct TenantAdmin(pub Restaurant);
+
+impl FromRequest for TenantAdmin {
+    fn from_request<'a>(ctx: &'a RequestContext, _: &'a Arc<AppServices>) -> BoxFuture<'a, AppResult<Self>> {
+        Box::pin(async move { tenant::verified(ctx).map(TenantAdmin) })
+    }
+}
+
+// Audit logger bound to this request's actor, request id and client address.
+impl FromRequest for AuditLogger {
+    fn from_request<'a>(ctx: &'a RequestContext, services: &'a Arc<AppServices>) -> BoxFuture<'a, AppResult<Self>> {
+        Box::pin(async move { Ok(AuditLogger::for_request(ctx, services)) })
+    }
+}
+
+// The language negotiated from Accept-Language.
+pub struct Language(pub &'static str);
+
+impl FromRequest for Language {
+    fn from_request<'a>(ctx: &'a RequestContext, _: &'a Arc<AppServices>) -> BoxFuture<'a, AppResult<Self>> {
+        Box::pin(async move { Ok(Language(ctx.lang)) })
+    }
+}
+
+// Anything the router can register as a handler: the classic
+// Fn(RequestContext, Arc<AppServices>) or an async fn of up to six extractors.
+pub trait IntoHandler<Args> {
+    fn into_handler(self) -> Handler;
+}
+
+impl<F, Fut> IntoHandler<(RequestContext, Arc<AppServices>)> for F
+where
+    F: Fn(RequestContext, Arc<AppServices>) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = AppResult<Response<Bytes>>> + Send + 'static,
+{
+    fn into_handler(self) -> Handler {
+        Arc::new(move |ctx, services| Box::pin(self(ctx, services)))
+    }
+}
+
+macro_rules! extractor_handler {
+    ($($arg:ident),*) => {
+        impl<F, Fut, $($arg,)*> IntoHandler<($($arg,)*)> for F
+        where
+            F: Fn($($arg),*) -> Fut + Send + Sync + 'static,
+            Fut: Future<Output = AppResult<Response<Bytes>>> + Send + 'static,
+            $($arg: FromRequest + 'static,)*
+        {
+            #[allow(non_snake_case, unused_variables)]
+            fn into_handler(self) -> Handler {
+                let handler = Arc::new(self);
+                Arc::new(move |ctx, services| {
+                    let handler = handler.clone();
+                    Box::pin(async move {
+                        $(let $arg = $arg::from_request(&ctx, &services).await?;)*
+                        handler($($arg),*).await
+                    })
+                })
+            }
+        }
+    };
+}
+
+extractor_handler!();
+extractor_handler!(A);
+extractor_handler!(A, B);
+extractor_handler!(A, B, C);
+extractor_handler!(A, B, C, D);
+extractor_handler!(A, B, C, D, E);
+extractor_handler!(A, B, C, D, E, G);
+
+// Deserializes name/value string pairs (path captures or the query string) as a map.
+fn params<'a>(pairs: impl Iterator<Item = (&'a String, &'a String)>) -> MapDeserializer<'a, impl Iterator<Item = (&'a str, Param<'a>)>, DeError> {
+    MapDeserializer::new(pairs.map(|(name, value)| (name.as_str(), Param(value.as_str()))))
+}
+
+// One textual value. Numbers and booleans are parsed from the text on request, which
+// serde's plain string deserializer won't do.
+struct Param<'a>(&'a str);
+
+macro_rules! parse_param {
+    ($($method:ident => $visit:ident),*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+                match self.0.parse() {
+                    Ok(value) => visitor.$visit(value),
+                    Err(_) => Err(de::Error::custom(format!("invalid value {:?}", self.0))),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for Param<'_> {
+    type Error = DeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        visitor.visit_str(self.0)
+    }
+
+    parse_param!(
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64
+    );
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _: &'static str, visitor: V) -> Result<V::Value, DeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        _: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, DeError> {
+        visitor.visit_enum(self.0.to_string().into_deserializer())
+    }
+
+    serde::forward_to_deserialize_any! {
+        i128 u128 char str string bytes byte_buf unit unit_struct seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+impl<'de, 'a> IntoDeserializer<'de, DeError> for Param<'a> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
@@ -0,0 +1,205 @@
+use crate::config;
+use crate::logging;
+#[cfg(feature = "fault-injection")]
+use crate::metrics::{Metrics, METRICS};
+use anyhow::{anyhow, Result};
+#[cfg(feature = "fault-injection")]
+use rand::Rng;
+use serde_json::json;
+use sqlx::error::{DatabaseError, ErrorKind};
+use std::borrow::Cow;
+use std::fmt;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+// Fault injection for resilience testing. With FAULT_INJECTION set, database work,
+// webhook deliveries and response sends fail or stall at random, at the configured
+// rates, so retries, the database circuit breaker and error handling can be seen
+// working end to end. It is meant for test environments only: it is compiled in only
+// with the cargo feature fault-injection, other builds refuse to start with
+// FAULT_INJECTION set, and the server warns at startup while it is on.
+//   FAULT_INJECTION  comma-separated <fault>=<value> entries; rates are between 0 and 1:
+//     db_unavailable=<rate>  AppServices::db() fails as if the pool timed out
+//     db_deadlock=<rate>     a retried database operation fails with a deadlock first
+//     db_delay=<rate>        a retried database operation starts delay_ms late
+//     webhook_error=<rate>   a webhook delivery fails without being sent
+//     stream_error=<rate>    a response stream is reset instead of answered
+//     stream_delay=<rate>    a response is sent delay_ms late
+//     delay_ms=<ms>          length of injected delays (default 500)
+
+const DEFAULT_DELAY: Duration = Duration::from_millis(500);
+
+// Whether this build can inject faults. Without it the hooks below never fire.
+pub const ENABLED: bool = cfg!(feature = "fault-injection");
+
+static FAULTS: OnceLock<Faults> = OnceLock::new();
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Faults {
+    pub db_unavailable: f64,
+    pub db_deadlock: f64,
+    pub db_delay: f64,
+    pub webhook_error: f64,
+    pub stream_error: f64,
+    pub stream_delay: f64,
+    pub delay: Duration,
+}
+
+impl Default for Faults {
+    fn default() -> Self {
+        Self {
+            db_unavailable: 0.0,
+            db_deadlock: 0.0,
+            db_delay: 0.0,
+            webhook_error: 0.0,
+            stream_error: 0.0,
+            stream_delay: 0.0,
+            delay: DEFAULT_DELAY,
+        }
+    }
+}
+
+impl Faults {
+    pub fn parse(spec: &str) -> Result<Self> {
+        let mut faults = Faults::default();
+        for entry in spec.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let (name, value) = entry
+                .split_once('=')
+                .ok_or_else(|| anyhow!("fault {entry} must be <fault>=<value>"))?;
+            let (name, value) = (name.trim(), value.trim());
+            if name == "delay_ms" {
+                let ms = value.parse().map_err(|_| anyhow!("delay_ms must be a whole number of milliseconds"))?;
+                faults.delay = Duration::from_millis(ms);
+                continue;
+            }
+            let rate = value
+                .parse::<f64>()
+                .ok()
+                .filter(|rate| (0.0..=1.0).contains(rate))
+                .ok_or_else(|| anyhow!("rate of {name} must be between 0 and 1"))?;
+            let slot = match name {
+                "db_unavailable" => &mut faults.db_unavailable,
+                "db_deadlock" => &mut faults.db_deadlock,
+                "db_delay" => &mut faults.db_delay,
+                "webhook_error" => &mut faults.webhook_error,
+                "stream_error" => &mut faults.stream_error,
+                "stream_delay" => &mut faults.stream_delay,
+                _ => return Err(anyhow!("unknown fault {name}")),
+            };
+            *slot = rate;
+        }
+        Ok(faults)
+    }
+}
+
+// Reads FAULT_INJECTION. Until this has run, and when it is unset, nothing is injected.
+pub fn init() -> Result<()> {
+    let Some(spec) = config::var("FAULT_INJECTION") else {
+        return Ok(());
+    };
+    if !ENABLED {
+        return Err(anyhow!("FAULT_INJECTION is set, but this build has no fault injection (feature fault-injection)"));
+    }
+    let faults = Faults::parse(&spec)?;
+    logging::warn("fault injection is enabled; do not run this in production", json!({ "faults": spec }));
+    let _ = FAULTS.set(faults);
+    Ok(())
+}
+
+// Whether a fault with `rate`, picked from the configured faults, fires this time.
+#[cfg(feature = "fault-injection")]
+fn fires(rate: impl Fn(&Faults) -> f64) -> bool {
+    let Some(faults) = FAULTS.get() else {
+        return false;
+    };
+    let rate = rate(faults);
+    let fired = rate > 0.0 && rand::thread_rng().gen_bool(rate);
+    if fired {
+        Metrics::increment(&METRICS.faults_injected_total);
+    }
+    fired
+}
+
+#[cfg(not(feature = "fault-injection"))]
+fn fires(_: impl Fn(&Faults) -> f64) -> bool {
+    false
+}
+
+fn delay() -> Duration {
+    FAULTS.get().map_or(DEFAULT_DELAY, |faults| faults.delay)
+}
+
+// Checked by AppServices::db(): the error an exhausted pool would give, which also
+// counts towards opening the database circuit breaker.
+pub fn database_unavailable() -> Option<sqlx::Error> {
+    fires(|f| f.db_unavailable).then_some(sqlx::Error::PoolTimedOut)
+}
+
+// Run before each attempt of a retried database operation.
+pub async fn database_attempt() -> Result<(), sqlx::Error> {
+    if fires(|f| f.db_delay) {
+        tokio::time::sleep(delay()).await;
+    }
+    if fires(|f| f.db_deadlock) {
+        return Err(sqlx::Error::Database(Box::new(InjectedDeadlock)));
+    }
+    Ok(())
+}
+
+// Run before a webhook delivery is sent.
+pub fn webhook() -> Result<()> {
+    if fires(|f| f.webhook_error) {
+        return Err(anyhow!("injected fault: webhook delivery failed"));
+    }
+    Ok(())
+}
+
+// Run before a response is sent; an error means the stream should be reset instead.
+pub async fn response() -> Result<()> {
+    if fires(|f| f.stream_delay) {
+        tokio::time::sleep(delay()).await;
+    }
+    if fires(|f| f.stream_error) {
+        return Err(anyhow!("injected fault: response stream reset"));
+    }
+    Ok(())
+}
+
+// A deadlock as the server would report it: the transaction was rolled back, with
+// SQLSTATE 40001, so db_retry treats it like the real thing.
+#[derive(Debug)]
+pub struct InjectedDeadlock;
+
+impl fmt::Display for InjectedDeadlock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+impl std::error::Error for InjectedDeadlock {}
+
+impl DatabaseError for InjectedDeadlock {
+    fn message(&self) -> &str {
+        "Deadlock found when trying to get lock (injected fault)"
+    }
+
+    fn code(&self) -> Option<Cow<'_, str>> {
+        Some(Cow::Borrowed("40001"))
+    }
+
+    fn as_error(&self) -> &(dyn std::error::Error + Send + Sync + 'static) {
+        self
+    }
+
+    fn as_error_mut(&mut self) -> &mut (dyn std::error::Error + Send + Sync + 'static) {
+        self
+    }
+
+    fn into_error(self: Box<Self>) -> Box<dyn std::error::Error + Send + Sync + 'static> {
+        self
+    }
+
+    fn kind(&self) -> ErrorKind {
+        ErrorKind::Other
+    }
+}
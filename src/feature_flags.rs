@@ -0,0 +1,375 @@
+use crate::audit::{AuditEvent, AuditLogger};
+use crate::error::{AppError, AppResult, FieldError};
+use crate::extract::{Admin, Path, Query, State, ValidJson};
+use crate::logging;
+use crate::openapi::ApiSchema;
+use crate::request::RequestContext;
+use crate::response::ResponseBuilder;
+use crate::router::Router;
+use crate::validation::Validate;
+use anyhow::Result;
+use bytes::Bytes;
+use http::header::{HeaderName, HeaderValue};
+use http::{Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use sqlx::mysql::MySqlPool;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+// Feature flags for gradual rollouts and kill switches. Handlers ask FlagService
+// whether a flag is on for a subject, a stable string such as "order:42" or
+// "restaurant:7": the same subject always lands in the same of 100 buckets, so a
+// 10% rollout keeps the same orders in it as it grows. Callers that want their
+// decisions visible pass the evaluations to expose(), which echoes them in the
+// X-Feature-Flags response header when the request sent X-Debug-Flags.
+
+// Request header asking for flag evaluations, and the response header carrying them.
+const DEBUG_HEADER: &str = "x-debug-flags";
+const EXPOSURE_HEADER: &str = "x-feature-flags";
+
+// One row of feature_flags.
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct FeatureFlag {
+    #[sqlx(rename = "flag_key")]
+    pub key: String,
+    pub enabled: bool,
+    pub rollout_percent: u8,
+    #[sqlx(json)]
+    pub overrides: HashMap<String, bool>,
+    pub description: String,
+}
+
+impl ApiSchema for FeatureFlag {
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["key", "enabled", "rollout_percent", "overrides", "description"],
+            "properties": {
+                "key": { "type": "string" },
+                "enabled": { "type": "boolean" },
+                "rollout_percent": { "type": "integer", "minimum": 0, "maximum": 100 },
+                "overrides": { "type": "object", "additionalProperties": { "type": "boolean" } },
+                "description": { "type": "string" }
+            }
+        })
+    }
+}
+
+// Body of PUT /api/admin/flags/:key.
+#[derive(Debug, Deserialize)]
+pub struct FlagInput {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub rollout_percent: u8,
+    #[serde(default)]
+    pub overrides: HashMap<String, bool>,
+    #[serde(default)]
+    pub description: String,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl ApiSchema for FlagInput {
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "enabled": { "type": "boolean" },
+                "rollout_percent": { "type": "integer", "minimum": 0, "maximum": 100 },
+                "overrides": { "type": "object", "maxProperties": 1000, "additionalProperties": { "type": "boolean" } },
+                "description": { "type": "string", "maxLength": 500 }
+            }
+        })
+    }
+}
+
+impl Validate for FlagInput {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        if self.rollout_percent > 100 {
+            errors.push(FieldError::new("rollout_percent", "must be between 0 and 100"));
+        }
+        if self.overrides.keys().any(|subject| subject.trim().is_empty() || subject.len() > 200) {
+            errors.push(FieldError::new("overrides", "subjects must be 1 to 200 characters"));
+        }
+        errors
+    }
+}
+
+// The outcome of one flag check and why.
+#[derive(Debug, Clone, Serialize)]
+pub struct Evaluation {
+    pub key: String,
+    pub subject: String,
+    pub enabled: bool,
+    // "unknown" (no such flag), "disabled" (kill switch), "override" or "rollout".
+    pub reason: &'static str,
+    // The subject's bucket, 0-99; on when below the rollout percentage.
+    pub bucket: u8,
+}
+
+impl ApiSchema for Evaluation {
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["key", "subject", "enabled", "reason", "bucket"],
+            "properties": {
+                "key": { "type": "string" },
+                "subject": { "type": "string" },
+                "enabled": { "type": "boolean" },
+                "reason": { "type": "string", "enum": ["unknown", "disabled", "override", "rollout"] },
+                "bucket": { "type": "integer", "minimum": 0, "maximum": 99 }
+            }
+        })
+    }
+}
+
+// Stable bucket of a subject for a flag. The key is part of the hash so that separate
+// flags at the same percentage don't all pick the same subjects.
+pub fn bucket(key: &str, subject: &str) -> u8 {
+    let digest = Sha256::digest(format!("{key}:{subject}").as_bytes());
+    let head = u64::from_be_bytes(digest[..8].try_into().expect("sha256 digest is 32 bytes"));
+    (head % 100) as u8
+}
+
+// In-memory copy of feature_flags, refreshed by polling and after every admin write,
+// so checks never touch the database.
+#[derive(Default)]
+pub struct FlagService {
+    flags: RwLock<Arc<HashMap<String, FeatureFlag>>>,
+}
+
+impl FlagService {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub async fn refresh(&self, pool: &MySqlPool) -> Result<()> {
+        let rows: Vec<FeatureFlag> =
+            sqlx::query_as("SELECT flag_key, enabled, rollout_percent, overrides, description FROM feature_flags")
+                .fetch_all(pool)
+                .await?;
+        let fresh = rows.into_iter().map(|flag| (flag.key.clone(), flag)).collect();
+        *self.flags.write().unwrap_or_else(|p| p.into_inner()) = Arc::new(fresh);
+        Ok(())
+    }
+
+    // Polls the table every `interval` in the background.
+    pub fn spawn_refresh(self: Arc<Self>, pool: MySqlPool, interval: Duration) {
+        tokio::spawn(async move {
+            loop {
+                if let Err(err) = self.refresh(&pool).await {
+                    logging::warn("feature flag refresh failed", json!({ "error": format!("{err:#}") }));
+                }
+                tokio::time::sleep(interval).await;
+            }
+        });
+    }
+
+    pub fn snapshot(&self) -> Arc<HashMap<String, FeatureFlag>> {
+        self.flags.read().unwrap_or_else(|p| p.into_inner()).clone()
+    }
+
+    // Unknown flags are off, so code can ship before its flag is created.
+    pub fn evaluate(&self, key: &str, subject: &str) -> Evaluation {
+        let bucket = bucket(key, subject);
+        let (enabled, reason) = match self.snapshot().get(key) {
+            None => (false, "unknown"),
+            Some(flag) if !flag.enabled => (false, "disabled"),
+            Some(flag) => match flag.overrides.get(subject) {
+                Some(forced) => (*forced, "override"),
+                None => (bucket < flag.rollout_percent, "rollout"),
+            },
+        };
+        Evaluation { key: key.to_string(), subject: subject.to_string(), enabled, reason, bucket }
+    }
+
+    pub fn is_enabled(&self, key: &str, subject: &str) -> bool {
+        self.evaluate(key, subject).enabled
+    }
+}
+
+// Adds `evaluations` to the response as X-Feature-Flags, e.g.
+// "new_assignment=on (rollout), menu_v2=off (disabled)", when the request sent
+// X-Debug-Flags. Flag names are not secret, but the header is opt-in to keep
+// ordinary responses small.
+pub fn expose(ctx: &RequestContext, response: &mut Response<Bytes>, evaluations: &[Evaluation]) {
+    if ctx.header(DEBUG_HEADER).is_none() || evaluations.is_empty() {
+        return;
+    }
+    let value = evaluations
+        .iter()
+        .map(|e| format!("{}={} ({})", e.key, if e.enabled { "on" } else { "off" }, e.reason))
+        .collect::<Vec<_>>()
+        .join(", ");
+    if let Ok(value) = HeaderValue::from_str(&value) {
+        response.headers_mut().insert(HeaderName::from_static(EXPOSURE_HEADER), value);
+    }
+}
+
+// Database access for feature flag administration.
+pub struct FlagRepository<'a> {
+    pool: &'a MySqlPool,
+}
+
+impl<'a> FlagRepository<'a> {
+    pub fn new(pool: &'a MySqlPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn get(&self, key: &str) -> Result<Option<FeatureFlag>> {
+        Ok(sqlx::query_as(
+            "SELECT flag_key, enabled, rollout_percent, overrides, description FROM feature_flags WHERE flag_key = ?",
+        )
+        .bind(key)
+        .fetch_optional(self.pool)
+        .await?)
+    }
+
+    pub async fn upsert(&self, key: &str, input: &FlagInput) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO feature_flags (flag_key, enabled, rollout_percent, overrides, description) \
+             VALUES (?, ?, ?, ?, ?) \
+             ON DUPLICATE KEY UPDATE enabled = VALUES(enabled), rollout_percent = VALUES(rollout_percent), \
+             overrides = VALUES(overrides), description = VALUES(description)",
+        )
+        .bind(key)
+        .bind(input.enabled)
+        .bind(input.rollout_percent)
+        .bind(sqlx::types::Json(&input.overrides))
+        .bind(input.description.trim())
+        .execute(self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn delete(&self, key: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM feature_flags WHERE flag_key = ?")
+            .bind(key)
+            .execute(self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+// Query of GET /api/admin/flags/:key/evaluate.
+#[derive(Debug, Deserialize)]
+pub struct EvaluateQuery {
+    pub subject: String,
+}
+
+// Registers the admin feature flag endpoints.
+pub fn routes(router: Router) -> Router {
+    router.group("/api/admin/flags", |group| {
+        group
+            .requires_admin()
+            .get("", list_flags)
+            .summary("List feature flags")
+            .response_schema(json!({ "type": "array", "items": FeatureFlag::schema() }))
+            .put("/:key", put_flag)
+            .summary("Create or replace a feature flag")
+            .request_schema(FlagInput::schema())
+            .response_schema(FeatureFlag::schema())
+            .delete("/:key", delete_flag)
+            .summary("Delete a feature flag; checks of it then return off")
+            .get("/:key/evaluate", evaluate_flag)
+            .summary("Show how a flag evaluates for a subject")
+            .query_param("subject", true)
+            .response_schema(Evaluation::schema())
+    })
+}
+
+fn flag_not_found() -> AppError {
+    AppError::NotFound("feature flag not found".to_string())
+}
+
+fn check_key(key: &str) -> AppResult<()> {
+    let valid = !key.is_empty()
+        && key.len() <= 100
+        && key.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '.');
+    if valid {
+        Ok(())
+    } else {
+        Err(AppError::BadRequest(
+            "flag keys use lowercase letters, digits, '_' and '.' (at most 100)".to_string(),
+        ))
+    }
+}
+
+async fn audit_flag(
+    audit: &AuditLogger,
+    pool: &MySqlPool,
+    action: &str,
+    key: &str,
+    before: Option<&FeatureFlag>,
+    after: Option<&FeatureFlag>,
+) -> AppResult<()> {
+    let event = AuditEvent {
+        action,
+        target_type: "feature_flag",
+        target_id: key.to_string(),
+        restaurant_id: None,
+        before: before.map(|flag| json!(flag)),
+        after: after.map(|flag| json!(flag)),
+    };
+    audit.record(pool, event).await?;
+    Ok(())
+}
+
+async fn list_flags(_: Admin, State(services): State) -> AppResult<Response<Bytes>> {
+    let mut flags: Vec<FeatureFlag> = services.feature_flags.snapshot().values().cloned().collect();
+    flags.sort_by(|a, b| a.key.cmp(&b.key));
+    ResponseBuilder::json(StatusCode::OK, &flags)
+}
+
+// Writes the flag and reloads the cache so this instance applies it immediately;
+// other instances pick it up on their next poll.
+async fn put_flag(
+    _: Admin,
+    Path(key): Path<String>,
+    ValidJson(input): ValidJson<FlagInput>,
+    audit: AuditLogger,
+    State(services): State,
+) -> AppResult<Response<Bytes>> {
+    check_key(&key)?;
+    let pool = services.db()?;
+    let repository = FlagRepository::new(pool);
+    let before = repository.get(&key).await?;
+    repository.upsert(&key, &input).await?;
+    let flag = repository.get(&key).await?.ok_or_else(flag_not_found)?;
+    audit_flag(&audit, pool, "flag.update", &key, before.as_ref(), Some(&flag)).await?;
+    services.feature_flags.refresh(pool).await?;
+    ResponseBuilder::json(StatusCode::OK, &flag)
+}
+
+async fn delete_flag(
+    _: Admin,
+    Path(key): Path<String>,
+    audit: AuditLogger,
+    State(services): State,
+) -> AppResult<Response<Bytes>> {
+    let pool = services.db()?;
+    let repository = FlagRepository::new(pool);
+    let before = repository.get(&key).await?.ok_or_else(flag_not_found)?;
+    if !repository.delete(&key).await? {
+        return Err(flag_not_found());
+    }
+    audit_flag(&audit, pool, "flag.delete", &key, Some(&before), None).await?;
+    services.feature_flags.refresh(pool).await?;
+    ResponseBuilder::no_content()
+}
+
+async fn evaluate_flag(
+    _: Admin,
+    Path(key): Path<String>,
+    Query(query): Query<EvaluateQuery>,
+    State(services): State,
+) -> AppResult<Response<Bytes>> {
+    ResponseBuilder::json(StatusCode::OK, &services.feature_flags.evaluate(&key, &query.subject))
+}
@@ -0,0 +1,149 @@
+use crate::access_log::redacted_path;
+use crate::app::AppServices;
+use crate::config;
+use crate::error::{AppError, AppResult};
+use crate::logging;
+use crate::metrics::{Metrics, METRICS};
+use crate::middleware::Middleware;
+use crate::request::RequestContext;
+use anyhow::{anyhow, Result};
+use futures::future::BoxFuture;
+use serde_json::json;
+use std::net::IpAddr;
+
+// An IPv4 or IPv6 network, e.g. "10.0.0.0/8" or "fd00::/8". A bare address is a /32 or /128.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cidr {
+    network: IpAddr,
+    prefix: u8,
+}
+
+impl Cidr {
+    pub fn parse(spec: &str) -> Result<Self> {
+        let spec = spec.trim();
+        let (addr, prefix) = spec.split_once('/').map_or((spec, None), |(a, p)| (a, Some(p)));
+        let network: IpAddr = addr.parse().map_err(|_| anyhow!("invalid address in CIDR {spec}"))?;
+        let max = if network.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(p) => p.parse().ok().filter(|p| *p <= max).ok_or_else(|| anyhow!("invalid prefix in CIDR {spec}"))?,
+            None => max,
+        };
+        Ok(Self { network, prefix })
+    }
+
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        // IPv4 clients on a dual-stack socket show up as ::ffff:a.b.c.d.
+        let ip = match ip {
+            IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(ip, IpAddr::V4),
+            v4 => v4,
+        };
+        match (self.network, ip) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - u32::from(self.prefix)).unwrap_or(0);
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - u32::from(self.prefix)).unwrap_or(0);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+fn cidr_list(name: &str) -> Result<Vec<Cidr>> {
+    config::var(name)
+        .unwrap_or_default()
+        .split(',')
+        .filter(|entry| !entry.trim().is_empty())
+        .map(|entry| Cidr::parse(entry).map_err(|err| anyhow!("{name}: {err}")))
+        .collect()
+}
+
+// Paths only reachable from ADMIN_ALLOWED_CIDRS when that is set.
+const ADMIN_PREFIXES: [&str; 2] = ["/api/admin", "/health/detailed"];
+
+// Encoded and plain forms of "..": any of them in a path or query means a traversal attempt.
+const TRAVERSAL_PATTERNS: [&str; 8] = ["../", "..\\", "..%2f", "..%5c", "%2e%2e", "%2e.", ".%2e", "%252e"];
+
+// Network firewall and request sanity checks, run before any other middleware:
+//   FIREWALL_DENY        CIDRs always refused
+//   FIREWALL_ALLOW       CIDRs allowed in; unset allows everyone not denied
+//   ADMIN_ALLOWED_CIDRS  CIDRs allowed to reach admin routes (e.g. internal ranges)
+//   FIREWALL_MAX_HEADERS most headers accepted on one request (default 100)
+// Requests for path traversal or carrying NUL bytes are refused too. Every refusal is
+// a 403 and a "security event" warning.
+pub struct FirewallMiddleware {
+    allow: Vec<Cidr>,
+    deny: Vec<Cidr>,
+    admin_allow: Vec<Cidr>,
+    max_headers: usize,
+}
+
+impl FirewallMiddleware {
+    pub fn from_env() -> Result<Self> {
+        Ok(Self {
+            allow: cidr_list("FIREWALL_ALLOW")?,
+            deny: cidr_list("FIREWALL_DENY")?,
+            admin_allow: cidr_list("ADMIN_ALLOWED_CIDRS")?,
+            max_headers: config::var("FIREWALL_MAX_HEADERS")
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(100),
+        })
+    }
+
+    // The reason to refuse the request, if any.
+    fn check(&self, ctx: &RequestContext) -> Option<&'static str> {
+        let ip = ctx.remote_addr.ip();
+        if self.deny.iter().any(|cidr| cidr.contains(ip)) {
+            return Some("address denied");
+        }
+        if !self.allow.is_empty() && !self.allow.iter().any(|cidr| cidr.contains(ip)) {
+            return Some("address not allowed");
+        }
+        if !self.admin_allow.is_empty()
+            && ADMIN_PREFIXES.iter().any(|prefix| ctx.path.starts_with(prefix))
+            && !self.admin_allow.iter().any(|cidr| cidr.contains(ip))
+        {
+            return Some("admin route from outside the allowed ranges");
+        }
+        if ctx.headers.len() > self.max_headers {
+            return Some("too many headers");
+        }
+        let suspicious = |text: &str| {
+            let lower = text.to_ascii_lowercase();
+            lower.contains('\0') || lower.contains("%00") || TRAVERSAL_PATTERNS.iter().any(|p| lower.contains(p))
+        };
+        if suspicious(&ctx.path) || ctx.query().iter().any(|(name, value)| suspicious(name) || suspicious(value)) {
+            return Some("path traversal or NUL byte");
+        }
+        None
+    }
+}
+
+impl Middleware for FirewallMiddleware {
+    fn name(&self) -> &'static str {
+        "firewall"
+    }
+
+    fn before<'a>(&'a self, ctx: &'a RequestContext, _services: &'a AppServices) -> BoxFuture<'a, AppResult<()>> {
+        Box::pin(async move {
+            let Some(reason) = self.check(ctx) else {
+                return Ok(());
+            };
+            Metrics::increment(&METRICS.firewall_blocked_total);
+            logging::warn(
+                "security event",
+                json!({
+                    "kind": "firewall_block",
+                    "reason": reason,
+                    "remote_addr": ctx.remote_addr.to_string(),
+                    "method": ctx.method.as_str(),
+                    "path": redacted_path(&ctx.path),
+                    "request_id": ctx.request_id,
+                }),
+            );
+            Err(AppError::Forbidden("forbidden".to_string()))
+        })
+    }
+}
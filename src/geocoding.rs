@@ -0,0 +1,106 @@
+use crate::openapi::ApiSchema;
+use serde::{Deserialize, Serialize};
+
+// A latitude/longitude pair in decimal degrees (WGS84).
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Coordinates {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+impl Coordinates {
+    // Great-circle distance to `other` in kilometres (haversine formula).
+    pub fn distance_km(&self, other: Coordinates) -> f64 {
+        const EARTH_RADIUS_KM: f64 = 6371.0;
+        let d_lat = (other.latitude - self.latitude).to_radians();
+        let d_lng = (other.longitude - self.longitude).to_radians();
+        let a = (d_lat / 2.0).sin().powi(2)
+            + self.latitude.to_radians().cos() * other.latitude.to_radians().cos() * (d_lng / 2.0).sin().powi(2);
+        2.0 * EARTH_RADIUS_KM * a.sqrt().asin()
+    }
+}
+
+impl ApiSchema for Coordinates {
+    fn schema() -> serde_json::Value {
+        serde_json::json!({
+            "type": "object",
+            "required": ["latitude", "longitude"],
+            "properties": {
+                "latitude": { "type": "number", "minimum": -90, "maximum": 90 },
+                "longitude": { "type": "number", "minimum": -180, "maximum": 180 }
+            }
+        })
+    }
+}
+
+// A delivery area described by a closed polygon of coordinates.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeliveryZone {
+    pub name: String,
+    pub polygon: Vec<Coordinates>,
+}
+
+impl DeliveryZone {
+    // Ray-casting point-in-polygon test. Longitude is treated as x, latitude as y,
+    // which is accurate enough for city-sized zones away from the antimeridian.
+    pub fn contains(&self, point: Coordinates) -> bool {
+        let (x, y) = (point.longitude, point.latitude);
+        let mut inside = false;
+        let mut j = self.polygon.len() - 1;
+        for i in 0..self.polygon.len() {
+            let (xi, yi) = (self.polygon[i].longitude, self.polygon[i].latitude);
+            let (xj, yj) = (self.polygon[j].longitude, self.polygon[j].latitude);
+            if (yi > y) != (yj > y) && x < (xj - xi) * (y - yi) / (yj - yi) + xi {
+                inside = !inside;
+            }
+            j = i;
+        }
+        inside
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Coordinates;
+
+    const DELHI: Coordinates = Coordinates { latitude: 28.6139, longitude: 77.2090 };
+    const MUMBAI: Coordinates = Coordinates { latitude: 19.0760, longitude: 72.8777 };
+
+    fn at(latitude: f64, longitude: f64) -> Coordinates {
+        Coordinates { latitude, longitude }
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 0.01, "{actual} km, expected {expected} km");
+    }
+
+    #[test]
+    fn distance_to_the_same_point_is_zero() {
+        assert_close(DELHI.distance_km(DELHI), 0.0);
+    }
+
+    #[test]
+    fn distance_is_symmetric() {
+        assert_close(DELHI.distance_km(MUMBAI), MUMBAI.distance_km(DELHI));
+    }
+
+    #[test]
+    fn distance_between_cities() {
+        assert_close(DELHI.distance_km(MUMBAI), 1148.09);
+    }
+
+    #[test]
+    fn one_degree_of_latitude_is_about_111_km() {
+        assert_close(at(0.0, 0.0).distance_km(at(1.0, 0.0)), 111.19);
+    }
+
+    #[test]
+    fn distance_across_the_antimeridian_takes_the_short_way() {
+        assert_close(at(0.0, 179.5).distance_km(at(0.0, -179.5)), 111.19);
+    }
+
+    #[test]
+    fn antipodes_are_half_the_circumference_apart() {
+        assert_close(at(0.0, 0.0).distance_km(at(0.0, 180.0)), 20015.09);
+    }
+}
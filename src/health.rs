@@ -0,0 +1,290 @@
+use crate::app::AppServices;
+use crate::db_breaker::BreakerState;
+use crate::extract::{Admin, State};
+use crate::error::AppResult;
+use crate::response::{CachePolicy, ResponseBuilder};
+use crate::router::Router;
+use anyhow::{anyhow, Result};
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
+use http::{Response, StatusCode};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// A single named check. Returning an error fails the probe it is registered with.
+pub type Check = Arc<dyn Fn(Arc<AppServices>) -> BoxFuture<'static, Result<()>> + Send + Sync>;
+
+// Checks that take longer than this count as failed, so a hung dependency can't hang the probe.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+// Set once the accept loop is running.
+static STARTED: AtomicBool = AtomicBool::new(false);
+
+// Called by the server when it starts accepting connections.
+pub fn mark_started() {
+    STARTED.store(true, Ordering::Relaxed);
+}
+
+// The registry of checks behind one probe endpoint.
+#[derive(Clone, Default)]
+pub struct Probe {
+    checks: Vec<(String, Check)>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CheckResult {
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProbeReport {
+    pub status: &'static str,
+    pub checks: BTreeMap<String, CheckResult>,
+}
+
+impl Probe {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Registers a check under `name`.
+    pub fn check<F, Fut>(mut self, name: &str, check: F) -> Self
+    where
+        F: Fn(Arc<AppServices>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        self.checks
+            .push((name.to_string(), Arc::new(move |services| Box::pin(check(services)))));
+        self
+    }
+
+    // Runs every check concurrently. The probe passes only if all of them pass.
+    pub async fn run(&self, services: Arc<AppServices>) -> ProbeReport {
+        let results = futures::future::join_all(self.checks.iter().map(|(name, check)| {
+            let check = check(services.clone());
+            async move {
+                let outcome = match tokio::time::timeout(CHECK_TIMEOUT, check).await {
+                    Ok(outcome) => outcome,
+                    Err(_) => Err(anyhow!("timed out after {}s", CHECK_TIMEOUT.as_secs())),
+                };
+                let result = CheckResult {
+                    ok: outcome.is_ok(),
+                    error: outcome.err().map(|err| format!("{err:#}")),
+                };
+                (name.clone(), result)
+            }
+        }))
+        .await;
+
+        let checks: BTreeMap<_, _> = results.into_iter().collect();
+        let status = if checks.values().all(|c| c.ok) { "ok" } else { "fail" };
+        ProbeReport { status, checks }
+    }
+}
+
+// Per-component status shown by /health/detailed.
+#[derive(Debug, Serialize)]
+pub struct ComponentStatus {
+    pub status: &'static str,
+    pub latency_ms: f64,
+    // When this component's check last passed, as seen by this process.
+    pub last_success: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DetailedReport {
+    pub status: &'static str,
+    pub components: BTreeMap<String, ComponentStatus>,
+}
+
+// Subsystems register a check here to appear in /health/detailed. Unlike the probes this
+// is for operators: it times each check and remembers when it last succeeded.
+#[derive(Default)]
+pub struct HealthRegistry {
+    components: Vec<(String, Check)>,
+    last_success: Mutex<HashMap<String, DateTime<Utc>>>,
+}
+
+impl HealthRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Registers a component check under `name`.
+    pub fn register<F, Fut>(mut self, name: &str, check: F) -> Self
+    where
+        F: Fn(Arc<AppServices>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        self.components
+            .push((name.to_string(), Arc::new(move |services| Box::pin(check(services)))));
+        self
+    }
+
+    // Runs every component check concurrently. Overall status is "degraded" when any is down.
+    pub async fn report(&self, services: Arc<AppServices>) -> DetailedReport {
+        let results = futures::future::join_all(self.components.iter().map(|(name, check)| {
+            let check = check(services.clone());
+            async move {
+                let started = Instant::now();
+                let outcome = match tokio::time::timeout(CHECK_TIMEOUT, check).await {
+                    Ok(outcome) => outcome,
+                    Err(_) => Err(anyhow!("timed out after {}s", CHECK_TIMEOUT.as_secs())),
+                };
+                (name.clone(), outcome, started.elapsed())
+            }
+        }))
+        .await;
+
+        let mut last_success = self.last_success.lock().unwrap_or_else(|p| p.into_inner());
+        let mut components = BTreeMap::new();
+        for (name, outcome, latency) in results {
+            if outcome.is_ok() {
+                last_success.insert(name.clone(), Utc::now());
+            }
+            let status = ComponentStatus {
+                status: if outcome.is_ok() { "up" } else { "down" },
+                latency_ms: latency.as_secs_f64() * 1000.0,
+                last_success: last_success.get(&name).map(|at| at.to_rfc3339()),
+                error: outcome.err().map(|err| format!("{err:#}")),
+            };
+            components.insert(name, status);
+        }
+        let status = if components.values().all(|c| c.status == "up") { "ok" } else { "degraded" };
+        DetailedReport { status, components }
+    }
+}
+
+// The three Kubernetes probes:
+//   liveness  (/healthz)  - the process is up; never depends on other systems, so a
+//                           database blip does not get the pod restarted.
+//   readiness (/readyz)   - dependencies are usable; failing takes the pod out of rotation.
+//   startup   (/startupz) - one-time initialisation finished; gates the other two.
+pub struct HealthProbes {
+    pub liveness: Probe,
+    pub readiness: Probe,
+    pub startup: Probe,
+    // Components listed by /health/detailed.
+    pub components: HealthRegistry,
+}
+
+impl HealthProbes {
+    // The standard checks. Only dependencies that are configured are listed as components.
+    pub fn new(services: &AppServices) -> Self {
+        let mut components = HealthRegistry::new();
+        if services.db.is_some() {
+            components = components.register("database", |services| async move { ping_database(&services).await });
+        }
+        if services.redis.is_some() {
+            components = components.register("redis", |services| async move {
+                match &services.redis {
+                    Some(redis) => redis.ping().await,
+                    None => Ok(()),
+                }
+            });
+        }
+        Self {
+            liveness: Probe::new(),
+            readiness: Probe::new()
+                .check("database", |services| async move { ping_database(&services).await })
+                .check("database_breaker", |services| async move {
+                    match services.db_breaker.state() {
+                        BreakerState::Closed => Ok(()),
+                        BreakerState::Open => Err(anyhow!("circuit breaker is open")),
+                    }
+                })
+                .check("migrations", |services| async move { migrations_applied(&services).await }),
+            startup: Probe::new().check("server", |_| async {
+                if STARTED.load(Ordering::Relaxed) {
+                    Ok(())
+                } else {
+                    Err(anyhow!("not accepting connections yet"))
+                }
+            }),
+            components,
+        }
+    }
+}
+
+// A database is optional; without DATABASE_URL there is nothing to be unready about.
+async fn ping_database(services: &AppServices) -> Result<()> {
+    let Some(pool) = &services.db else {
+        return Ok(());
+    };
+    sqlx::query("SELECT 1").execute(pool).await?;
+    Ok(())
+}
+
+// Every migration shipped in ./migrations must be recorded as applied (by `sqlx migrate run`).
+async fn migrations_applied(services: &AppServices) -> Result<()> {
+    let Some(pool) = &services.db else {
+        return Ok(());
+    };
+    let applied: Vec<i64> = sqlx::query_scalar("SELECT version FROM _sqlx_migrations WHERE success = TRUE")
+        .fetch_all(pool)
+        .await?;
+    let applied: HashSet<i64> = applied.into_iter().collect();
+    let pending: Vec<String> = sqlx::migrate!("./migrations")
+        .iter()
+        .filter(|migration| !applied.contains(&migration.version))
+        .map(|migration| format!("{}_{}", migration.version, migration.description))
+        .collect();
+    if pending.is_empty() {
+        Ok(())
+    } else {
+        Err(anyhow!("pending migrations: {}", pending.join(", ")))
+    }
+}
+
+// Registers /healthz, /readyz and /startupz. Each answers 200 with the per-check report
+// when all its checks pass, or 503 otherwise. /health/detailed (admin) always answers 200
+// with every component's status.
+pub fn routes(router: Router, probes: HealthProbes) -> Router {
+    let probes = Arc::new(probes);
+    let (live, ready, startup, detailed) = (probes.clone(), probes.clone(), probes.clone(), probes);
+    router
+        .get("/healthz", move |State(services): State| {
+            let probes = live.clone();
+            async move { respond(probes.liveness.run(services).await) }
+        })
+        .summary("Liveness probe")
+        .cache(CachePolicy::NoStore)
+        .get("/readyz", move |State(services): State| {
+            let probes = ready.clone();
+            async move { respond(probes.readiness.run(services).await) }
+        })
+        .summary("Readiness probe")
+        .cache(CachePolicy::NoStore)
+        .get("/startupz", move |State(services): State| {
+            let probes = startup.clone();
+            async move { respond(probes.startup.run(services).await) }
+        })
+        .summary("Startup probe")
+        .cache(CachePolicy::NoStore)
+        .get("/health/detailed", move |_: Admin, State(services): State| {
+            let probes = detailed.clone();
+            async move {
+                ResponseBuilder::json(StatusCode::OK, &probes.components.report(services.clone()).await)
+            }
+        })
+        .summary("Per-component dependency health")
+        .requires_admin()
+}
+
+fn respond(report: ProbeReport) -> AppResult<Response<Bytes>> {
+    let status = if report.status == "ok" {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    ResponseBuilder::json(status, &report)
+}
@@ -0,0 +1,107 @@
+// Message catalogs for client-facing text. Each request is served in the best match
+// for its Accept-Language header among SUPPORTED, falling back to English. Keys are
+// "<namespace>.<value>", e.g. "order_status.served" or "error.not_found"; a key missing
+// from a catalog falls back to English, and then to the raw value.
+
+pub const DEFAULT_LANGUAGE: &str = "en";
+pub const SUPPORTED: [&str; 2] = ["en", "hi"];
+
+const EN: &[(&str, &str)] = &[
+    ("error.bad_request", "The request could not be understood."),
+    ("error.unauthorized", "Please sign in to continue."),
+    ("error.forbidden", "You are not allowed to do this."),
+    ("error.not_found", "We couldn't find what you were looking for."),
+    ("error.method_not_allowed", "That action isn't supported here."),
+    ("error.conflict", "This conflicts with the current state. Please refresh and try again."),
+    ("error.validation_error", "Some fields are invalid."),
+    ("error.payload_too_large", "The upload is too large."),
+    ("error.rate_limited", "Too many requests. Please wait a moment and try again."),
+    ("error.overloaded", "We're very busy right now. Please try again in a moment."),
+    ("error.service_unavailable", "The service is temporarily unavailable. Please try again shortly."),
+    ("error.internal_error", "Something went wrong on our side."),
+    ("order_status.queued", "Waiting for the kitchen"),
+    ("order_status.placed", "Order placed"),
+    ("order_status.preparing", "Being prepared"),
+    ("order_status.ready", "Ready"),
+    ("order_status.served", "Served"),
+    ("order_status.out_for_delivery", "Out for delivery"),
+    ("order_status.delivered", "Delivered"),
+    ("order_status.cancelled", "Cancelled"),
+    ("fulfillment.delivery", "Delivery"),
+    ("fulfillment.dine_in", "Dine-in"),
+    ("table_kind.table", "Table"),
+    ("table_kind.room", "Room"),
+];
+
+const HI: &[(&str, &str)] = &[
+    ("error.bad_request", "अनुरोध समझ में नहीं आया।"),
+    ("error.unauthorized", "जारी रखने के लिए कृपया साइन इन करें।"),
+    ("error.forbidden", "आपको यह करने की अनुमति नहीं है।"),
+    ("error.not_found", "आप जो खोज रहे थे वह नहीं मिला।"),
+    ("error.method_not_allowed", "यहाँ यह क्रिया समर्थित नहीं है।"),
+    ("error.conflict", "यह वर्तमान स्थिति से मेल नहीं खाता। कृपया रीफ़्रेश करके फिर से प्रयास करें।"),
+    ("error.validation_error", "कुछ फ़ील्ड अमान्य हैं।"),
+    ("error.payload_too_large", "अपलोड बहुत बड़ा है।"),
+    ("error.rate_limited", "बहुत अधिक अनुरोध। कृपया थोड़ी देर बाद फिर से प्रयास करें।"),
+    ("error.overloaded", "अभी बहुत व्यस्तता है। कृपया थोड़ी देर में फिर से प्रयास करें।"),
+    ("error.service_unavailable", "सेवा अस्थायी रूप से उपलब्ध नहीं है। कृपया थोड़ी देर में फिर से प्रयास करें।"),
+    ("error.internal_error", "हमारी ओर से कुछ गड़बड़ हो गई।"),
+    ("order_status.queued", "रसोई की प्रतीक्षा में"),
+    ("order_status.placed", "ऑर्डर दिया गया"),
+    ("order_status.preparing", "तैयार हो रहा है"),
+    ("order_status.ready", "तैयार"),
+    ("order_status.served", "परोसा गया"),
+    ("order_status.out_for_delivery", "डिलीवरी के लिए निकला"),
+    ("order_status.delivered", "डिलीवर हो गया"),
+    ("order_status.cancelled", "रद्द"),
+    ("fulfillment.delivery", "डिलीवरी"),
+    ("fulfillment.dine_in", "रेस्टोरेंट में"),
+    ("table_kind.table", "टेबल"),
+    ("table_kind.room", "कमरा"),
+];
+
+fn catalog(lang: &str) -> &'static [(&'static str, &'static str)] {
+    match lang {
+        "hi" => HI,
+        _ => EN,
+    }
+}
+
+fn lookup(lang: &str, key: &str) -> Option<&'static str> {
+    catalog(lang).iter().find(|(k, _)| *k == key).map(|(_, v)| *v)
+}
+
+// The catalog message for `key`, in `lang` or else English.
+pub fn t(lang: &str, key: &str) -> Option<&'static str> {
+    lookup(lang, key).or_else(|| lookup(DEFAULT_LANGUAGE, key))
+}
+
+// Display label for an enum value such as an order status; unknown values are shown as-is.
+pub fn label(lang: &str, namespace: &str, value: &str) -> String {
+    t(lang, &format!("{namespace}.{value}")).map_or_else(|| value.to_string(), str::to_string)
+}
+
+// Picks the supported language the client prefers most. Tags are matched on their
+// primary subtag ("hi-IN" selects "hi"); q=0 excludes a language.
+pub fn negotiate(accept_language: Option<&str>) -> &'static str {
+    let Some(header) = accept_language else {
+        return DEFAULT_LANGUAGE;
+    };
+    let mut best: Option<(&'static str, f32)> = None;
+    for entry in header.split(',') {
+        let mut pieces = entry.split(';');
+        let tag = pieces.next().unwrap_or("").trim();
+        let quality = pieces
+            .filter_map(|p| p.trim().strip_prefix("q="))
+            .find_map(|q| q.parse::<f32>().ok())
+            .unwrap_or(1.0);
+        let primary = tag.split('-').next().unwrap_or("").to_ascii_lowercase();
+        let Some(lang) = SUPPORTED.iter().find(|l| **l == primary) else {
+            continue;
+        };
+        if quality > 0.0 && best.is_none_or(|(_, q)| quality > q) {
+            best = Some((lang, quality));
+        }
+    }
+    best.map_or(DEFAULT_LANGUAGE, |(lang, _)| lang)
+}
@@ -0,0 +1,516 @@
+use crate::app::AppServices;
+use crate::audit::{AuditEvent, AuditLogger};
+use crate::error::{AppError, AppResult, FieldError};
+use crate::extract::{Path, State, TenantAdmin, ValidJson};
+use crate::openapi::ApiSchema;
+use crate::request::RequestContext;
+use crate::response::{CachePolicy, ResponseBuilder};
+use crate::router::Router;
+use crate::tenant;
+use crate::validation::Validate;
+use anyhow::Result;
+use bytes::Bytes;
+use http::{Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sqlx::mysql::{MySql, MySqlPool};
+use sqlx::{QueryBuilder, Transaction};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+// Customisation schema of menu items. An item has option groups (size, toppings, spice
+// level) and each group lists options with what they add to the item's price. Orders
+// name the option ids a guest picked per line; choose() checks them against the
+// schema and the line's unit price becomes the item's price plus their deltas.
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OptionGroup {
+    pub id: i64,
+    pub name: String,
+    // How many of the group's options a guest must and may pick.
+    pub min_select: i32,
+    pub max_select: i32,
+    pub options: Vec<ItemOption>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ItemOption {
+    pub id: i64,
+    pub name: String,
+    pub price_delta_paise: i64,
+    pub available: bool,
+}
+
+impl OptionGroup {
+    // The group as guests see it: unavailable options left out.
+    fn available_only(mut self) -> Self {
+        self.options.retain(|option| option.available);
+        self
+    }
+}
+
+impl ApiSchema for OptionGroup {
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["id", "name", "min_select", "max_select", "options"],
+            "properties": {
+                "id": { "type": "integer" },
+                "name": { "type": "string" },
+                "min_select": { "type": "integer" },
+                "max_select": { "type": "integer" },
+                "options": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "required": ["id", "name", "price_delta_paise", "available"],
+                        "properties": {
+                            "id": { "type": "integer" },
+                            "name": { "type": "string" },
+                            "price_delta_paise": { "type": "integer" },
+                            "available": { "type": "boolean" }
+                        }
+                    }
+                }
+            }
+        })
+    }
+}
+
+// An option as recorded on an order line, copied so later schema edits don't change
+// past orders.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChosenOption {
+    pub option_id: i64,
+    pub group: String,
+    pub name: String,
+    pub price_delta_paise: i64,
+}
+
+impl ApiSchema for ChosenOption {
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "option_id": { "type": "integer" },
+                "group": { "type": "string" },
+                "name": { "type": "string" },
+                "price_delta_paise": { "type": "integer" }
+            }
+        })
+    }
+}
+
+// Checks the option ids picked for one line against the item's groups and returns them
+// in menu order. Every id must be an available option of the item, picked once, and
+// each group's min_select/max_select must hold. The error is shown to the guest as is.
+pub fn choose(groups: &[OptionGroup], selected: &[i64]) -> Result<Vec<ChosenOption>, String> {
+    let mut remaining = HashSet::new();
+    for id in selected {
+        if !remaining.insert(*id) {
+            return Err(format!("option {id} is chosen more than once"));
+        }
+    }
+    let mut chosen = Vec::new();
+    for group in groups {
+        let picked: Vec<&ItemOption> = group
+            .options
+            .iter()
+            .filter(|option| option.available && remaining.remove(&option.id))
+            .collect();
+        let count = picked.len() as i32;
+        if count < group.min_select {
+            return Err(format!("choose at least {} from {}", group.min_select, group.name));
+        }
+        if count > group.max_select {
+            return Err(format!("choose at most {} from {}", group.max_select, group.name));
+        }
+        chosen.extend(picked.into_iter().map(|option| ChosenOption {
+            option_id: option.id,
+            group: group.name.clone(),
+            name: option.name.clone(),
+            price_delta_paise: option.price_delta_paise,
+        }));
+    }
+    if let Some(id) = remaining.iter().min() {
+        return Err(format!("option {id} is not offered for this item"));
+    }
+    Ok(chosen)
+}
+
+// Body of PUT /api/admin/menu/items/:sku/options: the item's complete schema. Groups
+// and options are matched to existing ones by name, so their ids (which guests' carts
+// refer to) survive edits; ones left out are deleted.
+#[derive(Debug, Deserialize)]
+pub struct ItemOptionsInput {
+    pub groups: Vec<OptionGroupInput>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OptionGroupInput {
+    pub name: String,
+    #[serde(default)]
+    pub min_select: i32,
+    #[serde(default = "default_max_select")]
+    pub max_select: i32,
+    pub options: Vec<ItemOptionInput>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ItemOptionInput {
+    pub name: String,
+    #[serde(default)]
+    pub price_delta_paise: i64,
+    #[serde(default = "default_available")]
+    pub available: bool,
+}
+
+fn default_max_select() -> i32 {
+    1
+}
+
+fn default_available() -> bool {
+    true
+}
+
+impl ApiSchema for ItemOptionsInput {
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["groups"],
+            "properties": {
+                "groups": {
+                    "type": "array",
+                    "maxItems": 20,
+                    "items": {
+                        "type": "object",
+                        "required": ["name", "options"],
+                        "properties": {
+                            "name": { "type": "string", "minLength": 1, "maxLength": 100 },
+                            "min_select": { "type": "integer", "minimum": 0 },
+                            "max_select": { "type": "integer", "minimum": 1 },
+                            "options": {
+                                "type": "array",
+                                "minItems": 1,
+                                "maxItems": 50,
+                                "items": {
+                                    "type": "object",
+                                    "required": ["name"],
+                                    "properties": {
+                                        "name": { "type": "string", "minLength": 1, "maxLength": 100 },
+                                        "price_delta_paise": { "type": "integer", "minimum": 0 },
+                                        "available": { "type": "boolean" }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        })
+    }
+}
+
+// Deltas can't be negative; a size variant is priced by putting the smallest size's
+// price on the item and charging extra for the larger ones.
+impl Validate for ItemOptionsInput {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        let mut group_names = HashSet::new();
+        for (g, group) in self.groups.iter().enumerate() {
+            let name = group.name.trim();
+            if name.is_empty() {
+                errors.push(FieldError::new(format!("groups[{g}].name"), "must not be blank"));
+            } else if !group_names.insert(name.to_lowercase()) {
+                errors.push(FieldError::new(format!("groups[{g}].name"), format!("duplicate group {name}")));
+            }
+            if group.min_select < 0 {
+                errors.push(FieldError::new(format!("groups[{g}].min_select"), "must not be negative"));
+            }
+            if group.max_select < 1 {
+                errors.push(FieldError::new(format!("groups[{g}].max_select"), "must be at least 1"));
+            } else if group.max_select < group.min_select {
+                errors.push(FieldError::new(format!("groups[{g}].max_select"), "must not be less than min_select"));
+            } else if group.max_select as usize > group.options.len() {
+                errors.push(FieldError::new(
+                    format!("groups[{g}].max_select"),
+                    "must not exceed the number of options",
+                ));
+            }
+            let mut option_names = HashSet::new();
+            for (o, option) in group.options.iter().enumerate() {
+                let name = option.name.trim();
+                if name.is_empty() {
+                    errors.push(FieldError::new(format!("groups[{g}].options[{o}].name"), "must not be blank"));
+                } else if !option_names.insert(name.to_lowercase()) {
+                    errors.push(FieldError::new(
+                        format!("groups[{g}].options[{o}].name"),
+                        format!("duplicate option {name}"),
+                    ));
+                }
+                if option.price_delta_paise < 0 {
+                    errors.push(FieldError::new(
+                        format!("groups[{g}].options[{o}].price_delta_paise"),
+                        "must not be negative",
+                    ));
+                }
+            }
+        }
+        errors
+    }
+}
+
+// Option groups of the given menu items, keyed by item id, in menu order. Takes any
+// executor so order placement can read the schema inside its own transaction.
+pub async fn load_groups<'e, E>(executor: E, menu_item_ids: &[i64]) -> Result<HashMap<i64, Vec<OptionGroup>>>
+where
+    E: sqlx::Executor<'e, Database = MySql>,
+{
+    if menu_item_ids.is_empty() {
+        return Ok(HashMap::new());
+    }
+    let placeholders = vec!["?"; menu_item_ids.len()].join(", ");
+    let sql = format!(
+        "SELECT g.menu_item_id, g.id, g.name, g.min_select, g.max_select, \
+         o.id, o.name, o.price_delta_paise, o.available \
+         FROM option_groups g LEFT JOIN item_options o ON o.option_group_id = g.id \
+         WHERE g.menu_item_id IN ({placeholders}) ORDER BY g.menu_item_id, g.position, g.id, o.position, o.id"
+    );
+    type Row = (i64, i64, String, i32, i32, Option<i64>, Option<String>, Option<i64>, Option<bool>);
+    let mut query = sqlx::query_as::<_, Row>(&sql);
+    for id in menu_item_ids {
+        query = query.bind(id);
+    }
+    let mut groups: HashMap<i64, Vec<OptionGroup>> = HashMap::new();
+    for (item_id, group_id, group_name, min_select, max_select, option_id, name, delta, available) in
+        query.fetch_all(executor).await?
+    {
+        let item_groups = groups.entry(item_id).or_default();
+        if item_groups.last().is_none_or(|group| group.id != group_id) {
+            item_groups.push(OptionGroup { id: group_id, name: group_name, min_select, max_select, options: Vec::new() });
+        }
+        if let (Some(id), Some(name), Some(price_delta_paise), Some(available)) = (option_id, name, delta, available)
+            && let Some(group) = item_groups.last_mut()
+        {
+            group.options.push(ItemOption { id, name, price_delta_paise, available });
+        }
+    }
+    Ok(groups)
+}
+
+// The audited shape of a schema: names and prices, without ids.
+fn schema_snapshot(groups: &[OptionGroup]) -> Value {
+    groups
+        .iter()
+        .map(|group| {
+            json!({
+                "name": group.name,
+                "min_select": group.min_select,
+                "max_select": group.max_select,
+                "options": group.options.iter().map(|option| json!({
+                    "name": option.name,
+                    "price_delta_paise": option.price_delta_paise,
+                    "available": option.available,
+                })).collect::<Vec<_>>(),
+            })
+        })
+        .collect()
+}
+
+// Database access for the option schemas of one restaurant's menu items.
+pub struct OptionRepository<'a> {
+    pool: &'a MySqlPool,
+    restaurant_id: i64,
+}
+
+impl<'a> OptionRepository<'a> {
+    pub fn new(pool: &'a MySqlPool, restaurant_id: i64) -> Self {
+        Self { pool, restaurant_id }
+    }
+
+    // (id, available) of the restaurant's item with `sku`.
+    pub async fn menu_item(&self, sku: &str) -> Result<Option<(i64, bool)>> {
+        Ok(sqlx::query_as("SELECT id, available FROM menu_items WHERE restaurant_id = ? AND sku = ?")
+            .bind(self.restaurant_id)
+            .bind(sku)
+            .fetch_optional(self.pool)
+            .await?)
+    }
+
+    pub async fn for_item(&self, menu_item_id: i64) -> Result<Vec<OptionGroup>> {
+        Ok(load_groups(self.pool, &[menu_item_id]).await?.remove(&menu_item_id).unwrap_or_default())
+    }
+
+    // Replaces the item's schema with `input` in one transaction, upserting groups and
+    // options by name and deleting the rest. Returns the schema as stored.
+    pub async fn replace(
+        &self,
+        menu_item_id: i64,
+        sku: &str,
+        input: &ItemOptionsInput,
+        audit: &AuditLogger,
+    ) -> Result<Vec<OptionGroup>> {
+        let mut tx = self.pool.begin().await?;
+        // Concurrent edits of the same item apply one after the other.
+        sqlx::query("SELECT id FROM menu_items WHERE id = ? FOR UPDATE")
+            .bind(menu_item_id)
+            .execute(&mut *tx)
+            .await?;
+        let before = load_groups(&mut *tx, &[menu_item_id]).await?.remove(&menu_item_id).unwrap_or_default();
+
+        let mut group_ids = Vec::with_capacity(input.groups.len());
+        for (position, group) in input.groups.iter().enumerate() {
+            sqlx::query(
+                "INSERT INTO option_groups (menu_item_id, name, min_select, max_select, position) \
+                 VALUES (?, ?, ?, ?, ?) \
+                 ON DUPLICATE KEY UPDATE min_select = VALUES(min_select), max_select = VALUES(max_select), \
+                 position = VALUES(position)",
+            )
+            .bind(menu_item_id)
+            .bind(group.name.trim())
+            .bind(group.min_select)
+            .bind(group.max_select)
+            .bind(position as i32)
+            .execute(&mut *tx)
+            .await?;
+            let group_id: i64 = sqlx::query_scalar("SELECT id FROM option_groups WHERE menu_item_id = ? AND name = ?")
+                .bind(menu_item_id)
+                .bind(group.name.trim())
+                .fetch_one(&mut *tx)
+                .await?;
+
+            let mut option_ids = Vec::with_capacity(group.options.len());
+            for (position, option) in group.options.iter().enumerate() {
+                sqlx::query(
+                    "INSERT INTO item_options (option_group_id, name, price_delta_paise, available, position) \
+                     VALUES (?, ?, ?, ?, ?) \
+                     ON DUPLICATE KEY UPDATE price_delta_paise = VALUES(price_delta_paise), \
+                     available = VALUES(available), position = VALUES(position)",
+                )
+                .bind(group_id)
+                .bind(option.name.trim())
+                .bind(option.price_delta_paise)
+                .bind(option.available)
+                .bind(position as i32)
+                .execute(&mut *tx)
+                .await?;
+                let option_id: i64 =
+                    sqlx::query_scalar("SELECT id FROM item_options WHERE option_group_id = ? AND name = ?")
+                        .bind(group_id)
+                        .bind(option.name.trim())
+                        .fetch_one(&mut *tx)
+                        .await?;
+                option_ids.push(option_id);
+            }
+            delete_except(&mut tx, "item_options", "option_group_id", group_id, &option_ids).await?;
+            group_ids.push(group_id);
+        }
+        delete_except(&mut tx, "option_groups", "menu_item_id", menu_item_id, &group_ids).await?;
+
+        let after = load_groups(&mut *tx, &[menu_item_id]).await?.remove(&menu_item_id).unwrap_or_default();
+        let (old, new) = (schema_snapshot(&before), schema_snapshot(&after));
+        if old != new {
+            let event = AuditEvent {
+                action: "menu.options",
+                target_type: "menu_item",
+                target_id: sku.to_string(),
+                restaurant_id: Some(self.restaurant_id),
+                before: Some(old),
+                after: Some(new),
+            };
+            audit.record(&mut *tx, event).await?;
+        }
+        tx.commit().await?;
+        Ok(after)
+    }
+}
+
+// Deletes the rows of `table` under `parent_id` whose id is not in `keep`.
+async fn delete_except(
+    tx: &mut Transaction<'_, MySql>,
+    table: &str,
+    parent_column: &str,
+    parent_id: i64,
+    keep: &[i64],
+) -> Result<()> {
+    let mut query = QueryBuilder::<MySql>::new(format!("DELETE FROM {table} WHERE {parent_column} = "));
+    query.push_bind(parent_id);
+    if !keep.is_empty() {
+        query.push(" AND id NOT IN (");
+        let mut ids = query.separated(", ");
+        for id in keep {
+            ids.push_bind(*id);
+        }
+        ids.push_unseparated(")");
+    }
+    query.build().execute(&mut **tx).await?;
+    Ok(())
+}
+
+// Registers the guest view of an item's options and the admin endpoints that edit them.
+pub fn routes(router: Router) -> Router {
+    router
+        .get("/api/menu/items/:sku/options", public_options)
+        .summary("Option groups a guest chooses from when ordering an item")
+        .cache(CachePolicy::public(60).stale_while_revalidate(300))
+        .response_schema(json!({ "type": "array", "items": OptionGroup::schema() }))
+        .get("/api/admin/menu/items/:sku/options", admin_options)
+        .summary("An item's option schema, including unavailable options")
+        .urgency(6)
+        .requires_tenant_admin()
+        .response_schema(json!({ "type": "array", "items": OptionGroup::schema() }))
+        .put("/api/admin/menu/items/:sku/options", replace_options)
+        .summary("Replace an item's option groups and options; an empty list removes them")
+        .urgency(6)
+        .requires_tenant_admin()
+        .request_schema(ItemOptionsInput::schema())
+        .response_schema(json!({ "type": "array", "items": OptionGroup::schema() }))
+}
+
+fn sku(ctx: &RequestContext) -> &str {
+    ctx.param("sku").unwrap_or_default()
+}
+
+fn item_not_found() -> AppError {
+    AppError::NotFound("menu item not found".to_string())
+}
+
+async fn public_options(ctx: RequestContext, services: Arc<AppServices>) -> AppResult<Response<Bytes>> {
+    let restaurant = tenant::current(&ctx, &services).await?;
+    let repository = OptionRepository::new(services.db()?, restaurant.id);
+    let (id, _) = repository
+        .menu_item(sku(&ctx))
+        .await?
+        .filter(|(_, available)| *available)
+        .ok_or_else(item_not_found)?;
+    let groups: Vec<OptionGroup> = repository
+        .for_item(id)
+        .await?
+        .into_iter()
+        .map(OptionGroup::available_only)
+        .collect();
+    ResponseBuilder::json(StatusCode::OK, &groups)
+}
+
+async fn admin_options(
+    TenantAdmin(restaurant): TenantAdmin,
+    Path(sku): Path<String>,
+    State(services): State,
+) -> AppResult<Response<Bytes>> {
+    let repository = OptionRepository::new(services.db()?, restaurant.id);
+    let (id, _) = repository.menu_item(&sku).await?.ok_or_else(item_not_found)?;
+    ResponseBuilder::json(StatusCode::OK, &repository.for_item(id).await?)
+}
+
+async fn replace_options(
+    TenantAdmin(restaurant): TenantAdmin,
+    Path(sku): Path<String>,
+    ValidJson(input): ValidJson<ItemOptionsInput>,
+    audit: AuditLogger,
+    State(services): State,
+) -> AppResult<Response<Bytes>> {
+    let repository = OptionRepository::new(services.db()?, restaurant.id);
+    let (id, _) = repository.menu_item(&sku).await?.ok_or_else(item_not_found)?;
+    let groups = repository.replace(id, &sku, &input, &audit).await?;
+    ResponseBuilder::json(StatusCode::OK, &groups)
+}
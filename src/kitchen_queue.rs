@@ -0,0 +1,129 @@
+use crate::logging;
+use crate::order_events::{self, OrderEvent};
+use crate::runtime_config::ConfigService;
+use anyhow::Result;
+use serde_json::json;
+use sqlx::mysql::{MySql, MySqlPool};
+use sqlx::Transaction;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+// Peak-time throttling of the kitchen. With runtime config kitchen_max_active_orders
+// set, an order placed while that many orders are placed or preparing, or while
+// others are already waiting, is stored as 'queued'. Queued orders are admitted as
+// 'placed', oldest first, as the kitchen frees up: right after staff move an order
+// on, and by a background sweep that also picks up a raised limit.
+
+// How often the background sweep admits orders.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(10);
+
+// Locks the restaurant's queue for the rest of `tx`, so placements and admissions
+// are decided one at a time.
+async fn lock(tx: &mut Transaction<'_, MySql>, restaurant_id: i64) -> Result<()> {
+    sqlx::query("SELECT id FROM restaurants WHERE id = ? FOR UPDATE")
+        .bind(restaurant_id)
+        .execute(&mut **tx)
+        .await?;
+    Ok(())
+}
+
+async fn active_orders(tx: &mut Transaction<'_, MySql>, restaurant_id: i64) -> Result<i64> {
+    Ok(
+        sqlx::query_scalar("SELECT COUNT(*) FROM orders WHERE restaurant_id = ? AND status IN ('placed', 'preparing')")
+            .bind(restaurant_id)
+            .fetch_one(&mut **tx)
+            .await?,
+    )
+}
+
+// Whether an order being placed in `tx` has to wait. Holds the queue lock until `tx` ends.
+pub async fn must_queue(tx: &mut Transaction<'_, MySql>, restaurant_id: i64, max_active: u32) -> Result<bool> {
+    if max_active == 0 {
+        return Ok(false);
+    }
+    lock(tx, restaurant_id).await?;
+    let waiting: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM orders WHERE restaurant_id = ? AND status = 'queued'")
+        .bind(restaurant_id)
+        .fetch_one(&mut **tx)
+        .await?;
+    Ok(waiting > 0 || active_orders(tx, restaurant_id).await? >= i64::from(max_active))
+}
+
+// Admits queued orders, oldest first, while the kitchen has room; every queued order
+// when there is no limit. Returns the ids admitted.
+pub async fn admit(pool: &MySqlPool, restaurant_id: i64, max_active: u32) -> Result<Vec<i64>> {
+    let mut tx = pool.begin().await?;
+    lock(&mut tx, restaurant_id).await?;
+    let room = match max_active {
+        0 => i64::MAX,
+        max => i64::from(max) - active_orders(&mut tx, restaurant_id).await?,
+    };
+    if room <= 0 {
+        return Ok(Vec::new());
+    }
+    let ids: Vec<i64> = sqlx::query_scalar(
+        "SELECT id FROM orders WHERE restaurant_id = ? AND status = 'queued' ORDER BY created_at, id LIMIT ?",
+    )
+    .bind(restaurant_id)
+    .bind(room)
+    .fetch_all(&mut *tx)
+    .await?;
+    for id in &ids {
+        sqlx::query("UPDATE orders SET status = 'placed' WHERE id = ? AND status = 'queued'")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        let admitted = OrderEvent::StatusChanged { from: "queued".to_string(), to: "placed".to_string() };
+        order_events::append(&mut tx, *id, restaurant_id, "kitchen_queue", &admitted).await?;
+    }
+    tx.commit().await?;
+    Ok(ids)
+}
+
+// admit() with the current settings, for after a change that may have freed the
+// kitchen. Failures are only logged: the sweep will retry, and the change that
+// triggered this has already been made.
+pub async fn admit_now(pool: &MySqlPool, restaurant_id: i64, config: &ConfigService) {
+    if let Err(err) = admit(pool, restaurant_id, config.kitchen_max_active_orders()).await {
+        logging::warn(
+            "kitchen queue admission failed",
+            json!({ "restaurant_id": restaurant_id, "error": format!("{err:#}") }),
+        );
+    }
+}
+
+// 1-based place of each queued order of the restaurant, by order id.
+pub async fn positions(pool: &MySqlPool, restaurant_id: i64) -> Result<HashMap<i64, u32>> {
+    let ids: Vec<i64> =
+        sqlx::query_scalar("SELECT id FROM orders WHERE restaurant_id = ? AND status = 'queued' ORDER BY created_at, id")
+            .bind(restaurant_id)
+            .fetch_all(pool)
+            .await?;
+    Ok(ids.into_iter().zip(1..).collect())
+}
+
+// Background sweep over restaurants with waiting orders.
+pub fn spawn_admitter(pool: MySqlPool, config: Arc<ConfigService>) {
+    tokio::spawn(async move {
+        loop {
+            match sweep(&pool, &config).await {
+                Ok(0) => {}
+                Ok(admitted) => logging::info("kitchen queue admitted orders", json!({ "admitted": admitted })),
+                Err(err) => logging::warn("kitchen queue sweep failed", json!({ "error": format!("{err:#}") })),
+            }
+            tokio::time::sleep(SWEEP_INTERVAL).await;
+        }
+    });
+}
+
+async fn sweep(pool: &MySqlPool, config: &ConfigService) -> Result<usize> {
+    let restaurants: Vec<i64> = sqlx::query_scalar("SELECT DISTINCT restaurant_id FROM orders WHERE status = 'queued'")
+        .fetch_all(pool)
+        .await?;
+    let mut admitted = 0;
+    for restaurant_id in restaurants {
+        admitted += admit(pool, restaurant_id, config.kitchen_max_active_orders()).await?.len();
+    }
+    Ok(admitted)
+}
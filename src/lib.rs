@@ -0,0 +1,66 @@
+// Library crate shared by the server and client binaries.
+// Feature modules live here so they can be used from either binary.
+
+pub mod access_log;
+pub mod admin_sessions;
+pub mod allergens;
+pub mod app;
+pub mod audit;
+pub mod auth;
+pub mod auth_throttle;
+pub mod backpressure;
+pub mod cli;
+pub mod config;
+pub mod connections;
+pub mod contract;
+pub mod csv;
+pub mod db_breaker;
+pub mod db_retry;
+pub mod error;
+pub mod eta;
+pub mod experiments;
+pub mod extract;
+pub mod faults;
+pub mod feature_flags;
+pub mod firewall;
+pub mod geocoding;
+pub mod health;
+pub mod i18n;
+pub mod item_options;
+pub mod kitchen_queue;
+pub mod load_shed;
+pub mod logging;
+pub mod menu;
+pub mod menu_schedule;
+pub mod menu_search;
+pub mod metrics;
+pub mod middleware;
+pub mod mtls;
+pub mod multipart;
+pub mod openapi;
+pub mod qlog;
+pub mod order_archive;
+pub mod order_events;
+pub mod orders;
+pub mod projections;
+pub mod query_plans;
+pub mod rate_limit;
+pub mod recommendations;
+pub mod redis;
+pub mod request;
+pub mod response;
+pub mod router;
+pub mod runtime_config;
+pub mod scheduler;
+pub mod sdk_gen;
+pub mod secrets;
+pub mod security;
+pub mod seed;
+pub mod server;
+pub mod tables;
+pub mod tenant;
+pub mod tickets;
+pub mod totp;
+pub mod validation;
+pub mod webhooks;
+pub mod zones;
@@ -0,0 +1,142 @@
+use crate::access_log::redacted_path;
+use crate::app::AppServices;
+use crate::config;
+use crate::error::{AppError, AppResult};
+use crate::logging;
+use crate::metrics::{Metrics, METRICS};
+use crate::middleware::Middleware;
+use crate::request::RequestContext;
+use anyhow::{anyhow, Result};
+use futures::future::BoxFuture;
+use serde_json::json;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// How often the runtime's scheduling delay is measured.
+const LAG_PROBE_INTERVAL: Duration = Duration::from_millis(100);
+
+// Sheds low-priority requests while the server is overloaded:
+//   LOAD_SHED_MAX_IN_FLIGHT     requests being handled at once before shedding starts
+//   LOAD_SHED_MAX_LAG_MS        runtime scheduling delay before shedding starts
+//   LOAD_SHED_PROTECTED         comma-separated path prefixes never shed (default: health
+//                               probes, metrics and order placement/handling)
+//   LOAD_SHED_RETRY_AFTER_SECS  Retry-After sent with the 503 (default 5)
+// With neither threshold set nothing is shed.
+pub struct LoadShedder {
+    max_in_flight: Option<usize>,
+    max_lag: Option<Duration>,
+    protected: Vec<String>,
+    retry_after_secs: u64,
+    in_flight: AtomicUsize,
+    lag_micros: AtomicU64,
+}
+
+impl LoadShedder {
+    pub fn from_env() -> Result<Self> {
+        let number = |name: &str| -> Result<Option<u64>> {
+            config::var(name)
+                .map(|v| v.parse().map_err(|_| anyhow!("{name} must be a positive integer")))
+                .transpose()
+        };
+        Ok(Self {
+            max_in_flight: number("LOAD_SHED_MAX_IN_FLIGHT")?.map(|n| n as usize),
+            max_lag: number("LOAD_SHED_MAX_LAG_MS")?.map(Duration::from_millis),
+            protected: config::var("LOAD_SHED_PROTECTED")
+                .unwrap_or_else(|| "/healthz,/readyz,/startupz,/metrics,/api/orders,/api/staff/orders".to_string())
+                .split(',')
+                .map(|prefix| prefix.trim().to_string())
+                .filter(|prefix| !prefix.is_empty())
+                .collect(),
+            retry_after_secs: number("LOAD_SHED_RETRY_AFTER_SECS")?.unwrap_or(5).max(1),
+            in_flight: AtomicUsize::new(0),
+            lag_micros: AtomicU64::new(0),
+        })
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.max_in_flight.is_some() || self.max_lag.is_some()
+    }
+
+    // Counts a request as in flight until the returned guard is dropped.
+    pub fn start(&self) -> InFlight<'_> {
+        self.in_flight.fetch_add(1, Ordering::Relaxed);
+        InFlight(self)
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::Relaxed)
+    }
+
+    // Latest measured scheduling delay of the runtime.
+    pub fn lag(&self) -> Duration {
+        Duration::from_micros(self.lag_micros.load(Ordering::Relaxed))
+    }
+
+    // Measures how late a short timer fires, which grows when tasks hog the runtime.
+    pub fn spawn_lag_monitor(self: Arc<Self>) {
+        if self.max_lag.is_none() {
+            return;
+        }
+        tokio::spawn(async move {
+            loop {
+                let started = Instant::now();
+                tokio::time::sleep(LAG_PROBE_INTERVAL).await;
+                let lag = started.elapsed().saturating_sub(LAG_PROBE_INTERVAL);
+                self.lag_micros.store(lag.as_micros() as u64, Ordering::Relaxed);
+            }
+        });
+    }
+
+    // Why the server counts as overloaded right now, if it does.
+    fn overloaded(&self) -> Option<&'static str> {
+        if self.max_in_flight.is_some_and(|max| self.in_flight() > max) {
+            return Some("in_flight");
+        }
+        if self.max_lag.is_some_and(|max| self.lag() > max) {
+            return Some("event_loop_lag");
+        }
+        None
+    }
+
+    fn is_protected(&self, path: &str) -> bool {
+        self.protected.iter().any(|prefix| path.starts_with(prefix.as_str()))
+    }
+}
+
+// Decrements the in-flight count when the request finishes.
+pub struct InFlight<'a>(&'a LoadShedder);
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.0.in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl Middleware for LoadShedder {
+    fn name(&self) -> &'static str {
+        "load_shed"
+    }
+
+    fn before<'a>(&'a self, ctx: &'a RequestContext, _services: &'a AppServices) -> BoxFuture<'a, AppResult<()>> {
+        Box::pin(async move {
+            let Some(reason) = self.overloaded() else {
+                return Ok(());
+            };
+            if self.is_protected(&ctx.path) {
+                return Ok(());
+            }
+            Metrics::increment(&METRICS.shed_requests_total);
+            logging::warn(
+                "request shed",
+                json!({
+                    "reason": reason,
+                    "in_flight": self.in_flight(),
+                    "lag_ms": self.lag().as_secs_f64() * 1000.0,
+                    "path": redacted_path(&ctx.path),
+                }),
+            );
+            Err(AppError::Overloaded { retry_after_secs: self.retry_after_secs })
+        })
+    }
+}
@@ -0,0 +1,409 @@
+use crate::config;
+use anyhow::{anyhow, Result};
+use serde_json::{json, Value};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::net::UdpSocket;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+// Severity of a log event, ordered from most to least verbose.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Debug = 0,
+    Info = 1,
+    Warn = 2,
+    Error = 3,
+}
+
+impl Level {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Level::Debug => "debug",
+            Level::Info => "info",
+            Level::Warn => "warn",
+            Level::Error => "error",
+        }
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "debug" => Some(Level::Debug),
+            "info" => Some(Level::Info),
+            "warn" | "warning" => Some(Level::Warn),
+            "error" => Some(Level::Error),
+            _ => None,
+        }
+    }
+
+    // RFC 5424 severity used by the syslog sink.
+    fn syslog_severity(self) -> u8 {
+        match self {
+            Level::Debug => 7,
+            Level::Info => 6,
+            Level::Warn => 4,
+            Level::Error => 3,
+        }
+    }
+}
+
+// Minimum level that gets written; events below it are dropped.
+static MIN_LEVEL: AtomicU8 = AtomicU8::new(Level::Info as u8);
+
+tokio::task_local! {
+    // Id of the request being served by the current task; added to every event logged in it.
+    static REQUEST_ID: String;
+}
+
+// Runs `future` with `request_id` attached to all events it logs.
+pub async fn with_request_id<F: std::future::Future>(request_id: String, future: F) -> F::Output {
+    REQUEST_ID.scope(request_id, future).await
+}
+
+// Sinks configured by init_logging. Until then events go to stdout as JSON.
+static SINKS: OnceLock<Vec<Box<dyn LogSink>>> = OnceLock::new();
+
+// A destination for log events. `event` is the full JSON object.
+pub trait LogSink: Send + Sync {
+    fn write(&self, level: Level, event: &Value);
+}
+
+// One JSON object per line on stdout.
+pub struct JsonConsole;
+
+impl LogSink for JsonConsole {
+    fn write(&self, _level: Level, event: &Value) {
+        println!("{event}");
+    }
+}
+
+// Human-friendly console output for development:
+//   2026-01-01T10:00:00Z  WARN request failed method=GET path=/x
+pub struct PrettyConsole;
+
+impl LogSink for PrettyConsole {
+    fn write(&self, level: Level, event: &Value) {
+        let mut line = format!(
+            "{} {:>5} {}",
+            event["timestamp"].as_str().unwrap_or_default(),
+            level.as_str().to_uppercase(),
+            event["message"].as_str().unwrap_or_default()
+        );
+        if let Value::Object(fields) = event {
+            for (key, value) in fields {
+                if matches!(key.as_str(), "timestamp" | "level" | "message") || value.is_null() {
+                    continue;
+                }
+                match value {
+                    Value::String(s) => line.push_str(&format!(" {key}={s}")),
+                    other => line.push_str(&format!(" {key}={other}")),
+                }
+            }
+        }
+        println!("{line}");
+    }
+}
+
+// When a log file is rotated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rotation {
+    // Once the file would exceed this many bytes.
+    Size(u64),
+    Hourly,
+    Daily,
+}
+
+impl Rotation {
+    // Parses "daily", "hourly", or "size:<n>[KB|MB|GB]".
+    pub fn parse(spec: &str) -> Result<Self> {
+        let spec = spec.trim().to_ascii_lowercase();
+        match spec.as_str() {
+            "daily" => return Ok(Rotation::Daily),
+            "hourly" => return Ok(Rotation::Hourly),
+            _ => {}
+        }
+        let size = spec
+            .strip_prefix("size:")
+            .ok_or_else(|| anyhow!("LOG_ROTATION must be daily, hourly or size:<bytes>, got {spec}"))?;
+        let (number, multiplier) = match size {
+            s if s.ends_with("gb") => (&s[..s.len() - 2], 1024 * 1024 * 1024),
+            s if s.ends_with("mb") => (&s[..s.len() - 2], 1024 * 1024),
+            s if s.ends_with("kb") => (&s[..s.len() - 2], 1024),
+            s => (s, 1),
+        };
+        let bytes: u64 = number.trim().parse().map_err(|_| anyhow!("invalid rotation size: {size}"))?;
+        Ok(Rotation::Size(bytes * multiplier))
+    }
+
+    // Identifies the current time period for time-based rotation.
+    fn period(self) -> Option<String> {
+        let now = chrono::Utc::now();
+        match self {
+            Rotation::Size(_) => None,
+            Rotation::Hourly => Some(now.format("%Y-%m-%dT%H").to_string()),
+            Rotation::Daily => Some(now.format("%Y-%m-%d").to_string()),
+        }
+    }
+}
+
+// File writer that rotates by size or time, keeping `retention` old generations
+// as path.1 (newest) .. path.N (oldest); older ones are deleted.
+pub struct RotatingFile {
+    path: PathBuf,
+    rotation: Rotation,
+    retention: usize,
+    file: File,
+    written: u64,
+    period: Option<String>,
+}
+
+impl RotatingFile {
+    pub fn open(path: impl Into<PathBuf>, rotation: Rotation, retention: usize) -> Result<Self> {
+        let path = path.into();
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let written = file.metadata()?.len();
+        Ok(Self {
+            path,
+            rotation,
+            retention,
+            file,
+            written,
+            period: rotation.period(),
+        })
+    }
+
+    // Appends one line, rotating first when the size limit or time period is crossed.
+    pub fn write_line(&mut self, line: &str) -> Result<()> {
+        let len = line.len() as u64 + 1;
+        let due = match self.rotation {
+            Rotation::Size(max_bytes) => self.written > 0 && self.written + len > max_bytes,
+            _ => self.rotation.period() != self.period,
+        };
+        if due {
+            self.rotate()?;
+        }
+        writeln!(self.file, "{line}")?;
+        self.written += len;
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> Result<()> {
+        self.file.flush()?;
+        // Shift path.(n-1) -> path.n; the rename onto path.retention drops the oldest generation.
+        for n in (1..self.retention).rev() {
+            let from = self.generation(n);
+            if from.exists() {
+                fs::rename(&from, self.generation(n + 1))?;
+            }
+        }
+        if self.retention > 0 {
+            fs::rename(&self.path, self.generation(1))?;
+        }
+        self.file = OpenOptions::new().create(true).write(true).truncate(true).open(&self.path)?;
+        self.written = 0;
+        self.period = self.rotation.period();
+        Ok(())
+    }
+
+    fn generation(&self, n: usize) -> PathBuf {
+        let mut name = self.path.clone().into_os_string();
+        name.push(format!(".{n}"));
+        name.into()
+    }
+}
+
+// JSON lines written to a rotating file.
+pub struct FileLog {
+    file: Mutex<RotatingFile>,
+}
+
+impl FileLog {
+    pub fn new(file: RotatingFile) -> Self {
+        Self { file: Mutex::new(file) }
+    }
+
+    pub fn write_line(&self, line: &str) {
+        if let Ok(mut file) = self.file.lock()
+            && let Err(err) = file.write_line(line)
+        {
+            eprintln!("log file write failed: {err}");
+        }
+    }
+}
+
+impl LogSink for FileLog {
+    fn write(&self, _level: Level, event: &Value) {
+        self.write_line(&event.to_string());
+    }
+}
+
+// Sends RFC 5424 messages over UDP to a syslog collector (facility local0).
+pub struct Syslog {
+    socket: UdpSocket,
+    hostname: String,
+}
+
+impl Syslog {
+    pub fn connect(addr: &str) -> Result<Self> {
+        let socket = UdpSocket::bind("0.0.0.0:0")?;
+        socket.connect(addr)?;
+        socket.set_nonblocking(true)?;
+        let hostname = std::env::var("HOSTNAME").unwrap_or_else(|_| "-".into());
+        Ok(Self { socket, hostname })
+    }
+}
+
+impl LogSink for Syslog {
+    fn write(&self, level: Level, event: &Value) {
+        const FACILITY_LOCAL0: u8 = 16;
+        let priority = FACILITY_LOCAL0 * 8 + level.syslog_severity();
+        let message = format!(
+            "<{priority}>1 {} {} rotiride {} - - {event}",
+            event["timestamp"].as_str().unwrap_or("-"),
+            self.hostname,
+            std::process::id()
+        );
+        // Logging must never block or fail the caller; a full socket buffer drops the event.
+        let _ = self.socket.send(message.as_bytes());
+    }
+}
+
+// Posts JSON values in batches to an HTTP collector from a background task.
+// Values are dropped rather than queued without bound when the collector falls behind.
+pub struct HttpShipper {
+    sender: mpsc::Sender<Value>,
+}
+
+impl HttpShipper {
+    const BATCH_SIZE: usize = 100;
+    const FLUSH_INTERVAL: Duration = Duration::from_secs(2);
+
+    // Starts the background shipper task. Must be called inside the tokio runtime.
+    pub fn spawn(url: String) -> Self {
+        let (sender, mut receiver) = mpsc::channel::<Value>(Self::BATCH_SIZE * 10);
+        tokio::spawn(async move {
+            let client = reqwest::Client::new();
+            let mut batch = Vec::with_capacity(Self::BATCH_SIZE);
+            let mut ticker = tokio::time::interval(Self::FLUSH_INTERVAL);
+            loop {
+                tokio::select! {
+                    value = receiver.recv() => match value {
+                        Some(value) => {
+                            batch.push(value);
+                            if batch.len() < Self::BATCH_SIZE {
+                                continue;
+                            }
+                        }
+                        None => break,
+                    },
+                    _ = ticker.tick() => {}
+                }
+                if batch.is_empty() {
+                    continue;
+                }
+                if let Err(err) = client.post(&url).json(&batch).send().await.and_then(|r| r.error_for_status()) {
+                    eprintln!("log shipping to {url} failed ({} records dropped): {err}", batch.len());
+                }
+                batch.clear();
+            }
+        });
+        Self { sender }
+    }
+
+    pub fn send(&self, value: Value) {
+        let _ = self.sender.try_send(value);
+    }
+}
+
+impl LogSink for HttpShipper {
+    fn write(&self, _level: Level, event: &Value) {
+        self.send(event.clone());
+    }
+}
+
+// Configures logging from the environment. Call once at startup, inside the runtime.
+//   LOG_LEVEL      debug | info (default) | warn | error
+//   LOG_FORMAT     json (default) | pretty   -- console output
+//   LOG_FILE       also write JSON lines to this file
+//   LOG_ROTATION   daily (default) | hourly | size:<n>[KB|MB|GB]
+//   LOG_RETENTION  rotated files to keep (default 7)
+//   LOG_SYSLOG     host:port of a UDP syslog collector
+//   LOG_HTTP_URL   collector URL receiving JSON arrays of events
+pub fn init_logging() -> Result<()> {
+    fn env(name: &str) -> Option<String> {
+        config::var(name).filter(|v| !v.trim().is_empty())
+    }
+
+    if let Some(level) = env("LOG_LEVEL") {
+        let level = Level::parse(&level).ok_or_else(|| anyhow!("invalid LOG_LEVEL: {level}"))?;
+        MIN_LEVEL.store(level as u8, Ordering::Relaxed);
+    }
+
+    let mut sinks: Vec<Box<dyn LogSink>> = Vec::new();
+    match env("LOG_FORMAT").as_deref().unwrap_or("json") {
+        "json" => sinks.push(Box::new(JsonConsole)),
+        "pretty" => sinks.push(Box::new(PrettyConsole)),
+        other => return Err(anyhow!("LOG_FORMAT must be json or pretty, got {other}")),
+    }
+    if let Some(path) = env("LOG_FILE") {
+        let rotation = Rotation::parse(env("LOG_ROTATION").as_deref().unwrap_or("daily"))?;
+        let retention = match env("LOG_RETENTION") {
+            Some(v) => v.parse().map_err(|_| anyhow!("invalid LOG_RETENTION: {v}"))?,
+            None => 7,
+        };
+        sinks.push(Box::new(FileLog::new(RotatingFile::open(path, rotation, retention)?)));
+    }
+    if let Some(addr) = env("LOG_SYSLOG") {
+        sinks.push(Box::new(Syslog::connect(&addr)?));
+    }
+    if let Some(url) = env("LOG_HTTP_URL") {
+        sinks.push(Box::new(HttpShipper::spawn(url)));
+    }
+
+    SINKS
+        .set(sinks)
+        .map_err(|_| anyhow!("init_logging called more than once"))
+}
+
+pub fn enabled(level: Level) -> bool {
+    level as u8 >= MIN_LEVEL.load(Ordering::Relaxed)
+}
+
+// Emits one event {"timestamp", "level", "message", ...fields} to every sink.
+// `fields` should be a JSON object; its keys are merged into the event.
+pub fn log(level: Level, message: &str, fields: Value) {
+    if !enabled(level) {
+        return;
+    }
+    let mut event = json!({
+        "timestamp": chrono::Utc::now().to_rfc3339(),
+        "level": level.as_str(),
+        "message": message,
+    });
+    if let (Value::Object(event), Value::Object(fields)) = (&mut event, fields) {
+        if let Ok(request_id) = REQUEST_ID.try_with(String::clone) {
+            event.insert("request_id".to_string(), Value::String(request_id));
+        }
+        event.extend(fields);
+    }
+
+    match SINKS.get() {
+        Some(sinks) => sinks.iter().for_each(|sink| sink.write(level, &event)),
+        None => JsonConsole.write(level, &event),
+    }
+}
+
+pub fn info(message: &str, fields: Value) {
+    log(Level::Info, message, fields);
+}
+
+pub fn warn(message: &str, fields: Value) {
+    log(Level::Warn, message, fields);
+}
+
+pub fn error(message: &str, fields: Value) {
+    log(Level::Error, message, fields);
+}
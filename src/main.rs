@@ -1,27 +1,103 @@
 use anyhow::{Result}; // Removed 'Ok' as it's a variant, not a type to import directly
-use bytes::Bytes;
+use http::StatusCode;
 use quinn::{Endpoint, ServerConfig};
+use rotiride::app::AppServices;
+use rotiride::cli::{self, Cli, Command, ConfigAction};
+use rotiride::extract::State;
+use rotiride::firewall::FirewallMiddleware;
+use rotiride::health::HealthProbes;
+use rotiride::metrics::METRICS;
+use rotiride::mtls::AdminListener;
+use rotiride::rate_limit::{InMemoryBackend, RateLimitBackend, RateLimitMiddleware};
+use rotiride::redis::RedisRateLimitBackend;
+use rotiride::response::ResponseBuilder;
+use rotiride::router::Router;
+use rotiride::secrets::SecretBindings;
+use rotiride::seed::SeedOptions;
+use rotiride::{admin_sessions, allergens, audit, config, connections, experiments, faults, feature_flags, health, item_options, kitchen_queue, logging, menu, menu_schedule, menu_search, openapi, order_archive, order_events, orders, projections, query_plans, recommendations, runtime_config, server, tables, tenant, tickets, totp, webhooks, zones};
 use rustls::{pki_types::PrivateKeyDer, ServerConfig as TlsServerConfig}; // Alias ServerConfig to TlsServerConfig to avoid name collision with quinn::ServerConfig
+use serde_json::json;
+use sqlx::mysql::MySqlConnectOptions;
 use std::sync::Arc;
+use std::time::Duration;
 
 #[tokio::main]
 async fn main() -> Result<()> { // Changed main to return Result<()> to handle errors
+    // Load variables from a .env file if present; real environment variables take precedence.
+    dotenvy::dotenv().ok();
+    match Cli::from_args().command {
+        Command::Serve(settings) => serve(settings.args).await,
+        Command::Migrate { action } => cli::migrate(action).await,
+        Command::CreateAdmin { phone, client_identity, settings } => {
+            cli::create_admin(&phone, client_identity.as_deref(), settings).await
+        }
+        Command::Seed { restaurant, orders, days, settings } => {
+            cli::seed(SeedOptions { restaurant_slug: restaurant, orders, days }, settings).await
+        }
+        Command::Config { action: ConfigAction::Check(settings) } => cli::config_check(settings),
+        Command::GenCert { hosts, out_dir } => cli::gen_cert(hosts, out_dir),
+        Command::ContractTest { recordings, base_url, cert, settings } => {
+            cli::contract_test(recordings, &base_url, cert, settings).await
+        }
+        Command::GenClient { out_dir } => cli::gen_client(&api_routes(), out_dir),
+    }
+}
+
+// The application's routes, also read by `server gen-client`. Unknown paths keep the
+// original greeting response.
+fn api_routes() -> Router {
+    connections::routes(audit::routes(admin_sessions::routes(totp::routes(orders::routes(order_events::routes(projections::routes(query_plans::routes(tickets::routes(tables::routes(tenant::routes(runtime_config::routes(feature_flags::routes(experiments::routes(webhooks::routes(item_options::routes(allergens::routes(menu_schedule::routes(menu_search::routes(recommendations::routes(menu::routes(zones::routes(Router::new()))))))))))))))))))))))
+        .get("/", || async { ResponseBuilder::text(StatusCode::OK, "hello from http3") })
+        .summary("Greeting")
+        .get("/test", || async {
+            ResponseBuilder::text(StatusCode::OK, "hello from http3 test endpoint")
+        })
+        .summary("Test endpoint")
+        .get("/metrics", |State(services): State| async move {
+            let mut body = METRICS.render_prometheus()
+                + &services.connections.render_prometheus()
+                + &services.db_breaker.render_prometheus();
+            // Left out rather than failing the scrape when the database can't be read.
+            if let Some(pool) = &services.db
+                && let Ok(statements) = query_plans::render_statement_cache(pool).await
+            {
+                body += &statements;
+            }
+            ResponseBuilder::text(StatusCode::OK, body)
+        })
+        .summary("Prometheus metrics")
+        .fallback(|| async {
+            ResponseBuilder::text(StatusCode::OK, "hello from http3 - unknown endpoint")
+        })
+}
+
+// Runs the HTTP/3 server until it fails.
+async fn serve(args: Vec<String>) -> Result<()> {
+    // Settings layer config file < environment < command-line flags; see config.rs.
+    config::load(args)?;
+    logging::init_logging()?;
+    faults::init()?;
+
     // Install the default crypto provider for rustls.
     // This is necessary for rustls to function correctly, especially with AWS-LC-RS.
     rustls::crypto::aws_lc_rs::default_provider()
         .install_default()
         .unwrap(); // Panics if installation fails, which is acceptable for a startup step.
 
-    // Generate a self-signed certificate and private key for the server.
-    let cert_chain_and_key = generate_self_signed_cert()?;
+    // Use the configured certificate (TLS_CERT_FILE / TLS_KEY_FILE, e.g. from `server gen-cert`),
+    // or generate a self-signed one for localhost.
+    let cert_chain_and_key = match cli::tls_files()? {
+        Some((cert_chain, private_key)) => CertificateChain { cert_chain, private_key },
+        None => generate_self_signed_cert()?,
+    };
 
     // Build the TLS server configuration using the generated certificate and key.
     // TlsServerConfig::builder() is used to construct the rustls server configuration.
     let mut tls_config = TlsServerConfig::builder()
         .with_no_client_auth() // No client authentication required for this server
         .with_single_cert(
-            cert_chain_and_key.cert_chain, // Corrected field name from `cert.cert_chain` to `cert_chain_and_key.cert_chain`
-            cert_chain_and_key.private_key,
+            cert_chain_and_key.cert_chain.clone(), // Corrected field name from `cert.cert_chain` to `cert_chain_and_key.cert_chain`
+            cert_chain_and_key.private_key.clone_key(),
         )?;
 
     // Set the ALPN (Application-Layer Protocol Negotiation) protocols.
@@ -38,65 +114,113 @@ async fn main() -> Result<()> { // Changed main to return Result<()> to handle e
     let endpoint = Endpoint::server(server_config, "127.0.0.1:443".parse()?)?;
     println!("HTTP/3 server listening on 127.0.0.1:443");
 
-    // Main server loop: accept incoming connections.
-    while let Some(conn) = endpoint.accept().await {
-        // Await the connection to be established.
-        let conn = conn.await?;
+    // Register the HTTP routes.
+    let router = api_routes();
 
-        // Spawn a new task to handle each incoming QUIC connection.
-        tokio::spawn(async move {
-            // Create an h3 server connection from the Quinn connection.
-            let mut h3_conn = h3::server::Connection::new(h3_quinn::Connection::new(conn))
-                .await
-                .unwrap(); // Panics if h3 connection setup fails
-
-            // Loop to accept and handle HTTP/3 requests on this connection.
-            loop {
-                match h3_conn.accept().await {
-                    // If a request resolver is received, spawn a task to handle the request.
-                    Ok(Some(req_resolver)) => {
-                        tokio::spawn(async move {
-                            // Resolve the request to get the HTTP request and the stream.
-                            let (req, mut stream) = req_resolver.resolve_request().await.unwrap(); // Panics on error
-
-                            println!(
-                                "Got request for path: {}, protocol: {:?}",
-                                req.uri().path(),
-                                req.version()
-                            );
-
-                            // Determine the response body based on the request path.
-                            let response_body = match req.uri().path() {
-                                "/" => "hello from http3",
-                                "/test" => "hello from http3 test endpoint",
-                                "/health" => "hello from http3 health check",
-                                _ => "hello from http3 - unknown endpoint",
-                            };
-
-                            // Build the HTTP response.
-                            let response = http::Response::builder()
-                                .status(200)
-                                .header("content-type", "text/plain")
-                                .body(()) // Body is empty for the header part
-                                .unwrap(); // Panics if response building fails
-
-                            // Send the response headers.
-                            stream.send_response(response).await.unwrap(); // Panics on error
-                            // Send the response data (body).
-                            stream.send_data(Bytes::from(response_body)).await.unwrap(); // Panics on error
-                            // Finish the stream, indicating no more data will be sent.
-                            stream.finish().await.unwrap(); // Panics on error
-                        });
-                    }
-                    // If no more requests are available on this connection, break the loop.
-                    Ok(None) => break,
-                    // If an error occurs while accepting a request, break the loop.
-                    Err(_) => break,
+    // Credentials from a secrets manager (SECRETS_PROVIDER) must be in place before the
+    // services below read them.
+    let secrets = SecretBindings::from_config()?;
+    if let Some(secrets) = &secrets {
+        secrets.load().await?;
+    }
+
+    // Shared services (database pool, admin token, Redis) loaded from the environment.
+    let services = AppServices::from_env()?;
+
+    // Rotated secrets are re-fetched in the background. A new DATABASE_URL is applied to
+    // connections the pool opens from then on.
+    if let Some(secrets) = secrets {
+        let pool = services.db.clone();
+        let refresh_secs = config::var("SECRETS_REFRESH_SECS")
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(300);
+        secrets.spawn_refresh(Duration::from_secs(refresh_secs), move |changed| {
+            if let Some(pool) = &pool
+                && changed.iter().any(|name| name == "DATABASE_URL")
+                && let Some(url) = config::var("DATABASE_URL")
+            {
+                match url.parse::<MySqlConnectOptions>() {
+                    Ok(options) => pool.set_connect_options(options),
+                    Err(err) => logging::warn("rotated DATABASE_URL is invalid", json!({ "error": err.to_string() })),
                 }
             }
         });
     }
-    Ok(()) // Indicate successful execution of the main function
+
+    // Network allow/deny lists and request sanity checks run before anything else.
+    let router = router.middleware(Arc::new(FirewallMiddleware::from_env()?));
+
+    // Low-priority requests are shed with 503 while the server is overloaded.
+    let router = if services.load_shedder.enabled() {
+        services.load_shedder.clone().spawn_lag_monitor();
+        router.middleware(services.load_shedder.clone())
+    } else {
+        router
+    };
+
+    // Optional per-route-group rate limiting (RATE_LIMITS). Counters live in Redis when
+    // REDIS_URL is set so limits hold across instances.
+    let backend: Box<dyn RateLimitBackend> = match &services.redis {
+        Some(redis) => Box::new(RedisRateLimitBackend::new(redis.clone())),
+        None => Box::new(InMemoryBackend::new(100_000)),
+    };
+    let router = match RateLimitMiddleware::from_env(backend)? {
+        Some(rate_limit) => router.middleware(Arc::new(rate_limit)),
+        None => router,
+    };
+
+    // Kubernetes liveness/readiness/startup probes.
+    let router = health::routes(router, HealthProbes::new(&services));
+
+    // The OpenAPI spec is generated from the routes above, so it is registered last.
+    let router = openapi::routes(router);
+
+    if let Some(pool) = &services.db {
+        // While the database circuit breaker is open, a probe checks whether it's back.
+        services.db_breaker.clone().spawn_probe(pool.clone());
+        // Logged-out admin sessions are picked up from the other instances.
+        services.admin_sessions.clone().spawn_refresh(pool.clone());
+        // Outbound partner webhooks are sent from the outbox table in the background.
+        webhooks::spawn_dispatcher(pool.clone());
+        // Orders queued at peak times are admitted as the kitchen frees up.
+        kitchen_queue::spawn_admitter(pool.clone(), services.runtime_config.clone());
+        // Frequently-bought-together pairs are recomputed every night.
+        recommendations::spawn_miner(pool.clone(), services.runtime_config.clone());
+        // Dashboard read models follow the order event history.
+        projections::spawn_worker(pool.clone(), services.runtime_config.clone());
+        // Old finished orders move to the archive tables.
+        order_archive::spawn_archiver(pool.clone(), services.runtime_config.clone());
+        // Runtime configuration and feature flags are polled so edits apply without a restart.
+        let refresh_secs = config::var("RUNTIME_CONFIG_REFRESH_SECS")
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(30);
+        services
+            .runtime_config
+            .clone()
+            .spawn_refresh(pool.clone(), Duration::from_secs(refresh_secs));
+        services
+            .feature_flags
+            .clone()
+            .spawn_refresh(pool.clone(), Duration::from_secs(refresh_secs));
+    }
+
+    let (router, services) = (Arc::new(router), Arc::new(services));
+
+    // Optional internal admin listener that only accepts client-certificate holders.
+    if let Some(admin) = AdminListener::from_env()? {
+        let config = admin.server_config(cert_chain_and_key.cert_chain, cert_chain_and_key.private_key)?;
+        let admin_endpoint = Endpoint::server(config, admin.addr)?;
+        println!("HTTP/3 admin listener (mTLS) on {}", admin.addr);
+        let (router, services) = (router.clone(), services.clone());
+        tokio::spawn(async move {
+            if let Err(err) = server::run(admin_endpoint, router, services).await {
+                logging::error("admin listener stopped", json!({ "error": err.to_string() }));
+            }
+        });
+    }
+
+    // Main server loop: accept incoming connections and serve requests.
+    server::run(endpoint, router, services).await
 }
 
 // Struct to hold the certificate chain and private key.
@@ -0,0 +1,417 @@
+use crate::allergens::{self, AllergenRepository};
+use crate::app::AppServices;
+use crate::audit::{AuditEvent, AuditLogger};
+use crate::csv;
+use crate::error::{AppError, AppResult, FieldError};
+use crate::menu_schedule;
+use crate::openapi::ApiSchema;
+use crate::request::RequestContext;
+use crate::response::{CachePolicy, ResponseBuilder};
+use crate::router::Router;
+use crate::tenant;
+use crate::webhooks;
+use anyhow::Result;
+use bytes::Bytes;
+use chrono::{DateTime, FixedOffset, Utc};
+use http::{Response, StatusCode};
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use sqlx::mysql::MySqlPool;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+// A dish on the menu, as stored in the menu_items table.
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct MenuItem {
+    pub id: i64,
+    // Stable restaurant-assigned code; CSV imports match existing items on it.
+    pub sku: String,
+    pub name: String,
+    pub description: String,
+    pub category: String,
+    pub price_paise: i64,
+    pub available: bool,
+    // Nutrition per serving, when the restaurant has provided it.
+    pub calories_kcal: Option<i32>,
+    pub protein_grams: Option<i32>,
+    // Allergen codes, see GET /api/allergens.
+    #[sqlx(skip)]
+    pub allergens: Vec<String>,
+    // Whether the item's availability windows (menu_schedule.rs) include the current
+    // time; when not, orders for it are refused until next_available_at.
+    #[sqlx(skip)]
+    pub available_now: bool,
+    #[sqlx(skip)]
+    pub next_available_at: Option<DateTime<FixedOffset>>,
+}
+
+impl ApiSchema for MenuItem {
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["id", "sku", "name", "description", "category", "price_paise", "available", "allergens"],
+            "properties": {
+                "id": { "type": "integer" },
+                "sku": { "type": "string" },
+                "name": { "type": "string" },
+                "description": { "type": "string" },
+                "category": { "type": "string" },
+                "price_paise": { "type": "integer" },
+                "available": { "type": "boolean" },
+                "calories_kcal": { "type": ["integer", "null"] },
+                "protein_grams": { "type": ["integer", "null"] },
+                "allergens": { "type": "array", "items": { "type": "string" } },
+                "available_now": { "type": "boolean" },
+                "next_available_at": { "type": ["string", "null"], "format": "date-time" }
+            }
+        })
+    }
+}
+
+// One row of a menu CSV, validated and ready to upsert.
+#[derive(Debug, Clone)]
+pub struct MenuItemInput {
+    pub sku: String,
+    pub name: String,
+    pub description: String,
+    pub category: String,
+    pub price_paise: i64,
+    pub available: bool,
+}
+
+// Column order used by both import and export.
+pub const CSV_COLUMNS: [&str; 6] = ["sku", "name", "description", "category", "price_paise", "available"];
+
+// Outcome of an import.
+#[derive(Debug, Default, Serialize)]
+pub struct ImportSummary {
+    pub created: u64,
+    pub updated: u64,
+}
+
+impl MenuItemInput {
+    // Validates one CSV record. Errors are reported as "row[<line>].<column>" so the
+    // restaurant can find the cell in their spreadsheet.
+    fn from_record(record: &csv::Record, columns: &[usize; 6]) -> Result<Self, Vec<FieldError>> {
+        let line = record.line;
+        let cell = |i: usize| record.fields.get(columns[i]).map(|v| v.trim()).unwrap_or("");
+        let mut errors = Vec::new();
+        let mut required = |i: usize, max: usize| {
+            let value = cell(i);
+            if value.is_empty() {
+                errors.push(FieldError::new(format!("row[{line}].{}", CSV_COLUMNS[i]), "must not be blank"));
+            } else if value.chars().count() > max {
+                errors.push(FieldError::new(
+                    format!("row[{line}].{}", CSV_COLUMNS[i]),
+                    format!("must be at most {max} characters"),
+                ));
+            }
+            value.to_string()
+        };
+        let sku = required(0, 64);
+        let name = required(1, 200);
+        let category = required(3, 100);
+
+        let price_paise = match cell(4).parse::<i64>() {
+            Ok(price) if price >= 0 => price,
+            Ok(_) => {
+                errors.push(FieldError::new(format!("row[{line}].price_paise"), "must not be negative"));
+                0
+            }
+            Err(_) => {
+                errors.push(FieldError::new(format!("row[{line}].price_paise"), "must be a whole number of paise"));
+                0
+            }
+        };
+        let available = match cell(5).to_ascii_lowercase().as_str() {
+            "" | "true" | "yes" | "1" => true,
+            "false" | "no" | "0" => false,
+            _ => {
+                errors.push(FieldError::new(format!("row[{line}].available"), "must be true or false"));
+                true
+            }
+        };
+
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        Ok(Self {
+            sku,
+            name,
+            description: cell(2).to_string(),
+            category,
+            price_paise,
+            available,
+        })
+    }
+}
+
+// Parses and validates a menu CSV. The header row names the columns (in any order;
+// description and available are optional). Every problem in the file is collected so
+// one upload reports all of them.
+pub fn parse_menu_csv(text: &str) -> Result<Vec<MenuItemInput>, Vec<FieldError>> {
+    let records = csv::parse(text).map_err(|err| vec![FieldError::new("file", err)])?;
+    let Some((header, rows)) = records.split_first() else {
+        return Err(vec![FieldError::new("file", "CSV file is empty")]);
+    };
+
+    // Map each known column to its position; optional columns missing from the header
+    // point past the end of the row and so read as blank.
+    let mut columns = [usize::MAX; 6];
+    let mut errors = Vec::new();
+    for (i, name) in CSV_COLUMNS.iter().enumerate() {
+        match header.fields.iter().position(|h| h.trim().eq_ignore_ascii_case(name)) {
+            Some(pos) => columns[i] = pos,
+            None if matches!(*name, "description" | "available") => {}
+            None => errors.push(FieldError::new("header", format!("missing column {name}"))),
+        }
+    }
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    let mut items = Vec::with_capacity(rows.len());
+    let mut seen = HashSet::new();
+    for record in rows {
+        match MenuItemInput::from_record(record, &columns) {
+            Ok(item) if !seen.insert(item.sku.clone()) => errors.push(FieldError::new(
+                format!("row[{}].sku", record.line),
+                format!("duplicate sku {} in file", item.sku),
+            )),
+            Ok(item) => items.push(item),
+            Err(row_errors) => errors.extend(row_errors),
+        }
+    }
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    Ok(items)
+}
+
+// Renders items as a CSV document with a header row, in the import format.
+pub fn to_csv(items: &[MenuItem]) -> String {
+    let mut out = String::new();
+    csv::write_row(&mut out, &CSV_COLUMNS);
+    for item in items {
+        csv::write_row(
+            &mut out,
+            &[
+                item.sku.as_str(),
+                &item.name,
+                &item.description,
+                &item.category,
+                &item.price_paise.to_string(),
+                if item.available { "true" } else { "false" },
+            ],
+        );
+    }
+    out
+}
+
+// The audited fields of an item; prices are what reviewers usually look for.
+fn item_snapshot(name: &str, description: &str, category: &str, price_paise: i64, available: bool) -> Value {
+    json!({
+        "name": name,
+        "description": description,
+        "category": category,
+        "price_paise": price_paise,
+        "available": available,
+    })
+}
+
+const SELECT_ITEM: &str = "SELECT id, sku, name, description, category, price_paise, available, calories_kcal, \
+                           protein_grams FROM menu_items";
+
+// Database access for one restaurant's menu items.
+pub struct MenuRepository<'a> {
+    pool: &'a MySqlPool,
+    restaurant_id: i64,
+}
+
+impl<'a> MenuRepository<'a> {
+    pub fn new(pool: &'a MySqlPool, restaurant_id: i64) -> Self {
+        Self { pool, restaurant_id }
+    }
+
+    async fn with_allergens(&self, mut items: Vec<MenuItem>) -> Result<Vec<MenuItem>> {
+        let ids: Vec<i64> = items.iter().map(|item| item.id).collect();
+        let mut codes = allergens::for_items(self.pool, &ids).await?;
+        for item in &mut items {
+            item.allergens = codes.remove(&item.id).unwrap_or_default();
+        }
+        Ok(items)
+    }
+
+    pub async fn list(&self) -> Result<Vec<MenuItem>> {
+        let items = sqlx::query_as(&format!("{SELECT_ITEM} WHERE restaurant_id = ? ORDER BY category, name"))
+            .bind(self.restaurant_id)
+            .fetch_all(self.pool)
+            .await?;
+        self.with_allergens(items).await
+    }
+
+    // Available items, as guests see them.
+    pub async fn list_available(&self) -> Result<Vec<MenuItem>> {
+        let items =
+            sqlx::query_as(&format!("{SELECT_ITEM} WHERE restaurant_id = ? AND available ORDER BY category, name"))
+                .bind(self.restaurant_id)
+                .fetch_all(self.pool)
+                .await?;
+        self.with_allergens(items).await
+    }
+
+    // When any of the restaurant's items last changed; None for an empty menu.
+    pub async fn last_modified(&self) -> Result<Option<DateTime<Utc>>> {
+        Ok(sqlx::query_scalar("SELECT MAX(updated_at) FROM menu_items WHERE restaurant_id = ?")
+            .bind(self.restaurant_id)
+            .fetch_one(self.pool)
+            .await?)
+    }
+
+    // Inserts or updates every item by sku in a single transaction; nothing is written
+    // if any statement fails. Changed items are audited with before/after snapshots.
+    pub async fn upsert_all(&self, items: &[MenuItemInput], audit: &AuditLogger) -> Result<ImportSummary> {
+        let mut tx = self.pool.begin().await?;
+        // Affected-row counts can't tell a no-op update from an insert (sqlx sets
+        // CLIENT_FOUND_ROWS), so existing rows are read up front instead.
+        let existing: HashMap<String, MenuItem> =
+            sqlx::query_as::<_, MenuItem>(&format!("{SELECT_ITEM} WHERE restaurant_id = ? FOR UPDATE"))
+                .bind(self.restaurant_id)
+                .fetch_all(&mut *tx)
+                .await?
+                .into_iter()
+                .map(|item| (item.sku.clone(), item))
+                .collect();
+        let mut summary = ImportSummary::default();
+        let (mut before, mut after) = (Map::new(), Map::new());
+        for item in items {
+            let new = item_snapshot(&item.name, &item.description, &item.category, item.price_paise, item.available);
+            match existing.get(&item.sku) {
+                Some(old) => {
+                    let old = item_snapshot(&old.name, &old.description, &old.category, old.price_paise, old.available);
+                    if old != new {
+                        before.insert(item.sku.clone(), old);
+                        after.insert(item.sku.clone(), new);
+                    }
+                }
+                None => {
+                    after.insert(item.sku.clone(), new);
+                }
+            }
+            sqlx::query(
+                "INSERT INTO menu_items (restaurant_id, sku, name, description, category, price_paise, available) \
+                 VALUES (?, ?, ?, ?, ?, ?, ?) \
+                 ON DUPLICATE KEY UPDATE name = VALUES(name), description = VALUES(description), \
+                 category = VALUES(category), price_paise = VALUES(price_paise), available = VALUES(available)",
+            )
+            .bind(self.restaurant_id)
+            .bind(&item.sku)
+            .bind(&item.name)
+            .bind(&item.description)
+            .bind(&item.category)
+            .bind(item.price_paise)
+            .bind(item.available)
+            .execute(&mut *tx)
+            .await?;
+            if existing.contains_key(&item.sku) {
+                summary.updated += 1;
+            } else {
+                summary.created += 1;
+            }
+        }
+        // Partners hear about the change only if the import commits.
+        let payload = json!({
+            "restaurant_id": self.restaurant_id,
+            "created": summary.created,
+            "updated": summary.updated,
+        });
+        webhooks::enqueue(&mut *tx, self.restaurant_id, "menu.updated", &payload).await?;
+        if !after.is_empty() {
+            let event = AuditEvent {
+                action: "menu.import",
+                target_type: "menu",
+                target_id: self.restaurant_id.to_string(),
+                restaurant_id: Some(self.restaurant_id),
+                before: Some(Value::Object(before)),
+                after: Some(Value::Object(after)),
+            };
+            audit.record(&mut *tx, event).await?;
+        }
+        tx.commit().await?;
+        Ok(summary)
+    }
+}
+
+// Registers the guest menu and the admin menu import/export endpoints. All act on the
+// restaurant the request resolves to; import/export accept that restaurant's admin token.
+pub fn routes(router: Router) -> Router {
+    router
+        .get("/api/menu", public_menu)
+        .summary("List the restaurant's available menu items (supports ETag / If-Modified-Since)")
+        .cache(CachePolicy::public(60).stale_while_revalidate(300))
+        .query_param("exclude_allergens", false)
+        .response_schema(json!({ "type": "array", "items": MenuItem::schema() }))
+        .post("/api/admin/menu/import", import_menu)
+        .summary("Import menu items from a CSV upload (multipart field \"file\")")
+        .urgency(6)
+        .requires_tenant_admin()
+        .response_schema(json!({
+            "type": "object",
+            "properties": {
+                "created": { "type": "integer" },
+                "updated": { "type": "integer" }
+            }
+        }))
+        .get("/api/admin/menu/export", export_menu)
+        .summary("Download the menu as CSV")
+        .urgency(6)
+        .requires_tenant_admin()
+}
+
+// Import and export need the raw context (multipart upload, conditional response), so
+// they take the restaurant TenantAdminOnly verified from tenant::verified.
+async fn import_menu(ctx: RequestContext, services: Arc<AppServices>) -> AppResult<Response<Bytes>> {
+    let restaurant = tenant::verified(&ctx)?;
+    let file = ctx.part("file")?;
+    let items = parse_menu_csv(file.text()?).map_err(AppError::Validation)?;
+    let audit = AuditLogger::for_request(&ctx, &services);
+    let summary = MenuRepository::new(services.db()?, restaurant.id)
+        .upsert_all(&items, &audit)
+        .await?;
+    ResponseBuilder::json(StatusCode::OK, &summary)
+}
+
+async fn export_menu(ctx: RequestContext, services: Arc<AppServices>) -> AppResult<Response<Bytes>> {
+    let restaurant = tenant::verified(&ctx)?;
+    let repository = MenuRepository::new(services.db()?, restaurant.id);
+    let items = repository.list().await?;
+    let response = ResponseBuilder::attachment("text/csv; charset=utf-8", "menu.csv", to_csv(&items))?;
+    ResponseBuilder::conditional(&ctx, response, repository.last_modified().await?)
+}
+
+// Guests poll the menu often and it rarely changes, so it is served conditionally.
+// ?exclude_allergens=peanut,gluten leaves out items declaring any of those allergens.
+// Items outside their availability windows stay listed with available_now false and
+// the time they come back, so apps can show them greyed out.
+async fn public_menu(ctx: RequestContext, services: Arc<AppServices>) -> AppResult<Response<Bytes>> {
+    let restaurant = tenant::current(&ctx, &services).await?;
+    let excluded = match ctx.query_param("exclude_allergens") {
+        Some(list) => AllergenRepository::new(services.db()?).parse_codes("exclude_allergens", list).await?,
+        None => Vec::new(),
+    };
+    let repository = MenuRepository::new(services.db()?, restaurant.id);
+    let mut items = repository.list_available().await?;
+    items.retain(|item| !item.allergens.iter().any(|code| excluded.contains(code)));
+    let now = Utc::now();
+    let schedules = menu_schedule::load(services.db()?, restaurant.id, services.runtime_config.local_offset()).await?;
+    for item in &mut items {
+        item.available_now = schedules.is_open(item.id, &item.category, now);
+        if !item.available_now {
+            item.next_available_at = schedules.next_open(item.id, &item.category, now);
+        }
+    }
+    let response = ResponseBuilder::json(StatusCode::OK, &items)?;
+    // A window opening or closing changes the listing without touching menu_items.
+    let last_modified = repository.last_modified().await?.max(schedules.last_change(now));
+    ResponseBuilder::conditional(&ctx, response, last_modified)
+}
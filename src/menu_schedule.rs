@@ -0,0 +1,420 @@
+use crate::audit::{AuditEvent, AuditLogger};
+use crate::error::{AppError, AppResult, FieldError};
+use crate::extract::{State, TenantAdmin, ValidJson};
+use crate::openapi::ApiSchema;
+use crate::response::ResponseBuilder;
+use crate::router::Router;
+use crate::validation::Validate;
+use anyhow::Result;
+use bytes::Bytes;
+use chrono::{DateTime, Datelike, Duration, FixedOffset, NaiveDate, NaiveDateTime, Timelike, Utc, Weekday};
+use http::{Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sqlx::mysql::{MySql, MySqlPool};
+use std::collections::HashMap;
+
+// Availability windows for menu items, e.g. breakfast items from 07:00 to 11:00 on
+// weekdays. Windows are set per item or per category; an item's own windows replace
+// its category's, and an item with neither is served whenever it is available. Times
+// are local (runtime config utc_offset_minutes). A window ending at or before its
+// start runs past midnight into the next day.
+
+pub const DAYS: [&str; 7] = ["mon", "tue", "wed", "thu", "fri", "sat", "sun"];
+
+// A window as the API shows it: {"days": ["mon", "tue"], "start": "07:00", "end": "11:00"}.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Window {
+    pub days: Vec<String>,
+    pub start: String,
+    pub end: String,
+}
+
+fn window_schema() -> Value {
+    json!({
+        "type": "object",
+        "required": ["days", "start", "end"],
+        "properties": {
+            "days": { "type": "array", "minItems": 1, "items": { "type": "string", "enum": DAYS } },
+            "start": { "type": "string", "pattern": "^[0-9]{2}:[0-9]{2}$" },
+            "end": { "type": "string", "pattern": "^[0-9]{2}:[0-9]{2}$", "description": "24:00 for midnight" }
+        }
+    })
+}
+
+// A window as stored: weekdays as a bitmask (bit 0 = Monday), times as minutes after
+// local midnight.
+#[derive(Debug, Clone, Copy)]
+struct Slot {
+    weekdays: u8,
+    start: u32,
+    end: u32,
+}
+
+// "HH:MM" to minutes after midnight; "24:00" is allowed as an end time.
+fn parse_minute(value: &str) -> Option<u32> {
+    let (hours, minutes) = value.split_once(':')?;
+    let (hours, minutes): (u32, u32) = (hours.parse().ok()?, minutes.parse().ok()?);
+    match (hours, minutes) {
+        (0..=23, 0..=59) => Some(hours * 60 + minutes),
+        (24, 0) => Some(24 * 60),
+        _ => None,
+    }
+}
+
+impl Slot {
+    fn from_window(window: &Window) -> Result<Self, String> {
+        let mut weekdays = 0u8;
+        for day in &window.days {
+            let index = DAYS
+                .iter()
+                .position(|d| d.eq_ignore_ascii_case(day.trim()))
+                .ok_or_else(|| format!("unknown day {day}; use {}", DAYS.join(", ")))?;
+            weekdays |= 1 << index;
+        }
+        if weekdays == 0 {
+            return Err("must name at least one day".to_string());
+        }
+        let start = parse_minute(&window.start).filter(|m| *m < 24 * 60);
+        let end = parse_minute(&window.end);
+        match (start, end) {
+            (Some(start), Some(end)) if start != end && !(start == 0 && end == 24 * 60) => Ok(Self { weekdays, start, end }),
+            (Some(_), Some(_)) => Err("start and end must differ; leave the item unscheduled to serve it all day".to_string()),
+            _ => Err("times must be HH:MM between 00:00 and 24:00".to_string()),
+        }
+    }
+
+    fn to_window(self) -> Window {
+        let time = |minute: u32| format!("{:02}:{:02}", minute / 60, minute % 60);
+        Window {
+            days: (0..7).filter(|i| self.weekdays & (1 << i) != 0).map(|i| DAYS[i].to_string()).collect(),
+            start: time(self.start),
+            end: time(self.end),
+        }
+    }
+
+    fn runs_on(self, day: Weekday) -> bool {
+        self.weekdays & (1 << day.num_days_from_monday()) != 0
+    }
+
+    fn is_open(self, at: NaiveDateTime) -> bool {
+        let minute = at.hour() * 60 + at.minute();
+        let day = at.weekday();
+        if self.start < self.end {
+            self.runs_on(day) && (self.start..self.end).contains(&minute)
+        } else {
+            (self.runs_on(day) && minute >= self.start) || (self.runs_on(day.pred()) && minute < self.end)
+        }
+    }
+
+    // Start and end of the run beginning on `date`, if the window runs that day.
+    fn run_on(self, date: NaiveDate) -> Option<(NaiveDateTime, NaiveDateTime)> {
+        if !self.runs_on(date.weekday()) {
+            return None;
+        }
+        let midnight = date.and_hms_opt(0, 0, 0)?;
+        let start = midnight + Duration::minutes(i64::from(self.start));
+        let mut end = midnight + Duration::minutes(i64::from(self.end));
+        if self.end <= self.start {
+            end += Duration::days(1);
+        }
+        Some((start, end))
+    }
+}
+
+// Every window of one restaurant, for checking items against the clock.
+pub struct Schedules {
+    offset: FixedOffset,
+    items: HashMap<i64, Vec<Slot>>,
+    categories: HashMap<String, Vec<Slot>>,
+}
+
+impl Schedules {
+    fn slots(&self, menu_item_id: i64, category: &str) -> &[Slot] {
+        self.items
+            .get(&menu_item_id)
+            .or_else(|| self.categories.get(category))
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    fn local(&self, now: DateTime<Utc>) -> NaiveDateTime {
+        now.with_timezone(&self.offset).naive_local()
+    }
+
+    pub fn is_open(&self, menu_item_id: i64, category: &str, now: DateTime<Utc>) -> bool {
+        let slots = self.slots(menu_item_id, category);
+        let at = self.local(now);
+        slots.is_empty() || slots.iter().any(|slot| slot.is_open(at))
+    }
+
+    // When a currently closed item next opens, within the coming week.
+    pub fn next_open(&self, menu_item_id: i64, category: &str, now: DateTime<Utc>) -> Option<DateTime<FixedOffset>> {
+        let at = self.local(now);
+        (0..=7)
+            .flat_map(|days| {
+                let date = at.date() + Duration::days(days);
+                self.slots(menu_item_id, category).iter().filter_map(move |slot| slot.run_on(date))
+            })
+            .map(|(start, _)| start)
+            .filter(|start| *start > at)
+            .min()
+            .and_then(|start| start.and_local_timezone(self.offset).single())
+    }
+
+    // The last time any window opened or closed, so a cached menu from before then is
+    // known to be stale.
+    pub fn last_change(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let at = self.local(now);
+        let slots = self.items.values().chain(self.categories.values()).flatten();
+        slots
+            .flat_map(|slot| (0..=8).filter_map(move |days| slot.run_on(at.date() - Duration::days(days))))
+            .flat_map(|(start, end)| [start, end])
+            .filter(|boundary| *boundary <= at)
+            .max()
+            .and_then(|boundary| boundary.and_local_timezone(self.offset).single())
+            .map(|boundary| boundary.with_timezone(&Utc))
+    }
+}
+
+// Loads the restaurant's windows. Takes any executor so order placement can read them
+// inside its own transaction.
+pub async fn load<'e, E>(executor: E, restaurant_id: i64, offset: FixedOffset) -> Result<Schedules>
+where
+    E: sqlx::Executor<'e, Database = MySql>,
+{
+    let rows: Vec<(Option<i64>, Option<String>, u8, u32, u32)> = sqlx::query_as(
+        "SELECT menu_item_id, category, weekdays, start_minute, end_minute FROM menu_schedules \
+         WHERE restaurant_id = ? ORDER BY id",
+    )
+    .bind(restaurant_id)
+    .fetch_all(executor)
+    .await?;
+    let mut schedules = Schedules { offset, items: HashMap::new(), categories: HashMap::new() };
+    for (menu_item_id, category, weekdays, start, end) in rows {
+        let slot = Slot { weekdays, start, end };
+        match (menu_item_id, category) {
+            (Some(id), _) => schedules.items.entry(id).or_default().push(slot),
+            (None, Some(category)) => schedules.categories.entry(category).or_default().push(slot),
+            (None, None) => {}
+        }
+    }
+    Ok(schedules)
+}
+
+// Body of PUT /api/admin/menu/schedules: the complete set of windows for one item
+// (by sku) or one category. An empty list removes the schedule.
+#[derive(Debug, Deserialize)]
+pub struct ScheduleInput {
+    #[serde(default)]
+    pub sku: Option<String>,
+    #[serde(default)]
+    pub category: Option<String>,
+    pub windows: Vec<Window>,
+}
+
+impl ApiSchema for ScheduleInput {
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["windows"],
+            "properties": {
+                "sku": { "type": "string", "minLength": 1 },
+                "category": { "type": "string", "minLength": 1 },
+                "windows": { "type": "array", "maxItems": 20, "items": window_schema() }
+            }
+        })
+    }
+}
+
+impl Validate for ScheduleInput {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        if self.sku.is_some() == self.category.is_some() {
+            errors.push(FieldError::new("sku", "give either sku or category"));
+        }
+        for (i, window) in self.windows.iter().enumerate() {
+            if let Err(message) = Slot::from_window(window) {
+                errors.push(FieldError::new(format!("windows[{i}]"), message));
+            }
+        }
+        errors
+    }
+}
+
+// The windows set on one item or category.
+#[derive(Debug, Serialize)]
+pub struct ScheduleEntry {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sku: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    pub windows: Vec<Window>,
+}
+
+impl ApiSchema for ScheduleEntry {
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["windows"],
+            "properties": {
+                "sku": { "type": "string" },
+                "category": { "type": "string" },
+                "windows": { "type": "array", "items": window_schema() }
+            }
+        })
+    }
+}
+
+// Database access for one restaurant's menu schedules.
+pub struct ScheduleRepository<'a> {
+    pool: &'a MySqlPool,
+    restaurant_id: i64,
+}
+
+impl<'a> ScheduleRepository<'a> {
+    pub fn new(pool: &'a MySqlPool, restaurant_id: i64) -> Self {
+        Self { pool, restaurant_id }
+    }
+
+    // Every scheduled item and category, categories first.
+    pub async fn list(&self) -> Result<Vec<ScheduleEntry>> {
+        let rows: Vec<(Option<String>, Option<String>, u8, u32, u32)> = sqlx::query_as(
+            "SELECT m.sku, s.category, s.weekdays, s.start_minute, s.end_minute FROM menu_schedules s \
+             LEFT JOIN menu_items m ON m.id = s.menu_item_id \
+             WHERE s.restaurant_id = ? ORDER BY m.sku IS NOT NULL, s.category, m.sku, s.id",
+        )
+        .bind(self.restaurant_id)
+        .fetch_all(self.pool)
+        .await?;
+        let mut entries: Vec<ScheduleEntry> = Vec::new();
+        for (sku, category, weekdays, start, end) in rows {
+            let window = Slot { weekdays, start, end }.to_window();
+            match entries.last_mut() {
+                Some(entry) if entry.sku == sku && entry.category == category => entry.windows.push(window),
+                _ => entries.push(ScheduleEntry { sku, category, windows: vec![window] }),
+            }
+        }
+        Ok(entries)
+    }
+
+    // Replaces the windows of the item or category `input` names. The affected items'
+    // updated_at is bumped so cached menus revalidate.
+    pub async fn replace(&self, input: &ScheduleInput, audit: &AuditLogger) -> AppResult<ScheduleEntry> {
+        let slots = input
+            .windows
+            .iter()
+            .map(Slot::from_window)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|message| AppError::Validation(vec![FieldError::new("windows", message)]))?;
+        let mut tx = self.pool.begin().await?;
+        let menu_item_id = match &input.sku {
+            Some(sku) => Some(
+                sqlx::query_scalar::<_, i64>("SELECT id FROM menu_items WHERE restaurant_id = ? AND sku = ? FOR UPDATE")
+                    .bind(self.restaurant_id)
+                    .bind(sku)
+                    .fetch_optional(&mut *tx)
+                    .await?
+                    .ok_or_else(|| AppError::NotFound("menu item not found".to_string()))?,
+            ),
+            None => None,
+        };
+        let category = if menu_item_id.is_some() { None } else { input.category.as_deref().map(str::trim) };
+
+        let before: Vec<Window> = sqlx::query_as::<_, (u8, u32, u32)>(
+            "SELECT weekdays, start_minute, end_minute FROM menu_schedules \
+             WHERE restaurant_id = ? AND menu_item_id <=> ? AND category <=> ? ORDER BY id",
+        )
+        .bind(self.restaurant_id)
+        .bind(menu_item_id)
+        .bind(category)
+        .fetch_all(&mut *tx)
+        .await?
+        .into_iter()
+        .map(|(weekdays, start, end)| Slot { weekdays, start, end }.to_window())
+        .collect();
+        sqlx::query("DELETE FROM menu_schedules WHERE restaurant_id = ? AND menu_item_id <=> ? AND category <=> ?")
+            .bind(self.restaurant_id)
+            .bind(menu_item_id)
+            .bind(category)
+            .execute(&mut *tx)
+            .await?;
+        for slot in &slots {
+            sqlx::query(
+                "INSERT INTO menu_schedules (restaurant_id, menu_item_id, category, weekdays, start_minute, end_minute) \
+                 VALUES (?, ?, ?, ?, ?, ?)",
+            )
+            .bind(self.restaurant_id)
+            .bind(menu_item_id)
+            .bind(category)
+            .bind(slot.weekdays)
+            .bind(slot.start)
+            .bind(slot.end)
+            .execute(&mut *tx)
+            .await?;
+        }
+        sqlx::query(
+            "UPDATE menu_items SET updated_at = CURRENT_TIMESTAMP \
+             WHERE restaurant_id = ? AND (id <=> ? OR category <=> ?)",
+        )
+        .bind(self.restaurant_id)
+        .bind(menu_item_id)
+        .bind(category)
+        .execute(&mut *tx)
+        .await?;
+
+        let after: Vec<Window> = slots.iter().map(|slot| slot.to_window()).collect();
+        if before != after {
+            let (target_type, target_id) = match (&input.sku, category) {
+                (Some(sku), _) => ("menu_item", sku.clone()),
+                (None, category) => ("menu_category", category.unwrap_or_default().to_string()),
+            };
+            let event = AuditEvent {
+                action: "menu.schedule",
+                target_type,
+                target_id,
+                restaurant_id: Some(self.restaurant_id),
+                before: Some(json!(before)),
+                after: Some(json!(after)),
+            };
+            audit.record(&mut *tx, event).await?;
+        }
+        tx.commit().await?;
+        Ok(ScheduleEntry {
+            sku: input.sku.clone(),
+            category: category.map(str::to_string),
+            windows: after,
+        })
+    }
+}
+
+// Registers the admin endpoints for menu availability windows.
+pub fn routes(router: Router) -> Router {
+    router
+        .get("/api/admin/menu/schedules", list_schedules)
+        .summary("Availability windows set on menu items and categories")
+        .urgency(6)
+        .requires_tenant_admin()
+        .response_schema(json!({ "type": "array", "items": ScheduleEntry::schema() }))
+        .put("/api/admin/menu/schedules", replace_schedule)
+        .summary("Set the availability windows of one item or category; an empty list removes them")
+        .urgency(6)
+        .requires_tenant_admin()
+        .request_schema(ScheduleInput::schema())
+        .response_schema(ScheduleEntry::schema())
+}
+
+async fn list_schedules(TenantAdmin(restaurant): TenantAdmin, State(services): State) -> AppResult<Response<Bytes>> {
+    let entries = ScheduleRepository::new(services.db()?, restaurant.id).list().await?;
+    ResponseBuilder::json(StatusCode::OK, &entries)
+}
+
+async fn replace_schedule(
+    TenantAdmin(restaurant): TenantAdmin,
+    ValidJson(input): ValidJson<ScheduleInput>,
+    audit: AuditLogger,
+    State(services): State,
+) -> AppResult<Response<Bytes>> {
+    let entry = ScheduleRepository::new(services.db()?, restaurant.id).replace(&input, &audit).await?;
+    ResponseBuilder::json(StatusCode::OK, &entry)
+}
@@ -0,0 +1,228 @@
+use crate::app::AppServices;
+use crate::error::{AppError, AppResult};
+use crate::menu::{MenuItem, MenuRepository};
+use crate::openapi::ApiSchema;
+use crate::request::RequestContext;
+use crate::response::{CachePolicy, ResponseBuilder};
+use crate::router::Router;
+use crate::tenant;
+use anyhow::Result;
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use http::{Response, StatusCode};
+use serde::Serialize;
+use serde_json::{json, Value};
+use sqlx::mysql::MySqlPool;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::{Arc, RwLock};
+
+// Search-as-you-type over item names and categories. Each restaurant's available
+// items are indexed in memory: a sorted word list for prefix matches and a trigram
+// map that still finds "panner" for "paneer". An index is stamped with the menu's
+// last change and rebuilt when a request sees a newer one, so edits made through any
+// instance show up on the next keystroke.
+
+const DEFAULT_LIMIT: usize = 8;
+const MAX_LIMIT: usize = 20;
+const MAX_QUERY_LEN: usize = 50;
+// Share of the query's trigrams a name must contain to count as a fuzzy match.
+const MIN_TRIGRAM_SIMILARITY: f64 = 0.5;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Suggestion {
+    pub text: String,
+    // "item" or "category".
+    pub kind: &'static str,
+    // The item's sku; None for categories.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sku: Option<String>,
+    pub category: String,
+}
+
+impl ApiSchema for Suggestion {
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["text", "kind", "category"],
+            "properties": {
+                "text": { "type": "string" },
+                "kind": { "type": "string", "enum": ["item", "category"] },
+                "sku": { "type": "string" },
+                "category": { "type": "string" }
+            }
+        })
+    }
+}
+
+fn normalize(text: &str) -> String {
+    text.chars()
+        .map(|c| if c.is_alphanumeric() { c.to_ascii_lowercase() } else { ' ' })
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn trigrams(text: &str) -> BTreeSet<String> {
+    // Padded so short words and word starts still produce trigrams.
+    let chars: Vec<char> = format!("  {text} ").chars().collect();
+    chars.windows(3).map(|w| w.iter().collect()).collect()
+}
+
+// One restaurant's suggestions, indexed.
+pub struct SuggestIndex {
+    version: Option<DateTime<Utc>>,
+    entries: Vec<(Suggestion, String)>,
+    // (word, entry) pairs sorted by word, for prefix ranges.
+    words: Vec<(String, usize)>,
+    trigrams: HashMap<String, Vec<usize>>,
+}
+
+impl SuggestIndex {
+    pub fn build(version: Option<DateTime<Utc>>, items: &[MenuItem]) -> Self {
+        let mut entries = Vec::new();
+        let mut categories = BTreeSet::new();
+        for item in items {
+            let suggestion =
+                Suggestion { text: item.name.clone(), kind: "item", sku: Some(item.sku.clone()), category: item.category.clone() };
+            entries.push((suggestion, normalize(&item.name)));
+            categories.insert(item.category.clone());
+        }
+        for category in categories {
+            let normalized = normalize(&category);
+            entries.push((Suggestion { text: category.clone(), kind: "category", sku: None, category }, normalized));
+        }
+
+        let mut words = Vec::new();
+        let mut trigram_map: HashMap<String, Vec<usize>> = HashMap::new();
+        for (i, (_, normalized)) in entries.iter().enumerate() {
+            words.extend(normalized.split(' ').filter(|w| !w.is_empty()).map(|w| (w.to_string(), i)));
+            for trigram in trigrams(normalized) {
+                trigram_map.entry(trigram).or_default().push(i);
+            }
+        }
+        words.sort();
+        Self { version, entries, words, trigrams: trigram_map }
+    }
+
+    // Entries with a word starting with `prefix`.
+    fn with_word_prefix(&self, prefix: &str) -> BTreeSet<usize> {
+        let start = self.words.partition_point(|(word, _)| word.as_str() < prefix);
+        self.words[start..]
+            .iter()
+            .take_while(|(word, _)| word.starts_with(prefix))
+            .map(|(_, i)| *i)
+            .collect()
+    }
+
+    // Up to `limit` suggestions for `query`, best first:
+    //   0  the name is the query
+    //   1  the name starts with the query
+    //   2  every query word starts a word of the name, in any order
+    //   3  the name shares most of the query's trigrams (typos)
+    // Ties go to categories, then shorter names.
+    pub fn suggest(&self, query: &str, limit: usize) -> Vec<Suggestion> {
+        let query = normalize(query);
+        if query.is_empty() {
+            return Vec::new();
+        }
+        let mut ranked: HashMap<usize, u8> = HashMap::new();
+        let mut query_words = query.split(' ');
+        let first = query_words.next().unwrap_or_default();
+        let mut matches = self.with_word_prefix(first);
+        for word in query_words {
+            let next = self.with_word_prefix(word);
+            matches.retain(|i| next.contains(i));
+        }
+        for i in matches {
+            let name = &self.entries[i].1;
+            let rank = if *name == query {
+                0
+            } else if name.starts_with(&query) {
+                1
+            } else {
+                2
+            };
+            ranked.insert(i, rank);
+        }
+
+        if ranked.len() < limit && query.chars().count() >= 3 {
+            let wanted = trigrams(&query);
+            let mut shared: HashMap<usize, usize> = HashMap::new();
+            for trigram in &wanted {
+                for i in self.trigrams.get(trigram).into_iter().flatten() {
+                    *shared.entry(*i).or_default() += 1;
+                }
+            }
+            for (i, count) in shared {
+                if count as f64 / wanted.len() as f64 >= MIN_TRIGRAM_SIMILARITY {
+                    ranked.entry(i).or_insert(3);
+                }
+            }
+        }
+
+        let mut ranked: Vec<(usize, u8)> = ranked.into_iter().collect();
+        ranked.sort_by_key(|&(i, rank)| {
+            let (suggestion, name) = &self.entries[i];
+            (rank, suggestion.kind != "category", name.len(), name.clone())
+        });
+        ranked.into_iter().take(limit).map(|(i, _)| self.entries[i].0.clone()).collect()
+    }
+}
+
+// Per-restaurant indexes, shared by all requests of this instance.
+#[derive(Default)]
+pub struct SuggestCache {
+    indexes: RwLock<HashMap<i64, Arc<SuggestIndex>>>,
+}
+
+impl SuggestCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // The restaurant's index, rebuilt first if its menu changed since it was built.
+    // Concurrent rebuilds of the same menu are harmless; the last one is kept.
+    pub async fn index(&self, pool: &MySqlPool, restaurant_id: i64) -> Result<Arc<SuggestIndex>> {
+        let repository = MenuRepository::new(pool, restaurant_id);
+        let version = repository.last_modified().await?;
+        let cached = self.indexes.read().unwrap_or_else(|p| p.into_inner()).get(&restaurant_id).cloned();
+        if let Some(index) = cached
+            && index.version == version
+        {
+            return Ok(index);
+        }
+        let index = Arc::new(SuggestIndex::build(version, &repository.list_available().await?));
+        self.indexes.write().unwrap_or_else(|p| p.into_inner()).insert(restaurant_id, index.clone());
+        Ok(index)
+    }
+}
+
+// Registers the menu autocomplete endpoint.
+pub fn routes(router: Router) -> Router {
+    router
+        .get("/api/menu/suggest", suggest)
+        .summary("Item and category suggestions for a partly typed search")
+        .cache(CachePolicy::public(60))
+        .query_param("q", true)
+        .query_param("limit", false)
+        .response_schema(json!({ "type": "array", "items": Suggestion::schema() }))
+}
+
+async fn suggest(ctx: RequestContext, services: Arc<AppServices>) -> AppResult<Response<Bytes>> {
+    let restaurant = tenant::current(&ctx, &services).await?;
+    let query = ctx.query_param("q").unwrap_or_default();
+    if query.chars().count() > MAX_QUERY_LEN {
+        return Err(AppError::BadRequest(format!("q must be at most {MAX_QUERY_LEN} characters")));
+    }
+    let limit = match ctx.query_param("limit") {
+        Some(limit) => limit
+            .parse::<usize>()
+            .ok()
+            .filter(|limit| (1..=MAX_LIMIT).contains(limit))
+            .ok_or_else(|| AppError::BadRequest(format!("limit must be between 1 and {MAX_LIMIT}")))?,
+        None => DEFAULT_LIMIT,
+    };
+    let index = services.menu_suggest.index(services.db()?, restaurant.id).await?;
+    ResponseBuilder::json(StatusCode::OK, &index.suggest(query, limit))
+}
@@ -0,0 +1,142 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+// Process-wide counters, exported in Prometheus text format at /metrics.
+#[derive(Default)]
+pub struct Metrics {
+    pub requests_total: AtomicU64,
+    pub handler_panics_total: AtomicU64,
+    pub response_bytes_total: AtomicU64,
+    pub large_responses_total: AtomicU64,
+    pub request_bodies_rejected_total: AtomicU64,
+    pub firewall_blocked_total: AtomicU64,
+    pub shed_requests_total: AtomicU64,
+    pub saturated_requests_total: AtomicU64,
+    pub refused_connections_total: AtomicU64,
+    pub orders_archived_total: AtomicU64,
+    pub db_breaker_trips_total: AtomicU64,
+    pub db_breaker_rejected_total: AtomicU64,
+    pub db_read_retries_total: AtomicU64,
+    pub db_write_retries_total: AtomicU64,
+    pub db_retries_exhausted_total: AtomicU64,
+    pub faults_injected_total: AtomicU64,
+    // Requests per deprecated route, keyed by (method, path pattern).
+    pub deprecated_route_requests: Mutex<BTreeMap<(String, String), u64>>,
+}
+
+pub static METRICS: Metrics = Metrics {
+    requests_total: AtomicU64::new(0),
+    handler_panics_total: AtomicU64::new(0),
+    response_bytes_total: AtomicU64::new(0),
+    large_responses_total: AtomicU64::new(0),
+    request_bodies_rejected_total: AtomicU64::new(0),
+    firewall_blocked_total: AtomicU64::new(0),
+    shed_requests_total: AtomicU64::new(0),
+    saturated_requests_total: AtomicU64::new(0),
+    refused_connections_total: AtomicU64::new(0),
+    orders_archived_total: AtomicU64::new(0),
+    db_breaker_trips_total: AtomicU64::new(0),
+    db_breaker_rejected_total: AtomicU64::new(0),
+    db_read_retries_total: AtomicU64::new(0),
+    db_write_retries_total: AtomicU64::new(0),
+    db_retries_exhausted_total: AtomicU64::new(0),
+    faults_injected_total: AtomicU64::new(0),
+    deprecated_route_requests: Mutex::new(BTreeMap::new()),
+};
+
+impl Metrics {
+    pub fn increment(counter: &AtomicU64) {
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn add(counter: &AtomicU64, amount: u64) {
+        counter.fetch_add(amount, Ordering::Relaxed);
+    }
+
+    // Starts the request count of a deprecated route at zero, so a route nobody calls
+    // any more shows up as such instead of not at all.
+    pub fn register_deprecated_route(&self, method: &str, path: &str) {
+        self.deprecated_requests().entry((method.to_string(), path.to_string())).or_insert(0);
+    }
+
+    pub fn count_deprecated_request(&self, method: &str, path: &str) {
+        *self.deprecated_requests().entry((method.to_string(), path.to_string())).or_insert(0) += 1;
+    }
+
+    fn deprecated_requests(&self) -> std::sync::MutexGuard<'_, BTreeMap<(String, String), u64>> {
+        self.deprecated_route_requests.lock().unwrap_or_else(|p| p.into_inner())
+    }
+
+    // Renders all counters in the Prometheus text exposition format.
+    pub fn render_prometheus(&self) -> String {
+        let mut out = String::new();
+        let counters = [
+            ("rotiride_requests_total", "Requests dispatched by the router", &self.requests_total),
+            ("rotiride_handler_panics_total", "Handlers that panicked", &self.handler_panics_total),
+            ("rotiride_response_bytes_total", "Response body bytes produced", &self.response_bytes_total),
+            ("rotiride_large_responses_total", "Responses above RESPONSE_WARN_BYTES", &self.large_responses_total),
+            (
+                "rotiride_request_bodies_rejected_total",
+                "Request bodies refused for exceeding MAX_REQUEST_BODY_BYTES",
+                &self.request_bodies_rejected_total,
+            ),
+            ("rotiride_firewall_blocked_total", "Requests refused by the firewall", &self.firewall_blocked_total),
+            ("rotiride_shed_requests_total", "Requests shed with 503 under overload", &self.shed_requests_total),
+            (
+                "rotiride_saturated_requests_total",
+                "Requests refused with 503 because the request queue was full",
+                &self.saturated_requests_total,
+            ),
+            (
+                "rotiride_refused_connections_total",
+                "Connections refused at MAX_CONNECTIONS",
+                &self.refused_connections_total,
+            ),
+            ("rotiride_orders_archived_total", "Finished orders moved to the archive", &self.orders_archived_total),
+            (
+                "rotiride_db_breaker_trips_total",
+                "Times the database circuit breaker opened",
+                &self.db_breaker_trips_total,
+            ),
+            (
+                "rotiride_db_breaker_rejected_total",
+                "Requests refused with 503 while the database circuit breaker was open",
+                &self.db_breaker_rejected_total,
+            ),
+            (
+                "rotiride_db_read_retries_total",
+                "Database reads retried after a transient error",
+                &self.db_read_retries_total,
+            ),
+            (
+                "rotiride_db_write_retries_total",
+                "Database transactions retried after a deadlock or lock wait timeout",
+                &self.db_write_retries_total,
+            ),
+            (
+                "rotiride_db_retries_exhausted_total",
+                "Database operations that kept failing transiently until retries ran out",
+                &self.db_retries_exhausted_total,
+            ),
+            (
+                "rotiride_faults_injected_total",
+                "Faults injected by FAULT_INJECTION",
+                &self.faults_injected_total,
+            ),
+        ];
+        for (name, help, value) in counters {
+            let _ = writeln!(out, "# HELP {name} {help}");
+            let _ = writeln!(out, "# TYPE {name} counter");
+            let _ = writeln!(out, "{name} {}", value.load(Ordering::Relaxed));
+        }
+        let name = "rotiride_deprecated_route_requests_total";
+        let _ = writeln!(out, "# HELP {name} Requests to routes marked deprecated, by route");
+        let _ = writeln!(out, "# TYPE {name} counter");
+        for ((method, path), count) in self.deprecated_requests().iter() {
+            let _ = writeln!(out, "{name}{{method=\"{method}\",route=\"{path}\"}} {count}");
+        }
+        out
+    }
+}
@@ -0,0 +1,11 @@
+use crate::app::AppServices;
+use crate::error::AppResult;
+use crate::request::RequestContext;
+use futures::future::BoxFuture;
+
+// Pre-handler hook registered on the Router. Returning an error short-circuits the
+// request; the error is rendered like any handler error.
+pub trait Middleware: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn before<'a>(&'a self, ctx: &'a RequestContext, services: &'a AppServices) -> BoxFuture<'a, AppResult<()>>;
+}
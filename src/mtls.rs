@@ -0,0 +1,116 @@
+use crate::app::AppServices;
+use crate::auth::AdminUserRepository;
+use crate::config;
+use anyhow::{anyhow, Context, Result};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::WebPkiClientVerifier;
+use rustls::RootCertStore;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use x509_parser::extensions::GeneralName;
+use x509_parser::prelude::{FromDer, X509Certificate};
+
+// Internal admin listener that requires client certificates (mTLS):
+//   ADMIN_LISTEN_ADDR        address of the second QUIC listener; unset disables it
+//   ADMIN_CLIENT_CA          PEM bundle of CAs trusted to sign admin client certificates
+//   ADMIN_CLIENT_IDENTITIES  comma-separated identities allowed in; unset allows any
+//                            certificate the CA signed for an admin user
+//
+// Clients without a certificate from a trusted CA fail the TLS handshake, so they never
+// reach HTTP. A verified certificate's identity (subject CN, else its first DNS SAN)
+// must also be the client_identity of an active admin user (`server create-admin
+// --client-identity`), or the connection is closed.
+//
+// The certificate then stands in for that admin's bearer token on every request over
+// the connection, and skips every other admin check: no bearer token, session or TOTP
+// code is asked for, even when the admin enrolled in two-factor authentication. Holding
+// the certificate's private key is all it takes, so issue certificates only to devices
+// the admin alone controls. Deactivating the admin or clearing its
+// client_identity takes effect on the admin's next connection.
+pub struct AdminListener {
+    pub addr: SocketAddr,
+    roots: RootCertStore,
+}
+
+impl AdminListener {
+    pub fn from_env() -> Result<Option<Self>> {
+        let Some(addr) = config::var("ADMIN_LISTEN_ADDR") else {
+            return Ok(None);
+        };
+        let ca = config::var("ADMIN_CLIENT_CA")
+            .ok_or_else(|| anyhow!("ADMIN_CLIENT_CA must be set when ADMIN_LISTEN_ADDR is"))?;
+        let mut roots = RootCertStore::empty();
+        for cert in CertificateDer::pem_file_iter(&ca).with_context(|| format!("reading {ca}"))? {
+            roots.add(cert.with_context(|| format!("parsing {ca}"))?)?;
+        }
+        if roots.is_empty() {
+            return Err(anyhow!("{ca} contains no certificates"));
+        }
+        Ok(Some(Self {
+            addr: addr.parse().with_context(|| format!("ADMIN_LISTEN_ADDR {addr} is not host:port"))?,
+            roots,
+        }))
+    }
+
+    // QUIC server configuration presenting the server's own certificate and demanding a
+    // client certificate signed by one of the trusted CAs.
+    pub fn server_config(
+        &self,
+        cert_chain: Vec<CertificateDer<'static>>,
+        private_key: PrivateKeyDer<'static>,
+    ) -> Result<quinn::ServerConfig> {
+        let verifier = WebPkiClientVerifier::builder(Arc::new(self.roots.clone())).build()?;
+        let mut tls_config = rustls::ServerConfig::builder()
+            .with_client_cert_verifier(verifier)
+            .with_single_cert(cert_chain, private_key)?;
+        tls_config.alpn_protocols = vec![b"h3".to_vec()];
+        Ok(quinn::ServerConfig::with_crypto(Arc::new(
+            quinn::crypto::rustls::QuicServerConfig::try_from(tls_config)?,
+        )))
+    }
+}
+
+// An admin user authenticated by a verified client certificate.
+#[derive(Debug, Clone)]
+pub struct ClientIdentity {
+    // Subject CN, else first DNS SAN, of the certificate.
+    pub name: String,
+    pub admin_user_id: i64,
+}
+
+// The identity of a connection's verified client certificate, if it has one.
+pub fn client_identity(conn: &quinn::Connection) -> Option<String> {
+    let certs = conn.peer_identity()?.downcast::<Vec<CertificateDer<'static>>>().ok()?;
+    certificate_identity(certs.first()?)
+}
+
+// Subject CN of a DER certificate, else its first DNS subjectAltName.
+pub fn certificate_identity(der: &[u8]) -> Option<String> {
+    let (_, cert) = X509Certificate::from_der(der).ok()?;
+    let common_name = cert.subject().iter_common_name().next().and_then(|cn| cn.as_str().ok());
+    if let Some(common_name) = common_name {
+        return Some(common_name.to_string());
+    }
+    let names = cert.subject_alternative_name().ok()??;
+    names.value.general_names.iter().find_map(|name| match name {
+        GeneralName::DNSName(dns) => Some(dns.to_string()),
+        _ => None,
+    })
+}
+
+// The active admin user whose client_identity is `name`. Without a database there are
+// no admin users, so no certificate gets in.
+pub async fn admin_for(name: &str, services: &AppServices) -> Result<Option<ClientIdentity>> {
+    let Some(pool) = &services.db else {
+        return Ok(None);
+    };
+    let admin_user_id = AdminUserRepository::new(pool).by_client_identity(name).await?;
+    Ok(admin_user_id.map(|admin_user_id| ClientIdentity { name: name.to_string(), admin_user_id }))
+}
+
+// Whether ADMIN_CLIENT_IDENTITIES lets `identity` in.
+pub fn identity_allowed(identity: &str) -> bool {
+    config::var("ADMIN_CLIENT_IDENTITIES")
+        .is_none_or(|allowed| allowed.split(',').any(|a| a.trim() == identity))
+}
@@ -0,0 +1,259 @@
+use crate::config;
+use crate::error::{AppError, AppResult};
+use bytes::{Bytes, BytesMut};
+
+// Size limits enforced while a multipart body streams in.
+#[derive(Debug, Clone, Copy)]
+pub struct MultipartLimits {
+    pub max_part_bytes: usize,
+    pub max_total_bytes: usize,
+    pub max_parts: usize,
+}
+
+impl Default for MultipartLimits {
+    fn default() -> Self {
+        Self {
+            max_part_bytes: 10 * 1024 * 1024,
+            max_total_bytes: 25 * 1024 * 1024,
+            max_parts: 100,
+        }
+    }
+}
+
+impl MultipartLimits {
+    // Reads MULTIPART_MAX_PART_BYTES, MULTIPART_MAX_TOTAL_BYTES and MULTIPART_MAX_PARTS,
+    // keeping the defaults for anything unset or unparsable.
+    pub fn from_env() -> Self {
+        let var = |name: &str, default: usize| {
+            config::var(name).and_then(|v| v.parse().ok()).unwrap_or(default)
+        };
+        let defaults = Self::default();
+        Self {
+            max_part_bytes: var("MULTIPART_MAX_PART_BYTES", defaults.max_part_bytes),
+            max_total_bytes: var("MULTIPART_MAX_TOTAL_BYTES", defaults.max_total_bytes),
+            max_parts: var("MULTIPART_MAX_PARTS", defaults.max_parts),
+        }
+    }
+}
+
+// One field or file from a multipart/form-data body.
+#[derive(Debug, Clone)]
+pub struct Part {
+    pub name: String,
+    pub filename: Option<String>,
+    pub content_type: Option<String>,
+    pub data: Bytes,
+}
+
+impl Part {
+    pub fn text(&self) -> AppResult<&str> {
+        std::str::from_utf8(&self.data)
+            .map_err(|_| AppError::BadRequest(format!("multipart field {} is not valid UTF-8", self.name)))
+    }
+}
+
+// Returns the boundary of a multipart/form-data content type, if that is what it is.
+pub fn boundary(content_type: &str) -> Option<String> {
+    let mut params = content_type.split(';');
+    if !params.next()?.trim().eq_ignore_ascii_case("multipart/form-data") {
+        return None;
+    }
+    params.find_map(|param| {
+        let (key, value) = param.split_once('=')?;
+        key.trim()
+            .eq_ignore_ascii_case("boundary")
+            .then(|| value.trim().trim_matches('"').to_string())
+            .filter(|b| !b.is_empty())
+    })
+}
+
+enum State {
+    // Before the first boundary line.
+    Preamble,
+    // Just past a boundary: "--" ends the body, CRLF starts another part.
+    Boundary,
+    // Reading a part's header block.
+    Headers,
+    // Reading a part's body until the next boundary.
+    Body,
+    // After the closing boundary; anything further is ignored.
+    Done,
+}
+
+// Incremental multipart/form-data parser. Chunks are fed as they arrive from the
+// stream and limits are checked per chunk, so an oversized upload is rejected
+// without first buffering all of it.
+pub struct MultipartParser {
+    // "\r\n--<boundary>"; the first boundary is matched without the leading CRLF.
+    delimiter: Vec<u8>,
+    limits: MultipartLimits,
+    state: State,
+    buffer: BytesMut,
+    received: usize,
+    current: Option<Part>,
+    current_data: BytesMut,
+    parts: Vec<Part>,
+}
+
+impl MultipartParser {
+    pub fn new(boundary: &str, limits: MultipartLimits) -> Self {
+        Self {
+            delimiter: format!("\r\n--{boundary}").into_bytes(),
+            limits,
+            state: State::Preamble,
+            buffer: BytesMut::new(),
+            received: 0,
+            current: None,
+            current_data: BytesMut::new(),
+            parts: Vec::new(),
+        }
+    }
+
+    // Consumes the next chunk of the body.
+    pub fn feed(&mut self, chunk: &[u8]) -> AppResult<()> {
+        self.received += chunk.len();
+        if self.received > self.limits.max_total_bytes {
+            return Err(AppError::PayloadTooLarge(format!(
+                "multipart body exceeds {} bytes",
+                self.limits.max_total_bytes
+            )));
+        }
+        if matches!(self.state, State::Done) {
+            return Ok(());
+        }
+        self.buffer.extend_from_slice(chunk);
+        while self.step()? {}
+        Ok(())
+    }
+
+    // Finishes parsing once the stream has ended.
+    pub fn finish(self) -> AppResult<Vec<Part>> {
+        match self.state {
+            State::Done => Ok(self.parts),
+            _ => Err(AppError::BadRequest("multipart body ended before the closing boundary".into())),
+        }
+    }
+
+    // Advances the state machine; returns false when more input is needed.
+    fn step(&mut self) -> AppResult<bool> {
+        match self.state {
+            State::Preamble => {
+                let opening = &self.delimiter[2..];
+                let Some(pos) = find(&self.buffer, opening) else {
+                    // Keep only enough of the preamble to match a boundary split across chunks.
+                    let keep = self.buffer.len().min(opening.len());
+                    let _ = self.buffer.split_to(self.buffer.len() - keep);
+                    return Ok(false);
+                };
+                let _ = self.buffer.split_to(pos + opening.len());
+                self.state = State::Boundary;
+                Ok(true)
+            }
+            State::Boundary => self.after_boundary(),
+            State::Headers => {
+                let Some(pos) = find(&self.buffer, b"\r\n\r\n") else {
+                    if self.buffer.len() > 8 * 1024 {
+                        return Err(AppError::BadRequest("multipart part headers are too large".into()));
+                    }
+                    return Ok(false);
+                };
+                let head = self.buffer.split_to(pos + 4);
+                let head = std::str::from_utf8(&head[..pos])
+                    .map_err(|_| AppError::BadRequest("multipart part headers are not valid UTF-8".into()))?;
+                self.current = Some(parse_part_headers(head)?);
+                self.state = State::Body;
+                Ok(true)
+            }
+            State::Body => {
+                let found = find(&self.buffer, &self.delimiter);
+                // Without a delimiter, everything but a possible partial delimiter is part data.
+                let take = found.unwrap_or_else(|| self.buffer.len().saturating_sub(self.delimiter.len() - 1));
+                let data = self.buffer.split_to(take);
+                self.append_data(&data)?;
+                let Some(_) = found else {
+                    return Ok(false);
+                };
+                let _ = self.buffer.split_to(self.delimiter.len());
+                let mut part = self.current.take().expect("part headers parsed before body");
+                part.data = std::mem::take(&mut self.current_data).freeze();
+                self.parts.push(part);
+                self.state = State::Boundary;
+                Ok(true)
+            }
+            State::Done => Ok(false),
+        }
+    }
+
+    fn after_boundary(&mut self) -> AppResult<bool> {
+        if self.buffer.len() < 2 {
+            return Ok(false);
+        }
+        if self.buffer.starts_with(b"--") {
+            self.state = State::Done;
+            self.buffer.clear();
+            return Ok(false);
+        }
+        if !self.buffer.starts_with(b"\r\n") {
+            return Err(AppError::BadRequest("malformed multipart boundary".into()));
+        }
+        if self.parts.len() >= self.limits.max_parts {
+            return Err(AppError::PayloadTooLarge(format!(
+                "multipart body has more than {} parts",
+                self.limits.max_parts
+            )));
+        }
+        let _ = self.buffer.split_to(2);
+        self.state = State::Headers;
+        Ok(true)
+    }
+
+    fn append_data(&mut self, data: &[u8]) -> AppResult<()> {
+        if self.current_data.len() + data.len() > self.limits.max_part_bytes {
+            let name = self.current.as_ref().map_or("", |part| part.name.as_str());
+            return Err(AppError::PayloadTooLarge(format!(
+                "multipart field {name} exceeds {} bytes",
+                self.limits.max_part_bytes
+            )));
+        }
+        self.current_data.extend_from_slice(data);
+        Ok(())
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
+
+// Parses Content-Disposition and Content-Type from a part's header block.
+fn parse_part_headers(head: &str) -> AppResult<Part> {
+    let mut name = None;
+    let mut filename = None;
+    let mut content_type = None;
+    for line in head.split("\r\n") {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        if key.trim().eq_ignore_ascii_case("content-disposition") {
+            for param in value.split(';').skip(1) {
+                let Some((key, value)) = param.split_once('=') else {
+                    continue;
+                };
+                let value = value.trim().trim_matches('"').to_string();
+                match key.trim() {
+                    "name" => name = Some(value),
+                    "filename" => filename = Some(value),
+                    _ => {}
+                }
+            }
+        } else if key.trim().eq_ignore_ascii_case("content-type") {
+            content_type = Some(value.trim().to_string());
+        }
+    }
+    let name = name.ok_or_else(|| AppError::BadRequest("multipart part is missing a field name".into()))?;
+    Ok(Part {
+        name,
+        filename,
+        content_type,
+        data: Bytes::new(),
+    })
+}
@@ -0,0 +1,151 @@
+use crate::response::CachePolicy;
+use crate::router::{AuthRequirement, Route, Router};
+use bytes::Bytes;
+use http::{Response, StatusCode};
+use serde_json::{json, Map, Value};
+
+// Types that can describe themselves as a JSON Schema for the API docs.
+// Implemented by hand next to each model so the schema sits beside the serde attributes.
+pub trait ApiSchema {
+    fn schema() -> Value;
+}
+
+// Builds an OpenAPI 3.1 document from the router's registered routes.
+pub fn generate(router: &Router, title: &str, version: &str) -> Value {
+    let mut paths = Map::new();
+    for route in router.routes() {
+        let item = paths
+            .entry(openapi_path(&route.path))
+            .or_insert_with(|| Value::Object(Map::new()));
+        if let Value::Object(item) = item {
+            item.insert(route.method.as_str().to_lowercase(), operation(route));
+        }
+    }
+
+    json!({
+        "openapi": "3.1.0",
+        "info": { "title": title, "version": version },
+        "paths": paths,
+        "components": {
+            "securitySchemes": {
+                "adminToken": { "type": "http", "scheme": "bearer" }
+            }
+        }
+    })
+}
+
+// Converts ":id" captures into OpenAPI "{id}" templates.
+fn openapi_path(path: &str) -> String {
+    path.split('/')
+        .map(|segment| match segment.strip_prefix(':') {
+            Some(name) => format!("{{{name}}}"),
+            None => segment.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+// Describes a single route as an OpenAPI operation object.
+fn operation(route: &Route) -> Value {
+    let mut parameters: Vec<Value> = route
+        .param_names()
+        .map(|name| json!({ "name": name, "in": "path", "required": true, "schema": { "type": "string" } }))
+        .collect();
+    parameters.extend(route.doc.query_params.iter().map(|(name, required)| {
+        json!({ "name": name, "in": "query", "required": required, "schema": { "type": "string" } })
+    }));
+
+    let success = match &route.doc.response_schema {
+        Some(schema) => json!({
+            "description": "Success",
+            "content": { "application/json": { "schema": schema } }
+        }),
+        None => json!({ "description": "Success" }),
+    };
+
+    let mut op = json!({
+        "operationId": operation_id(route),
+        "parameters": parameters,
+        "responses": { "200": success },
+    });
+    if let Some(summary) = &route.doc.summary {
+        op["summary"] = json!(summary);
+    }
+    if let Some(schema) = &route.doc.request_schema {
+        op["requestBody"] = json!({
+            "required": true,
+            "content": { "application/json": { "schema": schema.as_ref() } }
+        });
+    }
+    if let Some(deprecation) = &route.doc.deprecation {
+        op["deprecated"] = json!(true);
+        if let Some(sunset) = deprecation.sunset {
+            op["description"] = json!(format!("Deprecated; removed after {}.", sunset.format("%Y-%m-%d")));
+        }
+    }
+    if route.doc.auth == AuthRequirement::Admin {
+        op["security"] = json!([{ "adminToken": [] }]);
+        op["responses"]["401"] = json!({ "description": "Missing bearer token" });
+        op["responses"]["403"] = json!({ "description": "Not an admin" });
+    }
+    op
+}
+
+// Stable operation id, e.g. "get_api_admin_zones_id".
+pub fn operation_id(route: &Route) -> String {
+    let path: Vec<&str> = route
+        .path
+        .split('/')
+        .filter(|s| !s.is_empty())
+        .map(|s| s.trim_start_matches(':'))
+        .collect();
+    let path = if path.is_empty() { "root".to_string() } else { path.join("_") };
+    format!("{}_{}", route.method.as_str().to_lowercase(), path)
+}
+
+// Swagger UI page pointing at the generated spec.
+const SWAGGER_UI_HTML: &str = r##"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta charset="utf-8" />
+  <title>RotiRide API</title>
+  <link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@5/swagger-ui.css" />
+</head>
+<body>
+  <div id="swagger-ui"></div>
+  <script src="https://unpkg.com/swagger-ui-dist@5/swagger-ui-bundle.js"></script>
+  <script>
+    window.ui = SwaggerUIBundle({ url: "/api/openapi.json", dom_id: "#swagger-ui" });
+  </script>
+</body>
+</html>
+"##;
+
+// Registers /api/openapi.json and the /api/docs Swagger UI page.
+// Call this last so the spec covers every route registered before it.
+pub fn routes(router: Router) -> Router {
+    let spec = generate(&router, "RotiRide API", env!("CARGO_PKG_VERSION"));
+    let spec = Bytes::from(serde_json::to_vec(&spec).unwrap_or_default());
+
+    router
+        .get("/api/openapi.json", move || {
+            let spec = spec.clone();
+            async move {
+                Ok(Response::builder()
+                    .status(StatusCode::OK)
+                    .header("content-type", "application/json")
+                    .body(spec)?)
+            }
+        })
+        .summary("OpenAPI document for this server")
+        .cache(CachePolicy::public(300))
+        .get("/api/docs", || async {
+            Ok(Response::builder()
+                .status(StatusCode::OK)
+                .header("content-type", "text/html; charset=utf-8")
+                .body(Bytes::from_static(SWAGGER_UI_HTML.as_bytes()))?)
+        })
+        .summary("Swagger UI")
+        .cache(CachePolicy::public(3600))
+}
+
@@ -0,0 +1,85 @@
+use crate::logging;
+use crate::metrics::{Metrics, METRICS};
+use crate::runtime_config::ConfigService;
+use anyhow::Result;
+use serde_json::json;
+use sqlx::mysql::MySqlPool;
+use std::sync::Arc;
+use std::time::Duration;
+
+// Archiving of old orders. Finished orders (served, delivered or cancelled) older
+// than runtime config order_archive_after_months have their lines moved from
+// order_items to archived_order_items, keeping the live table small for the kitchen
+// and guest queries. The orders row stays as a stub with archived_at set, so order
+// ids, tickets and event history still point somewhere, and OrderRepository reads an
+// archived order's lines from the archive without callers noticing.
+
+const BATCH: i64 = 500;
+const SWEEP_INTERVAL: Duration = Duration::from_secs(3600);
+
+// Archives one batch of due orders. Rows being archived by another instance are
+// skipped rather than waited for. Returns the number of orders archived.
+pub async fn archive_batch(pool: &MySqlPool, after_months: u32) -> Result<usize> {
+    let mut tx = pool.begin().await?;
+    let ids: Vec<i64> = sqlx::query_scalar(
+        "SELECT id FROM orders WHERE archived_at IS NULL AND status IN ('served', 'delivered', 'cancelled') \
+         AND created_at < NOW() - INTERVAL ? MONTH ORDER BY id LIMIT ? FOR UPDATE SKIP LOCKED",
+    )
+    .bind(after_months)
+    .bind(BATCH)
+    .fetch_all(&mut *tx)
+    .await?;
+    if ids.is_empty() {
+        return Ok(0);
+    }
+    let placeholders = vec!["?"; ids.len()].join(", ");
+    let statements = [
+        format!(
+            "INSERT INTO archived_order_items (id, order_id, menu_item_id, sku, name, unit_price_paise, quantity, options) \
+             SELECT id, order_id, menu_item_id, sku, name, unit_price_paise, quantity, options FROM order_items \
+             WHERE order_id IN ({placeholders})"
+        ),
+        format!("DELETE FROM order_items WHERE order_id IN ({placeholders})"),
+        format!("UPDATE orders SET archived_at = NOW() WHERE id IN ({placeholders})"),
+    ];
+    for sql in &statements {
+        let mut query = sqlx::query(sql);
+        for id in &ids {
+            query = query.bind(id);
+        }
+        query.execute(&mut *tx).await?;
+    }
+    tx.commit().await?;
+    Metrics::add(&METRICS.orders_archived_total, ids.len() as u64);
+    Ok(ids.len())
+}
+
+// Archives everything due, batch by batch. Does nothing while archiving is off.
+pub async fn sweep(pool: &MySqlPool, config: &ConfigService) -> Result<usize> {
+    let after_months = config.order_archive_after_months();
+    if after_months == 0 {
+        return Ok(0);
+    }
+    let mut archived = 0;
+    loop {
+        let batch = archive_batch(pool, after_months).await?;
+        archived += batch;
+        if (batch as i64) < BATCH {
+            return Ok(archived);
+        }
+    }
+}
+
+// Background archiver, hourly.
+pub fn spawn_archiver(pool: MySqlPool, config: Arc<ConfigService>) {
+    tokio::spawn(async move {
+        loop {
+            match sweep(&pool, &config).await {
+                Ok(0) => {}
+                Ok(archived) => logging::info("orders archived", json!({ "archived": archived })),
+                Err(err) => logging::warn("order archiving failed", json!({ "error": format!("{err:#}") })),
+            }
+            tokio::time::sleep(SWEEP_INTERVAL).await;
+        }
+    });
+}
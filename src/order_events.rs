@@ -0,0 +1,354 @@
+use crate::audit::{AuditEvent, AuditLogger};
+use crate::error::{AppError, AppResult};
+use crate::extract::{Path, State, TenantAdmin};
+use crate::openapi::ApiSchema;
+use crate::orders::{self, Order, OrderItem, OrderRepository};
+use crate::response::ResponseBuilder;
+use crate::router::Router;
+use crate::webhooks;
+use anyhow::{anyhow, Result};
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use http::{Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sqlx::mysql::{MySql, MySqlPool};
+use sqlx::types::Json;
+use sqlx::Transaction;
+
+// Event history of orders. Every change to an order appends an event in the same
+// transaction as the change itself, numbered per order, and forwards it to webhook
+// subscribers of order.event. The orders and order_items rows stay the read model
+// that handlers query; replay() folds the events back into it, which the admin
+// rebuild endpoint uses to check or repair an order. Orders placed before events
+// were recorded have no history and can't be rebuilt.
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OrderEvent {
+    Placed {
+        fulfillment: String,
+        table_id: Option<i64>,
+        status: String,
+        subtotal_paise: i64,
+        tax_paise: i64,
+        delivery_fee_paise: i64,
+        total_paise: i64,
+        notes: String,
+        items: Vec<OrderItem>,
+    },
+    ItemsReplaced {
+        subtotal_paise: i64,
+        tax_paise: i64,
+        total_paise: i64,
+        items: Vec<OrderItem>,
+    },
+    StatusChanged {
+        from: String,
+        to: String,
+    },
+}
+
+impl OrderEvent {
+    pub fn event_type(&self) -> &'static str {
+        match self {
+            OrderEvent::Placed { .. } => "placed",
+            OrderEvent::ItemsReplaced { .. } => "items_replaced",
+            OrderEvent::StatusChanged { .. } => "status_changed",
+        }
+    }
+}
+
+// An event as stored.
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct StoredEvent {
+    pub sequence: u32,
+    pub actor: String,
+    pub created_at: DateTime<Utc>,
+    #[sqlx(json)]
+    pub payload: OrderEvent,
+}
+
+impl ApiSchema for StoredEvent {
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["sequence", "actor", "created_at", "payload"],
+            "properties": {
+                "sequence": { "type": "integer", "minimum": 1 },
+                "actor": { "type": "string" },
+                "created_at": { "type": "string", "format": "date-time" },
+                "payload": {
+                    "type": "object",
+                    "required": ["type"],
+                    "properties": {
+                        "type": { "type": "string", "enum": ["placed", "items_replaced", "status_changed"] }
+                    }
+                }
+            }
+        })
+    }
+}
+
+// Appends `event` to the order's history inside the caller's transaction, and
+// forwards it as an order.event webhook. The caller must have written (and so locked)
+// the order's row in `tx`, which keeps sequences of one order from racing.
+pub async fn append(
+    tx: &mut Transaction<'_, MySql>,
+    order_id: i64,
+    restaurant_id: i64,
+    actor: &str,
+    event: &OrderEvent,
+) -> Result<u32> {
+    // Sequences start at 1 and have no gaps, so the count is the last one.
+    let recorded: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM order_events WHERE order_id = ?")
+        .bind(order_id)
+        .fetch_one(&mut **tx)
+        .await?;
+    let sequence = u32::try_from(recorded + 1)?;
+    sqlx::query(
+        "INSERT INTO order_events (order_id, restaurant_id, sequence, event_type, payload, actor) \
+         VALUES (?, ?, ?, ?, ?, ?)",
+    )
+    .bind(order_id)
+    .bind(restaurant_id)
+    .bind(sequence)
+    .bind(event.event_type())
+    .bind(Json(event))
+    .bind(actor)
+    .execute(&mut **tx)
+    .await?;
+    let payload = json!({
+        "order_id": order_id,
+        "restaurant_id": restaurant_id,
+        "sequence": sequence,
+        "actor": actor,
+        "event": event,
+    });
+    webhooks::enqueue(&mut **tx, restaurant_id, "order.event", &payload).await?;
+    Ok(sequence)
+}
+
+pub async fn load<'e, E>(executor: E, order_id: i64) -> Result<Vec<StoredEvent>>
+where
+    E: sqlx::Executor<'e, Database = MySql>,
+{
+    Ok(sqlx::query_as(
+        "SELECT sequence, actor, created_at, payload FROM order_events WHERE order_id = ? ORDER BY sequence",
+    )
+    .bind(order_id)
+    .fetch_all(executor)
+    .await?)
+}
+
+// The read-model fields events determine.
+#[derive(Debug, Clone, Serialize)]
+pub struct OrderState {
+    pub fulfillment: String,
+    pub table_id: Option<i64>,
+    pub status: String,
+    pub subtotal_paise: i64,
+    pub tax_paise: i64,
+    pub delivery_fee_paise: i64,
+    pub total_paise: i64,
+    pub notes: String,
+    pub version: i32,
+    pub items: Vec<OrderItem>,
+}
+
+impl OrderState {
+    pub fn of(order: &Order) -> Self {
+        Self {
+            fulfillment: order.fulfillment.clone(),
+            table_id: order.table_id,
+            status: order.status.clone(),
+            subtotal_paise: order.subtotal_paise,
+            tax_paise: order.tax_paise,
+            delivery_fee_paise: order.delivery_fee_paise,
+            total_paise: order.total_paise,
+            notes: order.notes.clone(),
+            version: order.version,
+            items: order.items.clone(),
+        }
+    }
+
+    // Whether the two agree on everything but display data: lines are compared by
+    // sku, quantity, unit price and options, not by name or allergens.
+    pub fn matches(&self, other: &OrderState) -> bool {
+        let key = |s: &OrderState| {
+            (
+                s.fulfillment.clone(),
+                s.table_id,
+                s.status.clone(),
+                (s.subtotal_paise, s.tax_paise, s.delivery_fee_paise, s.total_paise),
+                s.notes.clone(),
+                s.version,
+                lines_of(&s.items),
+            )
+        };
+        key(self) == key(other)
+    }
+}
+
+fn lines_of(items: &[OrderItem]) -> Vec<(String, i32, i64, Vec<i64>)> {
+    items
+        .iter()
+        .map(|item| {
+            let options = item.options.iter().map(|option| option.option_id).collect();
+            (item.sku.clone(), item.quantity, item.unit_price_paise, options)
+        })
+        .collect()
+}
+
+// Folds an order's events, in sequence order, into its state. Errors when the
+// history doesn't start with Placed or has a gap.
+pub fn replay(events: &[StoredEvent]) -> Result<OrderState> {
+    let mut state: Option<OrderState> = None;
+    for (expected, stored) in (1..).zip(events) {
+        if stored.sequence != expected {
+            return Err(anyhow!("event {expected} is missing"));
+        }
+        match (&mut state, &stored.payload) {
+            (
+                None,
+                OrderEvent::Placed {
+                    fulfillment,
+                    table_id,
+                    status,
+                    subtotal_paise,
+                    tax_paise,
+                    delivery_fee_paise,
+                    total_paise,
+                    notes,
+                    items,
+                },
+            ) => {
+                state = Some(OrderState {
+                    fulfillment: fulfillment.clone(),
+                    table_id: *table_id,
+                    status: status.clone(),
+                    subtotal_paise: *subtotal_paise,
+                    tax_paise: *tax_paise,
+                    delivery_fee_paise: *delivery_fee_paise,
+                    total_paise: *total_paise,
+                    notes: notes.clone(),
+                    version: 0,
+                    items: items.clone(),
+                });
+            }
+            (Some(order), OrderEvent::ItemsReplaced { subtotal_paise, tax_paise, total_paise, items }) => {
+                order.subtotal_paise = *subtotal_paise;
+                order.tax_paise = *tax_paise;
+                order.total_paise = *total_paise;
+                order.items = items.clone();
+                order.version += 1;
+            }
+            (Some(order), OrderEvent::StatusChanged { from, to }) => {
+                if order.status != *from {
+                    return Err(anyhow!("event {expected} moves from {from} but the order is {}", order.status));
+                }
+                order.status = to.clone();
+            }
+            (None, _) => return Err(anyhow!("history does not start with placed")),
+            (Some(_), OrderEvent::Placed { .. }) => return Err(anyhow!("event {expected} places the order again")),
+        }
+    }
+    state.ok_or_else(|| anyhow!("no events recorded"))
+}
+
+// Result of POST /api/admin/orders/:id/rebuild.
+#[derive(Debug, Serialize)]
+pub struct RebuildReport {
+    pub order_id: i64,
+    pub events: usize,
+    // Whether the stored order differed from its history and was rewritten.
+    pub repaired: bool,
+    pub before: OrderState,
+    pub after: OrderState,
+}
+
+// Rewrites an order's row and lines from its events, if they differ.
+pub async fn rebuild(pool: &MySqlPool, restaurant_id: i64, order_id: i64, audit: &AuditLogger) -> AppResult<RebuildReport> {
+    let mut tx = pool.begin().await?;
+    let order = orders::lock(&mut tx, restaurant_id, order_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("order not found".to_string()))?;
+    if order.archived_at.is_some() {
+        return Err(AppError::Conflict("archived orders are final and can't be rebuilt".to_string()));
+    }
+    let events = load(&mut *tx, order_id).await?;
+    let after = replay(&events).map_err(|err| AppError::Conflict(format!("cannot rebuild order: {err}")))?;
+    let before = OrderState::of(&order);
+    let repaired = !before.matches(&after);
+    if repaired {
+        sqlx::query(
+            "UPDATE orders SET fulfillment = ?, table_id = ?, status = ?, subtotal_paise = ?, tax_paise = ?, \
+             delivery_fee_paise = ?, total_paise = ?, notes = ?, version = ? WHERE id = ?",
+        )
+        .bind(&after.fulfillment)
+        .bind(after.table_id)
+        .bind(&after.status)
+        .bind(after.subtotal_paise)
+        .bind(after.tax_paise)
+        .bind(after.delivery_fee_paise)
+        .bind(after.total_paise)
+        .bind(&after.notes)
+        .bind(after.version)
+        .bind(order_id)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query("DELETE FROM order_items WHERE order_id = ?")
+            .bind(order_id)
+            .execute(&mut *tx)
+            .await?;
+        orders::insert_lines(&mut tx, order_id, &after.items).await?;
+        let event = AuditEvent {
+            action: "order.rebuild",
+            target_type: "order",
+            target_id: order_id.to_string(),
+            restaurant_id: Some(restaurant_id),
+            before: Some(json!(before)),
+            after: Some(json!(after)),
+        };
+        audit.record(&mut *tx, event).await?;
+    }
+    tx.commit().await?;
+    Ok(RebuildReport { order_id, events: events.len(), repaired, before, after })
+}
+
+// Registers the admin order history endpoints.
+pub fn routes(router: Router) -> Router {
+    router
+        .get("/api/admin/orders/:id/events", order_history)
+        .summary("Every recorded change to an order, oldest first")
+        .urgency(6)
+        .requires_tenant_admin()
+        .response_schema(json!({ "type": "array", "items": StoredEvent::schema() }))
+        .post("/api/admin/orders/:id/rebuild", rebuild_order)
+        .summary("Rebuild an order from its event history, repairing the stored order if it differs")
+        .urgency(6)
+        .requires_tenant_admin()
+}
+
+async fn order_history(
+    TenantAdmin(restaurant): TenantAdmin,
+    Path(id): Path<i64>,
+    State(services): State,
+) -> AppResult<Response<Bytes>> {
+    let pool = services.db()?;
+    OrderRepository::new(pool, restaurant.id)
+        .get(id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("order not found".to_string()))?;
+    ResponseBuilder::json(StatusCode::OK, &load(pool, id).await?)
+}
+
+async fn rebuild_order(
+    TenantAdmin(restaurant): TenantAdmin,
+    Path(id): Path<i64>,
+    audit: AuditLogger,
+    State(services): State,
+) -> AppResult<Response<Bytes>> {
+    let report = rebuild(services.db()?, restaurant.id, id, &audit).await?;
+    ResponseBuilder::json(StatusCode::OK, &report)
+}
@@ -0,0 +1,1144 @@
+use crate::allergens;
+use crate::app::AppServices;
+use crate::audit::{AuditEvent, AuditLogger};
+use crate::db_retry::{self, Access};
+use crate::error::{AppError, AppResult, FieldError};
+use crate::extract::{Json as JsonBody, Language, Path, Query, State, TenantAdmin};
+use crate::eta::{EtaEstimate, EtaEstimator, EtaInputs, OrderStage};
+use crate::i18n;
+use crate::item_options::{self, ChosenOption};
+use crate::kitchen_queue;
+use crate::menu_schedule;
+use crate::openapi::ApiSchema;
+use crate::order_events::{self, OrderEvent};
+use crate::response::ResponseBuilder;
+use crate::router::Router;
+use crate::runtime_config::ConfigService;
+use crate::tables::{DiningTable, DiningTableRepository};
+use crate::webhooks;
+use anyhow::Result;
+use bytes::Bytes;
+use chrono::{DateTime, FixedOffset, Utc};
+use http::{Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sqlx::mysql::{MySql, MySqlPool};
+use sqlx::types::Json;
+use sqlx::Transaction;
+use std::collections::HashMap;
+
+pub const FULFILLMENT_DELIVERY: &str = "delivery";
+pub const FULFILLMENT_DINE_IN: &str = "dine_in";
+
+pub const STATUSES: [&str; 8] = [
+    "queued",
+    "placed",
+    "preparing",
+    "ready",
+    "served",
+    "out_for_delivery",
+    "delivered",
+    "cancelled",
+];
+
+// Status moves allowed for each fulfillment. Dine-in orders never see a courier: the
+// kitchen marks them ready and a waiter marks them served. Orders can be cancelled
+// until the kitchen has finished them. Queued orders only become placed through the
+// kitchen queue (kitchen_queue.rs), never by hand.
+pub fn can_transition(fulfillment: &str, from: &str, to: &str) -> bool {
+    matches!(
+        (fulfillment, from, to),
+        (_, "placed", "preparing")
+            | (_, "preparing", "ready")
+            | (_, "queued" | "placed" | "preparing", "cancelled")
+            | (FULFILLMENT_DINE_IN, "ready", "served")
+            | (FULFILLMENT_DELIVERY, "ready", "out_for_delivery")
+            | (FULFILLMENT_DELIVERY, "out_for_delivery", "delivered")
+    )
+}
+
+// The webhook event a status change emits, if partners care about it.
+fn status_event(status: &str) -> Option<&'static str> {
+    match status {
+        "served" => Some("order.served"),
+        "delivered" => Some("order.delivered"),
+        "cancelled" => Some("order.cancelled"),
+        _ => None,
+    }
+}
+
+// One line of an order, priced when the order was placed.
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct OrderItem {
+    pub menu_item_id: i64,
+    pub sku: String,
+    pub name: String,
+    // The item's price plus the deltas of its options.
+    pub unit_price_paise: i64,
+    pub quantity: i32,
+    #[sqlx(json)]
+    pub options: Vec<ChosenOption>,
+    // The item's allergen codes as the menu declares them now, so the kitchen is warned
+    // on every line that contains one.
+    #[sqlx(skip)]
+    #[serde(default)]
+    pub allergens: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct Order {
+    pub id: i64,
+    pub restaurant_id: i64,
+    pub fulfillment: String,
+    pub table_id: Option<i64>,
+    pub status: String,
+    pub subtotal_paise: i64,
+    pub tax_paise: i64,
+    pub delivery_fee_paise: i64,
+    pub total_paise: i64,
+    pub notes: String,
+    // Bumped whenever the lines change; PATCH /api/orders/:id/items must send the
+    // version it was based on.
+    pub version: i32,
+    pub created_at: DateTime<Utc>,
+    // Last change to the row. Lines can only change before the kitchen starts, so once
+    // it has, this is when the order entered its current status.
+    #[serde(skip)]
+    pub updated_at: DateTime<Utc>,
+    // Set once the order is archived (order_archive.rs); its lines are then read from
+    // the archive.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub archived_at: Option<DateTime<Utc>>,
+    #[sqlx(skip)]
+    pub items: Vec<OrderItem>,
+    // For queued orders: 1 for the next to be admitted, and the minutes until the
+    // kitchen is expected to take it (filled in by estimate()).
+    #[sqlx(skip)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub queue_position: Option<u32>,
+    #[sqlx(skip)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub estimated_wait_minutes: Option<u32>,
+    // When the order should be served or delivered, until it is finished.
+    #[sqlx(skip)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub eta: Option<EtaEstimate>,
+    // Display labels in the request's language, filled in by localize().
+    #[sqlx(skip)]
+    pub status_label: String,
+    #[sqlx(skip)]
+    pub fulfillment_label: String,
+}
+
+impl Order {
+    // Fills in the queue wait and the ETA as of `now`. The queue position is the number
+    // of orders the kitchen must take before this one. The menu records no preparation
+    // times, so the kitchen's average minutes per order stands in for them, and only
+    // dine-in orders exist, so there is no delivery leg.
+    pub fn estimate(mut self, config: &ConfigService, now: DateTime<Utc>) -> Self {
+        let estimator = EtaEstimator::from_config(config);
+        self.estimated_wait_minutes = self
+            .queue_position
+            .map(|position| estimator.queue_wait_minutes(position).ceil() as u32);
+        self.eta = OrderStage::from_status(&self.status).map(|stage| {
+            let inputs = EtaInputs {
+                stage,
+                item_preparation_minutes: Vec::new(),
+                kitchen_queue_depth: self.queue_position.unwrap_or(0),
+                delivery: None,
+                stage_started_at: self.updated_at,
+            };
+            estimator.estimate(&inputs, now)
+        });
+        self
+    }
+
+    pub fn localize(mut self, lang: &str) -> Self {
+        self.status_label = i18n::label(lang, "order_status", &self.status);
+        self.fulfillment_label = i18n::label(lang, "fulfillment", &self.fulfillment);
+        self
+    }
+}
+
+impl ApiSchema for Order {
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["id", "restaurant_id", "fulfillment", "status", "subtotal_paise", "tax_paise",
+                         "delivery_fee_paise", "total_paise", "notes", "version", "created_at", "items"],
+            "properties": {
+                "id": { "type": "integer" },
+                "restaurant_id": { "type": "integer" },
+                "fulfillment": { "type": "string", "enum": [FULFILLMENT_DELIVERY, FULFILLMENT_DINE_IN] },
+                "table_id": { "type": ["integer", "null"] },
+                "status": { "type": "string", "enum": STATUSES },
+                "status_label": { "type": "string", "description": "Status in the Accept-Language language" },
+                "fulfillment_label": { "type": "string" },
+                "subtotal_paise": { "type": "integer" },
+                "tax_paise": { "type": "integer" },
+                "delivery_fee_paise": { "type": "integer" },
+                "total_paise": { "type": "integer" },
+                "notes": { "type": "string" },
+                "version": { "type": "integer" },
+                "queue_position": { "type": "integer", "description": "Only while the order is queued" },
+                "estimated_wait_minutes": { "type": "integer", "description": "Minutes until the kitchen takes a queued order" },
+                "eta": {
+                    "type": "object",
+                    "description": "Until the order is served, delivered or cancelled",
+                    "properties": {
+                        "estimated_delivery_time": { "type": "string", "format": "date-time" },
+                        "queue_wait_minutes": { "type": "integer" },
+                        "preparation_minutes": { "type": "integer" },
+                        "travel_minutes": { "type": "integer" },
+                        "stage": {
+                            "type": "string",
+                            "enum": ["placed", "preparing", "ready_for_pickup", "out_for_delivery", "delivered"]
+                        }
+                    }
+                },
+                "created_at": { "type": "string", "format": "date-time" },
+                "archived_at": { "type": "string", "format": "date-time", "description": "Only for archived orders" },
+                "items": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "menu_item_id": { "type": "integer" },
+                            "sku": { "type": "string" },
+                            "name": { "type": "string" },
+                            "unit_price_paise": { "type": "integer" },
+                            "quantity": { "type": "integer" },
+                            "options": { "type": "array", "items": ChosenOption::schema() },
+                            "allergens": { "type": "array", "items": { "type": "string" } }
+                        }
+                    }
+                }
+            }
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OrderLineInput {
+    pub sku: String,
+    pub quantity: i32,
+    // Ids of the item options picked for this line; see GET /api/menu/items/:sku/options.
+    #[serde(default)]
+    pub options: Vec<i64>,
+}
+
+// Body of POST /api/orders. Only dine-in orders exist so far, so the table token is
+// required; delivery orders will carry an address instead.
+#[derive(Debug, Deserialize)]
+pub struct OrderInput {
+    pub table_token: String,
+    pub items: Vec<OrderLineInput>,
+    #[serde(default)]
+    pub notes: String,
+}
+
+impl ApiSchema for OrderInput {
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["table_token", "items"],
+            "properties": {
+                "table_token": { "type": "string", "minLength": 1 },
+                "items": lines_schema(),
+                "notes": { "type": "string", "maxLength": 500 }
+            }
+        })
+    }
+}
+
+fn lines_schema() -> Value {
+    json!({
+        "type": "array",
+        "minItems": 1,
+        "maxItems": 100,
+        "items": {
+            "type": "object",
+            "required": ["sku", "quantity"],
+            "properties": {
+                "sku": { "type": "string", "minLength": 1 },
+                "quantity": { "type": "integer", "minimum": 1, "maximum": 99 },
+                "options": { "type": "array", "maxItems": 50, "items": { "type": "integer" } }
+            }
+        }
+    })
+}
+
+// Body of PATCH /api/orders/:id/items: the order's complete new set of lines, and the
+// version of the order they were edited from.
+#[derive(Debug, Deserialize)]
+pub struct OrderItemsInput {
+    pub table_token: String,
+    pub version: i32,
+    pub items: Vec<OrderLineInput>,
+}
+
+impl ApiSchema for OrderItemsInput {
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["table_token", "version", "items"],
+            "properties": {
+                "table_token": { "type": "string", "minLength": 1 },
+                "version": { "type": "integer", "minimum": 0 },
+                "items": lines_schema()
+            }
+        })
+    }
+}
+
+// Body of POST /api/orders/:id/cancel and /api/orders/:id/reorder. The table token
+// proves the guest is at the table the order was placed from.
+#[derive(Debug, Deserialize)]
+pub struct TableTokenInput {
+    pub table_token: String,
+}
+
+impl ApiSchema for TableTokenInput {
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["table_token"],
+            "properties": { "table_token": { "type": "string", "minLength": 1 } }
+        })
+    }
+}
+
+// A past order's lines repriced against today's menu, for the app to review and submit
+// as a new POST /api/orders. Nothing is placed until it does.
+#[derive(Debug, Serialize)]
+pub struct ReorderCart {
+    pub source_order_id: i64,
+    pub items: Vec<OrderItem>,
+    pub subtotal_paise: i64,
+    // What differs from the original order, so the app can point it out.
+    pub changes: Vec<ReorderChange>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "change", rename_all = "snake_case")]
+pub enum ReorderChange {
+    // No longer on the menu or currently unavailable; left out of the cart.
+    Removed { sku: String, name: String, quantity: i32 },
+    // Outside its availability windows right now; left out of the cart. next_open is
+    // when it is served again, if it ever is.
+    NotServedNow { sku: String, name: String, quantity: i32, next_open: Option<DateTime<FixedOffset>> },
+    // The options picked last time no longer fit the item's schema; left out of the
+    // cart so the guest can choose again.
+    OptionsChanged { sku: String, name: String, quantity: i32, reason: String },
+    PriceChanged { sku: String, name: String, old_price_paise: i64, new_price_paise: i64 },
+    // Same sku under a new name, e.g. after a menu import.
+    Renamed { sku: String, old_name: String, new_name: String },
+}
+
+impl ApiSchema for ReorderCart {
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["source_order_id", "items", "subtotal_paise", "changes"],
+            "properties": {
+                "source_order_id": { "type": "integer" },
+                "items": Order::schema()["properties"]["items"],
+                "subtotal_paise": { "type": "integer" },
+                "changes": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "required": ["change", "sku"],
+                        "properties": {
+                            "change": {
+                                "type": "string",
+                                "enum": ["removed", "not_served_now", "options_changed", "price_changed", "renamed"]
+                            },
+                            "sku": { "type": "string" },
+                            "name": { "type": "string" },
+                            "quantity": { "type": "integer" },
+                            "reason": { "type": "string" },
+                            "next_open": { "type": ["string", "null"], "format": "date-time" },
+                            "old_price_paise": { "type": "integer" },
+                            "new_price_paise": { "type": "integer" },
+                            "old_name": { "type": "string" },
+                            "new_name": { "type": "string" }
+                        }
+                    }
+                }
+            }
+        })
+    }
+}
+
+// Body of POST /api/staff/orders/:id/status.
+#[derive(Debug, Deserialize)]
+pub struct StatusInput {
+    pub status: String,
+}
+
+impl ApiSchema for StatusInput {
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["status"],
+            "properties": { "status": { "type": "string", "enum": STATUSES } }
+        })
+    }
+}
+
+// Tax on a subtotal at `bps` basis points, rounded half up to the paisa.
+pub fn tax_paise(subtotal_paise: i64, bps: u32) -> i64 {
+    (subtotal_paise * i64::from(bps) + 5_000) / 10_000
+}
+
+const SELECT_ORDER: &str = "SELECT id, restaurant_id, fulfillment, table_id, status, subtotal_paise, tax_paise, \
+                            delivery_fee_paise, total_paise, notes, version, created_at, updated_at, archived_at \
+                            FROM orders";
+
+// Database access for one restaurant's orders.
+pub struct OrderRepository<'a> {
+    pool: &'a MySqlPool,
+    restaurant_id: i64,
+}
+
+impl<'a> OrderRepository<'a> {
+    pub fn new(pool: &'a MySqlPool, restaurant_id: i64) -> Self {
+        Self { pool, restaurant_id }
+    }
+
+    async fn with_items(&self, mut orders: Vec<Order>) -> Result<Vec<Order>> {
+        if orders.is_empty() {
+            return Ok(orders);
+        }
+        let (archived, live): (Vec<&Order>, Vec<&Order>) = orders.iter().partition(|order| order.archived_at.is_some());
+        let mut items: HashMap<i64, Vec<OrderItem>> = HashMap::new();
+        for (table, batch) in [("order_items", live), ("archived_order_items", archived)] {
+            if batch.is_empty() {
+                continue;
+            }
+            let placeholders = vec!["?"; batch.len()].join(", ");
+            let sql = format!(
+                "SELECT order_id, menu_item_id, sku, name, unit_price_paise, quantity, options FROM {table} \
+                 WHERE order_id IN ({placeholders}) ORDER BY id"
+            );
+            let mut query = sqlx::query_as::<_, (i64, i64, String, String, i64, i32, Json<Vec<ChosenOption>>)>(&sql);
+            for order in batch {
+                query = query.bind(order.id);
+            }
+            for (order_id, menu_item_id, sku, name, unit_price_paise, quantity, options) in
+                query.fetch_all(self.pool).await?
+            {
+                items.entry(order_id).or_default().push(OrderItem {
+                    menu_item_id,
+                    sku,
+                    name,
+                    unit_price_paise,
+                    quantity,
+                    options: options.0,
+                    allergens: Vec::new(),
+                });
+            }
+        }
+        let item_ids: Vec<i64> = items.values().flatten().map(|item| item.menu_item_id).collect();
+        let codes = allergens::for_items(self.pool, &item_ids).await?;
+        for item in items.values_mut().flatten() {
+            item.allergens = codes.get(&item.menu_item_id).cloned().unwrap_or_default();
+        }
+        for order in &mut orders {
+            order.items = items.remove(&order.id).unwrap_or_default();
+        }
+        if orders.iter().any(|order| order.status == "queued") {
+            let positions = kitchen_queue::positions(self.pool, self.restaurant_id).await?;
+            for order in &mut orders {
+                order.queue_position = positions.get(&order.id).copied();
+            }
+        }
+        Ok(orders)
+    }
+
+    pub async fn get(&self, id: i64) -> Result<Option<Order>> {
+        db_retry::retry(Access::Read, "orders.get", move || async move {
+            let order: Option<Order> = sqlx::query_as(&format!("{SELECT_ORDER} WHERE id = ? AND restaurant_id = ?"))
+                .bind(id)
+                .bind(self.restaurant_id)
+                .fetch_optional(self.pool)
+                .await?;
+            Ok(self.with_items(order.into_iter().collect()).await?.pop())
+        })
+        .await
+    }
+
+    // Open orders, oldest first, optionally narrowed to one status.
+    pub async fn list_open(&self, status: Option<&str>) -> Result<Vec<Order>> {
+        db_retry::retry(Access::Read, "orders.list_open", move || async move {
+            let orders = sqlx::query_as(&format!(
+                "{SELECT_ORDER} WHERE restaurant_id = ? AND status NOT IN ('served', 'delivered', 'cancelled') \
+                 AND (? IS NULL OR status = ?) ORDER BY created_at, id"
+            ))
+            .bind(self.restaurant_id)
+            .bind(status)
+            .bind(status)
+            .fetch_all(self.pool)
+            .await?;
+            self.with_items(orders).await
+        })
+        .await
+    }
+
+    // A table's orders from the last 12 hours, newest first, so guests can follow them.
+    pub async fn list_for_table(&self, table_id: i64) -> Result<Vec<Order>> {
+        db_retry::retry(Access::Read, "orders.list_for_table", move || async move {
+            let orders = sqlx::query_as(&format!(
+                "{SELECT_ORDER} WHERE restaurant_id = ? AND table_id = ? \
+                 AND created_at > NOW() - INTERVAL 12 HOUR ORDER BY created_at DESC, id DESC"
+            ))
+            .bind(self.restaurant_id)
+            .bind(table_id)
+            .fetch_all(self.pool)
+            .await?;
+            self.with_items(orders).await
+        })
+        .await
+    }
+
+    // Prices the lines against the current menu and records a dine-in order for `table`,
+    // emitting order.created in the same transaction. The order is queued instead of
+    // placed while the kitchen is at capacity.
+    pub async fn place_dine_in(&self, table: &DiningTable, input: &OrderInput, config: &ConfigService) -> AppResult<Order> {
+        let order_id = db_retry::retry(Access::Write, "orders.place_dine_in", move || {
+            self.insert_dine_in(table, input, config)
+        })
+        .await?;
+        self.get(order_id)
+            .await?
+            .ok_or_else(|| AppError::Internal(anyhow::anyhow!("order {order_id} vanished after insert")))
+    }
+
+    // The transaction behind place_dine_in. Returns the new order's id.
+    async fn insert_dine_in(&self, table: &DiningTable, input: &OrderInput, config: &ConfigService) -> AppResult<i64> {
+        let mut tx = self.pool.begin().await?;
+        let (lines, subtotal) = self.price_lines(&mut tx, &input.items, config).await?;
+        let tax = tax_paise(subtotal, config.tax_rate_bps());
+        let queued = kitchen_queue::must_queue(&mut tx, self.restaurant_id, config.kitchen_max_active_orders()).await?;
+        let status = if queued { "queued" } else { "placed" };
+
+        let result = sqlx::query(
+            "INSERT INTO orders (restaurant_id, fulfillment, table_id, status, subtotal_paise, tax_paise, \
+             total_paise, notes) VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(self.restaurant_id)
+        .bind(FULFILLMENT_DINE_IN)
+        .bind(table.id)
+        .bind(status)
+        .bind(subtotal)
+        .bind(tax)
+        .bind(subtotal + tax)
+        .bind(input.notes.trim())
+        .execute(&mut *tx)
+        .await?;
+        let order_id = result.last_insert_id() as i64;
+        insert_lines(&mut tx, order_id, &lines).await?;
+        let placed = OrderEvent::Placed {
+            fulfillment: FULFILLMENT_DINE_IN.to_string(),
+            table_id: Some(table.id),
+            status: status.to_string(),
+            subtotal_paise: subtotal,
+            tax_paise: tax,
+            delivery_fee_paise: 0,
+            total_paise: subtotal + tax,
+            notes: input.notes.trim().to_string(),
+            items: lines,
+        };
+        order_events::append(&mut tx, order_id, self.restaurant_id, &format!("table:{}", table.id), &placed).await?;
+        let payload = json!({
+            "order_id": order_id,
+            "restaurant_id": self.restaurant_id,
+            "fulfillment": FULFILLMENT_DINE_IN,
+            "table": table.label,
+            "status": status,
+            "total_paise": subtotal + tax,
+        });
+        webhooks::enqueue(&mut *tx, self.restaurant_id, "order.created", &payload).await?;
+        tx.commit().await?;
+        Ok(order_id)
+    }
+
+    // Prices `lines` against the current menu and option schemas, inside `tx` so the
+    // prices read are the ones recorded. Items outside their availability windows are
+    // refused with the time they come back. Returns the priced lines and the subtotal.
+    async fn price_lines(
+        &self,
+        tx: &mut Transaction<'_, MySql>,
+        lines: &[OrderLineInput],
+        config: &ConfigService,
+    ) -> AppResult<(Vec<OrderItem>, i64)> {
+        let placeholders = vec!["?"; lines.len()].join(", ");
+        let sql = format!(
+            "SELECT sku, id, name, price_paise, category FROM menu_items \
+             WHERE restaurant_id = ? AND available AND sku IN ({placeholders})"
+        );
+        let mut query = sqlx::query_as::<_, (String, i64, String, i64, String)>(&sql).bind(self.restaurant_id);
+        for line in lines {
+            query = query.bind(&line.sku);
+        }
+        let mut menu = HashMap::new();
+        let mut categories = HashMap::new();
+        for (sku, id, name, price, category) in query.fetch_all(&mut **tx).await? {
+            categories.insert(id, category);
+            menu.insert(sku, (id, name, price));
+        }
+        let item_ids: Vec<i64> = menu.values().map(|(id, _, _)| *id).collect();
+        let groups = item_options::load_groups(&mut **tx, &item_ids).await?;
+        let schedules = menu_schedule::load(&mut **tx, self.restaurant_id, config.local_offset()).await?;
+        let now = Utc::now();
+
+        let mut errors = Vec::new();
+        let mut priced = Vec::with_capacity(lines.len());
+        let mut subtotal = 0i64;
+        for (index, line) in lines.iter().enumerate() {
+            let Some((menu_item_id, name, price)) = menu.get(&line.sku) else {
+                errors.push(FieldError::new(format!("items[{index}].sku"), "not on the menu or unavailable"));
+                continue;
+            };
+            let category = &categories[menu_item_id];
+            if !schedules.is_open(*menu_item_id, category, now) {
+                let message = match schedules.next_open(*menu_item_id, category, now) {
+                    Some(at) => format!("not served now; available again at {}", at.format("%a %H:%M")),
+                    None => "not served now".to_string(),
+                };
+                errors.push(FieldError::new(format!("items[{index}].sku"), message));
+                continue;
+            }
+            let item_groups = groups.get(menu_item_id).map(Vec::as_slice).unwrap_or_default();
+            let options = match item_options::choose(item_groups, &line.options) {
+                Ok(options) => options,
+                Err(message) => {
+                    errors.push(FieldError::new(format!("items[{index}].options"), message));
+                    continue;
+                }
+            };
+            let unit_price = price + options.iter().map(|option| option.price_delta_paise).sum::<i64>();
+            subtotal += unit_price * i64::from(line.quantity);
+            priced.push(OrderItem {
+                menu_item_id: *menu_item_id,
+                sku: line.sku.clone(),
+                name: name.clone(),
+                unit_price_paise: unit_price,
+                quantity: line.quantity,
+                options,
+                allergens: Vec::new(),
+            });
+        }
+        if !errors.is_empty() {
+            return Err(AppError::Validation(errors));
+        }
+        let min_order_paise = config.min_order_value_paise();
+        if subtotal < min_order_paise {
+            return Err(AppError::Validation(vec![FieldError::new(
+                "items",
+                format!("order total must be at least {min_order_paise} paise"),
+            )]));
+        }
+        Ok((priced, subtotal))
+    }
+
+    // Rebuilds the cart of an order placed from `table`, at today's menu and prices.
+    // Items outside their availability windows are left out, like price_lines refuses them.
+    pub async fn reorder_cart(&self, id: i64, table: &DiningTable, config: &ConfigService) -> AppResult<ReorderCart> {
+        let order = self
+            .get(id)
+            .await?
+            .filter(|order| order.table_id == Some(table.id))
+            .ok_or_else(order_not_found)?;
+        let menu: HashMap<String, (i64, String, i64, String)> = sqlx::query_as::<_, (String, i64, String, i64, String)>(
+            "SELECT sku, id, name, price_paise, category FROM menu_items WHERE restaurant_id = ? AND available",
+        )
+        .bind(self.restaurant_id)
+        .fetch_all(self.pool)
+        .await?
+        .into_iter()
+        .map(|(sku, id, name, price, category)| (sku, (id, name, price, category)))
+        .collect();
+        let item_ids: Vec<i64> = order
+            .items
+            .iter()
+            .filter_map(|line| menu.get(&line.sku).map(|(id, _, _, _)| *id))
+            .collect();
+        let groups = item_options::load_groups(self.pool, &item_ids).await?;
+        let codes = allergens::for_items(self.pool, &item_ids).await?;
+        let schedules = menu_schedule::load(self.pool, self.restaurant_id, config.local_offset()).await?;
+        let now = Utc::now();
+
+        let mut cart = ReorderCart { source_order_id: id, items: Vec::new(), subtotal_paise: 0, changes: Vec::new() };
+        for line in order.items {
+            let Some((menu_item_id, name, price, category)) = menu.get(&line.sku) else {
+                cart.changes.push(ReorderChange::Removed { sku: line.sku, name: line.name, quantity: line.quantity });
+                continue;
+            };
+            if !schedules.is_open(*menu_item_id, category, now) {
+                cart.changes.push(ReorderChange::NotServedNow {
+                    sku: line.sku,
+                    name: line.name,
+                    quantity: line.quantity,
+                    next_open: schedules.next_open(*menu_item_id, category, now),
+                });
+                continue;
+            }
+            let selected: Vec<i64> = line.options.iter().map(|option| option.option_id).collect();
+            let item_groups = groups.get(menu_item_id).map(Vec::as_slice).unwrap_or_default();
+            let options = match item_options::choose(item_groups, &selected) {
+                Ok(options) => options,
+                Err(reason) => {
+                    cart.changes.push(ReorderChange::OptionsChanged {
+                        sku: line.sku,
+                        name: line.name,
+                        quantity: line.quantity,
+                        reason,
+                    });
+                    continue;
+                }
+            };
+            let unit_price = price + options.iter().map(|option| option.price_delta_paise).sum::<i64>();
+            if unit_price != line.unit_price_paise {
+                cart.changes.push(ReorderChange::PriceChanged {
+                    sku: line.sku.clone(),
+                    name: name.clone(),
+                    old_price_paise: line.unit_price_paise,
+                    new_price_paise: unit_price,
+                });
+            }
+            if *name != line.name {
+                cart.changes.push(ReorderChange::Renamed {
+                    sku: line.sku.clone(),
+                    old_name: line.name.clone(),
+                    new_name: name.clone(),
+                });
+            }
+            cart.subtotal_paise += unit_price * i64::from(line.quantity);
+            cart.items.push(OrderItem {
+                menu_item_id: *menu_item_id,
+                sku: line.sku,
+                name: name.clone(),
+                unit_price_paise: unit_price,
+                quantity: line.quantity,
+                options,
+                allergens: codes.get(menu_item_id).cloned().unwrap_or_default(),
+            });
+        }
+        Ok(cart)
+    }
+
+    // Replaces the lines of a guest's order and reprices it. Only orders still queued or
+    // placed can change; once the kitchen starts one it is fixed. The update is conditional on
+    // the version the guest edited, so a stale edit gets 409 instead of overwriting.
+    pub async fn replace_items(
+        &self,
+        id: i64,
+        table: &DiningTable,
+        input: &OrderItemsInput,
+        config: &ConfigService,
+        audit: &AuditLogger,
+    ) -> AppResult<Order> {
+        let order = self
+            .get(id)
+            .await?
+            .filter(|order| order.table_id == Some(table.id))
+            .ok_or_else(order_not_found)?;
+        if !matches!(order.status.as_str(), "queued" | "placed") {
+            return Err(AppError::Conflict(format!("order is already {} and can no longer be changed", order.status)));
+        }
+        if order.version != input.version {
+            return Err(stale_order());
+        }
+        db_retry::retry(Access::Write, "orders.replace_items", || self.write_items(&order, input, config, audit))
+            .await?;
+        self.get(id).await?.ok_or_else(order_not_found)
+    }
+
+    // The transaction behind replace_items.
+    async fn write_items(
+        &self,
+        order: &Order,
+        input: &OrderItemsInput,
+        config: &ConfigService,
+        audit: &AuditLogger,
+    ) -> AppResult<()> {
+        let id = order.id;
+        let mut tx = self.pool.begin().await?;
+        let (lines, subtotal) = self.price_lines(&mut tx, &input.items, config).await?;
+        let tax = tax_paise(subtotal, config.tax_rate_bps());
+        let total = subtotal + tax + order.delivery_fee_paise;
+        let updated = sqlx::query(
+            "UPDATE orders SET subtotal_paise = ?, tax_paise = ?, total_paise = ?, version = version + 1 \
+             WHERE id = ? AND restaurant_id = ? AND status IN ('queued', 'placed') AND version = ?",
+        )
+        .bind(subtotal)
+        .bind(tax)
+        .bind(total)
+        .bind(id)
+        .bind(self.restaurant_id)
+        .bind(input.version)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+        if updated == 0 {
+            return Err(stale_order());
+        }
+        sqlx::query("DELETE FROM order_items WHERE order_id = ?")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        insert_lines(&mut tx, id, &lines).await?;
+        let replaced =
+            OrderEvent::ItemsReplaced { subtotal_paise: subtotal, tax_paise: tax, total_paise: total, items: lines };
+        order_events::append(&mut tx, id, self.restaurant_id, audit.actor(), &replaced).await?;
+
+        let event = AuditEvent {
+            action: "order.items",
+            target_type: "order",
+            target_id: id.to_string(),
+            restaurant_id: Some(self.restaurant_id),
+            before: Some(json!({
+                "items": lines_snapshot(order.items.iter().map(|item| (item.sku.as_str(), item.quantity))),
+                "total_paise": order.total_paise,
+            })),
+            after: Some(json!({
+                "items": lines_snapshot(input.items.iter().map(|line| (line.sku.as_str(), line.quantity))),
+                "total_paise": total,
+            })),
+        };
+        audit.record(&mut *tx, event).await?;
+        let payload = json!({
+            "order_id": id,
+            "restaurant_id": self.restaurant_id,
+            "total_paise": total,
+            "difference_paise": total - order.total_paise,
+        });
+        webhooks::enqueue(&mut *tx, self.restaurant_id, "order.updated", &payload).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    // Moves an order to `to` if its fulfillment allows it from the current status.
+    // The update is conditional on the status read, so concurrent moves can't both win.
+    pub async fn transition(&self, id: i64, to: &str, audit: &AuditLogger) -> AppResult<Order> {
+        let order = self.get(id).await?.ok_or_else(order_not_found)?;
+        if !can_transition(&order.fulfillment, &order.status, to) {
+            return Err(AppError::Conflict(format!(
+                "cannot move a {} order from {} to {to}",
+                order.fulfillment, order.status
+            )));
+        }
+        self.apply_transition(&order, to, audit).await
+    }
+
+    // Cancels an order on behalf of a guest at `table`. Guests can only cancel their own
+    // table's orders and only before the kitchen starts them; staff can still cancel
+    // while an order is being prepared.
+    pub async fn cancel_for_guest(&self, id: i64, table: &DiningTable, audit: &AuditLogger) -> AppResult<Order> {
+        let order = self
+            .get(id)
+            .await?
+            .filter(|order| order.table_id == Some(table.id))
+            .ok_or_else(order_not_found)?;
+        if !matches!(order.status.as_str(), "queued" | "placed") {
+            return Err(AppError::Conflict(format!(
+                "order is already {}; ask the staff to cancel it",
+                order.status
+            )));
+        }
+        self.apply_transition(&order, "cancelled", audit).await
+    }
+
+    // Writes the move from the order's current status to `to`, with its audit entry and
+    // webhook, in one transaction.
+    async fn apply_transition(&self, order: &Order, to: &str, audit: &AuditLogger) -> AppResult<Order> {
+        db_retry::retry(Access::Write, "orders.transition", move || self.write_transition(order, to, audit)).await?;
+        self.get(order.id).await?.ok_or_else(order_not_found)
+    }
+
+    async fn write_transition(&self, order: &Order, to: &str, audit: &AuditLogger) -> AppResult<()> {
+        let id = order.id;
+        let mut tx = self.pool.begin().await?;
+        let updated = sqlx::query("UPDATE orders SET status = ? WHERE id = ? AND restaurant_id = ? AND status = ?")
+            .bind(to)
+            .bind(id)
+            .bind(self.restaurant_id)
+            .bind(&order.status)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        if updated == 0 {
+            return Err(AppError::Conflict("order status changed concurrently; retry".to_string()));
+        }
+        let changed = OrderEvent::StatusChanged { from: order.status.clone(), to: to.to_string() };
+        order_events::append(&mut tx, id, self.restaurant_id, audit.actor(), &changed).await?;
+        let event = AuditEvent {
+            action: "order.status",
+            target_type: "order",
+            target_id: id.to_string(),
+            restaurant_id: Some(self.restaurant_id),
+            before: Some(json!({ "status": order.status })),
+            after: Some(json!({ "status": to })),
+        };
+        audit.record(&mut *tx, event).await?;
+        if let Some(event) = status_event(to) {
+            let payload = json!({ "order_id": id, "restaurant_id": self.restaurant_id, "status": to });
+            webhooks::enqueue(&mut *tx, self.restaurant_id, event, &payload).await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
+// The order with its lines as `tx` sees them, its row locked until `tx` ends. Unlike
+// OrderRepository::get, the queue position is left unset.
+pub async fn lock(tx: &mut Transaction<'_, MySql>, restaurant_id: i64, id: i64) -> Result<Option<Order>> {
+    let order: Option<Order> =
+        sqlx::query_as(&format!("{SELECT_ORDER} WHERE id = ? AND restaurant_id = ? FOR UPDATE"))
+            .bind(id)
+            .bind(restaurant_id)
+            .fetch_optional(&mut **tx)
+            .await?;
+    let Some(mut order) = order else {
+        return Ok(None);
+    };
+    let table = if order.archived_at.is_some() { "archived_order_items" } else { "order_items" };
+    let lines = sqlx::query_as::<_, (i64, String, String, i64, i32, Json<Vec<ChosenOption>>)>(&format!(
+        "SELECT menu_item_id, sku, name, unit_price_paise, quantity, options FROM {table} WHERE order_id = ? ORDER BY id"
+    ))
+    .bind(id)
+    .fetch_all(&mut **tx)
+    .await?;
+    let item_ids: Vec<i64> = lines.iter().map(|line| line.0).collect();
+    let codes = allergens::for_items(&mut **tx, &item_ids).await?;
+    order.items = lines
+        .into_iter()
+        .map(|(menu_item_id, sku, name, unit_price_paise, quantity, options)| OrderItem {
+            menu_item_id,
+            sku,
+            name,
+            unit_price_paise,
+            quantity,
+            options: options.0,
+            allergens: codes.get(&menu_item_id).cloned().unwrap_or_default(),
+        })
+        .collect();
+    Ok(Some(order))
+}
+
+// Records the lines `price_lines` returned for `order_id`.
+pub async fn insert_lines(tx: &mut Transaction<'_, MySql>, order_id: i64, lines: &[OrderItem]) -> Result<()> {
+    for line in lines {
+        sqlx::query(
+            "INSERT INTO order_items (order_id, menu_item_id, sku, name, unit_price_paise, quantity, options) \
+             VALUES (?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(order_id)
+        .bind(line.menu_item_id)
+        .bind(&line.sku)
+        .bind(&line.name)
+        .bind(line.unit_price_paise)
+        .bind(line.quantity)
+        .bind(Json(&line.options))
+        .execute(&mut **tx)
+        .await?;
+    }
+    Ok(())
+}
+
+// (sku, quantity) pairs as recorded in the audit log.
+fn lines_snapshot<'a>(lines: impl Iterator<Item = (&'a str, i32)>) -> Value {
+    lines.map(|(sku, quantity)| json!({ "sku": sku, "quantity": quantity })).collect()
+}
+
+fn stale_order() -> AppError {
+    AppError::Conflict("order was changed since it was loaded; reload it and try again".to_string())
+}
+
+fn order_not_found() -> AppError {
+    AppError::NotFound("order not found".to_string())
+}
+
+// Readies an order for a response: queue wait and ETA estimated with the current
+// kitchen settings, labels in `lang`.
+fn present(order: Order, lang: &str, services: &AppServices) -> Order {
+    order.estimate(&services.runtime_config, Utc::now()).localize(lang)
+}
+
+// Looks up the table behind a QR token; unknown tokens are 404.
+pub async fn table_for_token(services: &AppServices, token: &str) -> AppResult<DiningTable> {
+    DiningTableRepository::new(services.db()?)
+        .by_token(token)
+        .await?
+        .ok_or_else(|| AppError::NotFound("table not found".to_string()))
+}
+
+// Registers guest ordering and the staff (waiter/kitchen) order endpoints. Guests are
+// identified only by the table token; staff use the restaurant's admin token.
+pub fn routes(router: Router) -> Router {
+    router
+        .post("/api/orders/:id/cancel", cancel_order)
+        .summary("Cancel an order from the table it was placed at, before the kitchen starts it")
+        .urgency(0)
+        .request_schema(TableTokenInput::schema())
+        .response_schema(Order::schema())
+        .post("/api/orders/:id/reorder", reorder)
+        .summary("Rebuild the cart of a past order at today's menu and prices")
+        .request_schema(TableTokenInput::schema())
+        .response_schema(ReorderCart::schema())
+        .patch("/api/orders/:id/items", update_items)
+        .summary("Change the lines of an order before the kitchen starts it")
+        .urgency(0)
+        .request_schema(OrderItemsInput::schema())
+        .response_schema(Order::schema())
+        .post("/api/orders", place_order)
+        .summary("Place a dine-in order from a table or room QR token")
+        .urgency(0)
+        .request_schema(OrderInput::schema())
+        .response_schema(Order::schema())
+        .get("/api/tables/:token/orders", table_orders)
+        .summary("Orders placed from a table in the last 12 hours")
+        .response_schema(json!({ "type": "array", "items": Order::schema() }))
+        .get("/api/staff/orders", open_orders)
+        .summary("Open orders for the restaurant, oldest first")
+        .urgency(1)
+        .requires_tenant_admin()
+        .query_param("status", false)
+        .response_schema(json!({ "type": "array", "items": Order::schema() }))
+        .post("/api/staff/orders/:id/status", set_status)
+        .summary("Move an order along its fulfillment path")
+        .urgency(1)
+        .requires_tenant_admin()
+        .request_schema(StatusInput::schema())
+        .response_schema(Order::schema())
+        .post("/api/staff/orders/:id/served", mark_served)
+        .summary("Mark a dine-in order served")
+        .urgency(1)
+        .requires_tenant_admin()
+        .response_schema(Order::schema())
+}
+
+// The table token decides the restaurant, so this works without a /r/<slug> prefix.
+async fn place_order(
+    JsonBody(input): JsonBody<OrderInput>,
+    Language(lang): Language,
+    State(services): State,
+) -> AppResult<Response<Bytes>> {
+    let table = table_for_token(&services, &input.table_token).await?;
+    let pool = services.db()?;
+    let order = OrderRepository::new(pool, table.restaurant_id)
+        .place_dine_in(&table, &input, &services.runtime_config)
+        .await?;
+    ResponseBuilder::json(StatusCode::CREATED, &present(order, lang, &services))
+}
+
+// Only builds the cart; the app submits it to POST /api/orders once the guest confirms.
+async fn reorder(
+    Path(id): Path<i64>,
+    JsonBody(input): JsonBody<TableTokenInput>,
+    State(services): State,
+) -> AppResult<Response<Bytes>> {
+    let table = table_for_token(&services, &input.table_token).await?;
+    let cart = OrderRepository::new(services.db()?, table.restaurant_id)
+        .reorder_cart(id, &table, &services.runtime_config)
+        .await?;
+    ResponseBuilder::json(StatusCode::OK, &cart)
+}
+
+async fn update_items(
+    Path(id): Path<i64>,
+    JsonBody(input): JsonBody<OrderItemsInput>,
+    audit: AuditLogger,
+    Language(lang): Language,
+    State(services): State,
+) -> AppResult<Response<Bytes>> {
+    let table = table_for_token(&services, &input.table_token).await?;
+    let order = OrderRepository::new(services.db()?, table.restaurant_id)
+        .replace_items(id, &table, &input, &services.runtime_config, &audit.as_guest(&table))
+        .await?;
+    ResponseBuilder::json(StatusCode::OK, &present(order, lang, &services))
+}
+
+// Emits order.cancelled like a staff cancellation, so the kitchen display drops it,
+// and lets the next queued order in.
+async fn cancel_order(
+    Path(id): Path<i64>,
+    JsonBody(input): JsonBody<TableTokenInput>,
+    audit: AuditLogger,
+    Language(lang): Language,
+    State(services): State,
+) -> AppResult<Response<Bytes>> {
+    let table = table_for_token(&services, &input.table_token).await?;
+    let order = OrderRepository::new(services.db()?, table.restaurant_id)
+        .cancel_for_guest(id, &table, &audit.as_guest(&table))
+        .await?;
+    kitchen_queue::admit_now(services.db()?, table.restaurant_id, &services.runtime_config).await;
+    ResponseBuilder::json(StatusCode::OK, &present(order, lang, &services))
+}
+
+async fn table_orders(
+    Path(token): Path<String>,
+    Language(lang): Language,
+    State(services): State,
+) -> AppResult<Response<Bytes>> {
+    let table = table_for_token(&services, &token).await?;
+    let orders = OrderRepository::new(services.db()?, table.restaurant_id)
+        .list_for_table(table.id)
+        .await?;
+    let orders: Vec<Order> = orders.into_iter().map(|order| present(order, lang, &services)).collect();
+    ResponseBuilder::json(StatusCode::OK, &orders)
+}
+
+// Query of GET /api/staff/orders.
+#[derive(Debug, Deserialize)]
+pub struct OpenOrdersQuery {
+    pub status: Option<String>,
+}
+
+async fn open_orders(
+    TenantAdmin(restaurant): TenantAdmin,
+    Query(query): Query<OpenOrdersQuery>,
+    Language(lang): Language,
+    State(services): State,
+) -> AppResult<Response<Bytes>> {
+    let status = query.status.as_deref();
+    if let Some(status) = status
+        && !STATUSES.contains(&status)
+    {
+        return Err(AppError::BadRequest(format!("unknown status {status}")));
+    }
+    let orders = OrderRepository::new(services.db()?, restaurant.id).list_open(status).await?;
+    let orders: Vec<Order> = orders.into_iter().map(|order| present(order, lang, &services)).collect();
+    ResponseBuilder::json(StatusCode::OK, &orders)
+}
+
+async fn set_status(
+    TenantAdmin(restaurant): TenantAdmin,
+    Path(id): Path<i64>,
+    JsonBody(input): JsonBody<StatusInput>,
+    audit: AuditLogger,
+    Language(lang): Language,
+    State(services): State,
+) -> AppResult<Response<Bytes>> {
+    let order = OrderRepository::new(services.db()?, restaurant.id)
+        .transition(id, &input.status, &audit)
+        .await?;
+    kitchen_queue::admit_now(services.db()?, restaurant.id, &services.runtime_config).await;
+    ResponseBuilder::json(StatusCode::OK, &present(order, lang, &services))
+}
+
+async fn mark_served(
+    TenantAdmin(restaurant): TenantAdmin,
+    Path(id): Path<i64>,
+    audit: AuditLogger,
+    Language(lang): Language,
+    State(services): State,
+) -> AppResult<Response<Bytes>> {
+    let order = OrderRepository::new(services.db()?, restaurant.id)
+        .transition(id, "served", &audit)
+        .await?;
+    kitchen_queue::admit_now(services.db()?, restaurant.id, &services.runtime_config).await;
+    ResponseBuilder::json(StatusCode::OK, &present(order, lang, &services))
+}
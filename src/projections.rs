@@ -0,0 +1,344 @@
+use crate::error::{AppError, AppResult};
+use crate::extract::{Query, State, TenantAdmin};
+use crate::logging;
+use crate::openapi::ApiSchema;
+use crate::order_events::{self, OrderState, StoredEvent};
+use crate::response::ResponseBuilder;
+use crate::router::Router;
+use crate::runtime_config::ConfigService;
+use anyhow::Result;
+use bytes::Bytes;
+use chrono::{Days, NaiveDate, Utc};
+use http::{Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sqlx::mysql::{MySql, MySqlPool};
+use sqlx::types::Json;
+use sqlx::Transaction;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+use std::time::Duration;
+
+// Sales read models for dashboards. A background worker follows order_events with a
+// cursor and keeps daily_sales and item_popularity up to date, so the analytics
+// endpoints read a few pre-aggregated rows instead of grouping the live orders.
+// Each order's current contribution is remembered in projected_orders: when a later
+// event changes the order, the old contribution is subtracted and the replayed new
+// one added, so edits and cancellations correct the totals.
+
+const CURSOR: &str = "sales";
+const BATCH: i64 = 500;
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+// Event ids follow insert order, not commit order, so an event can become visible
+// after the cursor has moved past its id. Orders with events this recent are replayed
+// again on every pass, which changes nothing unless such a late event has appeared.
+// Must outlast any order transaction (innodb_lock_wait_timeout is 50s by default).
+const RESCAN_SECONDS: u32 = 120;
+const DEFAULT_RANGE_DAYS: u64 = 30;
+const MAX_RANGE_DAYS: u64 = 366;
+
+// What one order adds to the sales tables.
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow)]
+struct Contribution {
+    restaurant_id: i64,
+    day: NaiveDate,
+    cancelled: bool,
+    total_paise: i64,
+    // (menu_item_id, quantity, revenue_paise), by item.
+    #[sqlx(json)]
+    line_totals: Vec<(i64, i64, i64)>,
+}
+
+impl Contribution {
+    fn of(restaurant_id: i64, day: NaiveDate, state: &OrderState) -> Self {
+        let cancelled = state.status == "cancelled";
+        let mut lines: BTreeMap<i64, (i64, i64)> = BTreeMap::new();
+        if !cancelled {
+            for item in &state.items {
+                let quantity = i64::from(item.quantity);
+                let entry = lines.entry(item.menu_item_id).or_default();
+                entry.0 += quantity;
+                entry.1 += item.unit_price_paise * quantity;
+            }
+        }
+        Self {
+            restaurant_id,
+            day,
+            cancelled,
+            total_paise: state.total_paise,
+            line_totals: lines.into_iter().map(|(id, (quantity, revenue))| (id, quantity, revenue)).collect(),
+        }
+    }
+
+    // Adds (sign 1) or takes back (sign -1) this contribution.
+    async fn apply(&self, tx: &mut Transaction<'_, MySql>, sign: i64) -> Result<()> {
+        let (orders, cancelled, revenue) =
+            if self.cancelled { (0, sign, 0) } else { (sign, 0, sign * self.total_paise) };
+        sqlx::query(
+            "INSERT INTO daily_sales (restaurant_id, day, orders, cancelled_orders, revenue_paise) \
+             VALUES (?, ?, ?, ?, ?) \
+             ON DUPLICATE KEY UPDATE orders = orders + VALUES(orders), \
+             cancelled_orders = cancelled_orders + VALUES(cancelled_orders), \
+             revenue_paise = revenue_paise + VALUES(revenue_paise)",
+        )
+        .bind(self.restaurant_id)
+        .bind(self.day)
+        .bind(orders)
+        .bind(cancelled)
+        .bind(revenue)
+        .execute(&mut **tx)
+        .await?;
+        for (menu_item_id, quantity, revenue) in &self.line_totals {
+            sqlx::query(
+                "INSERT INTO item_popularity (restaurant_id, day, menu_item_id, quantity, revenue_paise) \
+                 VALUES (?, ?, ?, ?, ?) \
+                 ON DUPLICATE KEY UPDATE quantity = quantity + VALUES(quantity), \
+                 revenue_paise = revenue_paise + VALUES(revenue_paise)",
+            )
+            .bind(self.restaurant_id)
+            .bind(self.day)
+            .bind(menu_item_id)
+            .bind(sign * quantity)
+            .bind(sign * revenue)
+            .execute(&mut **tx)
+            .await?;
+        }
+        Ok(())
+    }
+}
+
+// Projects the next batch of events, plus the orders in the re-scan window. Holding
+// the cursor row locked makes instances take turns. Returns the number of new events
+// consumed.
+pub async fn run_once(pool: &MySqlPool, config: &ConfigService) -> Result<usize> {
+    let mut tx = pool.begin().await?;
+    let cursor: i64 = sqlx::query_scalar("SELECT last_event_id FROM projection_cursors WHERE name = ? FOR UPDATE")
+        .bind(CURSOR)
+        .fetch_one(&mut *tx)
+        .await?;
+    let events: Vec<(i64, i64, i64)> =
+        sqlx::query_as("SELECT id, order_id, restaurant_id FROM order_events WHERE id > ? ORDER BY id LIMIT ?")
+            .bind(cursor)
+            .bind(BATCH)
+            .fetch_all(&mut *tx)
+            .await?;
+    let recent: Vec<(i64, i64)> = sqlx::query_as(
+        "SELECT DISTINCT order_id, restaurant_id FROM order_events \
+         WHERE id <= ? AND created_at >= NOW(3) - INTERVAL ? SECOND",
+    )
+    .bind(cursor)
+    .bind(RESCAN_SECONDS)
+    .fetch_all(&mut *tx)
+    .await?;
+    let last_id = events.last().map_or(cursor, |&(id, _, _)| id);
+
+    // Only each order's state up to last_id matters, however many events it had.
+    let orders: BTreeSet<(i64, i64)> = events
+        .iter()
+        .map(|&(_, order_id, restaurant_id)| (order_id, restaurant_id))
+        .chain(recent)
+        .collect();
+    for (order_id, restaurant_id) in orders {
+        let history: Vec<StoredEvent> = sqlx::query_as(
+            "SELECT sequence, actor, created_at, payload FROM order_events \
+             WHERE order_id = ? AND id <= ? ORDER BY sequence",
+        )
+        .bind(order_id)
+        .bind(last_id)
+        .fetch_all(&mut *tx)
+        .await?;
+        let state = match order_events::replay(&history) {
+            Ok(state) => state,
+            Err(err) => {
+                // A broken history must not stall every other order behind it.
+                logging::warn(
+                    "sales projection skipped an order",
+                    json!({ "order_id": order_id, "error": format!("{err:#}") }),
+                );
+                continue;
+            }
+        };
+        let day = history[0].created_at.with_timezone(&config.local_offset()).date_naive();
+        let new = Contribution::of(restaurant_id, day, &state);
+        let old: Option<Contribution> = sqlx::query_as(
+            "SELECT restaurant_id, day, cancelled, total_paise, line_totals FROM projected_orders WHERE order_id = ?",
+        )
+        .bind(order_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+        if old.as_ref() == Some(&new) {
+            continue;
+        }
+        if let Some(old) = &old {
+            old.apply(&mut tx, -1).await?;
+        }
+        new.apply(&mut tx, 1).await?;
+        sqlx::query(
+            "INSERT INTO projected_orders (order_id, restaurant_id, day, cancelled, total_paise, line_totals) \
+             VALUES (?, ?, ?, ?, ?, ?) \
+             ON DUPLICATE KEY UPDATE day = VALUES(day), cancelled = VALUES(cancelled), \
+             total_paise = VALUES(total_paise), line_totals = VALUES(line_totals)",
+        )
+        .bind(order_id)
+        .bind(restaurant_id)
+        .bind(new.day)
+        .bind(new.cancelled)
+        .bind(new.total_paise)
+        .bind(Json(&new.line_totals))
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    sqlx::query("UPDATE projection_cursors SET last_event_id = ? WHERE name = ?")
+        .bind(last_id)
+        .bind(CURSOR)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    Ok(events.len())
+}
+
+// Background worker; catches up in full batches, then polls.
+pub fn spawn_worker(pool: MySqlPool, config: Arc<ConfigService>) {
+    tokio::spawn(async move {
+        loop {
+            match run_once(&pool, &config).await {
+                Ok(n) if n as i64 == BATCH => continue,
+                Ok(_) => {}
+                Err(err) => logging::warn("sales projection failed", json!({ "error": format!("{err:#}") })),
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    });
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct DailySales {
+    pub day: NaiveDate,
+    pub orders: i64,
+    pub cancelled_orders: i64,
+    pub revenue_paise: i64,
+}
+
+impl ApiSchema for DailySales {
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["day", "orders", "cancelled_orders", "revenue_paise"],
+            "properties": {
+                "day": { "type": "string", "format": "date" },
+                "orders": { "type": "integer" },
+                "cancelled_orders": { "type": "integer" },
+                "revenue_paise": { "type": "integer", "description": "Totals of orders not cancelled, tax included" }
+            }
+        })
+    }
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct ItemPopularity {
+    pub sku: String,
+    pub name: String,
+    pub quantity: i64,
+    pub revenue_paise: i64,
+}
+
+impl ApiSchema for ItemPopularity {
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["sku", "name", "quantity", "revenue_paise"],
+            "properties": {
+                "sku": { "type": "string" },
+                "name": { "type": "string" },
+                "quantity": { "type": "integer" },
+                "revenue_paise": { "type": "integer", "description": "Line subtotals, before tax" }
+            }
+        })
+    }
+}
+
+// Query of the analytics endpoints: local days, both inclusive. The default is the
+// last 30 days up to today.
+#[derive(Debug, Deserialize)]
+pub struct RangeQuery {
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+    pub limit: Option<u32>,
+}
+
+impl RangeQuery {
+    fn days(&self, config: &ConfigService) -> AppResult<(NaiveDate, NaiveDate)> {
+        let today = Utc::now().with_timezone(&config.local_offset()).date_naive();
+        let to = self.to.unwrap_or(today);
+        let from = self.from.unwrap_or_else(|| to - Days::new(DEFAULT_RANGE_DAYS - 1));
+        if from > to {
+            return Err(AppError::BadRequest("from must not be after to".to_string()));
+        }
+        if to - Days::new(MAX_RANGE_DAYS) > from {
+            return Err(AppError::BadRequest(format!("the range can span at most {MAX_RANGE_DAYS} days")));
+        }
+        Ok((from, to))
+    }
+}
+
+// Registers the admin analytics endpoints.
+pub fn routes(router: Router) -> Router {
+    router
+        .get("/api/admin/analytics/daily-sales", daily_sales)
+        .summary("Orders, cancellations and revenue per day")
+        .urgency(6)
+        .requires_tenant_admin()
+        .query_param("from", false)
+        .query_param("to", false)
+        .response_schema(json!({ "type": "array", "items": DailySales::schema() }))
+        .get("/api/admin/analytics/items", item_popularity)
+        .summary("Best-selling items over a range of days")
+        .urgency(6)
+        .requires_tenant_admin()
+        .query_param("from", false)
+        .query_param("to", false)
+        .query_param("limit", false)
+        .response_schema(json!({ "type": "array", "items": ItemPopularity::schema() }))
+}
+
+async fn daily_sales(
+    TenantAdmin(restaurant): TenantAdmin,
+    Query(range): Query<RangeQuery>,
+    State(services): State,
+) -> AppResult<Response<Bytes>> {
+    let (from, to) = range.days(&services.runtime_config)?;
+    let days: Vec<DailySales> = sqlx::query_as(
+        "SELECT day, orders, cancelled_orders, revenue_paise FROM daily_sales \
+         WHERE restaurant_id = ? AND day BETWEEN ? AND ? ORDER BY day",
+    )
+    .bind(restaurant.id)
+    .bind(from)
+    .bind(to)
+    .fetch_all(services.db()?)
+    .await?;
+    ResponseBuilder::json(StatusCode::OK, &days)
+}
+
+async fn item_popularity(
+    TenantAdmin(restaurant): TenantAdmin,
+    Query(range): Query<RangeQuery>,
+    State(services): State,
+) -> AppResult<Response<Bytes>> {
+    let (from, to) = range.days(&services.runtime_config)?;
+    let limit = range.limit.unwrap_or(20).clamp(1, 200);
+    let items: Vec<ItemPopularity> = sqlx::query_as(
+        "SELECT m.sku, m.name, CAST(SUM(p.quantity) AS SIGNED) AS quantity, \
+         CAST(SUM(p.revenue_paise) AS SIGNED) AS revenue_paise \
+         FROM item_popularity p JOIN menu_items m ON m.id = p.menu_item_id \
+         WHERE p.restaurant_id = ? AND p.day BETWEEN ? AND ? \
+         GROUP BY m.id, m.sku, m.name HAVING quantity > 0 ORDER BY quantity DESC, m.name LIMIT ?",
+    )
+    .bind(restaurant.id)
+    .bind(from)
+    .bind(to)
+    .bind(limit)
+    .fetch_all(services.db()?)
+    .await?;
+    ResponseBuilder::json(StatusCode::OK, &items)
+}
@@ -0,0 +1,157 @@
+use crate::config;
+use crate::logging;
+use anyhow::Result;
+use serde_json::{json, Value};
+use std::fs::{self, File};
+use std::io::Write;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+// qlog traces (JSON-SEQ, qlog 0.3) for connection debugging, viewable in qvis.
+//   QLOG_DIR         directory for trace files; unset disables tracing
+//   QLOG_ALL         true traces every connection; otherwise only connections switched
+//                    on through POST /api/admin/connections/:id/qlog are traced
+//   QLOG_SAMPLE_MS   how often transport metrics are sampled (default 250)
+//
+// quinn 0.11 doesn't emit packet-level qlog events, so traces are built from what it
+// does expose: connection start and close (with the close reason), periodic
+// recovery:metrics_updated samples of RTT, congestion window and loss counters, and
+// failed handshakes, which go to handshake-failures.sqlog.
+#[derive(Debug, Clone)]
+pub struct QlogConfig {
+    pub dir: Option<PathBuf>,
+    pub trace_all: bool,
+    pub sample_interval: Duration,
+}
+
+impl QlogConfig {
+    pub fn from_env() -> Self {
+        Self {
+            dir: config::var("QLOG_DIR").map(PathBuf::from),
+            trace_all: config::var("QLOG_ALL").is_some_and(|v| v == "true"),
+            sample_interval: Duration::from_millis(
+                config::var("QLOG_SAMPLE_MS")
+                    .and_then(|v| v.parse().ok())
+                    .unwrap_or(250),
+            ),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.dir.is_some()
+    }
+
+    // Starts tracing `conn` in the background until it closes. Returns the trace file.
+    pub fn trace(&self, conn: &quinn::Connection) -> Result<Option<PathBuf>> {
+        let Some(dir) = &self.dir else {
+            return Ok(None);
+        };
+        fs::create_dir_all(dir)?;
+        let path = dir.join(format!("{}-{}.sqlog", now_millis(), conn.stable_id()));
+        let mut trace = Trace::create(&path, &format!("connection {}", conn.stable_id()))?;
+        let (conn, interval) = (conn.clone(), self.sample_interval);
+        tokio::spawn(async move {
+            trace.event(
+                "connectivity:connection_started",
+                json!({ "dst_ip": conn.remote_address().ip().to_string(), "dst_port": conn.remote_address().port() }),
+            );
+            let mut ticker = tokio::time::interval(interval);
+            let mut last = Value::Null;
+            let reason = loop {
+                tokio::select! {
+                    reason = conn.closed() => break reason,
+                    _ = ticker.tick() => {
+                        let sample = metrics(&conn);
+                        if sample != last {
+                            trace.event("recovery:metrics_updated", sample.clone());
+                            last = sample;
+                        }
+                    }
+                }
+            };
+            trace.event("recovery:metrics_updated", metrics(&conn));
+            trace.event("connectivity:connection_closed", json!({ "owner": "remote", "reason": reason.to_string() }));
+        });
+        Ok(Some(path))
+    }
+
+    // Records a handshake that never produced a connection.
+    pub fn handshake_failed(&self, remote: SocketAddr, error: &str) {
+        let Some(dir) = &self.dir else {
+            return;
+        };
+        let path = dir.join("handshake-failures.sqlog");
+        let result = fs::create_dir_all(dir).and_then(|()| {
+            let new = !path.exists();
+            let file = fs::OpenOptions::new().create(true).append(true).open(&path)?;
+            let mut trace = Trace { file, started: Instant::now() };
+            if new {
+                trace.header("handshake failures")?;
+            }
+            trace.write(&json!({
+                "time": 0,
+                "name": "connectivity:connection_closed",
+                "data": { "owner": "local", "dst_ip": remote.ip().to_string(), "dst_port": remote.port(), "reason": error, "unix_ms": now_millis() }
+            }))
+        });
+        if let Err(err) = result {
+            logging::warn("qlog write failed", json!({ "path": path.display().to_string(), "error": err.to_string() }));
+        }
+    }
+}
+
+fn now_millis() -> u128 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis())
+}
+
+fn metrics(conn: &quinn::Connection) -> Value {
+    let stats = conn.stats();
+    json!({
+        "smoothed_rtt": stats.path.rtt.as_secs_f64() * 1000.0,
+        "congestion_window": stats.path.cwnd,
+        "packets_sent": stats.path.sent_packets,
+        "packets_lost": stats.path.lost_packets,
+        "bytes_lost": stats.path.lost_bytes,
+        "congestion_events": stats.path.congestion_events,
+        "mtu": stats.path.current_mtu,
+    })
+}
+
+// One JSON-SEQ trace file: a header record, then one record per event, each prefixed
+// with the RS character.
+struct Trace {
+    file: File,
+    started: Instant,
+}
+
+impl Trace {
+    fn create(path: &PathBuf, title: &str) -> Result<Self> {
+        let mut trace = Self { file: File::create(path)?, started: Instant::now() };
+        trace.header(title)?;
+        Ok(trace)
+    }
+
+    fn header(&mut self, title: &str) -> std::io::Result<()> {
+        self.write(&json!({
+            "qlog_version": "0.3",
+            "qlog_format": "JSON-SEQ",
+            "title": title,
+            "trace": {
+                "vantage_point": { "type": "server" },
+                "common_fields": { "time_format": "relative", "reference_time": now_millis() as u64 }
+            }
+        }))
+    }
+
+    fn event(&mut self, name: &str, data: Value) {
+        let time = self.started.elapsed().as_secs_f64() * 1000.0;
+        if let Err(err) = self.write(&json!({ "time": time, "name": name, "data": data })) {
+            logging::warn("qlog write failed", json!({ "event": name, "error": err.to_string() }));
+        }
+    }
+
+    fn write(&mut self, record: &Value) -> std::io::Result<()> {
+        writeln!(self.file, "\u{1e}{record}")
+    }
+}
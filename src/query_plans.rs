@@ -0,0 +1,171 @@
+use crate::error::{AppError, AppResult};
+use crate::extract::{Admin, Query, State};
+use crate::openapi::ApiSchema;
+use crate::response::{CachePolicy, ResponseBuilder};
+use crate::router::Router;
+use anyhow::Result;
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use http::{Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sqlx::mysql::MySqlPool;
+use sqlx::Row;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+// Query plan observability. The slowest statements come from MySQL's slow query log,
+// which must be written to a table (slow_query_log = ON, log_output = TABLE); each is
+// explained again against the current schema and data, so the plan shown is the one
+// the statement would get now. Statement cache effectiveness comes from the server's
+// prepare/execute counters, exported with the other metrics.
+
+const DEFAULT_LIMIT: u32 = 10;
+const MAX_LIMIT: u32 = 50;
+
+// One distinct statement from the slow query log.
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct SlowQuery {
+    pub sql_text: String,
+    pub executions: i64,
+    pub max_query_secs: f64,
+    pub avg_query_secs: f64,
+    pub max_rows_examined: i64,
+    pub last_seen: DateTime<Utc>,
+    // EXPLAIN FORMAT=JSON output; None when the statement can't be explained.
+    #[sqlx(skip)]
+    pub plan: Option<Value>,
+    #[sqlx(skip)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub plan_error: Option<String>,
+}
+
+impl ApiSchema for SlowQuery {
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": [
+                "sql_text", "executions", "max_query_secs", "avg_query_secs", "max_rows_examined", "last_seen", "plan"
+            ],
+            "properties": {
+                "sql_text": { "type": "string" },
+                "executions": { "type": "integer", "minimum": 1 },
+                "max_query_secs": { "type": "number" },
+                "avg_query_secs": { "type": "number" },
+                "max_rows_examined": { "type": "integer" },
+                "last_seen": { "type": "string", "format": "date-time" },
+                "plan": { "type": ["object", "null"] },
+                "plan_error": { "type": "string" }
+            }
+        })
+    }
+}
+
+// Only single statements EXPLAIN can describe without running them.
+fn explainable(sql: &str) -> bool {
+    let keyword = sql.trim_start().split_whitespace().next().unwrap_or_default();
+    let single = !sql.trim_end().trim_end_matches(';').contains(';');
+    single && ["SELECT", "UPDATE", "DELETE", "INSERT", "REPLACE"].iter().any(|k| keyword.eq_ignore_ascii_case(k))
+}
+
+// The `limit` slowest statements logged against this database, by their slowest run,
+// each with its current plan.
+pub async fn slowest(pool: &MySqlPool, limit: u32) -> Result<Vec<SlowQuery>> {
+    let mut queries: Vec<SlowQuery> = sqlx::query_as(
+        "SELECT CAST(sql_text AS CHAR) AS sql_text, COUNT(*) AS executions, \
+         CAST(MAX(TIME_TO_SEC(query_time) + MICROSECOND(query_time) / 1000000) AS DOUBLE) AS max_query_secs, \
+         CAST(AVG(TIME_TO_SEC(query_time) + MICROSECOND(query_time) / 1000000) AS DOUBLE) AS avg_query_secs, \
+         CAST(MAX(rows_examined) AS SIGNED) AS max_rows_examined, MAX(start_time) AS last_seen \
+         FROM mysql.slow_log WHERE db = DATABASE() GROUP BY sql_text ORDER BY max_query_secs DESC LIMIT ?",
+    )
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+    for query in &mut queries {
+        if !explainable(&query.sql_text) {
+            query.plan_error =
+                Some("only single SELECT, INSERT, REPLACE, UPDATE and DELETE statements can be explained".to_string());
+            continue;
+        }
+        // Sent as text: EXPLAIN can't be prepared, and every logged statement differs anyway.
+        let sql = format!("EXPLAIN FORMAT=JSON {}", query.sql_text.trim_end().trim_end_matches(';'));
+        let explained = sqlx::raw_sql(&sql).fetch_one(pool).await.and_then(|row| row.try_get::<String, _>(0));
+        match explained.map(|plan| serde_json::from_str(&plan)) {
+            Ok(Ok(plan)) => query.plan = Some(plan),
+            Ok(Err(err)) => query.plan_error = Some(format!("unreadable plan: {err}")),
+            Err(err) => query.plan_error = Some(err.to_string()),
+        }
+    }
+    Ok(queries)
+}
+
+// Prepared statement counters of the MySQL server, in Prometheus text format. They
+// count every client of the server, not only this one. A prepare is a statement
+// cache miss, so 1 - prepares / executes is the share of executions served from a
+// cached statement.
+pub async fn render_statement_cache(pool: &MySqlPool) -> Result<String> {
+    let status: HashMap<String, String> = sqlx::query_as(
+        "SELECT VARIABLE_NAME, VARIABLE_VALUE FROM performance_schema.global_status \
+         WHERE VARIABLE_NAME IN ('Com_stmt_prepare', 'Com_stmt_execute', 'Com_stmt_reprepare')",
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .collect();
+    let counter = |name: &str| status.get(name).and_then(|v| v.parse::<u64>().ok()).unwrap_or(0);
+    let (prepares, executes) = (counter("Com_stmt_prepare"), counter("Com_stmt_execute"));
+    let hit_ratio = if executes == 0 { 0.0 } else { (1.0 - prepares as f64 / executes as f64).max(0.0) };
+
+    let mut out = String::new();
+    let counters = [
+        ("rotiride_mysql_stmt_prepare_total", "Statements prepared by the MySQL server", prepares),
+        ("rotiride_mysql_stmt_execute_total", "Prepared statements executed by the MySQL server", executes),
+        (
+            "rotiride_mysql_stmt_reprepare_total",
+            "Prepared statements re-prepared after a schema change",
+            counter("Com_stmt_reprepare"),
+        ),
+    ];
+    for (name, help, value) in counters {
+        let _ = writeln!(out, "# HELP {name} {help}");
+        let _ = writeln!(out, "# TYPE {name} counter");
+        let _ = writeln!(out, "{name} {value}");
+    }
+    let name = "rotiride_mysql_stmt_cache_hit_ratio";
+    let _ = writeln!(out, "# HELP {name} Share of prepared statement executions that needed no prepare");
+    let _ = writeln!(out, "# TYPE {name} gauge");
+    let _ = writeln!(out, "{name} {hit_ratio:.4}");
+    Ok(out)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SlowQueryParams {
+    pub limit: Option<u32>,
+}
+
+// Registers the admin query plan endpoint.
+pub fn routes(router: Router) -> Router {
+    router
+        .get("/api/admin/db/slow-queries", slow_queries)
+        .summary("The slowest statements in the MySQL slow query log, with their current plans")
+        .urgency(7)
+        .requires_admin()
+        .cache(CachePolicy::NoStore)
+        .query_param("limit", false)
+        .response_schema(json!({ "type": "array", "items": SlowQuery::schema() }))
+}
+
+async fn slow_queries(
+    _: Admin,
+    Query(params): Query<SlowQueryParams>,
+    State(services): State,
+) -> AppResult<Response<Bytes>> {
+    let limit = params.limit.unwrap_or(DEFAULT_LIMIT);
+    if !(1..=MAX_LIMIT).contains(&limit) {
+        return Err(AppError::BadRequest(format!("limit must be between 1 and {MAX_LIMIT}")));
+    }
+    let queries = slowest(services.db()?, limit).await.map_err(|err| {
+        AppError::ServiceUnavailable(format!("slow query log is not readable (it must be logged to a table): {err:#}"))
+    })?;
+    ResponseBuilder::json(StatusCode::OK, &queries)
+}
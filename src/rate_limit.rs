@@ -0,0 +1,197 @@
+use crate::app::AppServices;
+use crate::config;
+use crate::error::{AppError, AppResult};
+use crate::middleware::Middleware;
+use crate::request::RequestContext;
+use anyhow::{anyhow, Result};
+use futures::future::BoxFuture;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// A token-bucket limit: `capacity` requests per `period`, refilled continuously.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+    pub capacity: u32,
+    pub period: Duration,
+}
+
+impl RateLimit {
+    // Parses "<n>/<sec|min|hour>", e.g. "5/min".
+    pub fn parse(spec: &str) -> Result<Self> {
+        let (count, unit) = spec
+            .trim()
+            .split_once('/')
+            .ok_or_else(|| anyhow!("rate limit must look like 60/min, got {spec}"))?;
+        let capacity: u32 = count.trim().parse().map_err(|_| anyhow!("invalid rate limit count in {spec}"))?;
+        let period = match unit.trim() {
+            "s" | "sec" | "second" => Duration::from_secs(1),
+            "m" | "min" | "minute" => Duration::from_secs(60),
+            "h" | "hour" => Duration::from_secs(3600),
+            other => return Err(anyhow!("invalid rate limit unit {other} in {spec}")),
+        };
+        if capacity == 0 {
+            return Err(anyhow!("rate limit count must be positive in {spec}"));
+        }
+        Ok(Self { capacity, period })
+    }
+
+    fn refill_per_sec(&self) -> f64 {
+        self.capacity as f64 / self.period.as_secs_f64()
+    }
+}
+
+// Outcome of taking a token.
+#[derive(Debug, Clone, Copy)]
+pub enum Decision {
+    Allowed { remaining: u32 },
+    Limited { retry_after: Duration },
+}
+
+// Storage for bucket state. The in-memory backend is per instance; a shared
+// backend makes limits hold across instances.
+pub trait RateLimitBackend: Send + Sync {
+    fn take<'a>(&'a self, key: &'a str, limit: RateLimit) -> BoxFuture<'a, Result<Decision>>;
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+    // Period of the limit this bucket enforces; after that long idle it is full again.
+    period: Duration,
+}
+
+// Process-local buckets. Idle buckets are pruned once the map grows past `max_keys`.
+pub struct InMemoryBackend {
+    buckets: Mutex<HashMap<String, Bucket>>,
+    max_keys: usize,
+}
+
+impl InMemoryBackend {
+    pub fn new(max_keys: usize) -> Self {
+        Self {
+            buckets: Mutex::new(HashMap::new()),
+            max_keys,
+        }
+    }
+
+    fn take_now(&self, key: &str, limit: RateLimit, now: Instant) -> Decision {
+        let mut buckets = self.buckets.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        // A bucket that has been idle for a full period is back to capacity, so it can be dropped.
+        if buckets.len() >= self.max_keys {
+            buckets.retain(|_, bucket| now.duration_since(bucket.updated) < bucket.period);
+        }
+
+        let bucket = buckets.entry(key.to_string()).or_insert(Bucket {
+            tokens: limit.capacity as f64,
+            updated: now,
+            period: limit.period,
+        });
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * limit.refill_per_sec()).min(limit.capacity as f64);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Decision::Allowed {
+                remaining: bucket.tokens as u32,
+            }
+        } else {
+            let wait = (1.0 - bucket.tokens) / limit.refill_per_sec();
+            Decision::Limited {
+                retry_after: Duration::from_secs_f64(wait),
+            }
+        }
+    }
+}
+
+impl RateLimitBackend for InMemoryBackend {
+    fn take<'a>(&'a self, key: &'a str, limit: RateLimit) -> BoxFuture<'a, Result<Decision>> {
+        let decision = self.take_now(key, limit, Instant::now());
+        Box::pin(async move { Ok(decision) })
+    }
+}
+
+// Applies token-bucket limits per route group. Each rule is a path prefix; the longest
+// matching prefix wins, and requests matching no rule use the default limit (if any).
+// Buckets are keyed by rule and client IP.
+pub struct RateLimitMiddleware {
+    rules: Vec<(String, RateLimit)>,
+    default: Option<RateLimit>,
+    backend: Box<dyn RateLimitBackend>,
+}
+
+impl RateLimitMiddleware {
+    pub fn new(backend: Box<dyn RateLimitBackend>) -> Self {
+        Self {
+            rules: Vec::new(),
+            default: None,
+            backend,
+        }
+    }
+
+    // Limits every path starting with `prefix`.
+    pub fn rule(mut self, prefix: &str, limit: RateLimit) -> Self {
+        self.rules.push((prefix.to_string(), limit));
+        // Keep longest prefixes first so the most specific rule is found first.
+        self.rules.sort_by_key(|(prefix, _)| std::cmp::Reverse(prefix.len()));
+        self
+    }
+
+    pub fn default_limit(mut self, limit: RateLimit) -> Self {
+        self.default = Some(limit);
+        self
+    }
+
+    // Builds the middleware from RATE_LIMITS, a comma-separated list of
+    // "<path prefix>=<n>/<unit>" entries plus an optional "default=<n>/<unit>", e.g.
+    //   RATE_LIMITS=/api/auth/otp=5/min,/api/menu=60/min,default=300/min
+    // Returns None when RATE_LIMITS is unset.
+    pub fn from_env(backend: Box<dyn RateLimitBackend>) -> Result<Option<Self>> {
+        let Some(spec) = config::var("RATE_LIMITS") else {
+            return Ok(None);
+        };
+        let mut middleware = Self::new(backend);
+        for entry in spec.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let (prefix, limit) = entry
+                .split_once('=')
+                .ok_or_else(|| anyhow!("RATE_LIMITS entry must be <prefix>=<limit>, got {entry}"))?;
+            let limit = RateLimit::parse(limit)?;
+            middleware = match prefix.trim() {
+                "default" => middleware.default_limit(limit),
+                prefix => middleware.rule(prefix, limit),
+            };
+        }
+        Ok(Some(middleware))
+    }
+
+    fn limit_for(&self, path: &str) -> Option<(&str, RateLimit)> {
+        self.rules
+            .iter()
+            .find(|(prefix, _)| path.starts_with(prefix.as_str()))
+            .map(|(prefix, limit)| (prefix.as_str(), *limit))
+            .or_else(|| self.default.map(|limit| ("default", limit)))
+    }
+}
+
+impl Middleware for RateLimitMiddleware {
+    fn name(&self) -> &'static str {
+        "rate_limit"
+    }
+
+    fn before<'a>(&'a self, ctx: &'a RequestContext, _services: &'a AppServices) -> BoxFuture<'a, AppResult<()>> {
+        Box::pin(async move {
+            let Some((group, limit)) = self.limit_for(&ctx.path) else {
+                return Ok(());
+            };
+            let key = format!("{group}|{}", ctx.remote_addr.ip());
+            match self.backend.take(&key, limit).await? {
+                Decision::Allowed { .. } => Ok(()),
+                Decision::Limited { retry_after } => Err(AppError::TooManyRequests {
+                    retry_after_secs: retry_after.as_secs_f64().ceil() as u64,
+                }),
+            }
+        })
+    }
+}
@@ -0,0 +1,318 @@
+use crate::app::AppServices;
+use crate::error::{AppError, AppResult, FieldError};
+use crate::extract::{Admin, State};
+use crate::logging;
+use crate::menu_schedule;
+use crate::openapi::ApiSchema;
+use crate::request::RequestContext;
+use crate::response::{CachePolicy, ResponseBuilder};
+use crate::router::Router;
+use crate::runtime_config::ConfigService;
+use crate::tenant;
+use crate::validation::Validate;
+use anyhow::Result;
+use bytes::Bytes;
+use chrono::{DateTime, FixedOffset, Utc};
+use http::{Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sqlx::mysql::MySqlPool;
+use std::sync::Arc;
+use std::time::Duration;
+
+// "Frequently bought together". A nightly job counts, per restaurant, how many recent
+// orders contained each pair of items into item_recommendations; the endpoints read
+// those counts for one item or a whole cart and top up with the restaurant's best
+// sellers when an item has too little history.
+
+// Orders considered by the miner, and by the best-seller fallback.
+const PAIR_WINDOW_DAYS: u32 = 90;
+const TOP_SELLER_WINDOW_DAYS: u32 = 30;
+// Pairs seen together fewer times than this are noise.
+const MIN_TOGETHER: u32 = 2;
+// Local hour the miner runs at (runtime config utc_offset_minutes).
+const MINE_AT_HOUR: u32 = 3;
+const DEFAULT_LIMIT: u32 = 5;
+const MAX_LIMIT: u32 = 20;
+// Instances race for this MySQL named lock so only one mines each night.
+const MINER_LOCK: &str = "rotiride.recommendations";
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct Recommendation {
+    #[serde(skip)]
+    pub id: i64,
+    pub sku: String,
+    pub name: String,
+    pub category: String,
+    pub price_paise: i64,
+    // "bought_together" or "top_seller".
+    #[sqlx(skip)]
+    pub reason: &'static str,
+    #[serde(skip)]
+    pub score: i64,
+}
+
+impl ApiSchema for Recommendation {
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["sku", "name", "category", "price_paise", "reason"],
+            "properties": {
+                "sku": { "type": "string" },
+                "name": { "type": "string" },
+                "category": { "type": "string" },
+                "price_paise": { "type": "integer" },
+                "reason": { "type": "string", "enum": ["bought_together", "top_seller"] }
+            }
+        })
+    }
+}
+
+// Body of POST /api/menu/recommendations: the skus already in the cart.
+#[derive(Debug, Deserialize)]
+pub struct CartInput {
+    pub skus: Vec<String>,
+    #[serde(default)]
+    pub limit: Option<u32>,
+}
+
+impl ApiSchema for CartInput {
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["skus"],
+            "properties": {
+                "skus": { "type": "array", "minItems": 1, "maxItems": 50, "items": { "type": "string" } },
+                "limit": { "type": "integer", "minimum": 1, "maximum": MAX_LIMIT }
+            }
+        })
+    }
+}
+
+impl Validate for CartInput {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        if self.skus.is_empty() || self.skus.len() > 50 {
+            errors.push(FieldError::new("skus", "must list 1 to 50 items"));
+        }
+        if self.limit.is_some_and(|limit| limit == 0 || limit > MAX_LIMIT) {
+            errors.push(FieldError::new("limit", format!("must be between 1 and {MAX_LIMIT}")));
+        }
+        errors
+    }
+}
+
+// Rebuilds item_recommendations for every restaurant in one transaction, so readers
+// never see a half-built table. Returns the number of pairs stored.
+pub async fn mine(pool: &MySqlPool) -> Result<u64> {
+    let mut tx = pool.begin().await?;
+    sqlx::query("DELETE FROM item_recommendations").execute(&mut *tx).await?;
+    let stored = sqlx::query(
+        "INSERT INTO item_recommendations (restaurant_id, menu_item_id, recommended_item_id, together_count) \
+         SELECT o.restaurant_id, a.menu_item_id, b.menu_item_id, COUNT(DISTINCT o.id) \
+         FROM orders o \
+         JOIN order_items a ON a.order_id = o.id \
+         JOIN order_items b ON b.order_id = o.id AND b.menu_item_id <> a.menu_item_id \
+         WHERE o.status <> 'cancelled' AND o.created_at > NOW() - INTERVAL ? DAY \
+         GROUP BY o.restaurant_id, a.menu_item_id, b.menu_item_id \
+         HAVING COUNT(DISTINCT o.id) >= ?",
+    )
+    .bind(PAIR_WINDOW_DAYS)
+    .bind(MIN_TOGETHER)
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+    tx.commit().await?;
+    Ok(stored)
+}
+
+// mine() unless another instance holds the miner lock. Returns None when skipped.
+pub async fn mine_exclusive(pool: &MySqlPool) -> Result<Option<u64>> {
+    // Named locks belong to a connection, so take and release it on the same one.
+    let mut conn = pool.acquire().await?;
+    let locked: Option<i64> = sqlx::query_scalar("SELECT GET_LOCK(?, 0)")
+        .bind(MINER_LOCK)
+        .fetch_one(&mut *conn)
+        .await?;
+    if locked != Some(1) {
+        return Ok(None);
+    }
+    let result = mine(pool).await;
+    sqlx::query("SELECT RELEASE_LOCK(?)").bind(MINER_LOCK).execute(&mut *conn).await?;
+    result.map(Some)
+}
+
+// Time from `now` until the next MINE_AT_HOUR in local time.
+fn until_next_run(now: DateTime<Utc>, offset: FixedOffset) -> Duration {
+    let local = now.with_timezone(&offset).naive_local();
+    let mut next = local.date().and_hms_opt(MINE_AT_HOUR, 0, 0).expect("MINE_AT_HOUR is a valid hour");
+    if next <= local {
+        next += chrono::Duration::days(1);
+    }
+    (next - local).to_std().unwrap_or(Duration::from_secs(3600))
+}
+
+// Background miner, once a night.
+pub fn spawn_miner(pool: MySqlPool, config: Arc<ConfigService>) {
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(until_next_run(Utc::now(), config.local_offset())).await;
+            match mine_exclusive(&pool).await {
+                Ok(Some(pairs)) => logging::info("recommendations mined", json!({ "pairs": pairs })),
+                Ok(None) => {}
+                Err(err) => logging::warn("recommendation mining failed", json!({ "error": format!("{err:#}") })),
+            }
+        }
+    });
+}
+
+// Database access for one restaurant's recommendations.
+pub struct RecommendationRepository<'a> {
+    pool: &'a MySqlPool,
+    restaurant_id: i64,
+}
+
+impl<'a> RecommendationRepository<'a> {
+    pub fn new(pool: &'a MySqlPool, restaurant_id: i64) -> Self {
+        Self { pool, restaurant_id }
+    }
+
+    // Available items most often ordered with any of `skus`, excluding `skus`
+    // themselves, topped up with best sellers to `limit`.
+    pub async fn for_cart(&self, skus: &[String], limit: u32) -> Result<Vec<Recommendation>> {
+        let placeholders = vec!["?"; skus.len()].join(", ");
+        let sql = format!(
+            "SELECT m.id, m.sku, m.name, m.category, m.price_paise, \
+             CAST(SUM(r.together_count) AS SIGNED) AS score \
+             FROM item_recommendations r \
+             JOIN menu_items source ON source.id = r.menu_item_id \
+             JOIN menu_items m ON m.id = r.recommended_item_id \
+             WHERE r.restaurant_id = ? AND m.available \
+             AND source.sku IN ({placeholders}) AND m.sku NOT IN ({placeholders}) \
+             GROUP BY m.id, m.sku, m.name, m.category, m.price_paise \
+             ORDER BY score DESC, m.name LIMIT ?"
+        );
+        let mut query = sqlx::query_as::<_, Recommendation>(&sql).bind(self.restaurant_id);
+        for sku in skus.iter().chain(skus) {
+            query = query.bind(sku);
+        }
+        let mut recommendations = query.bind(limit).fetch_all(self.pool).await?;
+        for recommendation in &mut recommendations {
+            recommendation.reason = "bought_together";
+        }
+
+        let missing = limit as usize - recommendations.len();
+        if missing > 0 {
+            let fill: Vec<Recommendation> = self
+                .top_sellers(limit + skus.len() as u32)
+                .await?
+                .into_iter()
+                .filter(|top| !skus.contains(&top.sku) && !recommendations.iter().any(|r| r.id == top.id))
+                .take(missing)
+                .collect();
+            recommendations.extend(fill);
+        }
+        Ok(recommendations)
+    }
+
+    // Available items by units sold recently, from the item_popularity projection.
+    pub async fn top_sellers(&self, limit: u32) -> Result<Vec<Recommendation>> {
+        let mut top: Vec<Recommendation> = sqlx::query_as(
+            "SELECT m.id, m.sku, m.name, m.category, m.price_paise, CAST(SUM(p.quantity) AS SIGNED) AS score \
+             FROM item_popularity p \
+             JOIN menu_items m ON m.id = p.menu_item_id \
+             WHERE p.restaurant_id = ? AND m.available AND p.day > CURRENT_DATE - INTERVAL ? DAY \
+             GROUP BY m.id, m.sku, m.name, m.category, m.price_paise \
+             HAVING score > 0 ORDER BY score DESC, m.name LIMIT ?",
+        )
+        .bind(self.restaurant_id)
+        .bind(TOP_SELLER_WINDOW_DAYS)
+        .bind(limit)
+        .fetch_all(self.pool)
+        .await?;
+        for recommendation in &mut top {
+            recommendation.reason = "top_seller";
+        }
+        Ok(top)
+    }
+
+    async fn item_exists(&self, sku: &str) -> Result<bool> {
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM menu_items WHERE restaurant_id = ? AND sku = ?")
+            .bind(self.restaurant_id)
+            .bind(sku)
+            .fetch_one(self.pool)
+            .await?;
+        Ok(count > 0)
+    }
+}
+
+// Registers the guest recommendation endpoints and the admin rebuild trigger.
+pub fn routes(router: Router) -> Router {
+    router
+        .get("/api/menu/items/:sku/recommendations", item_recommendations)
+        .summary("Items frequently ordered with this one, or best sellers")
+        .cache(CachePolicy::public(300).stale_while_revalidate(3600))
+        .query_param("limit", false)
+        .response_schema(json!({ "type": "array", "items": Recommendation::schema() }))
+        .post("/api/menu/recommendations", cart_recommendations)
+        .summary("Suggestions to add to a cart before ordering")
+        .request_schema(CartInput::schema())
+        .response_schema(json!({ "type": "array", "items": Recommendation::schema() }))
+        .post("/api/admin/recommendations/rebuild", rebuild)
+        .summary("Recompute frequently-bought-together pairs now instead of tonight")
+        .urgency(6)
+        .requires_admin()
+}
+
+// Drops items that are outside their availability windows right now.
+async fn open_now(
+    services: &AppServices,
+    restaurant_id: i64,
+    mut recommendations: Vec<Recommendation>,
+) -> AppResult<Vec<Recommendation>> {
+    let now = Utc::now();
+    let schedules = menu_schedule::load(services.db()?, restaurant_id, services.runtime_config.local_offset()).await?;
+    recommendations.retain(|r| schedules.is_open(r.id, &r.category, now));
+    Ok(recommendations)
+}
+
+async fn item_recommendations(ctx: RequestContext, services: Arc<AppServices>) -> AppResult<Response<Bytes>> {
+    let restaurant = tenant::current(&ctx, &services).await?;
+    let sku = ctx.param("sku").unwrap_or_default().to_string();
+    let limit = match ctx.query_param("limit") {
+        Some(limit) => limit
+            .parse::<u32>()
+            .ok()
+            .filter(|limit| (1..=MAX_LIMIT).contains(limit))
+            .ok_or_else(|| AppError::BadRequest(format!("limit must be between 1 and {MAX_LIMIT}")))?,
+        None => DEFAULT_LIMIT,
+    };
+    let repository = RecommendationRepository::new(services.db()?, restaurant.id);
+    if !repository.item_exists(&sku).await? {
+        return Err(AppError::NotFound("menu item not found".to_string()));
+    }
+    let recommendations = repository.for_cart(&[sku], limit).await?;
+    let recommendations = open_now(&services, restaurant.id, recommendations).await?;
+    ResponseBuilder::json(StatusCode::OK, &recommendations)
+}
+
+async fn cart_recommendations(ctx: RequestContext, services: Arc<AppServices>) -> AppResult<Response<Bytes>> {
+    let restaurant = tenant::current(&ctx, &services).await?;
+    let input: CartInput = ctx.json()?;
+    let errors = input.validate();
+    if !errors.is_empty() {
+        return Err(AppError::Validation(errors));
+    }
+    let recommendations = RecommendationRepository::new(services.db()?, restaurant.id)
+        .for_cart(&input.skus, input.limit.unwrap_or(DEFAULT_LIMIT))
+        .await?;
+    let recommendations = open_now(&services, restaurant.id, recommendations).await?;
+    ResponseBuilder::json(StatusCode::OK, &recommendations)
+}
+
+async fn rebuild(_: Admin, State(services): State) -> AppResult<Response<Bytes>> {
+    match mine_exclusive(services.db()?).await? {
+        Some(pairs) => ResponseBuilder::json(StatusCode::OK, &json!({ "pairs": pairs })),
+        None => Err(AppError::Conflict("recommendations are already being rebuilt".to_string())),
+    }
+}
@@ -0,0 +1,153 @@
+use crate::error::{AppError, AppResult};
+use crate::i18n;
+use crate::mtls::ClientIdentity;
+use crate::multipart::Part;
+use crate::tenant::{self, Restaurant};
+use crate::validation::ValidationMiddleware;
+use bytes::Bytes;
+use http::{HeaderMap, Method, Request};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, OnceLock};
+use uuid::Uuid;
+
+// Everything a handler needs to know about an incoming request.
+pub struct RequestContext {
+    // Correlation id: the caller's x-request-id, or a generated UUID when absent or unusable.
+    pub request_id: String,
+    pub method: Method,
+    pub path: String,
+    // The request's own header map, moved out of the request; values are read from it
+    // rather than copied into strings.
+    pub headers: HeaderMap,
+    // Raw query string, decoded into `query` the first time a handler asks for it.
+    raw_query: Option<String>,
+    query: OnceLock<HashMap<String, String>>,
+    // Path parameters captured by the router (e.g. ":id").
+    pub params: HashMap<String, String>,
+    pub body: Bytes,
+    // Request schema of the matched route. json() checks the body against it when the
+    // handler reads it, which is after the handler has authenticated the caller.
+    pub body_schema: Option<Arc<Value>>,
+    // Parts of a multipart/form-data body, parsed while it streamed in. The raw body is
+    // left empty for such requests.
+    pub multipart: Option<Vec<Part>>,
+    // Language negotiated from Accept-Language, one of i18n::SUPPORTED.
+    pub lang: &'static str,
+    // Restaurant slug from a "/r/<slug>" prefix (already stripped from `path`) or the host.
+    pub tenant: Option<String>,
+    pub remote_addr: SocketAddr,
+    // Admin user of the verified client certificate on the mTLS admin listener.
+    pub client_identity: Option<ClientIdentity>,
+    // Recorded by the AdminOnly and TenantAdminOnly route middleware once the caller
+    // passes, so the Admin and TenantAdmin extractors hand the handler what was
+    // verified instead of checking again.
+    pub admin: OnceLock<()>,
+    pub tenant_admin: OnceLock<Restaurant>,
+}
+
+impl RequestContext {
+    // Builds a context from the request head and the fully buffered body. The head is
+    // taken apart, so its header map becomes the context's without a copy.
+    pub fn from_request(req: Request<()>, body: Bytes, remote_addr: SocketAddr) -> Self {
+        let (head, ()) = req.into_parts();
+        let (path, tenant) = tenant::split_path(head.uri.path());
+        let tenant = tenant.or_else(|| {
+            let host = head
+                .uri
+                .authority()
+                .map(|a| a.as_str())
+                .or_else(|| head.headers.get("host").and_then(|v| v.to_str().ok()))?;
+            tenant::slug_from_host(host)
+        });
+
+        let lang = i18n::negotiate(head.headers.get("accept-language").and_then(|v| v.to_str().ok()));
+
+        Self {
+            request_id: request_id_from(&head.headers),
+            method: head.method,
+            path,
+            raw_query: head.uri.query().map(str::to_string),
+            headers: head.headers,
+            query: OnceLock::new(),
+            params: HashMap::new(),
+            body,
+            body_schema: None,
+            multipart: None,
+            lang,
+            tenant,
+            remote_addr,
+            client_identity: None,
+            admin: OnceLock::new(),
+            tenant_admin: OnceLock::new(),
+        }
+    }
+
+    // Header names are already lowercase in HTTP/3; values that are not valid UTF-8 read as absent.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).and_then(|value| value.to_str().ok())
+    }
+
+    // Decoded query parameters; a repeated name keeps its last value.
+    pub fn query(&self) -> &HashMap<String, String> {
+        self.query.get_or_init(|| {
+            self.raw_query
+                .as_deref()
+                .map(|q| form_urlencoded::parse(q.as_bytes()).into_owned().collect())
+                .unwrap_or_default()
+        })
+    }
+
+    pub fn query_param(&self, name: &str) -> Option<&str> {
+        self.query().get(name).map(String::as_str)
+    }
+
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params.get(name).map(String::as_str)
+    }
+
+    // Returns the token from an "Authorization: Bearer <token>" header.
+    pub fn bearer_token(&self) -> Option<&str> {
+        self.header("authorization")?.strip_prefix("Bearer ")
+    }
+
+    // Returns the parts of a multipart/form-data body.
+    pub fn multipart(&self) -> AppResult<&[Part]> {
+        self.multipart
+            .as_deref()
+            .ok_or_else(|| AppError::BadRequest("expected a multipart/form-data body".into()))
+    }
+
+    // Returns the multipart part with the given field name.
+    pub fn part(&self, name: &str) -> AppResult<&Part> {
+        self.multipart()?
+            .iter()
+            .find(|part| part.name == name)
+            .ok_or_else(|| AppError::BadRequest(format!("missing multipart field {name}")))
+    }
+
+    // Deserializes the request body as JSON, once it matches the route's request schema.
+    pub fn json<T: DeserializeOwned>(&self) -> AppResult<T> {
+        if let Some(schema) = &self.body_schema {
+            ValidationMiddleware::validate_body(schema, &self.body).map_err(AppError::Validation)?;
+        }
+        serde_json::from_slice(&self.body).map_err(|err| AppError::BadRequest(format!("invalid JSON body: {err}")))
+    }
+}
+
+// Longest incoming x-request-id we accept before generating our own.
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+// Honors a well-formed incoming x-request-id so traces correlate across proxies;
+// anything empty, oversized or containing non-printable characters is replaced.
+pub fn request_id_from(headers: &HeaderMap) -> String {
+    headers
+        .get("x-request-id")
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .filter(|id| !id.is_empty() && id.len() <= MAX_REQUEST_ID_LEN && id.bytes().all(|b| b.is_ascii_graphic()))
+        .map(str::to_string)
+        .unwrap_or_else(|| Uuid::new_v4().to_string())
+}
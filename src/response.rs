@@ -0,0 +1,293 @@
+use crate::error::{AppError, AppResult, ErrorResponse};
+use crate::request::RequestContext;
+use bytes::{BufMut, Bytes, BytesMut};
+use chrono::{DateTime, Utc};
+use http::{header, HeaderValue, Method, Response, StatusCode};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::cell::RefCell;
+
+// Size of the blocks JSON bodies are serialized into, and the free space below which
+// a new block is reserved.
+const JSON_BUFFER_BLOCK: usize = 64 * 1024;
+const JSON_BUFFER_MIN_FREE: usize = 4 * 1024;
+
+thread_local! {
+    static JSON_BUFFER: RefCell<BytesMut> = RefCell::new(BytesMut::with_capacity(JSON_BUFFER_BLOCK));
+}
+
+// HTTP-date format used by Last-Modified and If-Modified-Since.
+pub const HTTP_DATE: &str = "%a, %d %b %Y %H:%M:%S GMT";
+
+// Cache-Control policy for a response. Routes register a default with
+// Router::cache; a handler can override it per response with ResponseBuilder::cached.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CachePolicy {
+    // Never stored anywhere; the default for authenticated endpoints.
+    NoStore,
+    // Browser cache only; shared caches must not keep it.
+    Private { max_age_secs: u64 },
+    // Any cache, including CDNs, may keep it for max_age_secs and then serve it stale
+    // for up to stale_while_revalidate_secs while refetching in the background.
+    Public { max_age_secs: u64, stale_while_revalidate_secs: u64 },
+}
+
+impl CachePolicy {
+    pub fn public(max_age_secs: u64) -> Self {
+        CachePolicy::Public { max_age_secs, stale_while_revalidate_secs: 0 }
+    }
+
+    pub fn private(max_age_secs: u64) -> Self {
+        CachePolicy::Private { max_age_secs }
+    }
+
+    // Allows serving stale copies for `secs` while revalidating; only meaningful for Public.
+    pub fn stale_while_revalidate(self, secs: u64) -> Self {
+        match self {
+            CachePolicy::Public { max_age_secs, .. } => CachePolicy::Public { max_age_secs, stale_while_revalidate_secs: secs },
+            other => other,
+        }
+    }
+
+    pub fn header_value(&self) -> String {
+        match self {
+            CachePolicy::NoStore => "no-store".to_string(),
+            CachePolicy::Private { max_age_secs } => format!("private, max-age={max_age_secs}"),
+            CachePolicy::Public { max_age_secs, stale_while_revalidate_secs: 0 } => format!("public, max-age={max_age_secs}"),
+            CachePolicy::Public { max_age_secs, stale_while_revalidate_secs } => {
+                format!("public, max-age={max_age_secs}, stale-while-revalidate={stale_while_revalidate_secs}")
+            }
+        }
+    }
+}
+
+// Body shape for clients that opt in to the response envelope, by sending
+// `Accept: application/json; profile="envelope"` or `X-Response-Envelope: 1`. Other
+// clients keep getting the bare payload or ErrorResponse, so they can move over one
+// at a time. `data` is null on errors; `errors` is empty on success.
+#[derive(Debug, Serialize)]
+pub struct ApiResponse<T> {
+    pub data: Option<T>,
+    pub meta: ResponseMeta,
+    pub errors: Vec<ErrorResponse>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ResponseMeta {
+    pub request_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pagination: Option<Pagination>,
+}
+
+// Where a list response stopped. Pass `next_cursor` back (the endpoint documents the
+// parameter, e.g. before_id) for the next page; None means this was the last one.
+#[derive(Debug, Clone, Serialize)]
+pub struct Pagination {
+    pub limit: u32,
+    pub next_cursor: Option<String>,
+}
+
+// Content type of enveloped bodies, echoing the profile asked for.
+const ENVELOPE_CONTENT_TYPE: &str = "application/json; profile=\"envelope\"";
+
+// Marks a response whose body is already an ApiResponse, so the router leaves it alone.
+#[derive(Debug, Clone, Copy)]
+struct Enveloped;
+
+// Helpers for building the responses returned by handlers.
+pub struct ResponseBuilder;
+
+impl ResponseBuilder {
+    // Serializes `value` as a JSON response with the given status.
+    pub fn json<T: Serialize>(status: StatusCode, value: &T) -> AppResult<Response<Bytes>> {
+        Ok(Response::builder()
+            .status(status)
+            .header("content-type", "application/json")
+            .body(Self::json_bytes(value)?)?)
+    }
+
+    // Serializes `value` straight into the thread's reusable buffer and splits the
+    // result off without copying. Successive bodies share the buffer's allocation, so
+    // most responses cost no allocation of their own; a new block is reserved once
+    // the current one is used up. A block is freed when every body split from it is
+    // dropped, so bodies kept for the life of the process should not come from here.
+    pub fn json_bytes<T: Serialize>(value: &T) -> AppResult<Bytes> {
+        JSON_BUFFER.with(|buffer| {
+            let mut buffer = buffer.borrow_mut();
+            if buffer.capacity() < JSON_BUFFER_MIN_FREE {
+                buffer.reserve(JSON_BUFFER_BLOCK);
+            }
+            match serde_json::to_writer((&mut *buffer).writer(), value) {
+                Ok(()) => Ok(buffer.split().freeze()),
+                Err(err) => {
+                    buffer.clear();
+                    Err(AppError::Internal(err.into()))
+                }
+            }
+        })
+    }
+
+    // Like json, but for clients that asked for the envelope the body is an ApiResponse
+    // carrying `pagination` in its meta. Other clients get the bare `value`.
+    pub fn json_enveloped<T: Serialize>(
+        ctx: &RequestContext,
+        status: StatusCode,
+        value: &T,
+        pagination: Option<Pagination>,
+    ) -> AppResult<Response<Bytes>> {
+        if !Self::wants_envelope(ctx) {
+            return Self::json(status, value);
+        }
+        let envelope = ApiResponse {
+            data: Some(value),
+            meta: ResponseMeta { request_id: ctx.request_id.clone(), pagination },
+            errors: Vec::new(),
+        };
+        let mut response = Response::builder()
+            .status(status)
+            .header("content-type", ENVELOPE_CONTENT_TYPE)
+            .body(Self::json_bytes(&envelope)?)?;
+        response.extensions_mut().insert(Enveloped);
+        Ok(response)
+    }
+
+    // Whether the client opted in to the envelope through the Accept profile or the
+    // X-Response-Envelope header.
+    pub fn wants_envelope(ctx: &RequestContext) -> bool {
+        if ctx.header("x-response-envelope").is_some_and(|v| v == "1" || v.eq_ignore_ascii_case("true")) {
+            return true;
+        }
+        ctx.header("accept").is_some_and(|accept| {
+            accept.split(',').any(|range| {
+                range.split(';').skip(1).filter_map(|param| param.split_once('=')).any(|(name, value)| {
+                    name.trim().eq_ignore_ascii_case("profile") && value.trim().trim_matches('"') == "envelope"
+                })
+            })
+        })
+    }
+
+    // Wraps a JSON response built the plain way (by a handler not yet moved to
+    // json_enveloped, or by the error renderer) in an ApiResponse. Non-JSON and
+    // already enveloped responses are returned unchanged.
+    pub fn envelope(response: Response<Bytes>, request_id: &str) -> Response<Bytes> {
+        let is_json = response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.starts_with("application/json"));
+        if !is_json || response.extensions().get::<Enveloped>().is_some() {
+            return response;
+        }
+        let (mut parts, body) = response.into_parts();
+        let meta = ResponseMeta { request_id: request_id.to_string(), pagination: None };
+        let envelope = if parts.status.is_client_error() || parts.status.is_server_error() {
+            match serde_json::from_slice::<ErrorResponse>(&body) {
+                Ok(error) => ApiResponse { data: None, meta, errors: vec![error] },
+                Err(_) => return Response::from_parts(parts, body),
+            }
+        } else {
+            match serde_json::from_slice::<serde_json::Value>(&body) {
+                Ok(data) => ApiResponse { data: Some(data), meta, errors: Vec::new() },
+                Err(_) => return Response::from_parts(parts, body),
+            }
+        };
+        let Ok(body) = Self::json_bytes(&envelope) else {
+            return Response::from_parts(parts, body);
+        };
+        parts.headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(ENVELOPE_CONTENT_TYPE));
+        parts.extensions.insert(Enveloped);
+        Response::from_parts(parts, body)
+    }
+
+    // Plain-text response.
+    pub fn text(status: StatusCode, body: impl Into<String>) -> AppResult<Response<Bytes>> {
+        Ok(Response::builder()
+            .status(status)
+            .header("content-type", "text/plain")
+            .body(Bytes::from(body.into()))?)
+    }
+
+    // 200 download response, e.g. a CSV export the browser should save as `filename`.
+    pub fn attachment(content_type: &str, filename: &str, body: impl Into<Bytes>) -> AppResult<Response<Bytes>> {
+        Ok(Response::builder()
+            .status(StatusCode::OK)
+            .header("content-type", content_type)
+            .header("content-disposition", format!("attachment; filename=\"{filename}\""))
+            .body(body.into())?)
+    }
+
+    // Empty 204 response.
+    pub fn no_content() -> AppResult<Response<Bytes>> {
+        Ok(Response::builder()
+            .status(StatusCode::NO_CONTENT)
+            .body(Bytes::new())?)
+    }
+
+    // Sets Cache-Control on `response`, replacing the route's default.
+    pub fn cached(mut response: Response<Bytes>, policy: CachePolicy) -> Response<Bytes> {
+        if let Ok(value) = HeaderValue::from_str(&policy.header_value()) {
+            response.headers_mut().insert(header::CACHE_CONTROL, value);
+        }
+        response
+    }
+
+    // Weak ETag derived from a serialized body: equal bodies get equal tags.
+    pub fn weak_etag(body: &[u8]) -> String {
+        format!("W/\"{}\"", hex::encode(&Sha256::digest(body)[..16]))
+    }
+
+    // Makes a successful GET/HEAD response conditional. Sets ETag (the handler's own,
+    // or a weak one computed from the body) and Last-Modified when known, then answers
+    // 304 Not Modified with no body if the client's If-None-Match or, absent that,
+    // If-Modified-Since shows its cached copy is current.
+    pub fn conditional(
+        ctx: &RequestContext,
+        mut response: Response<Bytes>,
+        last_modified: Option<DateTime<Utc>>,
+    ) -> AppResult<Response<Bytes>> {
+        if response.status() != StatusCode::OK || !matches!(ctx.method, Method::GET | Method::HEAD) {
+            return Ok(response);
+        }
+        let etag = match response.headers().get(header::ETAG).and_then(|v| v.to_str().ok()) {
+            Some(etag) => etag.to_string(),
+            None => {
+                let etag = Self::weak_etag(response.body());
+                response.headers_mut().insert(
+                    header::ETAG,
+                    HeaderValue::from_str(&etag).map_err(|e| AppError::Internal(e.into()))?,
+                );
+                etag
+            }
+        };
+        if let Some(modified) = last_modified {
+            let value = modified.format(HTTP_DATE).to_string();
+            response.headers_mut().insert(
+                header::LAST_MODIFIED,
+                HeaderValue::from_str(&value).map_err(|e| AppError::Internal(e.into()))?,
+            );
+        }
+
+        let not_modified = match ctx.header("if-none-match") {
+            Some(tags) => etag_matches(tags, &etag),
+            None => match (ctx.header("if-modified-since"), last_modified) {
+                (Some(since), Some(modified)) => DateTime::parse_from_rfc2822(since)
+                    .is_ok_and(|since| modified.timestamp() <= since.timestamp()),
+                _ => false,
+            },
+        };
+        if not_modified {
+            *response.status_mut() = StatusCode::NOT_MODIFIED;
+            *response.body_mut() = Bytes::new();
+            response.headers_mut().remove(header::CONTENT_TYPE);
+        }
+        Ok(response)
+    }
+}
+
+// Weak comparison of an If-None-Match list against `etag`, as GET requires.
+fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    if_none_match
+        .split(',')
+        .any(|tag| tag.trim() == "*" || opaque(tag) == opaque(etag))
+}
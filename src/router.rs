@@ -0,0 +1,620 @@
+use crate::app::AppServices;
+use crate::auth::AdminOnly;
+use crate::db_breaker;
+use crate::error::{render_error, AppError, AppResult};
+use crate::extract::IntoHandler;
+use crate::metrics::{Metrics, METRICS};
+use crate::middleware::Middleware;
+use crate::request::RequestContext;
+use crate::response::{CachePolicy, ResponseBuilder, HTTP_DATE};
+use crate::scheduler::{requested_urgency, DEFAULT_URGENCY};
+use crate::security::CorsConfig;
+use crate::tenant::TenantAdminOnly;
+use anyhow::anyhow;
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use http::{HeaderMap, HeaderValue, Method, Response};
+use serde_json::Value;
+use std::any::Any;
+use std::collections::HashMap;
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+
+// Future returned by every handler.
+pub type HandlerFuture = BoxFuture<'static, AppResult<Response<Bytes>>>;
+
+// Type-erased request handler.
+pub type Handler = Arc<dyn Fn(RequestContext, Arc<AppServices>) -> HandlerFuture + Send + Sync>;
+
+// One piece of a route pattern: a literal segment or a ":name" capture.
+enum Segment {
+    Static(String),
+    Param(String),
+}
+
+// Who may call a route, as advertised in the API documentation.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AuthRequirement {
+    #[default]
+    Public,
+    Admin,
+}
+
+// Documentation attached to a route and used to generate the OpenAPI spec.
+#[derive(Debug, Clone, Default)]
+pub struct RouteDoc {
+    pub summary: Option<String>,
+    pub auth: AuthRequirement,
+    // JSON Schema of the request body, if the route accepts one.
+    pub request_schema: Option<Arc<Value>>,
+    // JSON Schema of the successful response body.
+    pub response_schema: Option<Value>,
+    // Query parameters as (name, required) pairs.
+    pub query_params: Vec<(String, bool)>,
+    // Cache-Control for successful responses that don't set their own. None means
+    // no-store for admin routes and no header otherwise.
+    pub cache: Option<CachePolicy>,
+    // RFC 9218 urgency (0 most urgent, 7 least) the route runs at; None lets the
+    // client's Priority header decide.
+    pub urgency: Option<u8>,
+    pub deprecation: Option<Deprecation>,
+}
+
+impl RouteDoc {
+    fn cache_policy(&self) -> Option<CachePolicy> {
+        match (self.cache, self.auth) {
+            (Some(policy), _) => Some(policy),
+            (None, AuthRequirement::Admin) => Some(CachePolicy::NoStore),
+            (None, AuthRequirement::Public) => None,
+        }
+    }
+}
+
+// Documentation of deprecated routes unless a route names its own.
+const DEFAULT_DEPRECATION_LINK: &str = "/api/docs";
+
+// Marks a route as on its way out. Its responses carry a Deprecation header (RFC 9745),
+// a Sunset header (RFC 8594) once a removal date is set, and a Link to the docs, and
+// its requests are counted per route in rotiride_deprecated_route_requests_total.
+#[derive(Debug, Clone)]
+pub struct Deprecation {
+    pub since: DateTime<Utc>,
+    // When the route will be removed, if decided.
+    pub sunset: Option<DateTime<Utc>>,
+    // Where clients read about the replacement.
+    pub link: String,
+}
+
+impl Deprecation {
+    pub fn since(since: DateTime<Utc>) -> Self {
+        Self { since, sunset: None, link: DEFAULT_DEPRECATION_LINK.to_string() }
+    }
+
+    pub fn sunset(mut self, sunset: DateTime<Utc>) -> Self {
+        self.sunset = Some(sunset);
+        self
+    }
+
+    pub fn link(mut self, link: &str) -> Self {
+        self.link = link.to_string();
+        self
+    }
+
+    fn apply(&self, headers: &mut HeaderMap) {
+        // A structured field date: "@" and Unix seconds.
+        if let Ok(value) = HeaderValue::from_str(&format!("@{}", self.since.timestamp())) {
+            headers.insert("deprecation", value);
+        }
+        if let Some(sunset) = self.sunset
+            && let Ok(value) = HeaderValue::from_str(&sunset.format(HTTP_DATE).to_string())
+        {
+            headers.insert("sunset", value);
+        }
+        let mut link = format!("<{}>; rel=\"deprecation\"", self.link);
+        if self.sunset.is_some() {
+            link += &format!(", <{}>; rel=\"sunset\"", self.link);
+        }
+        if let Ok(value) = HeaderValue::from_str(&link) {
+            headers.append(http::header::LINK, value);
+        }
+    }
+}
+
+// A registered route.
+pub struct Route {
+    pub method: Method,
+    pub path: String,
+    pub doc: RouteDoc,
+    segments: Vec<Segment>,
+    handler: Handler,
+    // Middleware declared by the route's group or its auth requirement; runs after the
+    // router-wide middleware.
+    middleware: Vec<Arc<dyn Middleware>>,
+}
+
+impl Route {
+    // Names of the ":name" captures in the path pattern, in order.
+    pub fn param_names(&self) -> impl Iterator<Item = &str> {
+        self.segments.iter().filter_map(|segment| match segment {
+            Segment::Param(name) => Some(name.as_str()),
+            Segment::Static(_) => None,
+        })
+    }
+
+    // Matches the request path against the pattern, returning captured parameters.
+    fn matches(&self, path: &str) -> Option<HashMap<String, String>> {
+        let parts: Vec<&str> = path.trim_matches('/').split('/').filter(|p| !p.is_empty()).collect();
+        if parts.len() != self.segments.len() {
+            return None;
+        }
+
+        let mut params = HashMap::new();
+        for (segment, part) in self.segments.iter().zip(parts) {
+            match segment {
+                Segment::Static(literal) if literal == part => {}
+                Segment::Static(_) => return None,
+                Segment::Param(name) => {
+                    params.insert(name.clone(), part.to_string());
+                }
+            }
+        }
+        Some(params)
+    }
+}
+
+// Maps (method, path pattern) pairs to handlers.
+#[derive(Default)]
+pub struct Router {
+    routes: Vec<Route>,
+    fallback: Option<Handler>,
+    middleware: Vec<Arc<dyn Middleware>>,
+}
+
+impl Router {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Registers a handler for `method` on `path`. Path segments starting with ':' are captures.
+    // The handler is either Fn(RequestContext, Arc<AppServices>) or an async fn of
+    // extractors (see extract::FromRequest).
+    pub fn route<H, Args>(mut self, method: Method, path: &str, handler: H) -> Self
+    where
+        H: IntoHandler<Args>,
+    {
+        let segments = path
+            .trim_matches('/')
+            .split('/')
+            .filter(|p| !p.is_empty())
+            .map(|p| match p.strip_prefix(':') {
+                Some(name) => Segment::Param(name.to_string()),
+                None => Segment::Static(p.to_string()),
+            })
+            .collect();
+
+        self.routes.push(Route {
+            method,
+            path: path.to_string(),
+            doc: RouteDoc::default(),
+            segments,
+            handler: handler.into_handler(),
+            middleware: Vec::new(),
+        });
+        self
+    }
+
+    // Declares routes sharing a path prefix, middleware and auth requirement:
+    //
+    //     router.group("/api/admin/config", |group| {
+    //         group.requires_admin().get("", list_config).put("/:key", put_config)
+    //     })
+    //
+    // Group middleware only runs for requests that matched one of the group's routes.
+    pub fn group(self, prefix: &str, build: impl FnOnce(RouteGroup) -> RouteGroup) -> Self {
+        build(RouteGroup {
+            router: self,
+            prefix: prefix.trim_end_matches('/').to_string(),
+            middleware: Vec::new(),
+            auth: AuthRequirement::Public,
+        })
+        .router
+    }
+
+    pub fn get<H, Args>(self, path: &str, handler: H) -> Self
+    where
+        H: IntoHandler<Args>,
+    {
+        self.route(Method::GET, path, handler)
+    }
+
+    pub fn post<H, Args>(self, path: &str, handler: H) -> Self
+    where
+        H: IntoHandler<Args>,
+    {
+        self.route(Method::POST, path, handler)
+    }
+
+    pub fn put<H, Args>(self, path: &str, handler: H) -> Self
+    where
+        H: IntoHandler<Args>,
+    {
+        self.route(Method::PUT, path, handler)
+    }
+
+    pub fn patch<H, Args>(self, path: &str, handler: H) -> Self
+    where
+        H: IntoHandler<Args>,
+    {
+        self.route(Method::PATCH, path, handler)
+    }
+
+    pub fn delete<H, Args>(self, path: &str, handler: H) -> Self
+    where
+        H: IntoHandler<Args>,
+    {
+        self.route(Method::DELETE, path, handler)
+    }
+
+    // Sets the summary of the most recently registered route.
+    pub fn summary(self, summary: &str) -> Self {
+        self.with_last_doc(|doc| doc.summary = Some(summary.to_string()))
+    }
+
+    // Requires the global admin token for the most recently registered route and documents
+    // it as admin-only, like RouteGroup::requires_admin.
+    pub fn requires_admin(self) -> Self {
+        self.with_last_auth(Arc::new(AdminOnly))
+    }
+
+    // Requires an admin of the request's restaurant (or the global admin) for the most
+    // recently registered route and documents it as admin-only.
+    pub fn requires_tenant_admin(self) -> Self {
+        self.with_last_auth(Arc::new(TenantAdminOnly))
+    }
+
+    // Documents the request body of the most recently registered route.
+    pub fn request_schema(self, schema: Value) -> Self {
+        self.with_last_doc(|doc| doc.request_schema = Some(Arc::new(schema)))
+    }
+
+    // Documents the response body of the most recently registered route.
+    pub fn response_schema(self, schema: Value) -> Self {
+        self.with_last_doc(|doc| doc.response_schema = Some(schema))
+    }
+
+    // Documents a query parameter of the most recently registered route.
+    pub fn query_param(self, name: &str, required: bool) -> Self {
+        self.with_last_doc(|doc| doc.query_params.push((name.to_string(), required)))
+    }
+
+    // Default Cache-Control for the most recently registered route.
+    pub fn cache(self, policy: CachePolicy) -> Self {
+        self.with_last_doc(|doc| doc.cache = Some(policy))
+    }
+
+    // Scheduling urgency of the most recently registered route, overriding the client's.
+    pub fn urgency(self, urgency: u8) -> Self {
+        self.with_last_doc(|doc| doc.urgency = Some(urgency.min(7)))
+    }
+
+    // Deprecates the most recently registered route.
+    pub fn deprecated(mut self, deprecation: Deprecation) -> Self {
+        if let Some(route) = self.routes.last_mut() {
+            METRICS.register_deprecated_route(route.method.as_str(), &route.path);
+            route.doc.deprecation = Some(deprecation);
+        }
+        self
+    }
+
+    // Adds an admin check to the most recently registered route, ahead of its other
+    // middleware, so it runs before anything reads the request body.
+    fn with_last_auth(mut self, middleware: Arc<dyn Middleware>) -> Self {
+        if let Some(route) = self.routes.last_mut() {
+            route.doc.auth = AuthRequirement::Admin;
+            route.middleware.insert(0, middleware);
+        }
+        self
+    }
+
+    fn with_last_doc(mut self, update: impl FnOnce(&mut RouteDoc)) -> Self {
+        if let Some(route) = self.routes.last_mut() {
+            update(&mut route.doc);
+        }
+        self
+    }
+
+    // Adds a middleware that runs before every request, in registration order.
+    pub fn middleware(mut self, middleware: Arc<dyn Middleware>) -> Self {
+        self.middleware.push(middleware);
+        self
+    }
+
+    // Handler used when no route matches. Without one the router answers 404.
+    pub fn fallback<H, Args>(mut self, handler: H) -> Self
+    where
+        H: IntoHandler<Args>,
+    {
+        self.fallback = Some(handler.into_handler());
+        self
+    }
+
+    // All registered routes, in registration order.
+    pub fn routes(&self) -> &[Route] {
+        &self.routes
+    }
+
+    // Finds the matching route and runs its handler.
+    // Every error, from middleware, validation or the handler, goes through the central error renderer.
+    pub async fn dispatch(&self, ctx: RequestContext, services: Arc<AppServices>) -> Response<Bytes> {
+        let request_id = Some(ctx.request_id.clone());
+        let (method, path, lang) = (ctx.method.clone(), ctx.path.clone(), ctx.lang);
+
+        Metrics::increment(&METRICS.requests_total);
+        // Preflights are answered from the CORS policy, whatever the path.
+        if CorsConfig::is_preflight(&ctx) {
+            return services
+                .cors
+                .preflight(&ctx)
+                .unwrap_or_else(|err| render_error(&err, request_id, lang, method.as_str(), &path));
+        }
+        let envelope = ResponseBuilder::wants_envelope(&ctx).then(|| ctx.request_id.clone());
+        let mut response = self
+            .run(ctx, services)
+            .await
+            .unwrap_or_else(|err| render_error(&err, request_id, lang, method.as_str(), &path));
+        if let Some(request_id) = envelope {
+            response = ResponseBuilder::envelope(response, &request_id);
+        }
+        // Whether the body is enveloped depends on these, so caches must key on them.
+        response
+            .headers_mut()
+            .append(http::header::VARY, http::HeaderValue::from_static("accept, x-response-envelope"));
+        // HEAD answers with the GET headers, including the length of the body it omits.
+        if method == Method::HEAD {
+            let length = response.body().len();
+            response
+                .headers_mut()
+                .insert(http::header::CONTENT_LENGTH, http::HeaderValue::from(length));
+            *response.body_mut() = Bytes::new();
+        }
+        response
+    }
+
+    // Methods registered for `path`, plus HEAD wherever GET is, for the Allow header.
+    fn allowed_methods(&self, path: &str) -> Vec<Method> {
+        let mut methods: Vec<Method> = self
+            .routes
+            .iter()
+            .filter(|route| route.matches(path).is_some())
+            .map(|route| route.method.clone())
+            .collect();
+        if methods.contains(&Method::GET) {
+            methods.push(Method::HEAD);
+        }
+        methods.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+        methods.dedup();
+        methods
+    }
+
+    async fn run(&self, mut ctx: RequestContext, services: Arc<AppServices>) -> AppResult<Response<Bytes>> {
+        // Middleware runs in registration order; the first error short-circuits the request.
+        for middleware in &self.middleware {
+            middleware.before(&ctx, &services).await?;
+        }
+
+        // HEAD is served by the GET handler; dispatch drops the body.
+        let method = if ctx.method == Method::HEAD { Method::GET } else { ctx.method.clone() };
+        let matched = self.routes.iter().find_map(|route| {
+            if route.method != method {
+                return None;
+            }
+            route.matches(&ctx.path).map(|params| (route, params))
+        });
+
+        let mut cache = None;
+        let mut deprecation = None;
+        let mut urgency = requested_urgency(&ctx).unwrap_or(DEFAULT_URGENCY);
+        let handler = match matched {
+            Some((route, params)) => {
+                cache = route.doc.cache_policy();
+                if let Some(deprecated) = &route.doc.deprecation {
+                    METRICS.count_deprecated_request(route.method.as_str(), &route.path);
+                    deprecation = Some(deprecated);
+                }
+                urgency = route.doc.urgency.unwrap_or(urgency);
+                // Requests carrying credentials are personal even on public routes.
+                if ctx.header("authorization").is_some() && matches!(cache, Some(CachePolicy::Public { .. })) {
+                    cache = Some(CachePolicy::NoStore);
+                }
+                for middleware in &route.middleware {
+                    middleware.before(&ctx, &services).await?;
+                }
+                // Bodies are checked against the documented schema when the handler reads
+                // them, so callers that fail authentication never learn the schema's rules.
+                ctx.body_schema = route.doc.request_schema.clone();
+                ctx.params = params;
+                route.handler.clone()
+            }
+            None => {
+                let allowed = self.allowed_methods(&ctx.path);
+                if !allowed.is_empty() {
+                    return Err(AppError::MethodNotAllowed { allowed });
+                }
+                self.fallback
+                    .clone()
+                    .ok_or_else(|| AppError::NotFound("not found".to_string()))?
+            }
+        };
+
+        // Under HANDLER_CONCURRENCY, urgent requests get the next free slot first.
+        let scheduler = services.clone();
+        let _permit = scheduler.scheduler.acquire(urgency).await;
+
+        // A panicking handler must not take the stream down silently: catch the unwind
+        // and answer 500 like any other internal error. How the handler fared with the
+        // database feeds the database circuit breaker.
+        let breaker = services.db_breaker.clone();
+        let outcome = AssertUnwindSafe(db_breaker::track(handler(ctx, services)))
+            .catch_unwind()
+            .await
+            .map(|(result, usage)| {
+                breaker.observe(usage);
+                result
+            });
+        match outcome {
+            Ok(Ok(mut response)) => {
+                if let Some(deprecation) = deprecation {
+                    deprecation.apply(response.headers_mut());
+                }
+                Ok(match cache {
+                    Some(policy)
+                        if (response.status().is_success() || response.status() == http::StatusCode::NOT_MODIFIED)
+                            && !response.headers().contains_key(http::header::CACHE_CONTROL) =>
+                    {
+                        ResponseBuilder::cached(response, policy)
+                    }
+                    _ => response,
+                })
+            }
+            Ok(Err(err)) => Err(err),
+            Err(panic) => {
+                Metrics::increment(&METRICS.handler_panics_total);
+                Err(AppError::Internal(anyhow!("handler panicked: {}", panic_message(&panic))))
+            }
+        }
+    }
+}
+
+// Routes registered through Router::group. Paths are relative to the group prefix;
+// settings made on the group apply to routes registered after them.
+pub struct RouteGroup {
+    router: Router,
+    prefix: String,
+    middleware: Vec<Arc<dyn Middleware>>,
+    auth: AuthRequirement,
+}
+
+impl RouteGroup {
+    // Adds a middleware run for every route of the group, in registration order.
+    pub fn middleware(mut self, middleware: Arc<dyn Middleware>) -> Self {
+        self.middleware.push(middleware);
+        self
+    }
+
+    // Requires the global admin token for the group's routes and documents them as admin-only.
+    pub fn requires_admin(mut self) -> Self {
+        self.auth = AuthRequirement::Admin;
+        self.middleware(Arc::new(AdminOnly))
+    }
+
+    // A nested group; it inherits this group's prefix, middleware and auth requirement.
+    pub fn group(self, prefix: &str, build: impl FnOnce(RouteGroup) -> RouteGroup) -> Self {
+        let nested = build(RouteGroup {
+            router: Router::new(),
+            prefix: format!("{}{}", self.prefix, prefix.trim_end_matches('/')),
+            middleware: self.middleware.clone(),
+            auth: self.auth,
+        });
+        let mut group = self;
+        group.router.routes.extend(nested.router.routes);
+        group
+    }
+
+    pub fn route<H, Args>(mut self, method: Method, path: &str, handler: H) -> Self
+    where
+        H: IntoHandler<Args>,
+    {
+        let full_path = match path {
+            "" | "/" if !self.prefix.is_empty() => self.prefix.clone(),
+            _ => format!("{}{path}", self.prefix),
+        };
+        self.router = self.router.route(method, &full_path, handler);
+        if let Some(route) = self.router.routes.last_mut() {
+            route.middleware = self.middleware.clone();
+            route.doc.auth = self.auth;
+        }
+        self
+    }
+
+    pub fn get<H, Args>(self, path: &str, handler: H) -> Self
+    where
+        H: IntoHandler<Args>,
+    {
+        self.route(Method::GET, path, handler)
+    }
+
+    pub fn post<H, Args>(self, path: &str, handler: H) -> Self
+    where
+        H: IntoHandler<Args>,
+    {
+        self.route(Method::POST, path, handler)
+    }
+
+    pub fn put<H, Args>(self, path: &str, handler: H) -> Self
+    where
+        H: IntoHandler<Args>,
+    {
+        self.route(Method::PUT, path, handler)
+    }
+
+    pub fn patch<H, Args>(self, path: &str, handler: H) -> Self
+    where
+        H: IntoHandler<Args>,
+    {
+        self.route(Method::PATCH, path, handler)
+    }
+
+    pub fn delete<H, Args>(self, path: &str, handler: H) -> Self
+    where
+        H: IntoHandler<Args>,
+    {
+        self.route(Method::DELETE, path, handler)
+    }
+
+    // The Router documentation setters, for the most recently registered route.
+    pub fn summary(mut self, summary: &str) -> Self {
+        self.router = self.router.summary(summary);
+        self
+    }
+
+    pub fn request_schema(mut self, schema: Value) -> Self {
+        self.router = self.router.request_schema(schema);
+        self
+    }
+
+    pub fn response_schema(mut self, schema: Value) -> Self {
+        self.router = self.router.response_schema(schema);
+        self
+    }
+
+    pub fn query_param(mut self, name: &str, required: bool) -> Self {
+        self.router = self.router.query_param(name, required);
+        self
+    }
+
+    pub fn cache(mut self, policy: CachePolicy) -> Self {
+        self.router = self.router.cache(policy);
+        self
+    }
+
+    pub fn urgency(mut self, urgency: u8) -> Self {
+        self.router = self.router.urgency(urgency);
+        self
+    }
+
+    pub fn deprecated(mut self, deprecation: Deprecation) -> Self {
+        self.router = self.router.deprecated(deprecation);
+        self
+    }
+}
+
+// Extracts the message from a panic payload (a &str or String for panic!/unwrap/expect).
+fn panic_message(panic: &Box<dyn Any + Send>) -> &str {
+    panic
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("non-string panic payload")
+}
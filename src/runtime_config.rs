@@ -0,0 +1,307 @@
+use crate::app::AppServices;
+use crate::audit::{AuditEvent, AuditLogger};
+use crate::error::{AppError, AppResult, FieldError};
+use crate::logging;
+use crate::openapi::ApiSchema;
+use crate::request::RequestContext;
+use crate::response::ResponseBuilder;
+use crate::router::Router;
+use anyhow::Result;
+use bytes::Bytes;
+use chrono::FixedOffset;
+use http::{Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sqlx::mysql::MySqlPool;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::sync::watch;
+
+// Keys with a typed getter. Writes to these are checked before they reach the table;
+// other keys are stored as free text.
+pub const DELIVERY_FEE_PAISE: &str = "delivery_fee_paise";
+pub const TAX_RATE_BPS: &str = "tax_rate_bps";
+pub const MIN_ORDER_VALUE_PAISE: &str = "min_order_value_paise";
+pub const UTC_OFFSET_MINUTES: &str = "utc_offset_minutes";
+pub const KITCHEN_MAX_ACTIVE_ORDERS: &str = "kitchen_max_active_orders";
+pub const KITCHEN_MINUTES_PER_ORDER: &str = "kitchen_minutes_per_order";
+pub const COURIER_SPEED_KMH: &str = "courier_speed_kmh";
+pub const DELIVERY_HANDOFF_MINUTES: &str = "delivery_handoff_minutes";
+pub const TICKET_RESPONSE_SLA_MINUTES: &str = "ticket_response_sla_minutes";
+pub const TICKET_RESOLUTION_SLA_MINUTES: &str = "ticket_resolution_sla_minutes";
+pub const ORDER_ARCHIVE_AFTER_MONTHS: &str = "order_archive_after_months";
+
+fn validate_value(key: &str, value: &str) -> Result<(), String> {
+    let ok = match key {
+        DELIVERY_FEE_PAISE | MIN_ORDER_VALUE_PAISE => value.parse::<i64>().is_ok_and(|v| v >= 0),
+        // Basis points: 1800 = 18%.
+        TAX_RATE_BPS => value.parse::<u32>().is_ok_and(|v| v <= 10_000),
+        UTC_OFFSET_MINUTES => value.parse::<i32>().is_ok_and(|v| (-720..=840).contains(&v)),
+        KITCHEN_MAX_ACTIVE_ORDERS => value.parse::<u32>().is_ok(),
+        COURIER_SPEED_KMH => value.parse::<f64>().is_ok_and(|v| v > 0.0),
+        DELIVERY_HANDOFF_MINUTES => value.parse::<f64>().is_ok_and(|v| v >= 0.0),
+        KITCHEN_MINUTES_PER_ORDER | TICKET_RESPONSE_SLA_MINUTES | TICKET_RESOLUTION_SLA_MINUTES => {
+            value.parse::<u32>().is_ok_and(|v| v > 0)
+        }
+        // Recommendations mine the last 90 days of order lines, so those stay live.
+        ORDER_ARCHIVE_AFTER_MONTHS => value.parse::<u32>().is_ok_and(|v| v == 0 || v >= 3),
+        _ => true,
+    };
+    if ok { Ok(()) } else { Err(format!("invalid value for {key}")) }
+}
+
+// One row of system_configurations.
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct ConfigEntry {
+    pub key: String,
+    pub value: String,
+    pub description: String,
+}
+
+impl ApiSchema for ConfigEntry {
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["key", "value", "description"],
+            "properties": {
+                "key": { "type": "string" },
+                "value": { "type": "string" },
+                "description": { "type": "string" }
+            }
+        })
+    }
+}
+
+// In-memory copy of system_configurations, refreshed by polling or on demand. Readers
+// get the current snapshot without touching the database; code that must react to a
+// change can `subscribe` and wait for the version to move.
+pub struct ConfigService {
+    entries: RwLock<Arc<HashMap<String, ConfigEntry>>>,
+    version: watch::Sender<u64>,
+}
+
+impl Default for ConfigService {
+    fn default() -> Self {
+        Self {
+            entries: RwLock::new(Arc::new(HashMap::new())),
+            version: watch::channel(0).0,
+        }
+    }
+}
+
+impl ConfigService {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Reloads the table. Returns true (and notifies subscribers) if anything changed.
+    pub async fn refresh(&self, pool: &MySqlPool) -> Result<bool> {
+        let rows: Vec<ConfigEntry> = sqlx::query_as(
+            "SELECT config_key AS `key`, config_value AS value, description FROM system_configurations",
+        )
+        .fetch_all(pool)
+        .await?;
+        let fresh: HashMap<String, ConfigEntry> = rows.into_iter().map(|e| (e.key.clone(), e)).collect();
+
+        let mut entries = self.entries.write().unwrap_or_else(|p| p.into_inner());
+        let changed = entries.len() != fresh.len()
+            || fresh
+                .iter()
+                .any(|(key, entry)| entries.get(key).is_none_or(|old| old.value != entry.value));
+        *entries = Arc::new(fresh);
+        drop(entries);
+        if changed {
+            self.version.send_modify(|v| *v += 1);
+        }
+        Ok(changed)
+    }
+
+    // Polls the table every `interval` in the background.
+    pub fn spawn_refresh(self: Arc<Self>, pool: MySqlPool, interval: Duration) {
+        tokio::spawn(async move {
+            loop {
+                match self.refresh(&pool).await {
+                    Ok(true) => logging::info("runtime configuration reloaded", json!({})),
+                    Ok(false) => {}
+                    Err(err) => logging::warn("runtime configuration refresh failed", json!({ "error": format!("{err:#}") })),
+                }
+                tokio::time::sleep(interval).await;
+            }
+        });
+    }
+
+    // Receives the snapshot version, bumped on every change.
+    pub fn subscribe(&self) -> watch::Receiver<u64> {
+        self.version.subscribe()
+    }
+
+    pub fn snapshot(&self) -> Arc<HashMap<String, ConfigEntry>> {
+        self.entries.read().unwrap_or_else(|p| p.into_inner()).clone()
+    }
+
+    pub fn get(&self, key: &str) -> Option<String> {
+        self.snapshot().get(key).map(|entry| entry.value.clone())
+    }
+
+    // Parses a value; missing or unparsable values give None.
+    pub fn get_parsed<T: FromStr>(&self, key: &str) -> Option<T> {
+        self.get(key)?.parse().ok()
+    }
+
+    // Flat delivery fee used where no delivery zone sets one.
+    pub fn delivery_fee_paise(&self) -> i64 {
+        self.get_parsed(DELIVERY_FEE_PAISE).unwrap_or(0)
+    }
+
+    // Tax applied to order subtotals, in basis points.
+    pub fn tax_rate_bps(&self) -> u32 {
+        self.get_parsed(TAX_RATE_BPS).unwrap_or(500)
+    }
+
+    pub fn min_order_value_paise(&self) -> i64 {
+        self.get_parsed(MIN_ORDER_VALUE_PAISE).unwrap_or(0)
+    }
+
+    // Orders the kitchen works on at once (placed or preparing) before new ones are
+    // queued; 0 means no limit.
+    pub fn kitchen_max_active_orders(&self) -> u32 {
+        self.get_parsed(KITCHEN_MAX_ACTIVE_ORDERS).unwrap_or(0)
+    }
+
+    // Average minutes the kitchen spends on one order, for ETA estimates.
+    pub fn kitchen_minutes_per_order(&self) -> u32 {
+        self.get_parsed(KITCHEN_MINUTES_PER_ORDER).filter(|v| *v > 0).unwrap_or(12)
+    }
+
+    // Average courier speed including traffic and stops, for delivery ETAs.
+    pub fn courier_speed_kmh(&self) -> f64 {
+        self.get_parsed(COURIER_SPEED_KMH).filter(|v| *v > 0.0).unwrap_or(20.0)
+    }
+
+    // Minutes from the courier's arrival to the customer having the order.
+    pub fn delivery_handoff_minutes(&self) -> f64 {
+        self.get_parsed(DELIVERY_HANDOFF_MINUTES).filter(|v| *v >= 0.0).unwrap_or(4.0)
+    }
+
+    // How soon staff must pick up a support ticket, and settle it, after it is raised.
+    pub fn ticket_response_sla_minutes(&self) -> u32 {
+        self.get_parsed(TICKET_RESPONSE_SLA_MINUTES).filter(|v| *v > 0).unwrap_or(15)
+    }
+
+    pub fn ticket_resolution_sla_minutes(&self) -> u32 {
+        self.get_parsed(TICKET_RESOLUTION_SLA_MINUTES).filter(|v| *v > 0).unwrap_or(240)
+    }
+
+    // Age in months after which finished orders are archived; 0 turns archiving off.
+    pub fn order_archive_after_months(&self) -> u32 {
+        self.get_parsed(ORDER_ARCHIVE_AFTER_MONTHS).filter(|v| *v == 0 || *v >= 3).unwrap_or(12)
+    }
+
+    // The restaurants' local time zone, which menu schedules are written in. Defaults
+    // to IST.
+    pub fn local_offset(&self) -> FixedOffset {
+        let minutes: i32 = self.get_parsed(UTC_OFFSET_MINUTES).unwrap_or(330);
+        FixedOffset::east_opt(minutes * 60).unwrap_or_else(|| FixedOffset::east_opt(330 * 60).expect("IST is a valid offset"))
+    }
+}
+
+// Body of PUT /api/admin/config/:key.
+#[derive(Debug, Deserialize)]
+pub struct ConfigInput {
+    pub value: String,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+impl ApiSchema for ConfigInput {
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["value"],
+            "properties": {
+                "value": { "type": "string" },
+                "description": { "type": "string", "maxLength": 500 }
+            }
+        })
+    }
+}
+
+// Registers the admin runtime-configuration endpoints.
+pub fn routes(router: Router) -> Router {
+    router.group("/api/admin/config", |group| {
+        group
+            .requires_admin()
+            .get("", list_config)
+            .summary("List runtime configuration")
+            .response_schema(json!({ "type": "array", "items": ConfigEntry::schema() }))
+            .put("/:key", put_config)
+            .summary("Set a runtime configuration value")
+            .request_schema(ConfigInput::schema())
+            .response_schema(ConfigEntry::schema())
+            .post("/refresh", refresh_config)
+            .summary("Reload runtime configuration from the database now")
+    })
+}
+
+async fn list_config(_: RequestContext, services: Arc<AppServices>) -> AppResult<Response<Bytes>> {
+    let mut entries: Vec<ConfigEntry> = services.runtime_config.snapshot().values().cloned().collect();
+    entries.sort_by(|a, b| a.key.cmp(&b.key));
+    ResponseBuilder::json(StatusCode::OK, &entries)
+}
+
+// Writes the value and reloads the cache so this instance sees it immediately; other
+// instances pick it up on their next poll.
+async fn put_config(ctx: RequestContext, services: Arc<AppServices>) -> AppResult<Response<Bytes>> {
+    let key = ctx.param("key").unwrap_or_default().to_string();
+    let input: ConfigInput = ctx.json()?;
+    if let Err(message) = validate_value(&key, &input.value) {
+        return Err(AppError::Validation(vec![FieldError::new("value", message)]));
+    }
+
+    let pool = services.db()?;
+    let mut tx = pool.begin().await?;
+    let before: Option<(String, String)> = sqlx::query_as(
+        "SELECT config_value, description FROM system_configurations WHERE config_key = ? FOR UPDATE",
+    )
+    .bind(&key)
+    .fetch_optional(&mut *tx)
+    .await?;
+    sqlx::query(
+        "INSERT INTO system_configurations (config_key, config_value, description) VALUES (?, ?, COALESCE(?, '')) \
+         ON DUPLICATE KEY UPDATE config_value = VALUES(config_value), \
+         description = COALESCE(?, description)",
+    )
+    .bind(&key)
+    .bind(&input.value)
+    .bind(&input.description)
+    .bind(&input.description)
+    .execute(&mut *tx)
+    .await?;
+    AuditLogger::for_request(&ctx, &services)
+        .record(
+            &mut *tx,
+            AuditEvent {
+                action: "config.update",
+                target_type: "system_configuration",
+                target_id: key.clone(),
+                restaurant_id: None,
+                before: before.map(|(value, description)| json!({ "value": value, "description": description })),
+                after: Some(json!({ "value": input.value, "description": input.description })),
+            },
+        )
+        .await?;
+    tx.commit().await?;
+    services.runtime_config.refresh(pool).await?;
+
+    let entry = services.runtime_config.snapshot().get(&key).cloned().ok_or_else(|| {
+        AppError::Internal(anyhow::anyhow!("configuration key {key} missing after write"))
+    })?;
+    ResponseBuilder::json(StatusCode::OK, &entry)
+}
+
+async fn refresh_config(_: RequestContext, services: Arc<AppServices>) -> AppResult<Response<Bytes>> {
+    let changed = services.runtime_config.refresh(services.db()?).await?;
+    ResponseBuilder::json(StatusCode::OK, &json!({ "changed": changed }))
+}
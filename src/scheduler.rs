@@ -0,0 +1,149 @@
+use crate::config;
+use crate::request::RequestContext;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::sync::Mutex;
+use tokio::sync::oneshot;
+
+// RFC 9218 urgency: 0 is most urgent, 7 least; 3 is the default.
+pub const DEFAULT_URGENCY: u8 = 3;
+
+// The urgency a client asked for in its Priority header ("u=1, i"), if any.
+pub fn requested_urgency(ctx: &RequestContext) -> Option<u8> {
+    ctx.header("priority")?
+        .split(',')
+        .filter_map(|param| param.trim().strip_prefix("u="))
+        .find_map(|value| value.parse::<u8>().ok())
+        .filter(|urgency| *urgency <= 7)
+}
+
+struct Waiter {
+    urgency: u8,
+    seq: u64,
+    wake: oneshot::Sender<()>,
+}
+
+// BinaryHeap is a max-heap: the most urgent, then the oldest, waiter compares greatest.
+impl Ord for Waiter {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.urgency.cmp(&self.urgency).then(other.seq.cmp(&self.seq))
+    }
+}
+
+impl PartialOrd for Waiter {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Waiter {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Waiter {}
+
+#[derive(Default)]
+struct State {
+    available: usize,
+    next_seq: u64,
+    waiting: BinaryHeap<Waiter>,
+}
+
+// Limits how many handlers run at once (HANDLER_CONCURRENCY) and, when they are all
+// busy, starts waiting requests in urgency order rather than arrival order, so heavy
+// admin reports queue behind customer-facing requests. Unset means no limit.
+pub struct PriorityScheduler {
+    state: Option<Mutex<State>>,
+}
+
+impl PriorityScheduler {
+    pub fn from_env() -> Self {
+        let limit: Option<usize> = config::var("HANDLER_CONCURRENCY").and_then(|v| v.parse().ok());
+        Self {
+            state: limit.filter(|n| *n > 0).map(|available| {
+                Mutex::new(State {
+                    available,
+                    ..State::default()
+                })
+            }),
+        }
+    }
+
+    // Waits for a handler slot. The slot is released when the permit is dropped.
+    pub async fn acquire(&self, urgency: u8) -> Permit<'_> {
+        let Some(state) = &self.state else {
+            return Permit(None);
+        };
+        let wait = {
+            let mut state = state.lock().unwrap();
+            if state.available > 0 {
+                state.available -= 1;
+                None
+            } else {
+                let (wake, woken) = oneshot::channel();
+                let seq = state.next_seq;
+                state.next_seq += 1;
+                state.waiting.push(Waiter { urgency, seq, wake });
+                Some(woken)
+            }
+        };
+        if let Some(woken) = wait {
+            // The releasing request hands its slot straight to us.
+            let mut waiting = Waiting { scheduler: self, woken: Some(woken) };
+            if let Some(woken) = waiting.woken.as_mut() {
+                let _ = woken.await;
+            }
+            waiting.woken = None;
+        }
+        Permit(Some(self))
+    }
+
+    fn release(&self) {
+        let Some(state) = &self.state else {
+            return;
+        };
+        let mut state = state.lock().unwrap();
+        // Skip waiters whose request was cancelled while queued.
+        while let Some(waiter) = state.waiting.pop() {
+            if waiter.wake.send(()).is_ok() {
+                return;
+            }
+        }
+        state.available += 1;
+    }
+
+    // Requests waiting for a slot.
+    pub fn queued(&self) -> usize {
+        self.state.as_ref().map_or(0, |state| state.lock().unwrap().waiting.len())
+    }
+}
+
+// A queued acquire. If the request is dropped after being handed a slot but before
+// taking it, the slot goes back to the scheduler.
+struct Waiting<'a> {
+    scheduler: &'a PriorityScheduler,
+    woken: Option<oneshot::Receiver<()>>,
+}
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        if let Some(mut woken) = self.woken.take()
+            && woken.try_recv().is_ok()
+        {
+            self.scheduler.release();
+        }
+    }
+}
+
+// A handler slot; returned to the scheduler on drop.
+pub struct Permit<'a>(Option<&'a PriorityScheduler>);
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        if let Some(scheduler) = self.0 {
+            scheduler.release();
+        }
+    }
+}
@@ -0,0 +1,521 @@
+use crate::openapi::operation_id;
+use crate::router::{AuthRequirement, Route, Router};
+use serde_json::Value;
+use std::fmt::Write;
+
+// Typed API clients generated from the registered routes, for `server gen-client`.
+// Each route under /api/ becomes one method named after its OpenAPI operation id
+// (GET /api/admin/zones/:id -> getAdminZonesId) taking the path parameters, the
+// documented query parameters and the request body; request and response types come
+// from the route's JSON Schemas. Routes without a response schema return untyped JSON.
+// The TypeScript client uses fetch; the Dart one package:http, whose Client can be
+// swapped for one that speaks HTTP/3 (e.g. cronet_http).
+
+// Where a generated type is used, for its name.
+const REQUEST_SUFFIX: &str = "Request";
+const RESPONSE_SUFFIX: &str = "Response";
+
+const DART_KEYWORDS: &[&str] = &[
+    "assert", "break", "case", "catch", "class", "const", "continue", "default", "do", "else", "enum", "extends",
+    "false", "final", "finally", "for", "if", "in", "is", "new", "null", "rethrow", "return", "super", "switch",
+    "this", "throw", "true", "try", "var", "void", "while", "with",
+];
+
+// One generated method.
+struct Endpoint<'a> {
+    route: &'a Route,
+    // lowerCamelCase method name.
+    name: String,
+    // UpperCamelCase prefix of the method's type names.
+    type_name: String,
+    params: Vec<&'a str>,
+}
+
+fn endpoints(router: &Router) -> Vec<Endpoint<'_>> {
+    router
+        .routes()
+        .iter()
+        .filter(|route| route.path.starts_with("/api/"))
+        .map(|route| {
+            // "get_api_admin_zones_id" -> "get_admin_zones_id"
+            let id = operation_id(route).replacen("_api_", "_", 1);
+            let type_name = pascal_case(&id);
+            let name = lower_first(&type_name);
+            Endpoint { route, name, type_name, params: route.param_names().collect() }
+        })
+        .collect()
+}
+
+fn pascal_case(text: &str) -> String {
+    text.split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            chars.next().map(|c| c.to_ascii_uppercase().to_string() + chars.as_str()).unwrap_or_default()
+        })
+        .collect()
+}
+
+fn lower_first(text: &str) -> String {
+    let mut chars = text.chars();
+    chars.next().map(|c| c.to_ascii_lowercase().to_string() + chars.as_str()).unwrap_or_default()
+}
+
+fn camel_case(text: &str) -> String {
+    lower_first(&pascal_case(text))
+}
+
+// The schema's "type" as a list: "string", ["string", "null"] or nothing.
+fn types(schema: &Value) -> Vec<&str> {
+    match &schema["type"] {
+        Value::String(t) => vec![t.as_str()],
+        Value::Array(ts) => ts.iter().filter_map(Value::as_str).collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn required(schema: &Value) -> Vec<&str> {
+    schema["required"].as_array().map(|r| r.iter().filter_map(Value::as_str).collect()).unwrap_or_default()
+}
+
+fn properties(schema: &Value) -> Vec<(&String, &Value)> {
+    schema["properties"].as_object().map(|p| p.iter().collect()).unwrap_or_default()
+}
+
+// TypeScript
+
+fn ts_type(schema: &Value) -> String {
+    if let Some(values) = schema["enum"].as_array() {
+        return values.iter().map(Value::to_string).collect::<Vec<_>>().join(" | ");
+    }
+    if let Some(variants) = schema["oneOf"].as_array().or(schema["anyOf"].as_array()) {
+        return variants.iter().map(ts_type).collect::<Vec<_>>().join(" | ");
+    }
+    let types = types(schema);
+    if types.is_empty() {
+        return "unknown".to_string();
+    }
+    types
+        .iter()
+        .map(|t| match *t {
+            "string" => "string".to_string(),
+            "integer" | "number" => "number".to_string(),
+            "boolean" => "boolean".to_string(),
+            "null" => "null".to_string(),
+            "array" => format!("Array<{}>", ts_type(&schema["items"])),
+            "object" => ts_object(schema),
+            _ => "unknown".to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(" | ")
+}
+
+fn ts_object(schema: &Value) -> String {
+    let properties = properties(schema);
+    if properties.is_empty() {
+        return match &schema["additionalProperties"] {
+            additional @ Value::Object(_) => format!("Record<string, {}>", ts_type(additional)),
+            _ => "Record<string, unknown>".to_string(),
+        };
+    }
+    let required = required(schema);
+    let fields: Vec<String> = properties
+        .iter()
+        .map(|(name, property)| {
+            let optional = if required.contains(&name.as_str()) { "" } else { "?" };
+            format!("{}{optional}: {}", ts_key(name), ts_type(property))
+        })
+        .collect();
+    format!("{{ {} }}", fields.join("; "))
+}
+
+fn ts_key(name: &str) -> String {
+    let identifier = name.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if identifier { name.to_string() } else { format!("{name:?}") }
+}
+
+// The TypeScript client module.
+pub fn typescript(router: &Router) -> String {
+    let endpoints = endpoints(router);
+    let mut out = String::from(TS_PRELUDE);
+    for endpoint in &endpoints {
+        let doc = &endpoint.route.doc;
+        if let Some(schema) = &doc.request_schema {
+            let _ = writeln!(out, "export type {}{REQUEST_SUFFIX} = {};\n", endpoint.type_name, ts_type(schema));
+        }
+        if let Some(schema) = &doc.response_schema {
+            let _ = writeln!(out, "export type {}{RESPONSE_SUFFIX} = {};\n", endpoint.type_name, ts_type(schema));
+        }
+    }
+
+    out.push_str(TS_CLIENT);
+    for endpoint in &endpoints {
+        let doc = &endpoint.route.doc;
+        let mut args: Vec<String> = endpoint.params.iter().map(|p| format!("{}: string", camel_case(p))).collect();
+        if doc.request_schema.is_some() {
+            args.push(format!("body: {}{REQUEST_SUFFIX}", endpoint.type_name));
+        }
+        if !doc.query_params.is_empty() {
+            let fields: Vec<String> = doc
+                .query_params
+                .iter()
+                .map(|(name, required)| format!("{}{}: string", ts_key(name), if *required { "" } else { "?" }))
+                .collect();
+            let optional = if doc.query_params.iter().any(|(_, required)| *required) { "" } else { "?" };
+            args.push(format!("query{optional}: {{ {} }}", fields.join("; ")));
+        }
+        let response = match doc.response_schema {
+            Some(_) => format!("{}{RESPONSE_SUFFIX}", endpoint.type_name),
+            None => "unknown".to_string(),
+        };
+        let path = endpoint
+            .route
+            .path
+            .split('/')
+            .map(|segment| match segment.strip_prefix(':') {
+                Some(name) => format!("${{encodeURIComponent({})}}", camel_case(name)),
+                None => segment.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("/");
+        let query = if doc.query_params.is_empty() { "undefined" } else { "query" };
+        let body = if doc.request_schema.is_some() { "body" } else { "undefined" };
+
+        out.push('\n');
+        if let Some(summary) = &doc.summary {
+            let _ = writeln!(out, "  /** {summary}{} */", admin_note(endpoint.route));
+        }
+        let _ = writeln!(out, "  {}({}): Promise<{response}> {{", endpoint.name, args.join(", "));
+        let _ = writeln!(
+            out,
+            "    return this.request<{response}>(\"{}\", `{path}`, {query}, {body});",
+            endpoint.route.method.as_str()
+        );
+        out.push_str("  }\n");
+    }
+    out.push_str("}\n");
+    out
+}
+
+fn admin_note(route: &Route) -> &'static str {
+    if route.doc.auth == AuthRequirement::Admin { " (admin)" } else { "" }
+}
+
+const TS_PRELUDE: &str = r#"// Generated by `server gen-client` from the server's routes. Do not edit.
+
+export class ApiError extends Error {
+  constructor(public readonly status: number, public readonly body: unknown) {
+    super(`request failed with status ${status}`);
+  }
+}
+
+"#;
+
+const TS_CLIENT: &str = r#"export class RotiRideClient {
+  constructor(
+    private readonly baseUrl: string,
+    public token?: string,
+    private readonly fetchImpl: typeof fetch = fetch,
+  ) {}
+
+  private async request<T>(
+    method: string,
+    path: string,
+    query?: Record<string, string | undefined>,
+    body?: unknown,
+  ): Promise<T> {
+    const url = new URL(path, this.baseUrl);
+    for (const [name, value] of Object.entries(query ?? {})) {
+      if (value !== undefined) url.searchParams.set(name, value);
+    }
+    const headers: Record<string, string> = { accept: "application/json" };
+    if (this.token) headers.authorization = `Bearer ${this.token}`;
+    if (body !== undefined) headers["content-type"] = "application/json";
+    const response = await this.fetchImpl(url, {
+      method,
+      headers,
+      body: body === undefined ? undefined : JSON.stringify(body),
+    });
+    const text = await response.text();
+    const json = text && response.headers.get("content-type")?.startsWith("application/json") ? JSON.parse(text) : text;
+    if (!response.ok) throw new ApiError(response.status, json);
+    return (response.status === 204 ? undefined : json) as T;
+  }
+"#;
+
+// Dart
+
+// A Dart type and how to convert it from and to decoded JSON.
+enum DartType {
+    Primitive(&'static str),
+    Int,
+    Double,
+    List(Box<DartType>),
+    Map(Box<DartType>),
+    Class(String),
+    Nullable(Box<DartType>),
+    Dynamic,
+}
+
+impl DartType {
+    fn name(&self) -> String {
+        match self {
+            DartType::Primitive(name) => name.to_string(),
+            DartType::Int => "int".to_string(),
+            DartType::Double => "double".to_string(),
+            DartType::List(item) => format!("List<{}>", item.name()),
+            DartType::Map(value) => format!("Map<String, {}>", value.name()),
+            DartType::Class(name) => name.clone(),
+            DartType::Nullable(inner) => format!("{}?", inner.name()),
+            DartType::Dynamic => "dynamic".to_string(),
+        }
+    }
+
+    // Expression converting decoded JSON `expr` into this type.
+    fn decode(&self, expr: &str) -> String {
+        match self {
+            DartType::Primitive(name) => format!("{expr} as {name}"),
+            DartType::Int => format!("({expr} as num).toInt()"),
+            DartType::Double => format!("({expr} as num).toDouble()"),
+            DartType::List(item) => format!("({expr} as List).map((e) => {}).toList()", item.decode("e")),
+            DartType::Map(value) => {
+                format!("({expr} as Map<String, dynamic>).map((k, e) => MapEntry(k, {}))", value.decode("e"))
+            }
+            DartType::Class(name) => format!("{name}.fromJson({expr} as Map<String, dynamic>)"),
+            DartType::Nullable(inner) => format!("{expr} == null ? null : {}", inner.decode(expr)),
+            DartType::Dynamic => expr.to_string(),
+        }
+    }
+
+    // Expression converting `expr` of this type into encodable JSON.
+    fn encode(&self, expr: &str) -> String {
+        match self {
+            DartType::List(item) => format!("{expr}.map((e) => {}).toList()", item.encode("e")),
+            DartType::Map(value) => format!("{expr}.map((k, e) => MapEntry(k, {}))", value.encode("e")),
+            DartType::Class(_) => format!("{expr}.toJson()"),
+            DartType::Nullable(inner) => match inner.as_ref() {
+                DartType::List(item) => format!("{expr}?.map((e) => {}).toList()", item.encode("e")),
+                DartType::Map(value) => format!("{expr}?.map((k, e) => MapEntry(k, {}))", value.encode("e")),
+                DartType::Class(_) => format!("{expr}?.toJson()"),
+                _ => expr.to_string(),
+            },
+            _ => expr.to_string(),
+        }
+    }
+}
+
+// Model classes collected while mapping schemas, in the order they are first needed.
+#[derive(Default)]
+struct DartModels {
+    classes: Vec<String>,
+}
+
+impl DartModels {
+    // Maps `schema` to a Dart type, generating a class named `name` for objects with
+    // properties (and `name` plus the field name for objects nested in it).
+    fn map(&mut self, schema: &Value, name: &str) -> DartType {
+        let types = types(schema);
+        let nullable = types.contains(&"null");
+        let non_null: Vec<&str> = types.into_iter().filter(|t| *t != "null").collect();
+        let mapped = match non_null.as_slice() {
+            _ if schema["enum"].is_array() => match schema["enum"].as_array().and_then(|v| v.first()) {
+                Some(Value::String(_)) => DartType::Primitive("String"),
+                _ => DartType::Dynamic,
+            },
+            ["string"] => DartType::Primitive("String"),
+            ["boolean"] => DartType::Primitive("bool"),
+            ["integer"] => DartType::Int,
+            ["number"] => DartType::Double,
+            ["array"] => DartType::List(Box::new(self.map(&schema["items"], &format!("{name}Item")))),
+            ["object"] if properties(schema).is_empty() => match &schema["additionalProperties"] {
+                additional @ Value::Object(_) => DartType::Map(Box::new(self.map(additional, &format!("{name}Value")))),
+                _ => DartType::Map(Box::new(DartType::Dynamic)),
+            },
+            ["object"] => {
+                self.class(schema, name);
+                DartType::Class(name.to_string())
+            }
+            _ => DartType::Dynamic,
+        };
+        match mapped {
+            DartType::Dynamic => DartType::Dynamic,
+            mapped if nullable => DartType::Nullable(Box::new(mapped)),
+            mapped => mapped,
+        }
+    }
+
+    fn class(&mut self, schema: &Value, name: &str) {
+        let required = required(schema);
+        let fields: Vec<(String, &String, DartType)> = properties(schema)
+            .into_iter()
+            .map(|(key, property)| {
+                let mapped = self.map(property, &format!("{name}{}", pascal_case(key)));
+                let mapped = match mapped {
+                    DartType::Nullable(_) | DartType::Dynamic => mapped,
+                    mapped if !required.contains(&key.as_str()) => DartType::Nullable(Box::new(mapped)),
+                    mapped => mapped,
+                };
+                (dart_identifier(key), key, mapped)
+            })
+            .collect();
+
+        let mut out = String::new();
+        let _ = writeln!(out, "class {name} {{");
+        let params: Vec<String> = fields
+            .iter()
+            .map(|(field, _, ty)| match ty {
+                DartType::Nullable(_) | DartType::Dynamic => format!("this.{field}"),
+                _ => format!("required this.{field}"),
+            })
+            .collect();
+        if params.is_empty() {
+            let _ = writeln!(out, "  {name}();\n");
+        } else {
+            let _ = writeln!(out, "  {name}({{{}}});\n", params.join(", "));
+        }
+        for (field, _, ty) in &fields {
+            let _ = writeln!(out, "  final {} {field};", ty.name());
+        }
+        let _ = writeln!(out, "\n  factory {name}.fromJson(Map<String, dynamic> json) => {name}(");
+        for (field, key, ty) in &fields {
+            let _ = writeln!(out, "        {field}: {},", ty.decode(&format!("json[{key:?}]")));
+        }
+        out.push_str("      );\n\n  Map<String, dynamic> toJson() => {\n");
+        for (field, key, ty) in &fields {
+            match ty {
+                DartType::Nullable(_) => {
+                    let _ = writeln!(out, "        if ({field} != null) {key:?}: {},", ty.encode(field));
+                }
+                _ => {
+                    let _ = writeln!(out, "        {key:?}: {},", ty.encode(field));
+                }
+            }
+        }
+        out.push_str("      };\n}\n");
+        self.classes.push(out);
+    }
+}
+
+fn dart_identifier(name: &str) -> String {
+    let name = camel_case(name);
+    if DART_KEYWORDS.contains(&name.as_str()) { format!("{name}Value") } else { name }
+}
+
+// The Dart client library.
+pub fn dart(router: &Router) -> String {
+    let endpoints = endpoints(router);
+    let mut models = DartModels::default();
+    let mut methods = String::new();
+    for endpoint in &endpoints {
+        let doc = &endpoint.route.doc;
+        let body = doc
+            .request_schema
+            .as_ref()
+            .map(|schema| models.map(schema, &format!("{}{REQUEST_SUFFIX}", endpoint.type_name)));
+        let response = doc
+            .response_schema
+            .as_ref()
+            .map(|schema| models.map(schema, &format!("{}{RESPONSE_SUFFIX}", endpoint.type_name)))
+            .unwrap_or(DartType::Dynamic);
+
+        let mut args: Vec<String> = endpoint.params.iter().map(|p| format!("String {}", dart_identifier(p))).collect();
+        if let Some(body) = &body {
+            args.push(format!("{} body", body.name()));
+        }
+        let named: Vec<String> = doc
+            .query_params
+            .iter()
+            .map(|(name, required)| {
+                if *required {
+                    format!("required String {}", dart_identifier(name))
+                } else {
+                    format!("String? {}", dart_identifier(name))
+                }
+            })
+            .collect();
+        if !named.is_empty() {
+            args.push(format!("{{{}}}", named.join(", ")));
+        }
+        let path = endpoint
+            .route
+            .path
+            .split('/')
+            .map(|segment| match segment.strip_prefix(':') {
+                Some(name) => format!("${{Uri.encodeComponent({})}}", dart_identifier(name)),
+                None => segment.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("/");
+        let query: Vec<String> =
+            doc.query_params.iter().map(|(name, _)| format!("{name:?}: {}", dart_identifier(name))).collect();
+
+        methods.push('\n');
+        if let Some(summary) = &doc.summary {
+            let _ = writeln!(methods, "  /// {summary}{}", admin_note(endpoint.route));
+        }
+        let _ = writeln!(methods, "  Future<{}> {}({}) async {{", response.name(), endpoint.name, args.join(", "));
+        let _ = write!(methods, "    final json = await _send('{}', '{path}'", endpoint.route.method.as_str());
+        if !query.is_empty() {
+            let _ = write!(methods, ", query: {{{}}}", query.join(", "));
+        }
+        if let Some(body) = &body {
+            let _ = write!(methods, ", body: {}", body.encode("body"));
+        }
+        methods.push_str(");\n");
+        let _ = writeln!(methods, "    return {};", response.decode("json"));
+        methods.push_str("  }\n");
+    }
+
+    let mut out = String::from(DART_PRELUDE);
+    out.push_str(&methods);
+    out.push_str("}\n");
+    for class in &models.classes {
+        out.push('\n');
+        out.push_str(class);
+    }
+    out
+}
+
+const DART_PRELUDE: &str = r#"// Generated by `server gen-client` from the server's routes. Do not edit.
+
+import 'dart:convert';
+
+import 'package:http/http.dart' as http;
+
+class ApiException implements Exception {
+  ApiException(this.status, this.body);
+
+  final int status;
+  final dynamic body;
+
+  @override
+  String toString() => 'ApiException($status): $body';
+}
+
+class RotiRideClient {
+  RotiRideClient(this.baseUrl, {this.token, http.Client? httpClient}) : _http = httpClient ?? http.Client();
+
+  final String baseUrl;
+  String? token;
+  final http.Client _http;
+
+  Future<dynamic> _send(String method, String path, {Map<String, String?>? query, Object? body}) async {
+    final params = {
+      for (final entry in (query ?? {}).entries)
+        if (entry.value != null) entry.key: entry.value!,
+    };
+    final uri = Uri.parse(baseUrl).resolve(path).replace(queryParameters: params.isEmpty ? null : params);
+    final request = http.Request(method, uri)..headers['accept'] = 'application/json';
+    if (token != null) request.headers['authorization'] = 'Bearer $token';
+    if (body != null) {
+      request.headers['content-type'] = 'application/json';
+      request.body = jsonEncode(body);
+    }
+    final response = await http.Response.fromStream(await _http.send(request));
+    final isJson = (response.headers['content-type'] ?? '').startsWith('application/json');
+    final decoded = isJson && response.body.isNotEmpty ? jsonDecode(response.body) : response.body;
+    if (response.statusCode >= 400) throw ApiException(response.statusCode, decoded);
+    return response.statusCode == 204 ? null : decoded;
+  }
+"#;
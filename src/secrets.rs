@@ -0,0 +1,326 @@
+use crate::config;
+use crate::logging;
+use anyhow::{anyhow, Context, Result};
+use base64::Engine;
+use futures::future::BoxFuture;
+use hmac::{Hmac, Mac};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::time::Duration;
+
+// Fetches credentials from an external secrets manager instead of plaintext env vars.
+//   SECRETS_PROVIDER      vault | aws | gcp
+//   SECRETS               <SETTING>=<secret id>[,...], e.g.
+//                         DATABASE_URL=prod/db#url,ADMIN_API_TOKEN=prod/admin-token
+//   SECRETS_REFRESH_SECS  re-fetch interval (default 300)
+// A secret id may end in "#field" to pick one key out of a JSON secret. Fetched values
+// override every other configuration source.
+
+// A secrets backend.
+pub trait SecretsProvider: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn fetch<'a>(&'a self, id: &'a str) -> BoxFuture<'a, Result<String>>;
+}
+
+// Splits "path#field" into ("path", Some("field")).
+fn split_field(id: &str) -> (&str, Option<&str>) {
+    match id.split_once('#') {
+        Some((path, field)) => (path, Some(field)),
+        None => (id, None),
+    }
+}
+
+// Picks `field` out of a JSON object secret; without a field the raw value is used.
+fn select_field(raw: String, field: Option<&str>, id: &str) -> Result<String> {
+    let Some(field) = field else {
+        return Ok(raw);
+    };
+    let value: Value = serde_json::from_str(&raw).with_context(|| format!("secret {id} is not a JSON object"))?;
+    match value.get(field) {
+        Some(Value::String(s)) => Ok(s.clone()),
+        Some(other) => Ok(other.to_string()),
+        None => Err(anyhow!("secret {id} has no field {field}")),
+    }
+}
+
+fn required(name: &str) -> Result<String> {
+    config::var(name).ok_or_else(|| anyhow!("{name} must be set for the configured SECRETS_PROVIDER"))
+}
+
+fn http_client() -> Result<reqwest::Client> {
+    Ok(reqwest::Client::builder().timeout(Duration::from_secs(10)).build()?)
+}
+
+// HashiCorp Vault KV v2 (VAULT_ADDR, VAULT_TOKEN, VAULT_MOUNT default "secret").
+// Ids are "<path>#<field>"; the field defaults to "value".
+pub struct VaultProvider {
+    client: reqwest::Client,
+    addr: String,
+    token: String,
+    mount: String,
+}
+
+impl VaultProvider {
+    pub fn from_config() -> Result<Self> {
+        Ok(Self {
+            client: http_client()?,
+            addr: required("VAULT_ADDR")?.trim_end_matches('/').to_string(),
+            token: required("VAULT_TOKEN")?,
+            mount: config::var("VAULT_MOUNT").unwrap_or_else(|| "secret".into()),
+        })
+    }
+}
+
+impl SecretsProvider for VaultProvider {
+    fn name(&self) -> &'static str {
+        "vault"
+    }
+
+    fn fetch<'a>(&'a self, id: &'a str) -> BoxFuture<'a, Result<String>> {
+        Box::pin(async move {
+            let (path, field) = split_field(id);
+            let url = format!("{}/v1/{}/data/{}", self.addr, self.mount, path.trim_start_matches('/'));
+            let body: Value = self
+                .client
+                .get(&url)
+                .header("x-vault-token", &self.token)
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?;
+            let field = field.unwrap_or("value");
+            match body.pointer(&format!("/data/data/{field}")) {
+                Some(Value::String(s)) => Ok(s.clone()),
+                Some(other) => Ok(other.to_string()),
+                None => Err(anyhow!("vault secret {path} has no field {field}")),
+            }
+        })
+    }
+}
+
+// AWS Secrets Manager (AWS_REGION, AWS_ACCESS_KEY_ID, AWS_SECRET_ACCESS_KEY and optional
+// AWS_SESSION_TOKEN). Requests are signed with Signature Version 4.
+pub struct AwsSecretsManager {
+    client: reqwest::Client,
+    region: String,
+    access_key: String,
+    secret_key: String,
+    session_token: Option<String>,
+}
+
+impl AwsSecretsManager {
+    pub fn from_config() -> Result<Self> {
+        Ok(Self {
+            client: http_client()?,
+            region: required("AWS_REGION")?,
+            access_key: required("AWS_ACCESS_KEY_ID")?,
+            secret_key: required("AWS_SECRET_ACCESS_KEY")?,
+            session_token: config::var("AWS_SESSION_TOKEN"),
+        })
+    }
+
+    // Builds the SigV4 Authorization header for a POST to "/" with the given headers.
+    // `headers` must be lowercase and sorted by name.
+    fn authorization(&self, amz_date: &str, headers: &[(&str, String)], body: &[u8]) -> String {
+        let date = &amz_date[..8];
+        let canonical_headers: String = headers.iter().map(|(k, v)| format!("{k}:{}\n", v.trim())).collect();
+        let signed_headers = headers.iter().map(|(k, _)| *k).collect::<Vec<_>>().join(";");
+        let canonical_request = format!(
+            "POST\n/\n\n{canonical_headers}\n{signed_headers}\n{}",
+            hex::encode(Sha256::digest(body))
+        );
+        let scope = format!("{date}/{}/secretsmanager/aws4_request", self.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{amz_date}\n{scope}\n{}",
+            hex::encode(Sha256::digest(canonical_request.as_bytes()))
+        );
+
+        let mut key = hmac_sha256(format!("AWS4{}", self.secret_key).as_bytes(), date.as_bytes());
+        for part in [self.region.as_str(), "secretsmanager", "aws4_request"] {
+            key = hmac_sha256(&key, part.as_bytes());
+        }
+        let signature = hex::encode(hmac_sha256(&key, string_to_sign.as_bytes()));
+        format!(
+            "AWS4-HMAC-SHA256 Credential={}/{scope}, SignedHeaders={signed_headers}, Signature={signature}",
+            self.access_key
+        )
+    }
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+impl SecretsProvider for AwsSecretsManager {
+    fn name(&self) -> &'static str {
+        "aws"
+    }
+
+    fn fetch<'a>(&'a self, id: &'a str) -> BoxFuture<'a, Result<String>> {
+        Box::pin(async move {
+            let (secret_id, field) = split_field(id);
+            let host = format!("secretsmanager.{}.amazonaws.com", self.region);
+            let body = serde_json::to_vec(&json!({ "SecretId": secret_id }))?;
+            let amz_date = chrono::Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+
+            let mut headers = vec![
+                ("content-type", "application/x-amz-json-1.1".to_string()),
+                ("host", host.clone()),
+                ("x-amz-date", amz_date.clone()),
+            ];
+            if let Some(token) = &self.session_token {
+                headers.push(("x-amz-security-token", token.clone()));
+            }
+            headers.push(("x-amz-target", "secretsmanager.GetSecretValue".to_string()));
+            let authorization = self.authorization(&amz_date, &headers, &body);
+
+            let mut request = self.client.post(format!("https://{host}/")).body(body);
+            for (name, value) in headers.into_iter().filter(|(name, _)| *name != "host") {
+                request = request.header(name, value);
+            }
+            let response = request.header("authorization", authorization).send().await?;
+            let status = response.status();
+            let body: Value = response.json().await?;
+            if !status.is_success() {
+                return Err(anyhow!("secrets manager answered {status}: {}", body["message"]));
+            }
+            let raw = body["SecretString"]
+                .as_str()
+                .ok_or_else(|| anyhow!("secret {secret_id} has no SecretString"))?
+                .to_string();
+            select_field(raw, field, secret_id)
+        })
+    }
+}
+
+// GCP Secret Manager (GCP_PROJECT). Uses GCP_ACCESS_TOKEN when set, otherwise asks the
+// metadata server for the instance service account's token.
+pub struct GcpSecretManager {
+    client: reqwest::Client,
+    project: String,
+    access_token: Option<String>,
+}
+
+impl GcpSecretManager {
+    pub fn from_config() -> Result<Self> {
+        Ok(Self {
+            client: http_client()?,
+            project: required("GCP_PROJECT")?,
+            access_token: config::var("GCP_ACCESS_TOKEN"),
+        })
+    }
+
+    async fn token(&self) -> Result<String> {
+        if let Some(token) = &self.access_token {
+            return Ok(token.clone());
+        }
+        let body: Value = self
+            .client
+            .get("http://metadata.google.internal/computeMetadata/v1/instance/service-accounts/default/token")
+            .header("metadata-flavor", "Google")
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        body["access_token"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| anyhow!("metadata server returned no access_token"))
+    }
+}
+
+impl SecretsProvider for GcpSecretManager {
+    fn name(&self) -> &'static str {
+        "gcp"
+    }
+
+    fn fetch<'a>(&'a self, id: &'a str) -> BoxFuture<'a, Result<String>> {
+        Box::pin(async move {
+            let (name, field) = split_field(id);
+            let url = format!(
+                "https://secretmanager.googleapis.com/v1/projects/{}/secrets/{name}/versions/latest:access",
+                self.project
+            );
+            let body: Value = self
+                .client
+                .get(&url)
+                .bearer_auth(self.token().await?)
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?;
+            let data = body
+                .pointer("/payload/data")
+                .and_then(Value::as_str)
+                .ok_or_else(|| anyhow!("secret {name} has no payload"))?;
+            let raw = String::from_utf8(base64::engine::general_purpose::STANDARD.decode(data)?)?;
+            select_field(raw, field, name)
+        })
+    }
+}
+
+// The configured provider and which settings it supplies.
+pub struct SecretBindings {
+    provider: Box<dyn SecretsProvider>,
+    bindings: Vec<(String, String)>,
+}
+
+impl SecretBindings {
+    // None when SECRETS_PROVIDER is unset.
+    pub fn from_config() -> Result<Option<Self>> {
+        let provider: Box<dyn SecretsProvider> = match config::var("SECRETS_PROVIDER").as_deref() {
+            None => return Ok(None),
+            Some("vault") => Box::new(VaultProvider::from_config()?),
+            Some("aws") => Box::new(AwsSecretsManager::from_config()?),
+            Some("gcp") => Box::new(GcpSecretManager::from_config()?),
+            Some(other) => return Err(anyhow!("unknown SECRETS_PROVIDER: {other}")),
+        };
+        let bindings = config::var("SECRETS")
+            .map(|spec| config::secret_bindings(&spec))
+            .unwrap_or_default();
+        Ok(Some(Self { provider, bindings }))
+    }
+
+    // Fetches every bound secret into the configuration. Returns the settings whose
+    // value changed since the last load.
+    pub async fn load(&self) -> Result<Vec<String>> {
+        let mut changed = Vec::new();
+        for (setting, id) in &self.bindings {
+            let value = self
+                .provider
+                .fetch(id)
+                .await
+                .with_context(|| format!("fetching {setting} from {} secret {id}", self.provider.name()))?;
+            if config::set_secret(setting, value).map_err(|err| anyhow!("{setting} from secret {id}: {err}"))? {
+                changed.push(setting.clone());
+            }
+        }
+        Ok(changed)
+    }
+
+    // Re-fetches every `interval`, calling `on_change` with the settings that changed so
+    // long-lived holders (e.g. the database pool) can pick up rotated credentials.
+    pub fn spawn_refresh<F>(self, interval: Duration, on_change: F)
+    where
+        F: Fn(&[String]) + Send + Sync + 'static,
+    {
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(interval).await;
+                match self.load().await {
+                    Ok(changed) if !changed.is_empty() => {
+                        logging::info("secrets rotated", json!({ "settings": changed }));
+                        on_change(&changed);
+                    }
+                    Ok(_) => {}
+                    Err(err) => logging::warn("secret refresh failed", json!({ "error": format!("{err:#}") })),
+                }
+            }
+        });
+    }
+}
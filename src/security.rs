@@ -0,0 +1,164 @@
+use crate::config;
+use crate::error::{AppError, AppResult};
+use crate::request::RequestContext;
+use anyhow::{anyhow, Result};
+use bytes::Bytes;
+use http::{HeaderMap, HeaderValue, Response, StatusCode};
+
+// Cross-origin policy for browser clients, from configuration:
+//   CORS_ALLOWED_ORIGINS    comma-separated origins, or * (default)
+//   CORS_ALLOWED_METHODS    default GET,POST,PUT,PATCH,DELETE,OPTIONS
+//   CORS_ALLOWED_HEADERS    default authorization,content-type,accept-language,x-request-id
+//   CORS_ALLOW_CREDENTIALS  true | false (default false)
+//   CORS_MAX_AGE_SECS       how long browsers may cache a preflight (default 600)
+#[derive(Debug, Clone)]
+pub struct CorsConfig {
+    // None allows any origin.
+    pub allowed_origins: Option<Vec<String>>,
+    pub allowed_methods: Vec<String>,
+    pub allowed_headers: Vec<String>,
+    pub allow_credentials: bool,
+    pub max_age_secs: u64,
+}
+
+fn list(name: &str, default: &str) -> Vec<String> {
+    config::var(name)
+        .unwrap_or_else(|| default.to_string())
+        .split(',')
+        .map(|item| item.trim().to_string())
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+impl CorsConfig {
+    pub fn from_env() -> Result<Self> {
+        let origins = list("CORS_ALLOWED_ORIGINS", "*");
+        let allowed_origins = if origins.iter().any(|o| o == "*") {
+            None
+        } else {
+            Some(origins.into_iter().map(|o| o.trim_end_matches('/').to_ascii_lowercase()).collect())
+        };
+        Ok(Self {
+            allowed_origins,
+            allowed_methods: list("CORS_ALLOWED_METHODS", "GET,POST,PUT,PATCH,DELETE,OPTIONS")
+                .into_iter()
+                .map(|m| m.to_ascii_uppercase())
+                .collect(),
+            allowed_headers: list("CORS_ALLOWED_HEADERS", "authorization,content-type,accept-language,x-request-id")
+                .into_iter()
+                .map(|h| h.to_ascii_lowercase())
+                .collect(),
+            allow_credentials: config::var("CORS_ALLOW_CREDENTIALS").is_some_and(|v| v == "true"),
+            max_age_secs: config::var("CORS_MAX_AGE_SECS")
+                .map(|v| v.parse().map_err(|_| anyhow!("CORS_MAX_AGE_SECS must be an integer")))
+                .transpose()?
+                .unwrap_or(600),
+        })
+    }
+
+    pub fn origin_allowed(&self, origin: &str) -> bool {
+        match &self.allowed_origins {
+            None => true,
+            Some(origins) => origins.iter().any(|o| o.eq_ignore_ascii_case(origin)),
+        }
+    }
+
+    // The Access-Control-Allow-Origin value for a request, or None to send no CORS
+    // headers. With credentials the origin is echoed, since browsers reject "*" then.
+    fn allow_origin(&self, origin: Option<&str>) -> Option<String> {
+        match (origin, &self.allowed_origins) {
+            (None, None) if !self.allow_credentials => Some("*".to_string()),
+            (None, _) => None,
+            (Some(_), None) if !self.allow_credentials => Some("*".to_string()),
+            (Some(origin), _) => self.origin_allowed(origin).then(|| origin.to_string()),
+        }
+    }
+
+    // Adds CORS headers to an actual (non-preflight) response.
+    pub fn apply(&self, origin: Option<&str>, headers: &mut HeaderMap) {
+        if let Some(value) = self.allow_origin(origin).and_then(|o| HeaderValue::from_str(&o).ok()) {
+            headers.insert("access-control-allow-origin", value);
+            if self.allow_credentials {
+                headers.insert("access-control-allow-credentials", HeaderValue::from_static("true"));
+            }
+            headers.insert(
+                "access-control-expose-headers",
+                HeaderValue::from_static("x-request-id, retry-after, content-language"),
+            );
+        }
+        // The answer depends on Origin unless it is always "*".
+        if self.allowed_origins.is_some() || self.allow_credentials {
+            headers.append(http::header::VARY, HeaderValue::from_static("origin"));
+        }
+    }
+
+    // True for a CORS preflight: OPTIONS carrying Access-Control-Request-Method.
+    pub fn is_preflight(ctx: &RequestContext) -> bool {
+        ctx.method == http::Method::OPTIONS && ctx.header("access-control-request-method").is_some()
+    }
+
+    // Answers a preflight. Disallowed origins, methods or headers get 403 with no CORS
+    // headers, so the browser blocks the real request.
+    pub fn preflight(&self, ctx: &RequestContext) -> AppResult<Response<Bytes>> {
+        let origin = ctx.header("origin");
+        let method = ctx.header("access-control-request-method").unwrap_or_default();
+        let requested_headers: Vec<String> = ctx
+            .header("access-control-request-headers")
+            .unwrap_or_default()
+            .split(',')
+            .map(|h| h.trim().to_ascii_lowercase())
+            .filter(|h| !h.is_empty())
+            .collect();
+
+        let Some(allow_origin) = origin.and_then(|o| self.allow_origin(Some(o))) else {
+            return Err(AppError::Forbidden("origin not allowed".to_string()));
+        };
+        if !self.allowed_methods.iter().any(|m| m.eq_ignore_ascii_case(method)) {
+            return Err(AppError::Forbidden(format!("method {method} not allowed")));
+        }
+        if let Some(header) = requested_headers.iter().find(|h| !self.allowed_headers.contains(h)) {
+            return Err(AppError::Forbidden(format!("header {header} not allowed")));
+        }
+
+        let mut response = Response::builder()
+            .status(StatusCode::NO_CONTENT)
+            .header("access-control-allow-origin", allow_origin)
+            .header("access-control-allow-methods", self.allowed_methods.join(", "))
+            .header("access-control-allow-headers", self.allowed_headers.join(", "))
+            .header("access-control-max-age", self.max_age_secs.to_string());
+        if self.allow_credentials {
+            response = response.header("access-control-allow-credentials", "true");
+        }
+        // Vary: origin is added with the other response headers by apply().
+        Ok(response.body(Bytes::new())?)
+    }
+}
+
+// Headers added to every response. HSTS_MAX_AGE_SECS (default one year, 0 disables)
+// sets Strict-Transport-Security; every connection here is already TLS.
+#[derive(Debug, Clone)]
+pub struct SecurityHeaders {
+    hsts: Option<HeaderValue>,
+}
+
+impl SecurityHeaders {
+    pub fn from_env() -> Result<Self> {
+        let max_age: u64 = config::var("HSTS_MAX_AGE_SECS")
+            .map(|v| v.parse().map_err(|_| anyhow!("HSTS_MAX_AGE_SECS must be an integer")))
+            .transpose()?
+            .unwrap_or(31_536_000);
+        let hsts = (max_age > 0)
+            .then(|| HeaderValue::from_str(&format!("max-age={max_age}; includeSubDomains")))
+            .transpose()?;
+        Ok(Self { hsts })
+    }
+
+    pub fn apply(&self, headers: &mut HeaderMap) {
+        headers.insert("x-content-type-options", HeaderValue::from_static("nosniff"));
+        headers.insert("x-frame-options", HeaderValue::from_static("DENY"));
+        headers.insert("referrer-policy", HeaderValue::from_static("no-referrer"));
+        if let Some(hsts) = &self.hsts {
+            headers.insert(http::header::STRICT_TRANSPORT_SECURITY, hsts.clone());
+        }
+    }
+}
@@ -0,0 +1,230 @@
+use crate::auth::AdminUserRepository;
+use crate::orders::{tax_paise, FULFILLMENT_DINE_IN};
+use crate::runtime_config::ConfigService;
+use anyhow::{anyhow, Result};
+use chrono::{Duration, DurationRound, Utc};
+use rand::rngs::StdRng;
+use rand::{Rng, RngCore, SeedableRng};
+use sqlx::mysql::MySqlPool;
+
+// Demo data for staging and sales demos. Every row has a natural key, so running the
+// seed again only adds what is missing:
+//   restaurant     slug
+//   menu items     (restaurant, sku); prices and descriptions are reset to the fixtures
+//   dining tables  (restaurant, label)
+//   admin users    phone; existing ones keep their token
+//   orders         (restaurant, notes "Demo order #<n>")
+// Orders are generated from a fixed seed per order number, so the same number always
+// has the same lines, table, status and time of day.
+
+// (sku, name, description, category, price in paise)
+const MENU: &[(&str, &str, &str, &str, i64)] = &[
+    ("STR-001", "Paneer Tikka", "Chargrilled cottage cheese with peppers and mint chutney", "Starters", 24_900),
+    ("STR-002", "Chicken 65", "Crisp fried chicken tossed with curry leaves and chilli", "Starters", 26_900),
+    ("STR-003", "Veg Samosa (2 pcs)", "Spiced potato and pea pastries with tamarind chutney", "Starters", 8_900),
+    ("MNS-001", "Butter Chicken", "Tandoori chicken simmered in a tomato and butter gravy", "Mains", 34_900),
+    ("MNS-002", "Dal Makhani", "Black lentils slow-cooked overnight with cream", "Mains", 22_900),
+    ("MNS-003", "Palak Paneer", "Cottage cheese in a smooth spinach gravy", "Mains", 27_900),
+    ("MNS-004", "Mutton Rogan Josh", "Kashmiri-style lamb curry with whole spices", "Mains", 42_900),
+    ("BRD-001", "Butter Naan", "Leavened flatbread from the tandoor, brushed with butter", "Breads", 6_900),
+    ("BRD-002", "Tandoori Roti", "Whole-wheat flatbread from the tandoor", "Breads", 3_900),
+    ("BRD-003", "Garlic Naan", "Naan topped with garlic and coriander", "Breads", 7_900),
+    ("RCE-001", "Veg Biryani", "Basmati rice layered with vegetables and saffron, with raita", "Rice", 27_900),
+    ("RCE-002", "Chicken Dum Biryani", "Hyderabadi-style biryani sealed and slow-cooked", "Rice", 32_900),
+    ("RCE-003", "Jeera Rice", "Basmati rice tempered with cumin", "Rice", 15_900),
+    ("DST-001", "Gulab Jamun (2 pcs)", "Milk dumplings in cardamom syrup", "Desserts", 9_900),
+    ("DST-002", "Rasmalai (2 pcs)", "Cottage cheese discs in saffron milk", "Desserts", 12_900),
+    ("BEV-001", "Masala Chai", "Tea brewed with milk, ginger and cardamom", "Beverages", 4_900),
+    ("BEV-002", "Sweet Lassi", "Chilled churned yoghurt", "Beverages", 8_900),
+    ("BEV-003", "Fresh Lime Soda", "Sweet or salted", "Beverages", 7_900),
+];
+
+const TABLES: &[(&str, &str)] = &[
+    ("T1", "table"),
+    ("T2", "table"),
+    ("T3", "table"),
+    ("T4", "table"),
+    ("T5", "table"),
+    ("T6", "table"),
+    ("Room 101", "room"),
+    ("Room 102", "room"),
+];
+
+const ADMIN_PHONES: &[&str] = &["+919800000001", "+919800000002"];
+
+// Lunch and dinner hours orders are spread over, in UTC minutes after midnight (IST - 5:30).
+const SERVICE_WINDOWS: &[(i64, i64)] = &[(6 * 60 + 30, 9 * 60 + 30), (13 * 60 + 30, 17 * 60 + 30)];
+
+// What the seed added; rows that already existed are not counted.
+#[derive(Debug, Default)]
+pub struct SeedSummary {
+    pub restaurant_created: bool,
+    pub menu_items: u64,
+    pub dining_tables: u64,
+    // (phone, token) of admins created by this run; their tokens are shown only here.
+    pub admin_users: Vec<(String, String)>,
+    pub orders: u64,
+}
+
+pub struct SeedOptions {
+    pub restaurant_slug: String,
+    pub orders: u32,
+    // Orders are spread over this many days before today.
+    pub days: u32,
+}
+
+pub async fn run(pool: &MySqlPool, options: &SeedOptions) -> Result<SeedSummary> {
+    let mut summary = SeedSummary::default();
+
+    let created = sqlx::query("INSERT IGNORE INTO restaurants (slug, name) VALUES (?, ?)")
+        .bind(&options.restaurant_slug)
+        .bind(format!("Demo restaurant ({})", options.restaurant_slug))
+        .execute(pool)
+        .await?;
+    summary.restaurant_created = created.rows_affected() > 0;
+    let restaurant_id: i64 = sqlx::query_scalar("SELECT id FROM restaurants WHERE slug = ?")
+        .bind(&options.restaurant_slug)
+        .fetch_one(pool)
+        .await?;
+
+    for (sku, name, description, category, price) in MENU {
+        let exists: Option<i64> = sqlx::query_scalar("SELECT id FROM menu_items WHERE restaurant_id = ? AND sku = ?")
+            .bind(restaurant_id)
+            .bind(sku)
+            .fetch_optional(pool)
+            .await?;
+        sqlx::query(
+            "INSERT INTO menu_items (restaurant_id, sku, name, description, category, price_paise, available) \
+             VALUES (?, ?, ?, ?, ?, ?, TRUE) \
+             ON DUPLICATE KEY UPDATE name = VALUES(name), description = VALUES(description), \
+             category = VALUES(category), price_paise = VALUES(price_paise), available = TRUE",
+        )
+        .bind(restaurant_id)
+        .bind(sku)
+        .bind(name)
+        .bind(description)
+        .bind(category)
+        .bind(price)
+        .execute(pool)
+        .await?;
+        if exists.is_none() {
+            summary.menu_items += 1;
+        }
+    }
+
+    for (label, kind) in TABLES {
+        let mut bytes = [0u8; 16];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        let inserted = sqlx::query("INSERT IGNORE INTO dining_tables (restaurant_id, label, kind, token) VALUES (?, ?, ?, ?)")
+            .bind(restaurant_id)
+            .bind(label)
+            .bind(kind)
+            .bind(hex::encode(bytes))
+            .execute(pool)
+            .await?;
+        summary.dining_tables += inserted.rows_affected();
+    }
+
+    let admins = AdminUserRepository::new(pool);
+    for phone in ADMIN_PHONES {
+        let exists: Option<i64> = sqlx::query_scalar("SELECT id FROM admin_users WHERE phone = ?")
+            .bind(phone)
+            .fetch_optional(pool)
+            .await?;
+        if exists.is_none() {
+            let (_, token) = admins.create_or_reissue(phone).await?;
+            summary.admin_users.push((phone.to_string(), token));
+        }
+    }
+
+    summary.orders = seed_orders(pool, restaurant_id, options).await?;
+    Ok(summary)
+}
+
+async fn seed_orders(pool: &MySqlPool, restaurant_id: i64, options: &SeedOptions) -> Result<u64> {
+    // Prices come from the rows just seeded and tax from the live configuration, so the
+    // totals match what the API would have charged.
+    let menu: Vec<(i64, String, String, i64)> =
+        sqlx::query_as("SELECT id, sku, name, price_paise FROM menu_items WHERE restaurant_id = ? ORDER BY sku")
+            .bind(restaurant_id)
+            .fetch_all(pool)
+            .await?;
+    let tables: Vec<i64> = sqlx::query_scalar("SELECT id FROM dining_tables WHERE restaurant_id = ? ORDER BY label")
+        .bind(restaurant_id)
+        .fetch_all(pool)
+        .await?;
+    if menu.is_empty() || tables.is_empty() {
+        return Err(anyhow!("restaurant {} has no menu items or tables to order from", options.restaurant_slug));
+    }
+    let config = ConfigService::new();
+    config.refresh(pool).await?;
+    let tax_bps = config.tax_rate_bps();
+    let today = Utc::now().duration_trunc(Duration::days(1))?;
+    let days = i64::from(options.days.max(1));
+
+    let mut created = 0;
+    for number in 1..=options.orders {
+        let notes = format!("Demo order #{number}");
+        let exists: Option<i64> = sqlx::query_scalar("SELECT id FROM orders WHERE restaurant_id = ? AND notes = ?")
+            .bind(restaurant_id)
+            .bind(&notes)
+            .fetch_optional(pool)
+            .await?;
+        if exists.is_some() {
+            continue;
+        }
+
+        let mut rng = StdRng::seed_from_u64(u64::from(number));
+        let mut lines: Vec<(&(i64, String, String, i64), i32)> = Vec::new();
+        for _ in 0..rng.gen_range(1..=4) {
+            let item = &menu[rng.gen_range(0..menu.len())];
+            match lines.iter_mut().find(|(line, _)| line.0 == item.0) {
+                Some((_, quantity)) => *quantity += 1,
+                None => lines.push((item, rng.gen_range(1..=3))),
+            }
+        }
+        let subtotal: i64 = lines.iter().map(|(item, quantity)| item.3 * i64::from(*quantity)).sum();
+        let tax = tax_paise(subtotal, tax_bps);
+        // Older orders are finished; about one in twelve was cancelled.
+        let status = if rng.gen_ratio(1, 12) { "cancelled" } else { "served" };
+        let (start, end) = SERVICE_WINDOWS[rng.gen_range(0..SERVICE_WINDOWS.len())];
+        let created_at = today - Duration::days(1 + i64::from(number - 1) % days) + Duration::minutes(rng.gen_range(start..end));
+        let updated_at = created_at + Duration::minutes(rng.gen_range(25..70));
+
+        let mut tx = pool.begin().await?;
+        let result = sqlx::query(
+            "INSERT INTO orders (restaurant_id, fulfillment, table_id, status, subtotal_paise, tax_paise, \
+             total_paise, notes, created_at, updated_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(restaurant_id)
+        .bind(FULFILLMENT_DINE_IN)
+        .bind(tables[rng.gen_range(0..tables.len())])
+        .bind(status)
+        .bind(subtotal)
+        .bind(tax)
+        .bind(subtotal + tax)
+        .bind(&notes)
+        .bind(created_at)
+        .bind(updated_at)
+        .execute(&mut *tx)
+        .await?;
+        let order_id = result.last_insert_id() as i64;
+        for ((menu_item_id, sku, name, price), quantity) in lines {
+            sqlx::query(
+                "INSERT INTO order_items (order_id, menu_item_id, sku, name, unit_price_paise, quantity) \
+                 VALUES (?, ?, ?, ?, ?, ?)",
+            )
+            .bind(order_id)
+            .bind(menu_item_id)
+            .bind(sku)
+            .bind(name)
+            .bind(price)
+            .bind(quantity)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        created += 1;
+    }
+    Ok(created)
+}
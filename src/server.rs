@@ -0,0 +1,337 @@
+use crate::access_log::{redacted_path, LoggedUser, RequestLog};
+use crate::app::AppServices;
+use crate::config;
+use crate::error::{render_error, AppError, AppResult};
+use crate::faults;
+use crate::health;
+use crate::i18n;
+use crate::logging;
+use crate::metrics::{Metrics, METRICS};
+use crate::mtls::{self, ClientIdentity};
+use crate::multipart::{self, MultipartParser};
+use crate::request::{request_id_from, RequestContext};
+use crate::router::Router;
+use anyhow::Result;
+use bytes::{Buf, Bytes, BytesMut};
+use h3::server::{RequestResolver, RequestStream};
+use http::HeaderValue;
+use quinn::Endpoint;
+use serde_json::json;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// How long a request refused for lack of room may take to send its headers and
+// receive the 503 before it is dropped.
+const REFUSE_TIMEOUT: Duration = Duration::from_secs(2);
+
+// Request body cap and large-response warning threshold, from MAX_REQUEST_BODY_BYTES
+// (default 1 MiB) and RESPONSE_WARN_BYTES (default 1 MiB). Multipart uploads are
+// capped by MultipartLimits instead.
+#[derive(Debug, Clone, Copy)]
+pub struct BodyLimits {
+    pub max_request_bytes: usize,
+    pub response_warn_bytes: usize,
+}
+
+impl Default for BodyLimits {
+    fn default() -> Self {
+        Self {
+            max_request_bytes: 1024 * 1024,
+            response_warn_bytes: 1024 * 1024,
+        }
+    }
+}
+
+impl BodyLimits {
+    pub fn from_env() -> Self {
+        let var = |name: &str, default: usize| config::var(name).and_then(|v| v.parse().ok()).unwrap_or(default);
+        let defaults = Self::default();
+        Self {
+            max_request_bytes: var("MAX_REQUEST_BODY_BYTES", defaults.max_request_bytes),
+            response_warn_bytes: var("RESPONSE_WARN_BYTES", defaults.response_warn_bytes),
+        }
+    }
+}
+
+// Main server loop: accept QUIC connections and serve HTTP/3 requests on each.
+pub async fn run(endpoint: Endpoint, router: Arc<Router>, services: Arc<AppServices>) -> Result<()> {
+    health::mark_started();
+    while let Some(incoming) = endpoint.accept().await {
+        let Some(connection_slot) = services.task_limits.connection() else {
+            Metrics::increment(&METRICS.refused_connections_total);
+            incoming.refuse();
+            continue;
+        };
+        let router = router.clone();
+        let services = services.clone();
+
+        // Spawn a new task to handle each incoming QUIC connection.
+        tokio::spawn(async move {
+            let _connection_slot = connection_slot;
+            // A failed handshake only affects this connection.
+            let remote = incoming.remote_address();
+            let conn = match incoming.await {
+                Ok(conn) => conn,
+                Err(err) => {
+                    services.qlog.handshake_failed(remote, &err.to_string());
+                    logging::warn("connection failed", json!({ "error": err.to_string() }));
+                    return;
+                }
+            };
+            if let Err(err) = handle_connection(conn, router, services).await {
+                logging::warn("connection error", json!({ "error": err.to_string() }));
+            }
+        });
+    }
+    Ok(())
+}
+
+// Accepts and dispatches HTTP/3 requests on a single QUIC connection.
+async fn handle_connection(conn: quinn::Connection, router: Arc<Router>, services: Arc<AppServices>) -> Result<()> {
+    let remote_addr = conn.remote_address();
+    // Only the mTLS admin listener asks for client certificates.
+    let client_identity = match mtls::client_identity(&conn) {
+        Some(identity) => {
+            let allowed = mtls::identity_allowed(&identity);
+            let admin = if allowed { mtls::admin_for(&identity, &services).await? } else { None };
+            let Some(admin) = admin else {
+                logging::warn(
+                    "client certificate not authorized",
+                    json!({ "identity": identity, "remote_addr": remote_addr.to_string() }),
+                );
+                conn.close(quinn::VarInt::from_u32(0x0101), b"client certificate not authorized");
+                return Ok(());
+            };
+            Some(admin)
+        }
+        None => None,
+    };
+    let _tracked = services.connections.track(&conn);
+    if services.qlog.trace_all
+        && let Err(err) = services.connections.start_qlog(conn.stable_id(), &services.qlog)
+    {
+        logging::warn("qlog trace failed", json!({ "error": err.to_string() }));
+    }
+
+    // Create an h3 server connection from the Quinn connection.
+    let mut h3_conn = h3::server::Connection::new(h3_quinn::Connection::new(conn)).await?;
+
+    // Loop to accept and handle HTTP/3 requests on this connection.
+    // A closed connection or an accept error ends the loop.
+    while let Ok(Some(resolver)) = h3_conn.accept().await {
+        // Past the queue the request is refused here, without a task of its own.
+        let Some(slot) = services.task_limits.admit() else {
+            Metrics::increment(&METRICS.saturated_requests_total);
+            refuse_request(resolver, services.task_limits.retry_after_secs).await;
+            continue;
+        };
+        let router = router.clone();
+        let services = services.clone();
+        let client_identity = client_identity.clone();
+
+        tokio::spawn(async move {
+            let _running = slot.ready().await;
+            // Resolve the request to get the HTTP request and the stream.
+            let (req, stream) = match resolver.resolve_request().await {
+                Ok(resolved) => resolved,
+                Err(err) => {
+                    logging::warn("failed to resolve request", json!({ "error": err.to_string() }));
+                    return;
+                }
+            };
+            if let Err(err) = handle_request(req, stream, remote_addr, client_identity, &router, services).await {
+                logging::warn("request stream error", json!({ "error": err.to_string() }));
+            }
+        });
+    }
+    Ok(())
+}
+
+// Answers a request the server has no room for with 503 and Retry-After. This runs on
+// the connection's accept loop, so that connection opens no new requests meanwhile.
+async fn refuse_request(resolver: RequestResolver<h3_quinn::Connection, Bytes>, retry_after_secs: u64) {
+    let refused = tokio::time::timeout(REFUSE_TIMEOUT, async {
+        let (req, mut stream) = resolver.resolve_request().await?;
+        let lang = i18n::negotiate(req.headers().get("accept-language").and_then(|v| v.to_str().ok()));
+        let err = AppError::Overloaded { retry_after_secs };
+        let response = render_error(
+            &err,
+            Some(request_id_from(req.headers())),
+            lang,
+            req.method().as_str(),
+            req.uri().path(),
+        );
+        let (parts, body) = response.into_parts();
+        send_response(&mut stream, http::Response::from_parts(parts, ()), body).await
+    })
+    .await;
+    match refused {
+        Ok(Ok(())) => {}
+        Ok(Err(err)) => logging::warn("failed to refuse request", json!({ "error": err.to_string() })),
+        Err(_) => logging::warn("refused request timed out", json!({})),
+    }
+}
+
+// Reads the request body, runs the router and writes the response back to the stream.
+async fn handle_request<S>(
+    req: http::Request<()>,
+    mut stream: RequestStream<S, Bytes>,
+    remote_addr: SocketAddr,
+    client_identity: Option<ClientIdentity>,
+    router: &Router,
+    services: Arc<AppServices>,
+) -> Result<()>
+where
+    S: h3::quic::BidiStream<Bytes>,
+{
+    let started = Instant::now();
+    let _in_flight = services.load_shedder.start();
+
+    // The context takes the request apart; keep what the response and access log need.
+    let (method, uri) = (req.method().clone(), req.uri().clone());
+    let origin = req.headers().get("origin").cloned();
+    let mut ctx = RequestContext::from_request(req, Bytes::new(), remote_addr);
+    ctx.client_identity = client_identity;
+    let request_id = ctx.request_id.clone();
+    let lang = ctx.lang;
+    let mut bytes_received = 0;
+
+    // A body that breaks a limit is answered right away; the rest of it is never read.
+    let response = match read_body(&mut stream, &mut ctx, &services, &mut bytes_received).await {
+        Ok(()) => logging::with_request_id(request_id.clone(), router.dispatch(ctx, services.clone())).await,
+        // The stream itself failed (e.g. the client reset it); there is no one to answer.
+        Err(AppError::Internal(err)) => return Err(err),
+        Err(err) => {
+            if matches!(err, AppError::PayloadTooLarge(_)) {
+                Metrics::increment(&METRICS.request_bodies_rejected_total);
+            }
+            stream.stop_sending(h3::error::Code::H3_NO_ERROR);
+            logging::with_request_id(request_id.clone(), async {
+                render_error(&err, Some(request_id.clone()), lang, method.as_str(), uri.path())
+            })
+            .await
+        }
+    };
+
+    // Capture what the access log needs before the response is consumed.
+    let (mut parts, body) = response.into_parts();
+
+    // Echo the request id so clients and proxies can correlate the exchange.
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        parts.headers.insert("x-request-id", value);
+    }
+    services.cors.apply(
+        origin.as_ref().and_then(|v| v.to_str().ok()),
+        &mut parts.headers,
+    );
+    services.security_headers.apply(&mut parts.headers);
+    // Text in the response follows Accept-Language, so caches must key on it too.
+    parts.headers.insert(http::header::CONTENT_LANGUAGE, HeaderValue::from_static(lang));
+    parts.headers.append(http::header::VARY, HeaderValue::from_static("accept-language"));
+
+    Metrics::add(&METRICS.response_bytes_total, body.len() as u64);
+    if body.len() > services.body_limits.response_warn_bytes {
+        Metrics::increment(&METRICS.large_responses_total);
+        logging::warn(
+            "large response",
+            json!({
+                "request_id": request_id,
+                "method": method.as_str(),
+                "path": redacted_path(uri.path()),
+                "bytes": body.len(),
+                "threshold": services.body_limits.response_warn_bytes,
+            }),
+        );
+    }
+
+    let mut log = RequestLog {
+        timestamp: chrono::Utc::now().to_rfc3339(),
+        request_id: Some(request_id),
+        method: method.to_string(),
+        path: redacted_path(uri.path()).into_owned(),
+        status: parts.status.as_u16(),
+        latency_ms: 0.0,
+        user_id: parts.extensions.get::<LoggedUser>().map(|user| user.0.clone()),
+        bytes_received,
+        response_bytes: body.len(),
+        bytes_sent: body.len(),
+        remote_addr: remote_addr.to_string(),
+    };
+
+    // Send the response headers, then the body, then finish the stream. An injected
+    // fault (FAULT_INJECTION) resets the stream instead, as a failed send would.
+    let sent = match faults::response().await {
+        Ok(()) => send_response(&mut stream, http::Response::from_parts(parts, ()), body).await,
+        Err(err) => {
+            stream.stop_stream(h3::error::Code::H3_INTERNAL_ERROR);
+            Err(err)
+        }
+    };
+
+    // The record is emitted even if the client went away mid-response.
+    log.latency_ms = started.elapsed().as_secs_f64() * 1000.0;
+    if sent.is_err() {
+        log.bytes_sent = 0;
+    }
+    services.access_log.emit(&log);
+    sent
+}
+
+// Reads the request body into the context; DATA frames arrive as a sequence of chunks.
+// Multipart bodies are parsed as each chunk arrives so size limits apply before the whole
+// upload is held in memory. Other bodies are refused with 413 as soon as Content-Length
+// or the bytes received so far exceed MAX_REQUEST_BODY_BYTES.
+async fn read_body<S>(
+    stream: &mut RequestStream<S, Bytes>,
+    ctx: &mut RequestContext,
+    services: &AppServices,
+    received: &mut usize,
+) -> AppResult<()>
+where
+    S: h3::quic::BidiStream<Bytes>,
+{
+    let boundary = ctx.header("content-type").and_then(multipart::boundary);
+    let Some(boundary) = boundary else {
+        let max = services.body_limits.max_request_bytes;
+        let too_large = || AppError::PayloadTooLarge(format!("request body exceeds {max} bytes"));
+        if ctx
+            .header("content-length")
+            .and_then(|v| v.parse::<usize>().ok())
+            .is_some_and(|length| length > max)
+        {
+            return Err(too_large());
+        }
+        let mut body = BytesMut::new();
+        while let Some(mut chunk) = stream.recv_data().await.map_err(anyhow::Error::from)? {
+            *received += chunk.remaining();
+            if *received > max {
+                return Err(too_large());
+            }
+            body.extend_from_slice(&chunk.copy_to_bytes(chunk.remaining()));
+        }
+        ctx.body = body.freeze();
+        return Ok(());
+    };
+
+    let mut parser = MultipartParser::new(&boundary, services.multipart_limits);
+    while let Some(mut chunk) = stream.recv_data().await.map_err(anyhow::Error::from)? {
+        *received += chunk.remaining();
+        parser.feed(&chunk.copy_to_bytes(chunk.remaining()))?;
+    }
+    ctx.multipart = Some(parser.finish()?);
+    Ok(())
+}
+
+// Writes the head, the body (if any) and the end of stream.
+async fn send_response<S>(stream: &mut RequestStream<S, Bytes>, head: http::Response<()>, body: Bytes) -> Result<()>
+where
+    S: h3::quic::BidiStream<Bytes>,
+{
+    stream.send_response(head).await?;
+    if !body.is_empty() {
+        stream.send_data(body).await?;
+    }
+    stream.finish().await?;
+    Ok(())
+}
@@ -0,0 +1,187 @@
+use crate::audit::{AuditEvent, AuditLogger};
+use crate::error::{AppError, AppResult, FieldError};
+use crate::extract::{State, TenantAdmin, ValidJson};
+use crate::openapi::ApiSchema;
+use crate::response::ResponseBuilder;
+use crate::router::Router;
+use crate::validation::Validate;
+use anyhow::{anyhow, Result};
+use bytes::Bytes;
+use http::{Response, StatusCode};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sqlx::mysql::MySqlPool;
+
+// A table or hotel room guests order from by scanning its QR code. The code encodes
+// order_path(), which carries the token; the token alone is enough to place an order.
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct DiningTable {
+    pub id: i64,
+    pub restaurant_id: i64,
+    pub label: String,
+    pub kind: String,
+    pub token: String,
+    pub active: bool,
+}
+
+impl DiningTable {
+    // Path the QR code points at; the guest app reads the token from it.
+    pub fn order_path(&self) -> String {
+        format!("/t/{}", self.token)
+    }
+}
+
+impl ApiSchema for DiningTable {
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["id", "restaurant_id", "label", "kind", "token", "active"],
+            "properties": {
+                "id": { "type": "integer" },
+                "restaurant_id": { "type": "integer" },
+                "label": { "type": "string" },
+                "kind": { "type": "string", "enum": ["table", "room"] },
+                "token": { "type": "string" },
+                "active": { "type": "boolean" },
+                "qr_path": { "type": "string", "description": "Path to encode in the table's QR code" }
+            }
+        })
+    }
+}
+
+// Body accepted by POST /api/admin/tables.
+#[derive(Debug, Deserialize)]
+pub struct DiningTableInput {
+    pub label: String,
+    #[serde(default = "default_kind")]
+    pub kind: String,
+}
+
+fn default_kind() -> String {
+    "table".to_string()
+}
+
+impl ApiSchema for DiningTableInput {
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["label"],
+            "properties": {
+                "label": { "type": "string", "minLength": 1, "maxLength": 64 },
+                "kind": { "type": "string", "enum": ["table", "room"] }
+            }
+        })
+    }
+}
+
+impl Validate for DiningTableInput {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        if self.label.trim().is_empty() {
+            errors.push(FieldError::new("label", "must not be blank"));
+        }
+        if !["table", "room"].contains(&self.kind.as_str()) {
+            errors.push(FieldError::new("kind", "must be table or room"));
+        }
+        errors
+    }
+}
+
+const SELECT_TABLE: &str = "SELECT id, restaurant_id, label, kind, token, active FROM dining_tables";
+
+// Database access for dining tables.
+pub struct DiningTableRepository<'a> {
+    pool: &'a MySqlPool,
+}
+
+impl<'a> DiningTableRepository<'a> {
+    pub fn new(pool: &'a MySqlPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn list(&self, restaurant_id: i64) -> Result<Vec<DiningTable>> {
+        Ok(sqlx::query_as(&format!("{SELECT_TABLE} WHERE restaurant_id = ? ORDER BY label"))
+            .bind(restaurant_id)
+            .fetch_all(self.pool)
+            .await?)
+    }
+
+    // Active table for a QR token, from any restaurant.
+    pub async fn by_token(&self, token: &str) -> Result<Option<DiningTable>> {
+        Ok(sqlx::query_as(&format!("{SELECT_TABLE} WHERE token = ? AND active"))
+            .bind(token)
+            .fetch_optional(self.pool)
+            .await?)
+    }
+
+    pub async fn create(&self, restaurant_id: i64, input: &DiningTableInput) -> Result<DiningTable> {
+        let mut bytes = [0u8; 16];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        let token = hex::encode(bytes);
+        sqlx::query("INSERT INTO dining_tables (restaurant_id, label, kind, token) VALUES (?, ?, ?, ?)")
+            .bind(restaurant_id)
+            .bind(input.label.trim())
+            .bind(&input.kind)
+            .bind(&token)
+            .execute(self.pool)
+            .await?;
+        self.by_token(&token)
+            .await?
+            .ok_or_else(|| anyhow!("dining table {token} vanished after insert"))
+    }
+}
+
+// Registers the restaurant-admin table management endpoints.
+pub fn routes(router: Router) -> Router {
+    router
+        .get("/api/admin/tables", list_tables)
+        .summary("List the restaurant's tables and rooms")
+        .requires_tenant_admin()
+        .response_schema(json!({ "type": "array", "items": DiningTable::schema() }))
+        .post("/api/admin/tables", create_table)
+        .summary("Add a table or room and issue its QR token")
+        .requires_tenant_admin()
+        .request_schema(DiningTableInput::schema())
+        .response_schema(DiningTable::schema())
+}
+
+fn with_qr_path(table: &DiningTable) -> AppResult<Value> {
+    let mut body = serde_json::to_value(table).map_err(|err| AppError::Internal(err.into()))?;
+    body["qr_path"] = Value::String(table.order_path());
+    Ok(body)
+}
+
+async fn list_tables(TenantAdmin(restaurant): TenantAdmin, State(services): State) -> AppResult<Response<Bytes>> {
+    let tables = DiningTableRepository::new(services.db()?).list(restaurant.id).await?;
+    let body = tables.iter().map(with_qr_path).collect::<AppResult<Vec<_>>>()?;
+    ResponseBuilder::json(StatusCode::OK, &body)
+}
+
+async fn create_table(
+    TenantAdmin(restaurant): TenantAdmin,
+    ValidJson(input): ValidJson<DiningTableInput>,
+    audit: AuditLogger,
+    State(services): State,
+) -> AppResult<Response<Bytes>> {
+    let repository = DiningTableRepository::new(services.db()?);
+    if repository
+        .list(restaurant.id)
+        .await?
+        .iter()
+        .any(|table| table.label == input.label.trim())
+    {
+        return Err(AppError::Conflict(format!("table {} already exists", input.label.trim())));
+    }
+    let table = repository.create(restaurant.id, &input).await?;
+    let event = AuditEvent {
+        action: "table.create",
+        target_type: "dining_table",
+        target_id: table.id.to_string(),
+        restaurant_id: Some(restaurant.id),
+        before: None,
+        after: Some(json!({ "label": table.label, "kind": table.kind })),
+    };
+    audit.record(services.db()?, event).await?;
+    ResponseBuilder::json(StatusCode::CREATED, &with_qr_path(&table)?)
+}
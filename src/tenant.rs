@@ -0,0 +1,277 @@
+use crate::app::AppServices;
+use crate::audit::{AuditEvent, AuditLogger};
+use crate::auth::{admin_token_allowed, constant_time_eq};
+use crate::config;
+use crate::error::{AppError, AppResult, FieldError};
+use crate::extract::{Admin, State, ValidJson};
+use crate::middleware::Middleware;
+use crate::openapi::ApiSchema;
+use crate::request::RequestContext;
+use crate::response::ResponseBuilder;
+use crate::router::Router;
+use crate::validation::Validate;
+use anyhow::{anyhow, Result};
+use bytes::Bytes;
+use futures::future::BoxFuture;
+use http::{Response, StatusCode};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use sqlx::mysql::MySqlPool;
+
+// Tenant resolution. A request is for a restaurant named by, in order:
+//   1. a "/r/<slug>" path prefix, which is stripped before routing, so
+//      /r/spice-hut/api/admin/menu/export is routed as /api/admin/menu/export;
+//   2. the Host, when it ends in TENANT_HOST_SUFFIX (e.g. ".rotiride.app" makes
+//      spice-hut.rotiride.app resolve to "spice-hut");
+//   3. otherwise DEFAULT_RESTAURANT_SLUG ("default"), so single-restaurant
+//      deployments keep working unchanged.
+
+// Splits a tenant path prefix off `path`. Returns the path to route and the slug, if any.
+pub fn split_path(path: &str) -> (String, Option<String>) {
+    if let Some(rest) = path.strip_prefix("/r/") {
+        let (slug, remainder) = rest.split_once('/').map_or((rest, ""), |(s, r)| (s, r));
+        if !slug.is_empty() {
+            return (format!("/{remainder}"), Some(slug.to_string()));
+        }
+    }
+    (path.to_string(), None)
+}
+
+// The tenant slug carried by the host name, if TENANT_HOST_SUFFIX is configured.
+pub fn slug_from_host(host: &str) -> Option<String> {
+    let suffix = config::var("TENANT_HOST_SUFFIX")?;
+    let host = host.split(':').next().unwrap_or(host);
+    let slug = host.strip_suffix(suffix.as_str())?;
+    (!slug.is_empty() && !slug.contains('.')).then(|| slug.to_string())
+}
+
+// A restaurant as stored in the restaurants table.
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct Restaurant {
+    pub id: i64,
+    pub slug: String,
+    pub name: String,
+    #[serde(skip_serializing)]
+    pub admin_token_hash: Option<String>,
+    pub active: bool,
+}
+
+impl ApiSchema for Restaurant {
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["id", "slug", "name", "active"],
+            "properties": {
+                "id": { "type": "integer" },
+                "slug": { "type": "string" },
+                "name": { "type": "string" },
+                "active": { "type": "boolean" },
+                "admin_token": { "type": "string", "description": "Restaurant admin token; only returned on create" }
+            }
+        })
+    }
+}
+
+// Body accepted by POST /api/admin/restaurants.
+#[derive(Debug, Deserialize)]
+pub struct RestaurantInput {
+    pub slug: String,
+    pub name: String,
+}
+
+impl ApiSchema for RestaurantInput {
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["slug", "name"],
+            "properties": {
+                "slug": { "type": "string", "minLength": 1, "maxLength": 64 },
+                "name": { "type": "string", "minLength": 1, "maxLength": 200 }
+            }
+        })
+    }
+}
+
+impl Validate for RestaurantInput {
+    // Checks rules the JSON Schema can't express; an empty list means the input is valid.
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        let valid_slug = self
+            .slug
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+        if self.slug.is_empty() || !valid_slug || self.slug.starts_with('-') {
+            errors.push(FieldError::new("slug", "must be lowercase letters, digits and dashes"));
+        }
+        if self.name.trim().is_empty() {
+            errors.push(FieldError::new("name", "must not be blank"));
+        }
+        errors
+    }
+}
+
+fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+const SELECT_RESTAURANT: &str = "SELECT id, slug, name, admin_token_hash, active FROM restaurants";
+
+// Database access for restaurants.
+pub struct RestaurantRepository<'a> {
+    pool: &'a MySqlPool,
+}
+
+impl<'a> RestaurantRepository<'a> {
+    pub fn new(pool: &'a MySqlPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn list(&self) -> Result<Vec<Restaurant>> {
+        Ok(sqlx::query_as(&format!("{SELECT_RESTAURANT} ORDER BY id"))
+            .fetch_all(self.pool)
+            .await?)
+    }
+
+    pub async fn by_slug(&self, slug: &str) -> Result<Option<Restaurant>> {
+        Ok(sqlx::query_as(&format!("{SELECT_RESTAURANT} WHERE slug = ?"))
+            .bind(slug)
+            .fetch_optional(self.pool)
+            .await?)
+    }
+
+    // Creates the restaurant with a fresh admin token, returned alongside it. Only the
+    // token's hash is stored.
+    pub async fn create(&self, input: &RestaurantInput) -> Result<(Restaurant, String)> {
+        let mut bytes = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        let token = format!("rt_{}", hex::encode(bytes));
+        sqlx::query("INSERT INTO restaurants (slug, name, admin_token_hash) VALUES (?, ?, ?)")
+            .bind(&input.slug)
+            .bind(&input.name)
+            .bind(hash_token(&token))
+            .execute(self.pool)
+            .await?;
+        let restaurant = self
+            .by_slug(&input.slug)
+            .await?
+            .ok_or_else(|| anyhow!("restaurant {} vanished after insert", input.slug))?;
+        Ok((restaurant, token))
+    }
+}
+
+// Loads the restaurant the request is for. Unknown or inactive restaurants are 404.
+pub async fn current(ctx: &RequestContext, services: &AppServices) -> AppResult<Restaurant> {
+    let slug = ctx
+        .tenant
+        .clone()
+        .or_else(|| config::var("DEFAULT_RESTAURANT_SLUG"))
+        .unwrap_or_else(|| "default".to_string());
+    RestaurantRepository::new(services.db()?)
+        .by_slug(&slug)
+        .await?
+        .filter(|restaurant| restaurant.active)
+        .ok_or_else(|| AppError::NotFound(format!("restaurant {slug} not found")))
+}
+
+// Admin check scoped to one restaurant: the global ADMIN_API_TOKEN and admin users'
+// tokens work for every restaurant, a restaurant's own token only for that restaurant.
+pub async fn require_tenant_admin(ctx: &RequestContext, services: &AppServices, restaurant: &Restaurant) -> AppResult<()> {
+    if ctx.client_identity.is_some() {
+        return Ok(());
+    }
+    let Some(token) = ctx.bearer_token() else {
+        return Err(AppError::Unauthorized("missing bearer token".to_string()));
+    };
+    let global = services
+        .admin_token()
+        .is_some_and(|expected| constant_time_eq(token.as_bytes(), expected.as_bytes()));
+    let tenant = restaurant
+        .admin_token_hash
+        .as_deref()
+        .is_some_and(|expected| constant_time_eq(hash_token(token).as_bytes(), expected.as_bytes()));
+    if global || tenant || services.admin_sessions.verify(token).is_some() {
+        return Ok(());
+    }
+    if let Some(pool) = &services.db
+        && admin_token_allowed(pool, token).await?
+    {
+        return Ok(());
+    }
+    Err(AppError::Forbidden("forbidden".to_string()))
+}
+
+// The restaurant TenantAdminOnly verified the caller against. A route without
+// requires_tenant_admin has none, which is a bug in the route table rather than the
+// caller's, so it is an internal error.
+pub fn verified(ctx: &RequestContext) -> AppResult<Restaurant> {
+    ctx.tenant_admin.get().cloned().ok_or_else(|| {
+        AppError::Internal(anyhow!("{} {} reads the restaurant without requires_tenant_admin", ctx.method, ctx.path))
+    })
+}
+
+// Resolves the request's restaurant and checks the caller is its admin, for routes a
+// restaurant's own admin may call (see Router::requires_tenant_admin). It is the only
+// place those routes check the caller; handlers get the restaurant from verified().
+pub struct TenantAdminOnly;
+
+impl Middleware for TenantAdminOnly {
+    fn name(&self) -> &'static str {
+        "tenant_admin_only"
+    }
+
+    fn before<'a>(&'a self, ctx: &'a RequestContext, services: &'a AppServices) -> BoxFuture<'a, AppResult<()>> {
+        Box::pin(async move {
+            let restaurant = current(ctx, services).await?;
+            require_tenant_admin(ctx, services, &restaurant).await?;
+            let _ = ctx.tenant_admin.set(restaurant);
+            Ok(())
+        })
+    }
+}
+
+// Registers the platform-admin restaurant endpoints (global admin token only).
+pub fn routes(router: Router) -> Router {
+    router.group("/api/admin/restaurants", |group| {
+        group
+            .requires_admin()
+            .get("", list_restaurants)
+            .summary("List restaurants")
+            .response_schema(json!({ "type": "array", "items": Restaurant::schema() }))
+            .post("", create_restaurant)
+            .summary("Create a restaurant and its admin token")
+            .request_schema(RestaurantInput::schema())
+            .response_schema(Restaurant::schema())
+    })
+}
+
+async fn list_restaurants(_: Admin, State(services): State) -> AppResult<Response<Bytes>> {
+    let restaurants = RestaurantRepository::new(services.db()?).list().await?;
+    ResponseBuilder::json(StatusCode::OK, &restaurants)
+}
+
+async fn create_restaurant(
+    _: Admin,
+    ValidJson(input): ValidJson<RestaurantInput>,
+    audit: AuditLogger,
+    State(services): State,
+) -> AppResult<Response<Bytes>> {
+    let repository = RestaurantRepository::new(services.db()?);
+    if repository.by_slug(&input.slug).await?.is_some() {
+        return Err(AppError::Conflict(format!("restaurant {} already exists", input.slug)));
+    }
+    let (restaurant, token) = repository.create(&input).await?;
+    let event = AuditEvent {
+        action: "restaurant.create",
+        target_type: "restaurant",
+        target_id: restaurant.id.to_string(),
+        restaurant_id: Some(restaurant.id),
+        before: None,
+        after: Some(json!(restaurant)),
+    };
+    audit.record(services.db()?, event).await?;
+    let mut body = serde_json::to_value(&restaurant).map_err(|err| AppError::Internal(err.into()))?;
+    body["admin_token"] = Value::String(token);
+    ResponseBuilder::json(StatusCode::CREATED, &body)
+}
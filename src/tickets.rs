@@ -0,0 +1,475 @@
+use crate::audit::{AuditEvent, AuditLogger};
+use crate::error::{AppError, AppResult, FieldError};
+use crate::extract::{Json, Path, Query, State, TenantAdmin};
+use crate::openapi::ApiSchema;
+use crate::orders::{table_for_token, OrderRepository};
+use crate::response::ResponseBuilder;
+use crate::router::Router;
+use crate::runtime_config::ConfigService;
+use crate::webhooks;
+use anyhow::Result;
+use bytes::Bytes;
+use chrono::{DateTime, Duration, Utc};
+use http::{Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sqlx::mysql::MySqlPool;
+
+// Support tickets: complaints and refund requests a guest raises about one of their
+// table's orders, triaged by staff. Staff first pick a ticket up (open -> in_progress)
+// and then settle it with one of the canned RESOLUTIONS; refunds are not paid here but
+// announced with a ticket.resolved webhook carrying refund_paise for the payment side.
+// Both steps have SLA timers (runtime config ticket_response_sla_minutes and
+// ticket_resolution_sla_minutes).
+
+pub const CATEGORIES: [&str; 6] = ["missing_items", "wrong_items", "late", "quality", "refund_request", "other"];
+pub const TICKET_STATUSES: [&str; 4] = ["open", "in_progress", "resolved", "rejected"];
+// no_action closes the ticket as rejected; the rest resolve it.
+pub const RESOLUTIONS: [&str; 5] = ["partial_refund", "full_refund", "replacement", "apology", "no_action"];
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct Ticket {
+    pub id: i64,
+    pub order_id: i64,
+    pub category: String,
+    pub description: String,
+    pub status: String,
+    pub resolution: Option<String>,
+    pub refund_paise: i64,
+    pub resolution_note: String,
+    pub created_at: DateTime<Utc>,
+    pub first_response_at: Option<DateTime<Utc>>,
+    pub resolved_at: Option<DateTime<Utc>>,
+    // SLA deadlines and whether either was missed, filled in by with_sla().
+    #[sqlx(skip)]
+    pub response_due_at: Option<DateTime<Utc>>,
+    #[sqlx(skip)]
+    pub resolution_due_at: Option<DateTime<Utc>>,
+    #[sqlx(skip)]
+    pub sla_breached: bool,
+}
+
+impl Ticket {
+    pub fn with_sla(mut self, config: &ConfigService, now: DateTime<Utc>) -> Self {
+        let response_due = self.created_at + Duration::minutes(config.ticket_response_sla_minutes().into());
+        let resolution_due = self.created_at + Duration::minutes(config.ticket_resolution_sla_minutes().into());
+        self.sla_breached =
+            self.first_response_at.unwrap_or(now) > response_due || self.resolved_at.unwrap_or(now) > resolution_due;
+        self.response_due_at = Some(response_due);
+        self.resolution_due_at = Some(resolution_due);
+        self
+    }
+}
+
+impl ApiSchema for Ticket {
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["id", "order_id", "category", "description", "status", "refund_paise", "created_at"],
+            "properties": {
+                "id": { "type": "integer" },
+                "order_id": { "type": "integer" },
+                "category": { "type": "string", "enum": CATEGORIES },
+                "description": { "type": "string" },
+                "status": { "type": "string", "enum": TICKET_STATUSES },
+                "resolution": { "type": ["string", "null"], "enum": [RESOLUTIONS[0], RESOLUTIONS[1], RESOLUTIONS[2], RESOLUTIONS[3], RESOLUTIONS[4], null] },
+                "refund_paise": { "type": "integer" },
+                "resolution_note": { "type": "string" },
+                "created_at": { "type": "string", "format": "date-time" },
+                "first_response_at": { "type": ["string", "null"], "format": "date-time" },
+                "resolved_at": { "type": ["string", "null"], "format": "date-time" },
+                "response_due_at": { "type": "string", "format": "date-time" },
+                "resolution_due_at": { "type": "string", "format": "date-time" },
+                "sla_breached": { "type": "boolean" }
+            }
+        })
+    }
+}
+
+// Body of POST /api/orders/:id/tickets.
+#[derive(Debug, Deserialize)]
+pub struct TicketInput {
+    pub table_token: String,
+    pub category: String,
+    #[serde(default)]
+    pub description: String,
+}
+
+impl ApiSchema for TicketInput {
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["table_token", "category"],
+            "properties": {
+                "table_token": { "type": "string", "minLength": 1 },
+                "category": { "type": "string", "enum": CATEGORIES },
+                "description": { "type": "string", "maxLength": 1000 }
+            }
+        })
+    }
+}
+
+// Body of POST /api/staff/tickets/:id/resolve. refund_paise is required for a partial
+// refund; a full refund is the order's total.
+#[derive(Debug, Deserialize)]
+pub struct ResolutionInput {
+    pub resolution: String,
+    #[serde(default)]
+    pub refund_paise: Option<i64>,
+    #[serde(default)]
+    pub note: String,
+}
+
+impl ApiSchema for ResolutionInput {
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["resolution"],
+            "properties": {
+                "resolution": { "type": "string", "enum": RESOLUTIONS },
+                "refund_paise": { "type": "integer", "minimum": 1 },
+                "note": { "type": "string", "maxLength": 1000 }
+            }
+        })
+    }
+}
+
+// Ticket counts and timings for the SLA dashboard.
+#[derive(Debug, Default, Serialize)]
+pub struct SlaSummary {
+    pub open: i64,
+    pub in_progress: i64,
+    // Unresolved tickets past their response or resolution deadline right now.
+    pub overdue: i64,
+    // Tickets settled in the last 7 days, how many within both deadlines, and their
+    // average time to resolution.
+    pub settled_last_7_days: i64,
+    pub settled_within_sla: i64,
+    pub average_resolution_minutes: Option<i64>,
+    pub refunded_paise_last_7_days: i64,
+}
+
+impl ApiSchema for SlaSummary {
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "open": { "type": "integer" },
+                "in_progress": { "type": "integer" },
+                "overdue": { "type": "integer" },
+                "settled_last_7_days": { "type": "integer" },
+                "settled_within_sla": { "type": "integer" },
+                "average_resolution_minutes": { "type": ["integer", "null"] },
+                "refunded_paise_last_7_days": { "type": "integer" }
+            }
+        })
+    }
+}
+
+const SELECT_TICKET: &str = "SELECT id, order_id, category, description, status, resolution, refund_paise, \
+                             resolution_note, created_at, first_response_at, resolved_at FROM support_tickets";
+
+// Database access for one restaurant's support tickets.
+pub struct TicketRepository<'a> {
+    pool: &'a MySqlPool,
+    restaurant_id: i64,
+}
+
+impl<'a> TicketRepository<'a> {
+    pub fn new(pool: &'a MySqlPool, restaurant_id: i64) -> Self {
+        Self { pool, restaurant_id }
+    }
+
+    pub async fn get(&self, id: i64) -> Result<Option<Ticket>> {
+        Ok(sqlx::query_as(&format!("{SELECT_TICKET} WHERE id = ? AND restaurant_id = ?"))
+            .bind(id)
+            .bind(self.restaurant_id)
+            .fetch_optional(self.pool)
+            .await?)
+    }
+
+    // The triage queue: unsettled tickets oldest first, or every ticket with `status`.
+    pub async fn list(&self, status: Option<&str>) -> Result<Vec<Ticket>> {
+        Ok(sqlx::query_as(&format!(
+            "{SELECT_TICKET} WHERE restaurant_id = ? \
+             AND (? IS NULL AND status IN ('open', 'in_progress') OR status = ?) \
+             ORDER BY created_at, id LIMIT 500"
+        ))
+        .bind(self.restaurant_id)
+        .bind(status)
+        .bind(status)
+        .fetch_all(self.pool)
+        .await?)
+    }
+
+    // Raises a ticket on an order. A guest can have one unsettled ticket per category
+    // and order, so repeated taps don't flood the queue.
+    pub async fn create(&self, order_id: i64, input: &TicketInput) -> AppResult<Ticket> {
+        let duplicate: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM support_tickets WHERE order_id = ? AND category = ? \
+             AND status IN ('open', 'in_progress')",
+        )
+        .bind(order_id)
+        .bind(&input.category)
+        .fetch_one(self.pool)
+        .await?;
+        if duplicate > 0 {
+            return Err(AppError::Conflict("a ticket about this is already open for the order".to_string()));
+        }
+        let result = sqlx::query(
+            "INSERT INTO support_tickets (restaurant_id, order_id, category, description) VALUES (?, ?, ?, ?)",
+        )
+        .bind(self.restaurant_id)
+        .bind(order_id)
+        .bind(&input.category)
+        .bind(input.description.trim())
+        .execute(self.pool)
+        .await?;
+        let id = result.last_insert_id() as i64;
+        self.get(id).await?.ok_or_else(ticket_not_found)
+    }
+
+    // open -> in_progress; starts nothing else, but stops the response timer.
+    pub async fn pick_up(&self, id: i64, audit: &AuditLogger) -> AppResult<Ticket> {
+        let mut tx = self.pool.begin().await?;
+        let updated = sqlx::query(
+            "UPDATE support_tickets SET status = 'in_progress', first_response_at = NOW() \
+             WHERE id = ? AND restaurant_id = ? AND status = 'open'",
+        )
+        .bind(id)
+        .bind(self.restaurant_id)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+        if updated == 0 {
+            let ticket = self.get(id).await?.ok_or_else(ticket_not_found)?;
+            return Err(AppError::Conflict(format!("ticket is already {}", ticket.status)));
+        }
+        let event = AuditEvent {
+            action: "ticket.status",
+            target_type: "support_ticket",
+            target_id: id.to_string(),
+            restaurant_id: Some(self.restaurant_id),
+            before: Some(json!({ "status": "open" })),
+            after: Some(json!({ "status": "in_progress" })),
+        };
+        audit.record(&mut *tx, event).await?;
+        tx.commit().await?;
+        self.get(id).await?.ok_or_else(ticket_not_found)
+    }
+
+    // Settles an unsettled ticket with a canned resolution and announces any refund.
+    pub async fn resolve(&self, id: i64, input: &ResolutionInput, audit: &AuditLogger) -> AppResult<Ticket> {
+        let ticket = self.get(id).await?.ok_or_else(ticket_not_found)?;
+        if !matches!(ticket.status.as_str(), "open" | "in_progress") {
+            return Err(AppError::Conflict(format!("ticket is already {}", ticket.status)));
+        }
+        let order = OrderRepository::new(self.pool, self.restaurant_id)
+            .get(ticket.order_id)
+            .await?
+            .ok_or_else(|| AppError::NotFound("order not found".to_string()))?;
+        let refund_paise = match (input.resolution.as_str(), input.refund_paise) {
+            ("full_refund", _) => order.total_paise,
+            ("partial_refund", Some(amount)) if amount > 0 && amount <= order.total_paise => amount,
+            ("partial_refund", _) => {
+                return Err(AppError::Validation(vec![FieldError::new(
+                    "refund_paise",
+                    format!("must be between 1 and the order total of {} paise", order.total_paise),
+                )]));
+            }
+            (resolution, _) if RESOLUTIONS.contains(&resolution) => 0,
+            (resolution, _) => {
+                return Err(AppError::Validation(vec![FieldError::new(
+                    "resolution",
+                    format!("unknown resolution {resolution}"),
+                )]));
+            }
+        };
+        let status = if input.resolution == "no_action" { "rejected" } else { "resolved" };
+
+        let mut tx = self.pool.begin().await?;
+        let updated = sqlx::query(
+            "UPDATE support_tickets SET status = ?, resolution = ?, refund_paise = ?, resolution_note = ?, \
+             first_response_at = COALESCE(first_response_at, NOW()), resolved_at = NOW() \
+             WHERE id = ? AND restaurant_id = ? AND status = ?",
+        )
+        .bind(status)
+        .bind(&input.resolution)
+        .bind(refund_paise)
+        .bind(input.note.trim())
+        .bind(id)
+        .bind(self.restaurant_id)
+        .bind(&ticket.status)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+        if updated == 0 {
+            return Err(AppError::Conflict("ticket changed concurrently; retry".to_string()));
+        }
+        let event = AuditEvent {
+            action: "ticket.resolve",
+            target_type: "support_ticket",
+            target_id: id.to_string(),
+            restaurant_id: Some(self.restaurant_id),
+            before: Some(json!({ "status": ticket.status })),
+            after: Some(json!({ "status": status, "resolution": input.resolution, "refund_paise": refund_paise })),
+        };
+        audit.record(&mut *tx, event).await?;
+        let payload = json!({
+            "ticket_id": id,
+            "order_id": ticket.order_id,
+            "restaurant_id": self.restaurant_id,
+            "resolution": input.resolution,
+            "refund_paise": refund_paise,
+        });
+        webhooks::enqueue(&mut *tx, self.restaurant_id, "ticket.resolved", &payload).await?;
+        tx.commit().await?;
+        self.get(id).await?.ok_or_else(ticket_not_found)
+    }
+
+    pub async fn sla_summary(&self, config: &ConfigService) -> Result<SlaSummary> {
+        let now = Utc::now();
+        let mut summary = SlaSummary::default();
+        for ticket in self.list(None).await? {
+            match ticket.status.as_str() {
+                "open" => summary.open += 1,
+                _ => summary.in_progress += 1,
+            }
+            if ticket.with_sla(config, now).sla_breached {
+                summary.overdue += 1;
+            }
+        }
+        let settled: Vec<Ticket> = sqlx::query_as(&format!(
+            "{SELECT_TICKET} WHERE restaurant_id = ? AND resolved_at > NOW() - INTERVAL 7 DAY"
+        ))
+        .bind(self.restaurant_id)
+        .fetch_all(self.pool)
+        .await?;
+        let mut total_minutes = 0;
+        for ticket in settled {
+            summary.settled_last_7_days += 1;
+            summary.refunded_paise_last_7_days += ticket.refund_paise;
+            if let Some(resolved_at) = ticket.resolved_at {
+                total_minutes += (resolved_at - ticket.created_at).num_minutes();
+            }
+            if !ticket.with_sla(config, now).sla_breached {
+                summary.settled_within_sla += 1;
+            }
+        }
+        if summary.settled_last_7_days > 0 {
+            summary.average_resolution_minutes = Some(total_minutes / summary.settled_last_7_days);
+        }
+        Ok(summary)
+    }
+}
+
+fn ticket_not_found() -> AppError {
+    AppError::NotFound("ticket not found".to_string())
+}
+
+// Registers the guest endpoint for raising tickets and the staff triage endpoints.
+pub fn routes(router: Router) -> Router {
+    router
+        .post("/api/orders/:id/tickets", create_ticket)
+        .summary("Report a problem with an order or ask for a refund")
+        .request_schema(TicketInput::schema())
+        .response_schema(Ticket::schema())
+        .get("/api/staff/tickets", list_tickets)
+        .summary("Support tickets to triage, oldest first, with SLA deadlines")
+        .urgency(2)
+        .requires_tenant_admin()
+        .query_param("status", false)
+        .response_schema(json!({ "type": "array", "items": Ticket::schema() }))
+        .get("/api/staff/tickets/sla", sla_summary)
+        .summary("Support ticket SLA summary for the dashboard")
+        .urgency(6)
+        .requires_tenant_admin()
+        .response_schema(SlaSummary::schema())
+        .post("/api/staff/tickets/:id/pick-up", pick_up)
+        .summary("Start working on an open ticket")
+        .urgency(2)
+        .requires_tenant_admin()
+        .response_schema(Ticket::schema())
+        .post("/api/staff/tickets/:id/resolve", resolve)
+        .summary("Settle a ticket with a canned resolution")
+        .urgency(2)
+        .requires_tenant_admin()
+        .request_schema(ResolutionInput::schema())
+        .response_schema(Ticket::schema())
+}
+
+// Guests can only raise tickets about their own table's orders.
+async fn create_ticket(
+    Path(order_id): Path<i64>,
+    Json(input): Json<TicketInput>,
+    State(services): State,
+) -> AppResult<Response<Bytes>> {
+    let table = table_for_token(&services, &input.table_token).await?;
+    let pool = services.db()?;
+    OrderRepository::new(pool, table.restaurant_id)
+        .get(order_id)
+        .await?
+        .filter(|order| order.table_id == Some(table.id))
+        .ok_or_else(|| AppError::NotFound("order not found".to_string()))?;
+    let ticket = TicketRepository::new(pool, table.restaurant_id)
+        .create(order_id, &input)
+        .await?;
+    ResponseBuilder::json(StatusCode::CREATED, &ticket.with_sla(&services.runtime_config, Utc::now()))
+}
+
+// Query of GET /api/staff/tickets.
+#[derive(Debug, Deserialize)]
+pub struct TicketFilter {
+    pub status: Option<String>,
+}
+
+async fn list_tickets(
+    TenantAdmin(restaurant): TenantAdmin,
+    Query(filter): Query<TicketFilter>,
+    State(services): State,
+) -> AppResult<Response<Bytes>> {
+    let status = filter.status.as_deref();
+    if let Some(status) = status
+        && !TICKET_STATUSES.contains(&status)
+    {
+        return Err(AppError::BadRequest(format!("unknown status {status}")));
+    }
+    let now = Utc::now();
+    let tickets: Vec<Ticket> = TicketRepository::new(services.db()?, restaurant.id)
+        .list(status)
+        .await?
+        .into_iter()
+        .map(|ticket| ticket.with_sla(&services.runtime_config, now))
+        .collect();
+    ResponseBuilder::json(StatusCode::OK, &tickets)
+}
+
+async fn sla_summary(TenantAdmin(restaurant): TenantAdmin, State(services): State) -> AppResult<Response<Bytes>> {
+    let summary = TicketRepository::new(services.db()?, restaurant.id)
+        .sla_summary(&services.runtime_config)
+        .await?;
+    ResponseBuilder::json(StatusCode::OK, &summary)
+}
+
+async fn pick_up(
+    TenantAdmin(restaurant): TenantAdmin,
+    Path(id): Path<i64>,
+    audit: AuditLogger,
+    State(services): State,
+) -> AppResult<Response<Bytes>> {
+    let ticket = TicketRepository::new(services.db()?, restaurant.id).pick_up(id, &audit).await?;
+    ResponseBuilder::json(StatusCode::OK, &ticket.with_sla(&services.runtime_config, Utc::now()))
+}
+
+async fn resolve(
+    TenantAdmin(restaurant): TenantAdmin,
+    Path(id): Path<i64>,
+    Json(input): Json<ResolutionInput>,
+    audit: AuditLogger,
+    State(services): State,
+) -> AppResult<Response<Bytes>> {
+    let ticket = TicketRepository::new(services.db()?, restaurant.id)
+        .resolve(id, &input, &audit)
+        .await?;
+    ResponseBuilder::json(StatusCode::OK, &ticket.with_sla(&services.runtime_config, Utc::now()))
+}
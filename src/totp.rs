@@ -0,0 +1,343 @@
+use crate::app::AppServices;
+use crate::audit::AuditLogger;
+use crate::auth_throttle;
+use crate::auth::{constant_time_eq, AdminUserRepository};
+use crate::error::{AppError, AppResult};
+use crate::openapi::ApiSchema;
+use crate::request::RequestContext;
+use crate::response::{CachePolicy, ResponseBuilder};
+use crate::router::Router;
+use anyhow::Result;
+use bytes::Bytes;
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use http::{Response, StatusCode};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use sqlx::mysql::MySqlPool;
+use sqlx::types::Json;
+use std::sync::Arc;
+
+// TOTP two-factor authentication for admin users (RFC 6238: HMAC-SHA1, 6 digits, 30
+// second steps), compatible with the usual authenticator apps. Enrolment is optional.
+// Once a user confirms it, their "adm_" token alone no longer passes admin checks
+// (see auth::admin_token_allowed): it only opens a session (admin_sessions), and only
+// together with a current code or one of ten single-use recovery codes. A stolen token
+// is then not enough to reach admin endpoints.
+
+const STEP_SECS: i64 = 30;
+// Codes from the previous and next step are accepted too, for clock drift.
+const ALLOWED_DRIFT_STEPS: i64 = 1;
+const RECOVERY_CODES: usize = 10;
+const ISSUER: &str = "RotiRide";
+const THROTTLE_SCOPE: &str = "admin_totp";
+
+// A current code or a recovery code, sent to open a session or disable two-factor.
+#[derive(Debug, Default, Deserialize)]
+pub struct SecondFactor {
+    pub code: Option<String>,
+    pub recovery_code: Option<String>,
+}
+
+impl SecondFactor {
+    pub fn schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "code": { "type": "string", "pattern": "^[0-9]{6}$" },
+                "recovery_code": { "type": "string" }
+            }
+        })
+    }
+}
+
+// Response of POST /api/admin/totp/enroll.
+#[derive(Debug, Serialize)]
+pub struct Enrolment {
+    pub secret: String,
+    // otpauth:// URI for a QR code.
+    pub provisioning_uri: String,
+}
+
+impl ApiSchema for Enrolment {
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["secret", "provisioning_uri"],
+            "properties": {
+                "secret": { "type": "string" },
+                "provisioning_uri": { "type": "string" }
+            }
+        })
+    }
+}
+
+// RFC 4648 base32 without padding, as authenticator apps expect secrets.
+fn base32_encode(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+    let mut out = String::new();
+    let (mut buffer, mut bits) = (0u32, 0);
+    for byte in bytes {
+        buffer = (buffer << 8) | u32::from(*byte);
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            out.push(ALPHABET[((buffer >> bits) & 31) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        out.push(ALPHABET[((buffer << (5 - bits)) & 31) as usize] as char);
+    }
+    out
+}
+
+fn base32_decode(text: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::new();
+    let (mut buffer, mut bits) = (0u32, 0);
+    for c in text.trim_end_matches('=').chars() {
+        let value = match c.to_ascii_uppercase() {
+            c @ 'A'..='Z' => c as u32 - 'A' as u32,
+            c @ '2'..='7' => c as u32 - '2' as u32 + 26,
+            _ => return None,
+        };
+        buffer = (buffer << 5) | value;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((buffer >> bits) as u8);
+        }
+    }
+    Some(out)
+}
+
+// The code for time step `step` (RFC 4226 dynamic truncation).
+fn code_at(secret: &[u8], step: i64) -> u32 {
+    let mut mac = Hmac::<Sha1>::new_from_slice(secret).expect("HMAC accepts any key length");
+    mac.update(&step.to_be_bytes());
+    let digest = mac.finalize().into_bytes();
+    let offset = usize::from(digest[19] & 0x0f);
+    let value = u32::from_be_bytes([digest[offset], digest[offset + 1], digest[offset + 2], digest[offset + 3]]);
+    (value & 0x7fff_ffff) % 1_000_000
+}
+
+// The step `code` is valid for around now, if any step after `last_step` matches.
+fn matching_step(secret: &str, code: &str, last_step: Option<i64>) -> Option<i64> {
+    let secret = base32_decode(secret)?;
+    let code: u32 = code.trim().parse().ok().filter(|_| code.trim().len() == 6)?;
+    let now = Utc::now().timestamp() / STEP_SECS;
+    (now - ALLOWED_DRIFT_STEPS..=now + ALLOWED_DRIFT_STEPS)
+        .filter(|step| last_step.is_none_or(|last| *step > last))
+        .find(|step| code_at(&secret, *step) == code)
+}
+
+fn hash_code(code: &str) -> String {
+    let normalized: String = code.chars().filter(|c| c.is_ascii_alphanumeric()).collect::<String>().to_lowercase();
+    hex::encode(Sha256::digest(normalized.as_bytes()))
+}
+
+#[derive(sqlx::FromRow)]
+struct TotpState {
+    totp_secret: Option<String>,
+    totp_enabled: bool,
+    totp_last_step: Option<i64>,
+    recovery_code_hashes: Option<Json<Vec<String>>>,
+}
+
+async fn state(pool: &MySqlPool, admin_id: i64) -> Result<Option<TotpState>> {
+    Ok(sqlx::query_as(
+        "SELECT totp_secret, totp_enabled, totp_last_step, recovery_code_hashes FROM admin_users \
+         WHERE id = ? AND active",
+    )
+    .bind(admin_id)
+    .fetch_optional(pool)
+    .await?)
+}
+
+// Checks the second factor of an enrolled admin user, consuming it: a code can't be
+// used twice and a recovery code is used up. Wrong codes count towards the user's
+// brute-force limit (see auth_throttle).
+pub async fn verify(pool: &MySqlPool, admin_id: i64, factor: &SecondFactor, audit: &AuditLogger) -> AppResult<()> {
+    let identifier = admin_id.to_string();
+    auth_throttle::check(pool, THROTTLE_SCOPE, &identifier).await?;
+    match consume(pool, admin_id, factor).await {
+        Ok(()) => {
+            auth_throttle::record_success(pool, THROTTLE_SCOPE, &identifier).await?;
+            Ok(())
+        }
+        Err(err @ AppError::Unauthorized(_)) => {
+            auth_throttle::record_failure(pool, THROTTLE_SCOPE, &identifier, audit).await?;
+            Err(err)
+        }
+        Err(err) => Err(err),
+    }
+}
+
+// Conditional updates keep two concurrent requests from both spending the same code.
+async fn consume(pool: &MySqlPool, admin_id: i64, factor: &SecondFactor) -> AppResult<()> {
+    let state = state(pool, admin_id).await?.filter(|state| state.totp_enabled);
+    let Some(TotpState { totp_secret: Some(secret), totp_last_step, recovery_code_hashes, .. }) = state else {
+        return Err(AppError::Conflict("two-factor authentication is not enabled".to_string()));
+    };
+    let rejected = || AppError::Unauthorized("invalid two-factor code".to_string());
+    if let Some(code) = &factor.code {
+        let step = matching_step(&secret, code, totp_last_step).ok_or_else(rejected)?;
+        let updated = sqlx::query(
+            "UPDATE admin_users SET totp_last_step = ? WHERE id = ? AND (totp_last_step IS NULL OR totp_last_step < ?)",
+        )
+        .bind(step)
+        .bind(admin_id)
+        .bind(step)
+        .execute(pool)
+        .await?
+        .rows_affected();
+        return if updated == 1 { Ok(()) } else { Err(rejected()) };
+    }
+    if let Some(recovery_code) = &factor.recovery_code {
+        let hashes = recovery_code_hashes.map(|Json(hashes)| hashes).unwrap_or_default();
+        let hash = hash_code(recovery_code);
+        if !hashes.iter().any(|stored| constant_time_eq(stored.as_bytes(), hash.as_bytes())) {
+            return Err(rejected());
+        }
+        let remaining: Vec<&String> = hashes.iter().filter(|stored| **stored != hash).collect();
+        let updated = sqlx::query(
+            "UPDATE admin_users SET recovery_code_hashes = ? WHERE id = ? AND JSON_CONTAINS(recovery_code_hashes, ?)",
+        )
+        .bind(Json(&remaining))
+        .bind(admin_id)
+        .bind(Json(&hash))
+        .execute(pool)
+        .await?
+        .rows_affected();
+        return if updated == 1 { Ok(()) } else { Err(rejected()) };
+    }
+    Err(AppError::Unauthorized("two-factor code required".to_string()))
+}
+
+// Registers the two-factor endpoints. Each acts on the calling admin user.
+pub fn routes(router: Router) -> Router {
+    router
+        .post("/api/admin/totp/enroll", enroll)
+        .summary("Start two-factor enrolment: a new secret and its provisioning URI")
+        .cache(CachePolicy::NoStore)
+        .response_schema(Enrolment::schema())
+        .post("/api/admin/totp/confirm", confirm)
+        .summary("Finish two-factor enrolment with a current code; returns the recovery codes")
+        .cache(CachePolicy::NoStore)
+        .request_schema(json!({
+            "type": "object",
+            "required": ["code"],
+            "properties": { "code": { "type": "string", "pattern": "^[0-9]{6}$" } }
+        }))
+        .post("/api/admin/totp/disable", disable)
+        .summary("Turn two-factor authentication off, with a current or recovery code")
+        .request_schema(SecondFactor::schema())
+}
+
+// The calling admin user: from a session, or from their own token while two-factor
+// is off (auth::admin_token_allowed refuses it otherwise).
+async fn caller(ctx: &RequestContext, services: &AppServices) -> AppResult<i64> {
+    let Some(token) = ctx.bearer_token() else {
+        return Err(AppError::Unauthorized("missing bearer token".to_string()));
+    };
+    if let Some(claims) = services.admin_sessions.verify(token) {
+        return Ok(claims.sub);
+    }
+    let credential = AdminUserRepository::new(services.db()?)
+        .find(token)
+        .await?
+        .ok_or_else(|| AppError::Forbidden("two-factor authentication is for admin users only".to_string()))?;
+    if credential.two_factor {
+        return Err(AppError::Unauthorized(
+            "two-factor authentication is enabled; sign in with POST /api/admin/sessions".to_string(),
+        ));
+    }
+    Ok(credential.id)
+}
+
+// Enrolment needs sessions: an enrolled user can only sign in through one.
+async fn enroll(ctx: RequestContext, services: Arc<AppServices>) -> AppResult<Response<Bytes>> {
+    if !services.admin_sessions.enabled() {
+        return Err(AppError::ServiceUnavailable(
+            "two-factor authentication needs admin sessions (ADMIN_SESSION_SECRET not set)".to_string(),
+        ));
+    }
+    let admin_id = caller(&ctx, &services).await?;
+    let mut bytes = [0u8; 20];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    let secret = base32_encode(&bytes);
+    let updated = sqlx::query(
+        "UPDATE admin_users SET totp_secret = ?, totp_last_step = NULL, recovery_code_hashes = NULL \
+         WHERE id = ? AND NOT totp_enabled",
+    )
+    .bind(&secret)
+    .bind(admin_id)
+    .execute(services.db()?)
+    .await?
+    .rows_affected();
+    if updated == 0 {
+        return Err(AppError::Conflict("two-factor authentication is already enabled".to_string()));
+    }
+    let provisioning_uri =
+        format!("otpauth://totp/{ISSUER}:admin-{admin_id}?secret={secret}&issuer={ISSUER}&digits=6&period={STEP_SECS}");
+    ResponseBuilder::json(StatusCode::CREATED, &Enrolment { secret, provisioning_uri })
+}
+
+#[derive(Deserialize)]
+struct ConfirmInput {
+    code: String,
+}
+
+async fn confirm(ctx: RequestContext, services: Arc<AppServices>) -> AppResult<Response<Bytes>> {
+    let admin_id = caller(&ctx, &services).await?;
+    let input: ConfirmInput = ctx.json()?;
+    let pool = services.db()?;
+    let pending = state(pool, admin_id).await?.filter(|state| !state.totp_enabled);
+    let Some(TotpState { totp_secret: Some(secret), .. }) = pending else {
+        return Err(AppError::Conflict("no two-factor enrolment is pending".to_string()));
+    };
+    let step = matching_step(&secret, &input.code, None)
+        .ok_or_else(|| AppError::Unauthorized("invalid two-factor code".to_string()))?;
+
+    let codes: Vec<String> = (0..RECOVERY_CODES)
+        .map(|_| {
+            let mut bytes = [0u8; 5];
+            rand::thread_rng().fill_bytes(&mut bytes);
+            let code = hex::encode(bytes);
+            format!("{}-{}", &code[..5], &code[5..])
+        })
+        .collect();
+    let hashes: Vec<String> = codes.iter().map(|code| hash_code(code)).collect();
+    let updated = sqlx::query(
+        "UPDATE admin_users SET totp_enabled = TRUE, totp_last_step = ?, recovery_code_hashes = ? \
+         WHERE id = ? AND totp_secret = ? AND NOT totp_enabled",
+    )
+    .bind(step)
+    .bind(Json(&hashes))
+    .bind(admin_id)
+    .bind(&secret)
+    .execute(pool)
+    .await?
+    .rows_affected();
+    if updated == 0 {
+        return Err(AppError::Conflict("the enrolment changed; start again".to_string()));
+    }
+    ResponseBuilder::json(StatusCode::OK, &json!({ "recovery_codes": codes }))
+}
+
+async fn disable(ctx: RequestContext, services: Arc<AppServices>) -> AppResult<Response<Bytes>> {
+    let admin_id = caller(&ctx, &services).await?;
+    let factor: SecondFactor = ctx.json()?;
+    let pool = services.db()?;
+    verify(pool, admin_id, &factor, &AuditLogger::for_request(&ctx, &services)).await?;
+    sqlx::query(
+        "UPDATE admin_users SET totp_secret = NULL, totp_enabled = FALSE, totp_last_step = NULL, \
+         recovery_code_hashes = NULL WHERE id = ?",
+    )
+    .bind(admin_id)
+    .execute(pool)
+    .await?;
+    ResponseBuilder::no_content()
+}
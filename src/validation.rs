@@ -0,0 +1,274 @@
+use crate::error::FieldError;
+use serde_json::Value;
+
+// Semantic checks a JSON Schema can't express (cross-field rules, formats). An empty
+// list means the value is valid; extract::ValidJson runs it before the handler.
+pub trait Validate {
+    fn validate(&self) -> Vec<FieldError>;
+}
+
+// Validates JSON request bodies against the route's documented request schema
+// when the handler reads them (see RequestContext::json), so clients get every field
+// problem at once instead of the first serde error.
+//
+// Supports the subset of JSON Schema used by our ApiSchema implementations:
+// type (one name or a list), required, properties, items, enum, minLength/maxLength,
+// minimum/maximum, minItems/maxItems and additionalProperties: false.
+pub struct ValidationMiddleware;
+
+impl ValidationMiddleware {
+    // Parses and validates `body`. Returns the list of violations on failure.
+    pub fn validate_body(schema: &Value, body: &[u8]) -> Result<(), Vec<FieldError>> {
+        let value: Value = match serde_json::from_slice(body) {
+            Ok(value) => value,
+            Err(err) => return Err(vec![FieldError::new("$", format!("invalid JSON: {err}"))]),
+        };
+
+        let mut errors = Vec::new();
+        validate_value(schema, &value, "$", &mut errors);
+        if errors.is_empty() { Ok(()) } else { Err(errors) }
+    }
+}
+
+// Recursively checks `value` against `schema`, appending violations to `errors`.
+fn validate_value(schema: &Value, value: &Value, path: &str, errors: &mut Vec<FieldError>) {
+    // "type" is one name or, for nullable fields, a list of them.
+    let expected: Vec<&str> = match schema.get("type") {
+        Some(Value::String(name)) => vec![name.as_str()],
+        Some(Value::Array(names)) => names.iter().filter_map(Value::as_str).collect(),
+        _ => Vec::new(),
+    };
+    if !expected.is_empty() && !expected.iter().any(|name| type_matches(name, value)) {
+        errors.push(FieldError::new(path, format!("expected {}, got {}", expected.join(" or "), type_name(value))));
+        // The remaining keywords assume the right type; stop here.
+        return;
+    }
+
+    if let Some(allowed) = schema.get("enum").and_then(Value::as_array)
+        && !allowed.contains(value)
+    {
+        errors.push(FieldError::new(path, format!("must be one of {}", Value::Array(allowed.clone()))));
+    }
+
+    match value {
+        Value::Object(map) => {
+            if let Some(required) = schema.get("required").and_then(Value::as_array) {
+                for name in required.iter().filter_map(Value::as_str) {
+                    if !map.contains_key(name) {
+                        errors.push(FieldError::new(join(path, name), "is required"));
+                    }
+                }
+            }
+            let properties = schema.get("properties").and_then(Value::as_object);
+            for (name, field) in map {
+                match properties.and_then(|p| p.get(name)) {
+                    Some(field_schema) => validate_value(field_schema, field, &join(path, name), errors),
+                    None if schema.get("additionalProperties") == Some(&Value::Bool(false)) => {
+                        errors.push(FieldError::new(join(path, name), "is not allowed"));
+                    }
+                    None => {}
+                }
+            }
+        }
+        Value::Array(items) => {
+            if let Some(min) = schema.get("minItems").and_then(Value::as_u64)
+                && (items.len() as u64) < min
+            {
+                errors.push(FieldError::new(path, format!("must contain at least {min} items")));
+            }
+            if let Some(max) = schema.get("maxItems").and_then(Value::as_u64)
+                && items.len() as u64 > max
+            {
+                errors.push(FieldError::new(path, format!("must contain at most {max} items")));
+            }
+            if let Some(item_schema) = schema.get("items") {
+                for (i, item) in items.iter().enumerate() {
+                    validate_value(item_schema, item, &format!("{path}[{i}]"), errors);
+                }
+            }
+        }
+        Value::String(s) => {
+            let len = s.chars().count() as u64;
+            if let Some(min) = schema.get("minLength").and_then(Value::as_u64)
+                && len < min
+            {
+                errors.push(FieldError::new(path, format!("must be at least {min} characters")));
+            }
+            if let Some(max) = schema.get("maxLength").and_then(Value::as_u64)
+                && len > max
+            {
+                errors.push(FieldError::new(path, format!("must be at most {max} characters")));
+            }
+        }
+        Value::Number(n) => {
+            let n = n.as_f64().unwrap_or_default();
+            if let Some(min) = schema.get("minimum").and_then(Value::as_f64)
+                && n < min
+            {
+                errors.push(FieldError::new(path, format!("must be >= {min}")));
+            }
+            if let Some(max) = schema.get("maximum").and_then(Value::as_f64)
+                && n > max
+            {
+                errors.push(FieldError::new(path, format!("must be <= {max}")));
+            }
+        }
+        Value::Bool(_) | Value::Null => {}
+    }
+}
+
+fn type_matches(expected: &str, value: &Value) -> bool {
+    match expected {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "number" => value.is_number(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        _ => true,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Object(_) => "object",
+        Value::Array(_) => "array",
+        Value::String(_) => "string",
+        Value::Number(_) => "number",
+        Value::Bool(_) => "boolean",
+        Value::Null => "null",
+    }
+}
+
+// Field paths use dots for properties and brackets for items, e.g. "polygon[0].latitude".
+fn join(path: &str, name: &str) -> String {
+    if path == "$" { name.to_string() } else { format!("{path}.{name}") }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ValidationMiddleware;
+    use serde_json::{json, Value};
+
+    // Violations of `body` against `schema`, as sorted "field: message" lines.
+    fn violations(schema: Value, body: Value) -> Vec<String> {
+        let Err(errors) = ValidationMiddleware::validate_body(&schema, body.to_string().as_bytes()) else {
+            return Vec::new();
+        };
+        let mut lines: Vec<String> = errors.into_iter().map(|e| format!("{}: {}", e.field, e.message)).collect();
+        lines.sort();
+        lines
+    }
+
+    fn order_schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["table_token", "items"],
+            "properties": {
+                "table_token": { "type": "string", "minLength": 32, "maxLength": 32 },
+                "notes": { "type": ["string", "null"], "maxLength": 10 },
+                "items": {
+                    "type": "array",
+                    "minItems": 1,
+                    "maxItems": 2,
+                    "items": {
+                        "type": "object",
+                        "required": ["sku", "quantity"],
+                        "properties": {
+                            "sku": { "type": "string" },
+                            "quantity": { "type": "integer", "minimum": 1, "maximum": 20 }
+                        }
+                    }
+                }
+            }
+        })
+    }
+
+    fn token() -> String {
+        "a".repeat(32)
+    }
+
+    #[test]
+    fn valid_body_passes() {
+        let body = json!({ "table_token": token(), "notes": "no onion", "items": [{ "sku": "R1", "quantity": 2 }] });
+        assert!(violations(order_schema(), body).is_empty());
+    }
+
+    #[test]
+    fn invalid_json_is_reported_at_the_root() {
+        let errors = ValidationMiddleware::validate_body(&order_schema(), b"{").unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].field, "$");
+    }
+
+    #[test]
+    fn wrong_type_stops_further_checks() {
+        let body = json!({ "table_token": 7, "items": [{ "sku": "R1", "quantity": 1.5 }] });
+        assert_eq!(
+            violations(order_schema(), body),
+            ["items[0].quantity: expected integer, got number", "table_token: expected string, got number"]
+        );
+    }
+
+    #[test]
+    fn missing_required_fields_are_all_reported() {
+        let body = json!({ "items": [{}] });
+        assert_eq!(
+            violations(order_schema(), body),
+            ["items[0].quantity: is required", "items[0].sku: is required", "table_token: is required"]
+        );
+    }
+
+    #[test]
+    fn minimum_and_maximum_bound_numbers() {
+        let items = json!([{ "sku": "R1", "quantity": 0 }, { "sku": "R2", "quantity": 21 }]);
+        let body = json!({ "table_token": token(), "items": items });
+        assert_eq!(
+            violations(order_schema(), body),
+            ["items[0].quantity: must be >= 1", "items[1].quantity: must be <= 20"]
+        );
+    }
+
+    #[test]
+    fn min_and_max_length_bound_strings() {
+        let items = json!([{ "sku": "R1", "quantity": 1 }]);
+        let body = json!({ "table_token": "short", "notes": "far too long", "items": items });
+        assert_eq!(
+            violations(order_schema(), body),
+            ["notes: must be at most 10 characters", "table_token: must be at least 32 characters"]
+        );
+    }
+
+    #[test]
+    fn min_and_max_items_bound_arrays() {
+        let empty = json!({ "table_token": token(), "items": [] });
+        assert_eq!(violations(order_schema(), empty), ["items: must contain at least 1 items"]);
+
+        let line = json!({ "sku": "R1", "quantity": 1 });
+        let full = json!({ "table_token": token(), "items": [line, line, line] });
+        assert_eq!(violations(order_schema(), full), ["items: must contain at most 2 items"]);
+    }
+
+    #[test]
+    fn nullable_union_accepts_null_and_the_named_type() {
+        let null = json!({ "table_token": token(), "notes": null, "items": [{ "sku": "R1", "quantity": 1 }] });
+        assert!(violations(order_schema(), null).is_empty());
+
+        let number = json!({ "table_token": token(), "notes": 3, "items": [{ "sku": "R1", "quantity": 1 }] });
+        assert_eq!(violations(order_schema(), number), ["notes: expected string or null, got number"]);
+    }
+
+    #[test]
+    fn enum_and_additional_properties() {
+        let schema = json!({
+            "type": "object",
+            "additionalProperties": false,
+            "properties": { "status": { "type": "string", "enum": ["ready", "served"] } }
+        });
+        assert_eq!(
+            violations(schema, json!({ "status": "lost", "extra": true })),
+            ["extra: is not allowed", "status: must be one of [\"ready\",\"served\"]"]
+        );
+    }
+}
@@ -0,0 +1,512 @@
+use crate::app::AppServices;
+use crate::audit::{AuditEvent, AuditLogger};
+use crate::error::{AppError, AppResult, FieldError};
+use crate::extract::{Path, State, TenantAdmin, ValidJson};
+use crate::faults;
+use crate::logging;
+use crate::openapi::ApiSchema;
+use crate::response::ResponseBuilder;
+use crate::router::Router;
+use crate::validation::Validate;
+use anyhow::{anyhow, Result};
+use bytes::Bytes;
+use hmac::{Hmac, Mac};
+use http::{Response, StatusCode};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use sqlx::mysql::{MySql, MySqlPool};
+use std::time::Duration;
+
+// Events partners can subscribe to.
+pub const EVENT_TYPES: [&str; 8] = [
+    "order.created",
+    "order.updated",
+    "order.event",
+    "order.served",
+    "order.delivered",
+    "order.cancelled",
+    "menu.updated",
+    "ticket.resolved",
+];
+
+// Deliveries are abandoned after this many failed attempts.
+const MAX_ATTEMPTS: i32 = 8;
+// How long a claimed delivery is hidden from other dispatchers while it is being sent.
+const LEASE_SECS: i64 = 60;
+// Deliveries claimed per poll.
+const BATCH_SIZE: i64 = 20;
+
+// A partner subscription as stored in the webhook_subscriptions table. The signing
+// secret is only ever returned when the subscription is created.
+#[derive(Debug, Clone, Serialize)]
+pub struct WebhookSubscription {
+    pub id: i64,
+    pub url: String,
+    #[serde(skip_serializing)]
+    pub secret: String,
+    pub event_types: Vec<String>,
+    pub active: bool,
+}
+
+impl ApiSchema for WebhookSubscription {
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["id", "url", "event_types", "active"],
+            "properties": {
+                "id": { "type": "integer" },
+                "url": { "type": "string" },
+                "event_types": { "type": "array", "items": { "type": "string", "enum": EVENT_TYPES } },
+                "active": { "type": "boolean" },
+                "secret": { "type": "string", "description": "HMAC signing secret; only returned on create" }
+            }
+        })
+    }
+}
+
+// Raw row; event_types is JSON and is read back as text.
+#[derive(sqlx::FromRow)]
+struct SubscriptionRow {
+    id: i64,
+    url: String,
+    secret: String,
+    event_types: String,
+    active: bool,
+}
+
+impl TryFrom<SubscriptionRow> for WebhookSubscription {
+    type Error = anyhow::Error;
+
+    fn try_from(row: SubscriptionRow) -> Result<Self> {
+        Ok(Self {
+            id: row.id,
+            url: row.url,
+            secret: row.secret,
+            event_types: serde_json::from_str(&row.event_types)?,
+            active: row.active,
+        })
+    }
+}
+
+// Body accepted by the admin create/update endpoints. A secret is generated on create
+// when none is given; on update an omitted secret keeps the current one.
+#[derive(Debug, Deserialize)]
+pub struct WebhookInput {
+    pub url: String,
+    pub event_types: Vec<String>,
+    #[serde(default)]
+    pub secret: Option<String>,
+    #[serde(default = "default_active")]
+    pub active: bool,
+}
+
+fn default_active() -> bool {
+    true
+}
+
+impl ApiSchema for WebhookInput {
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["url", "event_types"],
+            "properties": {
+                "url": { "type": "string", "minLength": 1 },
+                "event_types": {
+                    "type": "array",
+                    "items": { "type": "string", "enum": EVENT_TYPES },
+                    "minItems": 1
+                },
+                "secret": { "type": "string", "minLength": 16 },
+                "active": { "type": "boolean", "default": true }
+            }
+        })
+    }
+}
+
+impl Validate for WebhookInput {
+    // Checks rules the JSON Schema can't express; an empty list means the input is valid.
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        match reqwest::Url::parse(&self.url) {
+            Ok(url) if matches!(url.scheme(), "https" | "http") && url.host().is_some() => {}
+            _ => errors.push(FieldError::new("url", "must be an absolute http(s) URL")),
+        }
+        if self.event_types.is_empty() {
+            errors.push(FieldError::new("event_types", "needs at least one event type"));
+        }
+        for (i, event) in self.event_types.iter().enumerate() {
+            if !EVENT_TYPES.contains(&event.as_str()) {
+                errors.push(FieldError::new(format!("event_types[{i}]"), format!("unknown event type {event}")));
+            }
+        }
+        if let Some(secret) = &self.secret
+            && secret.len() < 16
+        {
+            errors.push(FieldError::new("secret", "must be at least 16 characters"));
+        }
+        errors
+    }
+}
+
+fn generate_secret() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    format!("whsec_{}", hex::encode(bytes))
+}
+
+// Signature sent in X-Webhook-Signature: hex HMAC-SHA256 of "<timestamp>.<body>".
+// Including the timestamp lets receivers reject replayed deliveries.
+pub fn sign(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+const SELECT_SUBSCRIPTION: &str =
+    "SELECT id, url, secret, CAST(event_types AS CHAR) AS event_types, active FROM webhook_subscriptions";
+
+// Database access for one restaurant's webhook subscriptions.
+pub struct WebhookRepository<'a> {
+    pool: &'a MySqlPool,
+    restaurant_id: i64,
+}
+
+impl<'a> WebhookRepository<'a> {
+    pub fn new(pool: &'a MySqlPool, restaurant_id: i64) -> Self {
+        Self { pool, restaurant_id }
+    }
+
+    pub async fn list(&self) -> Result<Vec<WebhookSubscription>> {
+        let rows: Vec<SubscriptionRow> =
+            sqlx::query_as(&format!("{SELECT_SUBSCRIPTION} WHERE restaurant_id = ? ORDER BY id"))
+                .bind(self.restaurant_id)
+                .fetch_all(self.pool)
+                .await?;
+        rows.into_iter().map(TryInto::try_into).collect()
+    }
+
+    pub async fn get(&self, id: i64) -> Result<Option<WebhookSubscription>> {
+        let row: Option<SubscriptionRow> =
+            sqlx::query_as(&format!("{SELECT_SUBSCRIPTION} WHERE id = ? AND restaurant_id = ?"))
+                .bind(id)
+                .bind(self.restaurant_id)
+                .fetch_optional(self.pool)
+                .await?;
+        row.map(TryInto::try_into).transpose()
+    }
+
+    pub async fn create(&self, input: &WebhookInput) -> Result<WebhookSubscription> {
+        let secret = input.secret.clone().unwrap_or_else(generate_secret);
+        let result = sqlx::query(
+            "INSERT INTO webhook_subscriptions (restaurant_id, url, secret, event_types, active) \
+             VALUES (?, ?, ?, ?, ?)",
+        )
+        .bind(self.restaurant_id)
+        .bind(&input.url)
+        .bind(&secret)
+        .bind(serde_json::to_string(&input.event_types)?)
+        .bind(input.active)
+        .execute(self.pool)
+        .await?;
+
+        let id = result.last_insert_id() as i64;
+        self.get(id)
+            .await?
+            .ok_or_else(|| anyhow!("webhook subscription {id} vanished after insert"))
+    }
+
+    // Returns None when no subscription has the given id.
+    pub async fn update(&self, id: i64, input: &WebhookInput) -> Result<Option<WebhookSubscription>> {
+        sqlx::query(
+            "UPDATE webhook_subscriptions SET url = ?, secret = COALESCE(?, secret), event_types = ?, \
+             active = ? WHERE id = ? AND restaurant_id = ?",
+        )
+        .bind(&input.url)
+        .bind(&input.secret)
+        .bind(serde_json::to_string(&input.event_types)?)
+        .bind(input.active)
+        .bind(id)
+        .bind(self.restaurant_id)
+        .execute(self.pool)
+        .await?;
+        self.get(id).await
+    }
+
+    // Returns true if a subscription was deleted. Its pending deliveries go with it.
+    pub async fn delete(&self, id: i64) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM webhook_subscriptions WHERE id = ? AND restaurant_id = ?")
+            .bind(id)
+            .bind(self.restaurant_id)
+            .execute(self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+// Queues `event` for every active subscription of the restaurant that wants it. Pass the
+// transaction that makes the change being announced, so the deliveries are only queued
+// if it commits.
+pub async fn enqueue<'e, E>(executor: E, restaurant_id: i64, event: &str, payload: &Value) -> Result<u64>
+where
+    E: sqlx::Executor<'e, Database = MySql>,
+{
+    let result = sqlx::query(
+        "INSERT INTO webhook_deliveries (subscription_id, event_type, payload) \
+         SELECT id, ?, ? FROM webhook_subscriptions \
+         WHERE restaurant_id = ? AND active = TRUE AND JSON_CONTAINS(event_types, JSON_QUOTE(?))",
+    )
+    .bind(event)
+    .bind(serde_json::to_string(payload)?)
+    .bind(restaurant_id)
+    .bind(event)
+    .execute(executor)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+// A delivery claimed by the dispatcher, joined with its subscription.
+#[derive(sqlx::FromRow)]
+struct DueDelivery {
+    id: i64,
+    event_type: String,
+    payload: String,
+    attempts: i32,
+    url: String,
+    secret: String,
+}
+
+// Delay before retry number `attempt` (1-based): 30s, 1m, 2m, ... capped at 6 hours.
+fn backoff_secs(attempt: i32) -> i64 {
+    (30i64 << (attempt - 1).clamp(0, 20)).min(6 * 3600)
+}
+
+// Background task that sends queued deliveries. Safe to run on several instances:
+// claimed rows are locked with SKIP LOCKED and leased before sending.
+pub fn spawn_dispatcher(pool: MySqlPool) {
+    tokio::spawn(async move {
+        let client = match reqwest::Client::builder().timeout(Duration::from_secs(10)).build() {
+            Ok(client) => client,
+            Err(err) => {
+                logging::error("webhook dispatcher failed to start", json!({ "error": err.to_string() }));
+                return;
+            }
+        };
+        loop {
+            match dispatch_due(&pool, &client).await {
+                // A full batch suggests a backlog; go again without waiting.
+                Ok(claimed) if claimed as i64 == BATCH_SIZE => continue,
+                Ok(_) => {}
+                Err(err) => logging::warn("webhook dispatch failed", json!({ "error": format!("{err:#}") })),
+            }
+            tokio::time::sleep(Duration::from_secs(5)).await;
+        }
+    });
+}
+
+// Claims and sends one batch of due deliveries; returns how many were claimed.
+async fn dispatch_due(pool: &MySqlPool, client: &reqwest::Client) -> Result<usize> {
+    let mut tx = pool.begin().await?;
+    let due: Vec<DueDelivery> = sqlx::query_as(
+        "SELECT d.id, d.event_type, CAST(d.payload AS CHAR) AS payload, d.attempts, s.url, s.secret \
+         FROM webhook_deliveries d JOIN webhook_subscriptions s ON s.id = d.subscription_id \
+         WHERE d.status = 'pending' AND d.next_attempt_at <= NOW() \
+         ORDER BY d.next_attempt_at LIMIT ? FOR UPDATE OF d SKIP LOCKED",
+    )
+    .bind(BATCH_SIZE)
+    .fetch_all(&mut *tx)
+    .await?;
+    for delivery in &due {
+        sqlx::query("UPDATE webhook_deliveries SET next_attempt_at = NOW() + INTERVAL ? SECOND WHERE id = ?")
+            .bind(LEASE_SECS)
+            .bind(delivery.id)
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await?;
+
+    for delivery in &due {
+        let outcome = deliver(client, delivery).await;
+        record_attempt(pool, delivery, outcome).await?;
+    }
+    Ok(due.len())
+}
+
+// POSTs one delivery. Any 2xx answer counts as success.
+async fn deliver(client: &reqwest::Client, delivery: &DueDelivery) -> Result<()> {
+    faults::webhook()?;
+    let data: Value = serde_json::from_str(&delivery.payload)?;
+    let body = serde_json::to_vec(&json!({
+        "id": delivery.id,
+        "event": delivery.event_type,
+        "data": data,
+    }))?;
+    let timestamp = chrono::Utc::now().timestamp();
+    let response = client
+        .post(&delivery.url)
+        .header("content-type", "application/json")
+        .header("x-webhook-id", delivery.id.to_string())
+        .header("x-webhook-event", &delivery.event_type)
+        .header("x-webhook-timestamp", timestamp.to_string())
+        .header("x-webhook-signature", format!("sha256={}", sign(&delivery.secret, timestamp, &body)))
+        .body(body)
+        .send()
+        .await?;
+    if !response.status().is_success() {
+        return Err(anyhow!("endpoint answered {}", response.status()));
+    }
+    Ok(())
+}
+
+async fn record_attempt(pool: &MySqlPool, delivery: &DueDelivery, outcome: Result<()>) -> Result<()> {
+    let attempts = delivery.attempts + 1;
+    match outcome {
+        Ok(()) => {
+            sqlx::query(
+                "UPDATE webhook_deliveries SET status = 'delivered', attempts = ?, delivered_at = NOW(), \
+                 last_error = NULL WHERE id = ?",
+            )
+            .bind(attempts)
+            .bind(delivery.id)
+            .execute(pool)
+            .await?;
+        }
+        Err(err) => {
+            let error: String = format!("{err:#}").chars().take(1024).collect();
+            let status = if attempts >= MAX_ATTEMPTS { "failed" } else { "pending" };
+            logging::warn(
+                "webhook delivery failed",
+                json!({ "delivery_id": delivery.id, "attempts": attempts, "status": status, "error": error }),
+            );
+            sqlx::query(
+                "UPDATE webhook_deliveries SET status = ?, attempts = ?, last_error = ?, \
+                 next_attempt_at = NOW() + INTERVAL ? SECOND WHERE id = ?",
+            )
+            .bind(status)
+            .bind(attempts)
+            .bind(error)
+            .bind(backoff_secs(attempts))
+            .bind(delivery.id)
+            .execute(pool)
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+// Registers the admin webhook subscription endpoints. Subscriptions belong to the
+// request's restaurant and only hear about its events.
+pub fn routes(router: Router) -> Router {
+    router
+        .get("/api/admin/webhooks", list_webhooks)
+        .summary("List webhook subscriptions")
+        .requires_tenant_admin()
+        .response_schema(json!({ "type": "array", "items": WebhookSubscription::schema() }))
+        .post("/api/admin/webhooks", create_webhook)
+        .summary("Create a webhook subscription")
+        .requires_tenant_admin()
+        .request_schema(WebhookInput::schema())
+        .response_schema(WebhookSubscription::schema())
+        .get("/api/admin/webhooks/:id", get_webhook)
+        .summary("Get a webhook subscription")
+        .requires_tenant_admin()
+        .response_schema(WebhookSubscription::schema())
+        .put("/api/admin/webhooks/:id", update_webhook)
+        .summary("Replace a webhook subscription")
+        .requires_tenant_admin()
+        .request_schema(WebhookInput::schema())
+        .response_schema(WebhookSubscription::schema())
+        .delete("/api/admin/webhooks/:id", delete_webhook)
+        .summary("Delete a webhook subscription")
+        .requires_tenant_admin()
+}
+
+// Audits a subscription change. The secret is never part of the snapshot.
+async fn audit_webhook(
+    audit: &AuditLogger,
+    services: &AppServices,
+    restaurant_id: i64,
+    action: &str,
+    id: i64,
+    before: Option<&WebhookSubscription>,
+    after: Option<&WebhookSubscription>,
+) -> AppResult<()> {
+    let snapshot = |webhook: Option<&WebhookSubscription>| webhook.map(|w| json!(w));
+    let event = AuditEvent {
+        action,
+        target_type: "webhook_subscription",
+        target_id: id.to_string(),
+        restaurant_id: Some(restaurant_id),
+        before: snapshot(before),
+        after: snapshot(after),
+    };
+    audit.record(services.db()?, event).await?;
+    Ok(())
+}
+
+fn webhook_not_found() -> AppError {
+    AppError::NotFound("webhook subscription not found".to_string())
+}
+
+async fn list_webhooks(TenantAdmin(restaurant): TenantAdmin, State(services): State) -> AppResult<Response<Bytes>> {
+    let webhooks = WebhookRepository::new(services.db()?, restaurant.id).list().await?;
+    ResponseBuilder::json(StatusCode::OK, &webhooks)
+}
+
+async fn get_webhook(
+    TenantAdmin(restaurant): TenantAdmin,
+    Path(id): Path<i64>,
+    State(services): State,
+) -> AppResult<Response<Bytes>> {
+    let webhook = WebhookRepository::new(services.db()?, restaurant.id)
+        .get(id)
+        .await?
+        .ok_or_else(webhook_not_found)?;
+    ResponseBuilder::json(StatusCode::OK, &webhook)
+}
+
+// The only response that includes the secret, so the partner can verify signatures.
+async fn create_webhook(
+    TenantAdmin(restaurant): TenantAdmin,
+    ValidJson(input): ValidJson<WebhookInput>,
+    audit: AuditLogger,
+    State(services): State,
+) -> AppResult<Response<Bytes>> {
+    let webhook = WebhookRepository::new(services.db()?, restaurant.id).create(&input).await?;
+    audit_webhook(&audit, &services, restaurant.id, "webhook.create", webhook.id, None, Some(&webhook)).await?;
+    let mut body = serde_json::to_value(&webhook).map_err(|err| AppError::Internal(err.into()))?;
+    body["secret"] = Value::String(webhook.secret);
+    ResponseBuilder::json(StatusCode::CREATED, &body)
+}
+
+async fn update_webhook(
+    TenantAdmin(restaurant): TenantAdmin,
+    Path(id): Path<i64>,
+    ValidJson(input): ValidJson<WebhookInput>,
+    audit: AuditLogger,
+    State(services): State,
+) -> AppResult<Response<Bytes>> {
+    let repository = WebhookRepository::new(services.db()?, restaurant.id);
+    let before = repository.get(id).await?.ok_or_else(webhook_not_found)?;
+    let webhook = repository.update(id, &input).await?.ok_or_else(webhook_not_found)?;
+    audit_webhook(&audit, &services, restaurant.id, "webhook.update", id, Some(&before), Some(&webhook)).await?;
+    ResponseBuilder::json(StatusCode::OK, &webhook)
+}
+
+async fn delete_webhook(
+    TenantAdmin(restaurant): TenantAdmin,
+    Path(id): Path<i64>,
+    audit: AuditLogger,
+    State(services): State,
+) -> AppResult<Response<Bytes>> {
+    let repository = WebhookRepository::new(services.db()?, restaurant.id);
+    let before = repository.get(id).await?.ok_or_else(webhook_not_found)?;
+    if !repository.delete(id).await? {
+        return Err(webhook_not_found());
+    }
+    audit_webhook(&audit, &services, restaurant.id, "webhook.delete", id, Some(&before), None).await?;
+    ResponseBuilder::no_content()
+}
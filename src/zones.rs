@@ -0,0 +1,355 @@
+use crate::app::AppServices;
+use crate::error::{AppError, AppResult, FieldError};
+use crate::extract::{Path, State, TenantAdmin, ValidJson};
+use crate::geocoding::{Coordinates, DeliveryZone};
+use crate::openapi::ApiSchema;
+use crate::request::RequestContext;
+use crate::response::ResponseBuilder;
+use crate::router::Router;
+use crate::tenant;
+use crate::validation::Validate;
+use anyhow::{anyhow, Result};
+use bytes::Bytes;
+use http::{Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sqlx::mysql::MySqlPool;
+use std::sync::Arc;
+
+// A delivery zone as stored in the delivery_zones table.
+#[derive(Debug, Clone, Serialize)]
+pub struct DeliveryZoneRecord {
+    pub id: i64,
+    pub name: String,
+    pub polygon: Vec<Coordinates>,
+    // Fee charged for deliveries inside this zone, in paise.
+    pub delivery_fee_paise: i64,
+    // Baseline delivery time quoted for this zone.
+    pub base_eta_minutes: i32,
+    pub active: bool,
+}
+
+impl DeliveryZoneRecord {
+    // Point-in-polygon check against this zone's boundary.
+    pub fn contains(&self, point: Coordinates) -> bool {
+        DeliveryZone {
+            name: self.name.clone(),
+            polygon: self.polygon.clone(),
+        }
+        .contains(point)
+    }
+}
+
+impl ApiSchema for DeliveryZoneRecord {
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["id", "name", "polygon", "delivery_fee_paise", "base_eta_minutes", "active"],
+            "properties": {
+                "id": { "type": "integer" },
+                "name": { "type": "string" },
+                "polygon": { "type": "array", "items": Coordinates::schema() },
+                "delivery_fee_paise": { "type": "integer" },
+                "base_eta_minutes": { "type": "integer" },
+                "active": { "type": "boolean" }
+            }
+        })
+    }
+}
+
+// Raw row; the polygon column is JSON and is read back as text.
+#[derive(sqlx::FromRow)]
+struct ZoneRow {
+    id: i64,
+    name: String,
+    polygon: String,
+    delivery_fee_paise: i64,
+    base_eta_minutes: i32,
+    active: bool,
+}
+
+impl TryFrom<ZoneRow> for DeliveryZoneRecord {
+    type Error = anyhow::Error;
+
+    fn try_from(row: ZoneRow) -> Result<Self> {
+        Ok(Self {
+            id: row.id,
+            name: row.name,
+            polygon: serde_json::from_str(&row.polygon)?,
+            delivery_fee_paise: row.delivery_fee_paise,
+            base_eta_minutes: row.base_eta_minutes,
+            active: row.active,
+        })
+    }
+}
+
+// Body accepted by the admin create/update endpoints.
+#[derive(Debug, Deserialize)]
+pub struct ZoneInput {
+    pub name: String,
+    pub polygon: Vec<Coordinates>,
+    pub delivery_fee_paise: i64,
+    pub base_eta_minutes: i32,
+    #[serde(default = "default_active")]
+    pub active: bool,
+}
+
+fn default_active() -> bool {
+    true
+}
+
+impl ApiSchema for ZoneInput {
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["name", "polygon", "delivery_fee_paise", "base_eta_minutes"],
+            "properties": {
+                "name": { "type": "string", "minLength": 1 },
+                "polygon": { "type": "array", "items": Coordinates::schema(), "minItems": 3 },
+                "delivery_fee_paise": { "type": "integer", "minimum": 0 },
+                "base_eta_minutes": { "type": "integer", "minimum": 1 },
+                "active": { "type": "boolean", "default": true }
+            }
+        })
+    }
+}
+
+impl Validate for ZoneInput {
+    // Checks rules the JSON Schema can't express; an empty list means the input is valid.
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        if self.name.trim().is_empty() {
+            errors.push(FieldError::new("name", "must not be blank"));
+        }
+        if self.polygon.len() < 3 {
+            errors.push(FieldError::new("polygon", "needs at least 3 points"));
+        }
+        for (i, point) in self.polygon.iter().enumerate() {
+            if !(-90.0..=90.0).contains(&point.latitude) || !(-180.0..=180.0).contains(&point.longitude) {
+                errors.push(FieldError::new(format!("polygon[{i}]"), "coordinates out of range"));
+            }
+        }
+        if self.delivery_fee_paise < 0 {
+            errors.push(FieldError::new("delivery_fee_paise", "must not be negative"));
+        }
+        if self.base_eta_minutes <= 0 {
+            errors.push(FieldError::new("base_eta_minutes", "must be positive"));
+        }
+        errors
+    }
+}
+
+const SELECT_ZONE: &str = "SELECT id, name, CAST(polygon AS CHAR) AS polygon, delivery_fee_paise, \
+     base_eta_minutes, active FROM delivery_zones";
+
+// Database access for one restaurant's delivery zones.
+pub struct ZoneRepository<'a> {
+    pool: &'a MySqlPool,
+    restaurant_id: i64,
+}
+
+impl<'a> ZoneRepository<'a> {
+    pub fn new(pool: &'a MySqlPool, restaurant_id: i64) -> Self {
+        Self { pool, restaurant_id }
+    }
+
+    pub async fn list(&self) -> Result<Vec<DeliveryZoneRecord>> {
+        let rows: Vec<ZoneRow> = sqlx::query_as(&format!("{SELECT_ZONE} WHERE restaurant_id = ? ORDER BY id"))
+            .bind(self.restaurant_id)
+            .fetch_all(self.pool)
+            .await?;
+        rows.into_iter().map(TryInto::try_into).collect()
+    }
+
+    pub async fn active(&self) -> Result<Vec<DeliveryZoneRecord>> {
+        let rows: Vec<ZoneRow> =
+            sqlx::query_as(&format!("{SELECT_ZONE} WHERE restaurant_id = ? AND active = TRUE ORDER BY id"))
+                .bind(self.restaurant_id)
+                .fetch_all(self.pool)
+                .await?;
+        rows.into_iter().map(TryInto::try_into).collect()
+    }
+
+    pub async fn get(&self, id: i64) -> Result<Option<DeliveryZoneRecord>> {
+        let row: Option<ZoneRow> = sqlx::query_as(&format!("{SELECT_ZONE} WHERE id = ? AND restaurant_id = ?"))
+            .bind(id)
+            .bind(self.restaurant_id)
+            .fetch_optional(self.pool)
+            .await?;
+        row.map(TryInto::try_into).transpose()
+    }
+
+    pub async fn create(&self, input: &ZoneInput) -> Result<DeliveryZoneRecord> {
+        let result = sqlx::query(
+            "INSERT INTO delivery_zones (restaurant_id, name, polygon, delivery_fee_paise, base_eta_minutes, active) \
+             VALUES (?, ?, ?, ?, ?, ?)",
+        )
+        .bind(self.restaurant_id)
+        .bind(&input.name)
+        .bind(serde_json::to_string(&input.polygon)?)
+        .bind(input.delivery_fee_paise)
+        .bind(input.base_eta_minutes)
+        .bind(input.active)
+        .execute(self.pool)
+        .await?;
+
+        let id = result.last_insert_id() as i64;
+        self.get(id)
+            .await?
+            .ok_or_else(|| anyhow!("delivery zone {id} vanished after insert"))
+    }
+
+    // Returns None when no zone has the given id.
+    pub async fn update(&self, id: i64, input: &ZoneInput) -> Result<Option<DeliveryZoneRecord>> {
+        sqlx::query(
+            "UPDATE delivery_zones SET name = ?, polygon = ?, delivery_fee_paise = ?, \
+             base_eta_minutes = ?, active = ? WHERE id = ? AND restaurant_id = ?",
+        )
+        .bind(&input.name)
+        .bind(serde_json::to_string(&input.polygon)?)
+        .bind(input.delivery_fee_paise)
+        .bind(input.base_eta_minutes)
+        .bind(input.active)
+        .bind(id)
+        .bind(self.restaurant_id)
+        .execute(self.pool)
+        .await?;
+
+        // MySQL reports 0 affected rows for a no-op update, so re-read instead of trusting the count.
+        self.get(id).await
+    }
+
+    // Returns true if a zone was deleted.
+    pub async fn delete(&self, id: i64) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM delivery_zones WHERE id = ? AND restaurant_id = ?")
+            .bind(id)
+            .bind(self.restaurant_id)
+            .execute(self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+// Finds the restaurant's active zone containing `point`; only the public lookup below
+// calls it. Orders are dine-in only and there is no courier assignment yet, so pricing
+// a delivery at checkout and keeping couriers inside their zone are left for when those
+// exist. Zones are checked in id order, so the oldest zone wins where polygons overlap.
+pub async fn zone_for_point(
+    pool: &MySqlPool,
+    restaurant_id: i64,
+    point: Coordinates,
+) -> Result<Option<DeliveryZoneRecord>> {
+    let zones = ZoneRepository::new(pool, restaurant_id).active().await?;
+    Ok(zones.into_iter().find(|zone| zone.contains(point)))
+}
+
+// Registers the delivery-zone endpoints. Zones belong to the request's restaurant.
+pub fn routes(router: Router) -> Router {
+    router
+        .get("/api/admin/zones", list_zones)
+        .summary("List delivery zones")
+        .requires_tenant_admin()
+        .response_schema(json!({ "type": "array", "items": DeliveryZoneRecord::schema() }))
+        .post("/api/admin/zones", create_zone)
+        .summary("Create a delivery zone")
+        .requires_tenant_admin()
+        .request_schema(ZoneInput::schema())
+        .response_schema(DeliveryZoneRecord::schema())
+        .get("/api/admin/zones/:id", get_zone)
+        .summary("Get a delivery zone")
+        .requires_tenant_admin()
+        .response_schema(DeliveryZoneRecord::schema())
+        .put("/api/admin/zones/:id", update_zone)
+        .summary("Replace a delivery zone")
+        .requires_tenant_admin()
+        .request_schema(ZoneInput::schema())
+        .response_schema(DeliveryZoneRecord::schema())
+        .delete("/api/admin/zones/:id", delete_zone)
+        .summary("Delete a delivery zone")
+        .requires_tenant_admin()
+        .get("/api/zones/lookup", lookup_zone)
+        .summary("Find the delivery zone, fee and ETA for a location")
+        .query_param("lat", true)
+        .query_param("lng", true)
+}
+
+fn zone_not_found() -> AppError {
+    AppError::NotFound("delivery zone not found".to_string())
+}
+
+async fn list_zones(TenantAdmin(restaurant): TenantAdmin, State(services): State) -> AppResult<Response<Bytes>> {
+    let zones = ZoneRepository::new(services.db()?, restaurant.id).list().await?;
+    ResponseBuilder::json(StatusCode::OK, &zones)
+}
+
+async fn get_zone(
+    TenantAdmin(restaurant): TenantAdmin,
+    Path(id): Path<i64>,
+    State(services): State,
+) -> AppResult<Response<Bytes>> {
+    let zone = ZoneRepository::new(services.db()?, restaurant.id)
+        .get(id)
+        .await?
+        .ok_or_else(zone_not_found)?;
+    ResponseBuilder::json(StatusCode::OK, &zone)
+}
+
+async fn create_zone(
+    TenantAdmin(restaurant): TenantAdmin,
+    ValidJson(input): ValidJson<ZoneInput>,
+    State(services): State,
+) -> AppResult<Response<Bytes>> {
+    let zone = ZoneRepository::new(services.db()?, restaurant.id).create(&input).await?;
+    ResponseBuilder::json(StatusCode::CREATED, &zone)
+}
+
+async fn update_zone(
+    TenantAdmin(restaurant): TenantAdmin,
+    Path(id): Path<i64>,
+    ValidJson(input): ValidJson<ZoneInput>,
+    State(services): State,
+) -> AppResult<Response<Bytes>> {
+    let zone = ZoneRepository::new(services.db()?, restaurant.id)
+        .update(id, &input)
+        .await?
+        .ok_or_else(zone_not_found)?;
+    ResponseBuilder::json(StatusCode::OK, &zone)
+}
+
+async fn delete_zone(
+    TenantAdmin(restaurant): TenantAdmin,
+    Path(id): Path<i64>,
+    State(services): State,
+) -> AppResult<Response<Bytes>> {
+    if !ZoneRepository::new(services.db()?, restaurant.id).delete(id).await? {
+        return Err(zone_not_found());
+    }
+    ResponseBuilder::no_content()
+}
+
+// Public lookup: GET /api/zones/lookup?lat=..&lng=.. returns the fee and ETA of the
+// restaurant's zone there, or 422 when the point is not deliverable.
+async fn lookup_zone(ctx: RequestContext, services: Arc<AppServices>) -> AppResult<Response<Bytes>> {
+    let point = match (
+        ctx.query_param("lat").and_then(|v| v.parse().ok()),
+        ctx.query_param("lng").and_then(|v| v.parse().ok()),
+    ) {
+        (Some(latitude), Some(longitude)) => Coordinates { latitude, longitude },
+        _ => return Err(AppError::BadRequest("lat and lng query parameters are required".to_string())),
+    };
+
+    let restaurant = tenant::current(&ctx, &services).await?;
+    let zone = zone_for_point(services.db()?, restaurant.id, point).await?.ok_or_else(|| {
+        AppError::Validation(vec![FieldError::new("lat,lng", "location is outside all delivery zones")])
+    })?;
+    ResponseBuilder::json(
+        StatusCode::OK,
+        &json!({
+            "zone_id": zone.id,
+            "name": zone.name,
+            "delivery_fee_paise": zone.delivery_fee_paise,
+            "base_eta_minutes": zone.base_eta_minutes,
+            "min_order_value_paise": services.runtime_config.min_order_value_paise(),
+        }),
+    )
+}